@@ -0,0 +1,23 @@
+CREATE TABLE IF NOT EXISTS config_template (
+	phase VARCHAR(16) PRIMARY KEY,
+	version INTEGER NOT NULL,
+	content TEXT NOT NULL,
+	updated_by VARCHAR(80) NOT NULL,
+	updated_at TIMESTAMP NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS config_template_version (
+	phase VARCHAR(16) NOT NULL,
+	version INTEGER NOT NULL,
+	content TEXT NOT NULL,
+	created_by VARCHAR(80) NOT NULL,
+	created_at TIMESTAMP NOT NULL,
+	PRIMARY KEY (phase, version)
+);
+
+CREATE TABLE IF NOT EXISTS config_variable (
+	name VARCHAR(32) PRIMARY KEY,
+	value VARCHAR(255) NOT NULL,
+	updated_by VARCHAR(80) NOT NULL,
+	updated_at TIMESTAMP NOT NULL
+);
@@ -0,0 +1,10 @@
+CREATE TABLE IF NOT EXISTS round_backup (
+	id SERIAL PRIMARY KEY,
+	game_match_id INTEGER NOT NULL REFERENCES game_match(id) ON DELETE CASCADE,
+	server_id INTEGER NOT NULL REFERENCES server(id),
+	round INTEGER NOT NULL,
+	file_name VARCHAR(64) NOT NULL,
+	content TEXT NOT NULL,
+	created_at TIMESTAMP NOT NULL,
+	UNIQUE (game_match_id, round)
+);
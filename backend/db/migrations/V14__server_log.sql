@@ -0,0 +1,22 @@
+CREATE TABLE IF NOT EXISTS server_log_token (
+	server_id INTEGER PRIMARY KEY REFERENCES server(id) ON DELETE CASCADE,
+	token_hash VARCHAR(255) NOT NULL,
+	created_by VARCHAR(255) NOT NULL,
+	created_at TIMESTAMP NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS player_match_stats (
+	id SERIAL PRIMARY KEY,
+	game_match_id INTEGER NOT NULL REFERENCES game_match(id) ON DELETE CASCADE,
+	steamid64 VARCHAR(255) NOT NULL,
+	name VARCHAR(255) NOT NULL,
+	side VARCHAR(32),
+	kills INTEGER NOT NULL DEFAULT 0,
+	deaths INTEGER NOT NULL DEFAULT 0,
+	assists INTEGER NOT NULL DEFAULT 0,
+	headshots INTEGER NOT NULL DEFAULT 0,
+	bomb_plants INTEGER NOT NULL DEFAULT 0,
+	bomb_defuses INTEGER NOT NULL DEFAULT 0,
+	updated_at TIMESTAMP NOT NULL,
+	UNIQUE (game_match_id, steamid64)
+);
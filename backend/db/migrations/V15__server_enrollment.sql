@@ -0,0 +1,29 @@
+CREATE TABLE IF NOT EXISTS enrollment_token (
+	id SERIAL PRIMARY KEY,
+	token_hash VARCHAR(255) NOT NULL,
+	label VARCHAR(64),
+	created_by VARCHAR(255) NOT NULL,
+	created_at TIMESTAMP NOT NULL,
+	expires_at TIMESTAMP NOT NULL,
+	revoked_at TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS pending_server (
+	id SERIAL PRIMARY KEY,
+	ip VARCHAR(15) NOT NULL,
+	port VARCHAR(5) NOT NULL,
+	enrollment_token_id INTEGER NOT NULL REFERENCES enrollment_token(id),
+	status VARCHAR(16) NOT NULL,
+	server_id INTEGER REFERENCES server(id) ON DELETE SET NULL,
+	decided_by VARCHAR(255),
+	created_at TIMESTAMP NOT NULL,
+	last_seen_at TIMESTAMP NOT NULL,
+	decided_at TIMESTAMP,
+	UNIQUE (ip, port)
+);
+
+CREATE TABLE IF NOT EXISTS server_credential (
+	server_id INTEGER PRIMARY KEY REFERENCES server(id) ON DELETE CASCADE,
+	token_hash VARCHAR(255) NOT NULL,
+	created_at TIMESTAMP NOT NULL
+);
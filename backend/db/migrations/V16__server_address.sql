@@ -0,0 +1,7 @@
+ALTER TABLE server ALTER COLUMN ip TYPE INET USING ip::inet;
+ALTER TABLE server ALTER COLUMN port TYPE INTEGER USING port::integer;
+ALTER TABLE server ADD COLUMN hostname VARCHAR(253);
+ALTER TABLE server ADD COLUMN resolved_at TIMESTAMP;
+
+ALTER TABLE pending_server ALTER COLUMN ip TYPE INET USING ip::inet;
+ALTER TABLE pending_server ALTER COLUMN port TYPE INTEGER USING port::integer;
@@ -0,0 +1,11 @@
+CREATE TABLE IF NOT EXISTS match_schedule (
+	game_match_id INTEGER PRIMARY KEY REFERENCES game_match(id) ON DELETE CASCADE,
+	scheduled_at TIMESTAMP NOT NULL,
+	status VARCHAR(16) NOT NULL,
+	server_id INTEGER REFERENCES server(id),
+	reserved_at TIMESTAMP,
+	started_at TIMESTAMP,
+	created_at TIMESTAMP NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS match_schedule_status_idx ON match_schedule (status, scheduled_at);
@@ -0,0 +1,30 @@
+CREATE TABLE IF NOT EXISTS steam_trust_check (
+	steamid64 VARCHAR(80) PRIMARY KEY,
+	vac_banned BOOLEAN NOT NULL,
+	number_of_vac_bans INTEGER NOT NULL,
+	number_of_game_bans INTEGER NOT NULL,
+	days_since_last_ban INTEGER NOT NULL,
+	community_banned BOOLEAN NOT NULL,
+	economy_ban VARCHAR(16) NOT NULL,
+	time_created BIGINT,
+	public_profile BOOLEAN NOT NULL,
+	verdict VARCHAR(8) NOT NULL,
+	reasons VARCHAR(255) NOT NULL,
+	checked_at TIMESTAMP NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS trust_policy (
+	id INTEGER PRIMARY KEY CHECK (id = 1),
+	vac_ban_action VARCHAR(8) NOT NULL DEFAULT 'block',
+	game_ban_action VARCHAR(8) NOT NULL DEFAULT 'flag',
+	community_ban_action VARCHAR(8) NOT NULL DEFAULT 'flag',
+	economy_ban_action VARCHAR(8) NOT NULL DEFAULT 'allow',
+	private_profile_action VARCHAR(8) NOT NULL DEFAULT 'flag',
+	new_account_action VARCHAR(8) NOT NULL DEFAULT 'flag',
+	min_account_age_days INTEGER NOT NULL DEFAULT 30,
+	ignore_bans_older_than_days INTEGER,
+	updated_by VARCHAR(80),
+	updated_at TIMESTAMP NOT NULL
+);
+
+INSERT INTO trust_policy (id, updated_at) VALUES (1, now()) ON CONFLICT DO NOTHING;
@@ -0,0 +1,5 @@
+CREATE TABLE IF NOT EXISTS live_match_snapshot (
+	game_match_id INTEGER PRIMARY KEY,
+	state TEXT NOT NULL,
+	created_at TIMESTAMP NOT NULL
+);
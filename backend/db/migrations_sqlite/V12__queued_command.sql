@@ -0,0 +1,16 @@
+CREATE TABLE IF NOT EXISTS queued_command (
+	seq INTEGER PRIMARY KEY AUTOINCREMENT,
+	server_id INTEGER NOT NULL REFERENCES server(id) ON DELETE CASCADE,
+	action VARCHAR(64) NOT NULL,
+	payload TEXT NOT NULL,
+	status VARCHAR(16) NOT NULL,
+	attempts INTEGER NOT NULL DEFAULT 0,
+	next_attempt_at TIMESTAMP NOT NULL,
+	expires_at TIMESTAMP NOT NULL,
+	last_error VARCHAR(255),
+	created_at TIMESTAMP NOT NULL,
+	acked_at TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS queued_command_status_idx ON queued_command (status, next_attempt_at);
+CREATE INDEX IF NOT EXISTS queued_command_server_idx ON queued_command (server_id, status);
@@ -0,0 +1,33 @@
+CREATE TABLE server_new (
+	id INTEGER PRIMARY KEY AUTOINCREMENT,
+	ip VARCHAR(45) NOT NULL,
+	port INTEGER NOT NULL,
+	hostname VARCHAR(253),
+	resolved_at TIMESTAMP,
+	created_at TIMESTAMP NOT NULL,
+	UNIQUE (ip, port)
+);
+INSERT INTO server_new (id, ip, port, created_at)
+	SELECT id, trim(ip), CAST(port AS INTEGER), created_at FROM server;
+DROP TABLE server;
+ALTER TABLE server_new RENAME TO server;
+
+CREATE TABLE pending_server_new (
+	id INTEGER PRIMARY KEY AUTOINCREMENT,
+	ip VARCHAR(45) NOT NULL,
+	port INTEGER NOT NULL,
+	enrollment_token_id INTEGER NOT NULL REFERENCES enrollment_token(id),
+	status VARCHAR(16) NOT NULL,
+	server_id INTEGER REFERENCES server(id) ON DELETE SET NULL,
+	decided_by VARCHAR(255),
+	created_at TIMESTAMP NOT NULL,
+	last_seen_at TIMESTAMP NOT NULL,
+	decided_at TIMESTAMP,
+	UNIQUE (ip, port)
+);
+INSERT INTO pending_server_new
+	SELECT id, trim(ip), CAST(port AS INTEGER), enrollment_token_id, status, server_id, decided_by,
+		created_at, last_seen_at, decided_at
+	FROM pending_server;
+DROP TABLE pending_server;
+ALTER TABLE pending_server_new RENAME TO pending_server;
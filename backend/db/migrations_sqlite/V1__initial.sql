@@ -0,0 +1,5 @@
+CREATE TABLE IF NOT EXISTS app_user (
+		steamid64 VARCHAR(80) PRIMARY KEY,
+		is_admin BOOLEAN NOT NULL DEFAULT FALSE,
+		created_at TIMESTAMP NOT NULL
+);
\ No newline at end of file
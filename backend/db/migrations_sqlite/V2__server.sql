@@ -0,0 +1,7 @@
+CREATE TABLE IF NOT EXISTS server (
+	id INTEGER PRIMARY KEY AUTOINCREMENT,
+	ip VARCHAR(15) NOT NULL,
+	port VARCHAR(5) NOT NULL,
+	created_at TIMESTAMP NOT NULL,
+	UNIQUE (ip, port)
+);
\ No newline at end of file
@@ -0,0 +1,45 @@
+CREATE TABLE IF NOT EXISTS team (
+	id INTEGER PRIMARY KEY AUTOINCREMENT,
+	name VARCHAR(64) NOT NULL UNIQUE,
+	tag VARCHAR(12) NOT NULL,
+	logo VARCHAR(255),
+	captain_steamid64 VARCHAR(80) NOT NULL REFERENCES app_user(steamid64),
+	created_at TIMESTAMP NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS team_member (
+	team_id INTEGER NOT NULL REFERENCES team(id) ON DELETE CASCADE,
+	steamid64 VARCHAR(80) NOT NULL REFERENCES app_user(steamid64),
+	role VARCHAR(16) NOT NULL,
+	joined_at TIMESTAMP NOT NULL,
+	PRIMARY KEY (team_id, steamid64)
+);
+
+CREATE TABLE IF NOT EXISTS team_invite (
+	id INTEGER PRIMARY KEY AUTOINCREMENT,
+	team_id INTEGER NOT NULL REFERENCES team(id) ON DELETE CASCADE,
+	steamid64 VARCHAR(80) NOT NULL,
+	role VARCHAR(16) NOT NULL,
+	invited_by VARCHAR(80) NOT NULL,
+	created_at TIMESTAMP NOT NULL,
+	UNIQUE (team_id, steamid64)
+);
+
+CREATE TABLE IF NOT EXISTS team_roster_event (
+	id INTEGER PRIMARY KEY AUTOINCREMENT,
+	team_id INTEGER NOT NULL REFERENCES team(id) ON DELETE CASCADE,
+	steamid64 VARCHAR(80) NOT NULL,
+	event VARCHAR(16) NOT NULL,
+	role VARCHAR(16),
+	actor_steamid64 VARCHAR(80) NOT NULL,
+	created_at TIMESTAMP NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS game_match (
+	id INTEGER PRIMARY KEY AUTOINCREMENT,
+	team1_id INTEGER NOT NULL REFERENCES team(id),
+	team2_id INTEGER NOT NULL REFERENCES team(id),
+	server_id INTEGER REFERENCES server(id),
+	created_at TIMESTAMP NOT NULL,
+	CHECK (team1_id <> team2_id)
+);
@@ -0,0 +1,38 @@
+ALTER TABLE game_match ADD COLUMN winner_team_id INTEGER REFERENCES team(id);
+ALTER TABLE game_match ADD COLUMN finished_at TIMESTAMP;
+
+CREATE TABLE IF NOT EXISTS tournament (
+	id INTEGER PRIMARY KEY AUTOINCREMENT,
+	name VARCHAR(64) NOT NULL,
+	format VARCHAR(24) NOT NULL,
+	status VARCHAR(16) NOT NULL,
+	swiss_rounds INTEGER,
+	winner_team_id INTEGER REFERENCES team(id),
+	created_at TIMESTAMP NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS tournament_team (
+	tournament_id INTEGER NOT NULL REFERENCES tournament(id) ON DELETE CASCADE,
+	team_id INTEGER NOT NULL REFERENCES team(id),
+	seed INTEGER NOT NULL,
+	PRIMARY KEY (tournament_id, team_id)
+);
+
+CREATE TABLE IF NOT EXISTS tournament_match (
+	id INTEGER PRIMARY KEY AUTOINCREMENT,
+	tournament_id INTEGER NOT NULL REFERENCES tournament(id) ON DELETE CASCADE,
+	bracket VARCHAR(16) NOT NULL,
+	round INTEGER NOT NULL,
+	position INTEGER NOT NULL,
+	status VARCHAR(16) NOT NULL,
+	team1_id INTEGER REFERENCES team(id),
+	team2_id INTEGER REFERENCES team(id),
+	winner_team_id INTEGER REFERENCES team(id),
+	loser_team_id INTEGER REFERENCES team(id),
+	game_match_id INTEGER REFERENCES game_match(id),
+	next_match_id INTEGER REFERENCES tournament_match(id),
+	next_match_slot INTEGER,
+	loser_next_match_id INTEGER REFERENCES tournament_match(id),
+	loser_next_match_slot INTEGER,
+	UNIQUE (tournament_id, bracket, round, position)
+);
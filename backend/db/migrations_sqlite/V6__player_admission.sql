@@ -0,0 +1,23 @@
+ALTER TABLE app_user ADD COLUMN is_caster BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE TABLE IF NOT EXISTS player_ban (
+	id INTEGER PRIMARY KEY AUTOINCREMENT,
+	steamid64 VARCHAR(80) NOT NULL,
+	reason VARCHAR(255) NOT NULL,
+	banned_by VARCHAR(80),
+	expires_at TIMESTAMP,
+	created_at TIMESTAMP NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS player_ban_steamid64_idx ON player_ban (steamid64);
+
+CREATE TABLE IF NOT EXISTS player_admission (
+	id INTEGER PRIMARY KEY AUTOINCREMENT,
+	server_id INTEGER NOT NULL REFERENCES server(id) ON DELETE CASCADE,
+	game_match_id INTEGER REFERENCES game_match(id) ON DELETE SET NULL,
+	steamid64 VARCHAR(80) NOT NULL,
+	allowed BOOLEAN NOT NULL,
+	team_id INTEGER REFERENCES team(id),
+	reason VARCHAR(32) NOT NULL,
+	created_at TIMESTAMP NOT NULL
+);
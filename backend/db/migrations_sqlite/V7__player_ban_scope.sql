@@ -0,0 +1,15 @@
+ALTER TABLE player_ban ADD COLUMN kind VARCHAR(16) NOT NULL DEFAULT 'game';
+ALTER TABLE player_ban ADD COLUMN scope VARCHAR(16) NOT NULL DEFAULT 'global';
+ALTER TABLE player_ban ADD COLUMN server_id INTEGER REFERENCES server(id) ON DELETE CASCADE;
+ALTER TABLE player_ban ADD COLUMN lifted_at TIMESTAMP;
+ALTER TABLE player_ban ADD COLUMN lifted_by VARCHAR(80);
+ALTER TABLE player_ban ADD COLUMN lift_reason VARCHAR(255);
+
+CREATE TABLE IF NOT EXISTS player_ban_event (
+	id INTEGER PRIMARY KEY AUTOINCREMENT,
+	ban_id INTEGER NOT NULL REFERENCES player_ban(id) ON DELETE CASCADE,
+	event VARCHAR(16) NOT NULL,
+	actor_steamid64 VARCHAR(80) NOT NULL,
+	reason VARCHAR(255),
+	created_at TIMESTAMP NOT NULL
+);
@@ -0,0 +1,14 @@
+CREATE TABLE IF NOT EXISTS audit_log (
+	id INTEGER PRIMARY KEY AUTOINCREMENT,
+	actor_steamid64 VARCHAR(80) NOT NULL,
+	source VARCHAR(8) NOT NULL,
+	action VARCHAR(128) NOT NULL,
+	target VARCHAR(255),
+	payload TEXT,
+	status INTEGER,
+	ip VARCHAR(45),
+	created_at TIMESTAMP NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS audit_log_created_at_idx ON audit_log (created_at);
+CREATE INDEX IF NOT EXISTS audit_log_actor_idx ON audit_log (actor_steamid64, created_at);
@@ -0,0 +1,212 @@
+use axum::{
+    http::{header, HeaderValue},
+    routing::{delete, get, post, put},
+    Extension, Router,
+};
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
+
+use crate::config::Config;
+use crate::middleware::{with_admin, with_auth, with_auth_qs, with_metrics, with_request_id};
+use crate::{routes, ws};
+
+/// Every route of the backend with its middlewares, served by `main` and by the integration tests
+pub fn build_router(config: &'static Config) -> Router {
+    let ws_router = Router::new()
+        .route(
+            "/user",
+            get(ws::user::on_user_connection).route_layer(axum::middleware::from_fn(with_auth_qs)),
+        )
+        .route("/server", get(ws::server::on_server_connection))
+        .route(
+            "/spectate/:match_id",
+            get(ws::spectator::on_spectator_connection),
+        );
+
+    let auth_router = Router::new()
+        .route("/login", get(routes::auth::login))
+        .route("/steam_callback", get(routes::auth::steam_callback));
+
+    let server_router = Router::new()
+        .route("/", post(routes::server::create_server))
+        .route("/:server_id/commands", post(routes::server::send_command))
+        .route("/:server_id/queue", get(routes::server::get_queue))
+        .route(
+            "/:server_id/queue/:seq/retry",
+            post(routes::server::retry_queued_command),
+        )
+        .route(
+            "/:server_id/log_token",
+            post(routes::server_log::create_log_token),
+        )
+        .route(
+            "/enrollment_tokens",
+            get(routes::enrollment::get_enrollment_tokens)
+                .post(routes::enrollment::create_enrollment_token),
+        )
+        .route(
+            "/enrollment_tokens/:token_id",
+            delete(routes::enrollment::revoke_enrollment_token),
+        )
+        .route("/pending", get(routes::enrollment::get_pending_servers))
+        .route(
+            "/pending/:pending_id/approve",
+            post(routes::enrollment::approve_server),
+        )
+        .route(
+            "/pending/:pending_id/reject",
+            post(routes::enrollment::reject_server),
+        )
+        .route_layer(axum::middleware::from_fn(with_admin));
+
+    let user_router = Router::new()
+        .route("/", get(routes::user::get_users))
+        .route("/:steamid64/caster", put(routes::user::set_caster))
+        .route("/:steamid64/trust", post(routes::user::refresh_trust))
+        .route_layer(axum::middleware::from_fn(with_admin));
+
+    let trust_router = Router::new()
+        .route(
+            "/policy",
+            get(routes::trust::get_policy).put(routes::trust::update_policy),
+        )
+        .route_layer(axum::middleware::from_fn(with_admin));
+
+    let ban_router = Router::new()
+        .route("/", get(routes::ban::get_bans).post(routes::ban::issue_ban))
+        .route("/:ban_id", get(routes::ban::get_ban))
+        .route("/:ban_id/lift", post(routes::ban::lift_ban))
+        .route_layer(axum::middleware::from_fn(with_admin));
+
+    let config_template_router = Router::new()
+        .route("/", get(routes::config_template::get_templates))
+        .route("/variables", get(routes::config_template::get_variables))
+        .route(
+            "/variables/:name",
+            put(routes::config_template::set_variable)
+                .delete(routes::config_template::delete_variable),
+        )
+        .route(
+            "/:phase",
+            get(routes::config_template::get_template)
+                .put(routes::config_template::save_template)
+                .delete(routes::config_template::delete_template),
+        )
+        .route(
+            "/:phase/versions/:version",
+            get(routes::config_template::get_template_version),
+        )
+        .route_layer(axum::middleware::from_fn(with_admin));
+
+    let audit_router = Router::new().route(
+        "/",
+        get(routes::audit::get_audit_log).route_layer(axum::middleware::from_fn(with_admin)),
+    );
+
+    let team_router = Router::new()
+        .route("/", post(routes::team::create_team))
+        .route("/invites", get(routes::team::get_my_invites))
+        .route("/invites/:invite_id", delete(routes::team::delete_invite))
+        .route(
+            "/invites/:invite_id/accept",
+            post(routes::team::accept_invite),
+        )
+        .route("/:team_id/invites", post(routes::team::invite_member))
+        .route("/:team_id/leave", post(routes::team::leave_team))
+        .route(
+            "/:team_id/members/:steamid64",
+            delete(routes::team::kick_member),
+        )
+        .route("/:team_id/captain", put(routes::team::transfer_captaincy))
+        .route_layer(axum::middleware::from_fn(with_auth))
+        .route("/", get(routes::team::get_teams))
+        .route("/:team_id", get(routes::team::get_team))
+        .route("/:team_id/history", get(routes::team::get_roster_history));
+
+    let match_router = Router::new()
+        .route("/", post(routes::game_match::create_match))
+        .route("/:match_id/result", post(routes::game_match::report_result))
+        .route(
+            "/:match_id/schedule",
+            put(routes::game_match::schedule_match).delete(routes::game_match::cancel_schedule),
+        )
+        .route("/:match_id/backups", get(routes::game_match::get_backups))
+        .route(
+            "/:match_id/backups/:round",
+            get(routes::game_match::get_backup),
+        )
+        .route(
+            "/:match_id/backups/:round/restore",
+            post(routes::game_match::restore_round),
+        )
+        .route_layer(axum::middleware::from_fn(with_admin))
+        .route("/:match_id", get(routes::game_match::get_match))
+        .route("/:match_id/stats", get(routes::server_log::get_match_stats));
+
+    let log_router = Router::new().route("/:server_id", post(routes::server_log::ingest_logs));
+
+    let tournament_router = Router::new()
+        .route("/", post(routes::tournament::create_tournament))
+        .route(
+            "/:tournament_id/teams",
+            post(routes::tournament::register_team),
+        )
+        .route(
+            "/:tournament_id/teams/:team_id",
+            delete(routes::tournament::unregister_team),
+        )
+        .route("/:tournament_id/seeds", put(routes::tournament::seed_teams))
+        .route(
+            "/:tournament_id/start",
+            post(routes::tournament::start_tournament),
+        )
+        .route_layer(axum::middleware::from_fn(with_admin))
+        .route("/", get(routes::tournament::get_tournaments))
+        .route("/:tournament_id", get(routes::tournament::get_tournament))
+        .route(
+            "/:tournament_id/standings",
+            get(routes::tournament::get_standings),
+        );
+
+    Router::new()
+        .nest(
+            "/api",
+            Router::new()
+                .nest("/auth", auth_router)
+                .nest("/servers", server_router)
+                .nest("/users", user_router)
+                .nest("/bans", ban_router)
+                .nest("/trust", trust_router)
+                .nest("/audit", audit_router)
+                .nest("/config_templates", config_template_router)
+                .nest("/teams", team_router)
+                .nest("/matches", match_router)
+                .nest("/tournaments", tournament_router)
+                .nest("/logs", log_router),
+        )
+        .nest("/ws", ws_router)
+        .route("/healthz", get(routes::health::healthz))
+        .route("/readyz", get(routes::health::readyz))
+        .route("/metrics", get(routes::health::get_metrics))
+        .layer(axum::middleware::from_fn(with_metrics))
+        .layer(Extension(config))
+        .layer(axum::middleware::from_fn(with_request_id))
+        .layer(build_cors(config))
+}
+
+fn build_cors(config: &Config) -> CorsLayer {
+    let origins = &config.server.cors_origins;
+    let allow_origin = if origins.iter().any(|origin| origin == "*") {
+        AllowOrigin::any()
+    } else {
+        //origins were validated when the config was loaded
+        AllowOrigin::list(
+            origins
+                .iter()
+                .filter_map(|origin| HeaderValue::from_str(origin).ok()),
+        )
+    };
+    CorsLayer::new()
+        .allow_origin(allow_origin)
+        .allow_methods(Any)
+        .allow_headers(vec![header::AUTHORIZATION, header::CONTENT_TYPE])
+}
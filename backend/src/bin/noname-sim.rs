@@ -0,0 +1,630 @@
+//! Load and protocol tester: spawns fake game servers on `/ws/server` cycling through a match, plus fake
+//! admins polling `admin_get_servers` on `/ws/user`, and reports throughput and latency.
+//!
+//! It reads the backend config (config.toml and the environment) to seed its admins, servers and the
+//! matches they host straight into the database and to mint the admin tokens, so run it next to the
+//! backend it targets. The seeded rows are deleted again when the run ends or is interrupted.
+//!
+//! Built with `cargo run --features sim --bin noname-sim`.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use clap::Parser;
+use dotenv::dotenv;
+use futures_util::{SinkExt, StreamExt};
+use noname::config::Config;
+use noname::driver::inet::InetAddr;
+use noname::model::game_match::{self, GameMatch};
+use noname::model::live_match_snapshot::LiveMatchSnapshot;
+use noname::model::team::{self, Team};
+use noname::model::{server, user};
+use noname::service::auth::create_access_token;
+use noname::ws::server::ServerStatus;
+use noname::ws::spectator::{LiveMatch, LivePlayer};
+use noname::{driver::db, global};
+use rbatis::rbdc::datetime::FastDateTime;
+use serde::Deserialize;
+use serde_json::json;
+use tokio::time::Instant;
+use tokio_tungstenite::tungstenite::{client::IntoClientRequest, http::HeaderValue, Message};
+
+/// Fake servers register as this ip, it has to pass `game_servers.allowlist`
+const SERVER_IP: &str = "127.0.0.1";
+/// Fake admins and players get steamids counting up from here, below the range of real accounts
+const SIM_STEAMID_BASE: u64 = 76561190009000000;
+/// Captains of the two teams playing every simulated match
+const CAPTAIN_STEAMID_OFFSET: u64 = 5_000;
+const PLAYER_STEAMID_OFFSET: u64 = 10_000;
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Time spent in each phase of a match on a real server, before `--time-scale`
+const IDLE_SECS: f64 = 30.0;
+const WAITING_FOR_PLAYERS_SECS: f64 = 120.0;
+const STARTING_SECS: f64 = 15.0;
+const KNIFE_ROUND_SECS: f64 = 60.0;
+const ROUND_SECS: f64 = 105.0;
+const ENDING_SECS: f64 = 20.0;
+const ROUNDS_TO_WIN: u32 = 16;
+const MAX_ROUNDS: u32 = 30;
+
+#[derive(Parser)]
+#[command(about = "Simulates game servers and admins against a running noname backend")]
+struct Options {
+    /// Backend to target, defaults to the host and port of the backend config
+    #[arg(long)]
+    url: Option<String>,
+    /// Fake game servers to connect
+    #[arg(long, default_value_t = 10)]
+    servers: u32,
+    /// Fake admins polling the server list
+    #[arg(long, default_value_t = 2)]
+    admins: u32,
+    /// Port of the first fake server, the others count up from it
+    #[arg(long, default_value_t = 27100)]
+    base_port: u32,
+    #[arg(long, default_value_t = 60)]
+    duration_secs: u64,
+    /// Multiplies the duration of every match phase, 0.01 plays a full match in about a minute
+    #[arg(long, default_value_t = 1.0)]
+    time_scale: f64,
+    /// Delay between two `admin_get_servers` of the same admin
+    #[arg(long, default_value_t = 1000)]
+    admin_interval_ms: u64,
+    #[arg(long, default_value_t = 5)]
+    report_secs: u64,
+}
+
+#[derive(Default)]
+struct Stats {
+    servers_connected: AtomicU64,
+    admins_connected: AtomicU64,
+    server_messages_sent: AtomicU64,
+    server_messages_received: AtomicU64,
+    admin_requests: AtomicU64,
+    errors: AtomicU64,
+    /// Latencies since the last report
+    window: Mutex<Latencies>,
+    /// Latencies of the whole run
+    total: Mutex<Latencies>,
+}
+
+#[derive(Default, Clone)]
+struct Latencies {
+    connect: Vec<Duration>,
+    admin_response: Vec<Duration>,
+}
+
+impl Stats {
+    fn record(&self, f: impl Fn(&mut Latencies)) {
+        f(&mut self.window.lock().unwrap());
+        f(&mut self.total.lock().unwrap());
+    }
+
+    fn error(&self, context: &str, error: impl std::fmt::Display) {
+        self.errors.fetch_add(1, Ordering::Relaxed);
+        eprintln!("{}: {}", context, error);
+    }
+}
+
+/// Small xorshift so every fake server gets its own reproducible jitter without another dependency
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Rng {
+        Rng(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1)
+    }
+
+    fn next_f64(&mut self) -> f64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// `secs` give or take 20%
+    fn jitter(&mut self, secs: f64) -> f64 {
+        secs * (0.8 + 0.4 * self.next_f64())
+    }
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let options = Arc::new(Options::parse());
+    dotenv().ok();
+    let config = Config::load()?;
+    db::init(&config.database.url)?;
+
+    let url = options
+        .url
+        .clone()
+        .unwrap_or_else(|| format!("http://127.0.0.1:{}", config.server.port));
+    let mut seeded = Seeded::default();
+    let outcome = tokio::select! {
+        outcome = simulate(&config, options, url, &mut seeded) => outcome,
+        _ = tokio::signal::ctrl_c() => {
+            println!("Interrupted");
+            Ok(())
+        }
+    };
+    seeded.delete().await;
+    outcome
+}
+
+async fn simulate(
+    config: &Config,
+    options: Arc<Options>,
+    url: String,
+    seeded: &mut Seeded,
+) -> anyhow::Result<()> {
+    let ws_url = url.replacen("http", "ws", 1);
+    let servers = seed_servers(&options, seeded).await?;
+    let tokens = seed_admins(config, options.admins, seeded).await?;
+    println!(
+        "Simulating {} servers and {} admins against {} for {}s",
+        servers.len(),
+        tokens.len(),
+        url,
+        options.duration_secs
+    );
+
+    let stats = Arc::new(Stats::default());
+    let started = Instant::now();
+    let deadline = started + Duration::from_secs(options.duration_secs);
+    let mut tasks = Vec::new();
+    for (index, (port, match_id)) in servers.into_iter().enumerate() {
+        tasks.push(tokio::spawn(run_server(
+            index as u32,
+            port,
+            match_id,
+            ws_url.clone(),
+            options.clone(),
+            stats.clone(),
+            deadline,
+        )));
+    }
+    for token in tokens {
+        tasks.push(tokio::spawn(run_admin(
+            token,
+            ws_url.clone(),
+            options.clone(),
+            stats.clone(),
+            deadline,
+        )));
+    }
+
+    let mut interval = tokio::time::interval(Duration::from_secs(options.report_secs.max(1)));
+    interval.tick().await;
+    let mut previous = Counters::read(&stats);
+    let mut previous_at = started;
+    while Instant::now() < deadline {
+        tokio::select! {
+            _ = interval.tick() => {}
+            _ = tokio::time::sleep_until(deadline) => break,
+        }
+        let now = Instant::now();
+        let counters = Counters::read(&stats);
+        let window = std::mem::take(&mut *stats.window.lock().unwrap());
+        print_report(
+            &format!("[{:>4}s]", started.elapsed().as_secs()),
+            &stats,
+            &counters.since(&previous),
+            now - previous_at,
+            &window,
+        );
+        previous = counters;
+        previous_at = now;
+    }
+
+    for task in tasks {
+        task.await.ok();
+    }
+    let total = stats.total.lock().unwrap().clone();
+    print_report(
+        "[total]",
+        &stats,
+        &Counters::read(&stats),
+        started.elapsed(),
+        &total,
+    );
+    Ok(())
+}
+
+/// Rows seeded for the run, only these are deleted afterwards
+#[derive(Default)]
+struct Seeded {
+    server_ids: Vec<u32>,
+    steamids: Vec<String>,
+    team_ids: Vec<u32>,
+    match_ids: Vec<u32>,
+}
+
+impl Seeded {
+    /// Children first, failures are reported and the rest is still deleted
+    async fn delete(self) {
+        let rb = &mut global::RB.clone();
+        for match_id in self.match_ids {
+            LiveMatchSnapshot::delete_by_column(rb, "game_match_id", match_id)
+                .await
+                .map_err(|e| eprintln!("Couldn't delete the snapshot of match {}: {}", match_id, e))
+                .ok();
+            GameMatch::delete_by_column(rb, "id", match_id)
+                .await
+                .map_err(|e| eprintln!("Couldn't delete match {}: {}", match_id, e))
+                .ok();
+        }
+        for team_id in self.team_ids {
+            Team::delete_by_column(rb, "id", team_id)
+                .await
+                .map_err(|e| eprintln!("Couldn't delete team {}: {}", team_id, e))
+                .ok();
+        }
+        for steamid64 in self.steamids {
+            user::User::delete_by_column(rb, "steamid64", &steamid64)
+                .await
+                .map_err(|e| eprintln!("Couldn't delete user {}: {}", steamid64, e))
+                .ok();
+        }
+        for server_id in self.server_ids {
+            server::Server::delete_by_column(rb, "id", server_id)
+                .await
+                .map_err(|e| eprintln!("Couldn't delete server {}: {}", server_id, e))
+                .ok();
+        }
+    }
+}
+
+/// Inserts a user of the simulated range, existing rows are refused so they're never deleted afterwards
+async fn seed_user(steamid64: u64, is_admin: bool, seeded: &mut Seeded) -> anyhow::Result<String> {
+    let mut new_user = user::User::from_steamid64(steamid64);
+    if user::select_by_steamid(&global::RB, new_user.steamid64.clone())
+        .await?
+        .is_some()
+    {
+        anyhow::bail!(
+            "User {} already exists, a previous run wasn't cleaned up",
+            steamid64
+        );
+    }
+    new_user.is_admin = is_admin;
+    user::User::insert(&mut global::RB.clone(), &new_user).await?;
+    seeded.steamids.push(new_user.steamid64.clone());
+    Ok(new_user.steamid64)
+}
+
+/// Registers the fake servers and a match hosted by each, so the backend accepts their match updates.
+/// Returns the port and the match id of every server.
+async fn seed_servers(
+    options: &Options,
+    seeded: &mut Seeded,
+) -> anyhow::Result<Vec<(String, u32)>> {
+    let now = FastDateTime::now();
+    let mut team_ids = Vec::new();
+    for index in 0..2 {
+        let captain = seed_user(
+            SIM_STEAMID_BASE + CAPTAIN_STEAMID_OFFSET + index,
+            false,
+            seeded,
+        )
+        .await?;
+        let name = format!("noname-sim {}", index + 1);
+        let sim_team = team::insert_returning(
+            &mut global::RB.clone(),
+            &name,
+            &format!("SIM{}", index + 1),
+            &None,
+            &captain,
+            &now,
+        )
+        .await
+        .map_err(|e| anyhow::anyhow!("Couldn't create the team {}: {}", name, e))?;
+        seeded.team_ids.push(sim_team.id.unwrap());
+        team_ids.push(sim_team.id.unwrap());
+    }
+
+    let mut servers = Vec::new();
+    let ip: InetAddr = SERVER_IP.parse()?;
+    for index in 0..options.servers {
+        let port = u16::try_from(options.base_port + index)?;
+        if server::select_by_address(&global::RB, &ip, port)
+            .await?
+            .is_some()
+        {
+            anyhow::bail!(
+                "A server is already registered at {}:{}, pick another --base-port",
+                SERVER_IP,
+                port
+            );
+        }
+        let server_id = server::insert_returning(&mut global::RB.clone(), &ip, port, &now)
+            .await?
+            .id
+            .unwrap();
+        seeded.server_ids.push(server_id);
+        let hosted = game_match::insert_returning(
+            &mut global::RB.clone(),
+            team_ids[0],
+            team_ids[1],
+            Some(server_id),
+            &now,
+        )
+        .await?;
+        seeded.match_ids.push(hosted.id.unwrap());
+        servers.push((port.to_string(), hosted.id.unwrap()));
+    }
+    Ok(servers)
+}
+
+/// Every admin needs its own steamid, the backend routes responses to the first connection of a user
+async fn seed_admins(
+    config: &Config,
+    count: u32,
+    seeded: &mut Seeded,
+) -> anyhow::Result<Vec<String>> {
+    let mut tokens = Vec::new();
+    for index in 0..count {
+        let steamid64 = seed_user(SIM_STEAMID_BASE + index as u64, true, seeded).await?;
+        tokens.push(
+            create_access_token(config, steamid64)
+                .map_err(|e| anyhow::anyhow!("Couldn't mint an admin token: {:?}", e))?,
+        );
+    }
+    Ok(tokens)
+}
+
+async fn run_server(
+    index: u32,
+    port: String,
+    match_id: u32,
+    ws_url: String,
+    options: Arc<Options>,
+    stats: Arc<Stats>,
+    deadline: Instant,
+) {
+    let mut request = match format!("{}/ws/server", ws_url).into_client_request() {
+        Ok(request) => request,
+        Err(e) => return stats.error("Invalid server url", e),
+    };
+    request
+        .headers_mut()
+        .insert("PORT", HeaderValue::from_str(&port).unwrap());
+    let connecting = Instant::now();
+    let (socket, _) = match tokio_tungstenite::connect_async(request).await {
+        Ok(connection) => connection,
+        Err(e) => return stats.error(&format!("Server {} couldn't connect", port), e),
+    };
+    stats.record(|latencies| latencies.connect.push(connecting.elapsed()));
+    stats.servers_connected.fetch_add(1, Ordering::Relaxed);
+    let (mut sender, mut receiver) = socket.split();
+
+    let reader_stats = stats.clone();
+    let reader = tokio::spawn(async move {
+        while let Some(Ok(message)) = receiver.next().await {
+            if message.is_text() {
+                reader_stats
+                    .server_messages_received
+                    .fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    });
+
+    let scale = options.time_scale;
+    let mut rng = Rng::new(index as u64 + 1);
+    let sleep = |secs: f64| tokio::time::sleep(Duration::from_secs_f64((secs * scale).max(0.0)));
+    let send = |message: serde_json::Value| {
+        stats.server_messages_sent.fetch_add(1, Ordering::Relaxed);
+        Message::Text(message.to_string())
+    };
+
+    let cycle = async {
+        //servers start at different points of the cycle so they don't all switch at once
+        sleep(rng.jitter(IDLE_SECS + WAITING_FOR_PLAYERS_SECS)).await;
+        loop {
+            for (status, secs) in [
+                (ServerStatus::Idle, IDLE_SECS),
+                (ServerStatus::WaitingForPlayers, WAITING_FOR_PLAYERS_SECS),
+                (ServerStatus::Starting, STARTING_SECS),
+                (ServerStatus::KnifeRound, KNIFE_ROUND_SECS),
+            ] {
+                let message = json!({
+                    "action": "server_2_backend_update_status",
+                    "data": { "status": status },
+                });
+                sender.send(send(message)).await?;
+                sleep(rng.jitter(secs)).await;
+            }
+
+            let mut live_match = LiveMatch {
+                match_id,
+                status: ServerStatus::Live,
+                round: 0,
+                team1_score: 0,
+                team2_score: 0,
+                players: (0..10)
+                    .map(|player| LivePlayer {
+                        steamid64: (SIM_STEAMID_BASE
+                            + PLAYER_STEAMID_OFFSET
+                            + index as u64 * 10
+                            + player)
+                            .to_string(),
+                        name: format!("sim-{}-{}", index, player),
+                        kills: 0,
+                        deaths: 0,
+                    })
+                    .collect(),
+                winner_team_id: None,
+            };
+            while live_match.round < MAX_ROUNDS
+                && live_match.team1_score < ROUNDS_TO_WIN
+                && live_match.team2_score < ROUNDS_TO_WIN
+            {
+                live_match.round += 1;
+                if rng.next_f64() < 0.5 {
+                    live_match.team1_score += 1;
+                } else {
+                    live_match.team2_score += 1;
+                }
+                for player in live_match.players.iter_mut() {
+                    player.kills += (rng.next_f64() * 3.0) as u32;
+                    player.deaths += (rng.next_f64() * 1.5) as u32;
+                }
+                let message = json!({
+                    "action": "server_2_backend_match_update",
+                    "data": { "match_update": &live_match },
+                });
+                sender.send(send(message)).await?;
+                sleep(rng.jitter(ROUND_SECS)).await;
+            }
+
+            //no winner is reported so the seeded match stays active for the next cycle
+            live_match.status = ServerStatus::Ending;
+            let message = json!({
+                "action": "server_2_backend_match_update",
+                "data": { "match_update": &live_match },
+            });
+            sender.send(send(message)).await?;
+            sleep(rng.jitter(ENDING_SECS)).await;
+        }
+        #[allow(unreachable_code)]
+        Ok::<(), tokio_tungstenite::tungstenite::Error>(())
+    };
+
+    if let Ok(Err(e)) = tokio::time::timeout_at(deadline, cycle).await {
+        stats.error(&format!("Server {} lost its connection", port), e);
+    }
+    sender.send(Message::Close(None)).await.ok();
+    reader.abort();
+}
+
+#[derive(Deserialize)]
+struct UserResponse {
+    action: String,
+}
+
+async fn run_admin(
+    token: String,
+    ws_url: String,
+    options: Arc<Options>,
+    stats: Arc<Stats>,
+    deadline: Instant,
+) {
+    let connecting = Instant::now();
+    let (mut socket, _) =
+        match tokio_tungstenite::connect_async(format!("{}/ws/user?token={}", ws_url, token)).await
+        {
+            Ok(connection) => connection,
+            Err(e) => return stats.error("Admin couldn't connect", e),
+        };
+    stats.record(|latencies| latencies.connect.push(connecting.elapsed()));
+    stats.admins_connected.fetch_add(1, Ordering::Relaxed);
+
+    let polling = async {
+        loop {
+            let sent = Instant::now();
+            socket
+                .send(Message::Text("admin_get_servers".to_string()))
+                .await
+                .map_err(|e| e.to_string())?;
+            stats.admin_requests.fetch_add(1, Ordering::Relaxed);
+            loop {
+                let message = tokio::time::timeout(RESPONSE_TIMEOUT, socket.next())
+                    .await
+                    .map_err(|_| "No response_get_servers in time".to_string())?
+                    .ok_or_else(|| "The backend closed the connection".to_string())?
+                    .map_err(|e| e.to_string())?;
+                let is_response = message
+                    .to_text()
+                    .ok()
+                    .and_then(|text| serde_json::from_str::<UserResponse>(text).ok())
+                    .is_some_and(|response| response.action == "response_get_servers");
+                if is_response {
+                    break;
+                }
+            }
+            stats.record(|latencies| latencies.admin_response.push(sent.elapsed()));
+            tokio::time::sleep(Duration::from_millis(options.admin_interval_ms)).await;
+        }
+        #[allow(unreachable_code)]
+        Ok::<(), String>(())
+    };
+
+    if let Ok(Err(e)) = tokio::time::timeout_at(deadline, polling).await {
+        stats.error("Admin stopped polling", e);
+    }
+    socket.send(Message::Close(None)).await.ok();
+}
+
+struct Counters {
+    server_messages_sent: u64,
+    server_messages_received: u64,
+    admin_requests: u64,
+    errors: u64,
+}
+
+impl Counters {
+    fn read(stats: &Stats) -> Counters {
+        Counters {
+            server_messages_sent: stats.server_messages_sent.load(Ordering::Relaxed),
+            server_messages_received: stats.server_messages_received.load(Ordering::Relaxed),
+            admin_requests: stats.admin_requests.load(Ordering::Relaxed),
+            errors: stats.errors.load(Ordering::Relaxed),
+        }
+    }
+
+    fn since(&self, previous: &Counters) -> Counters {
+        Counters {
+            server_messages_sent: self.server_messages_sent - previous.server_messages_sent,
+            server_messages_received: self.server_messages_received
+                - previous.server_messages_received,
+            admin_requests: self.admin_requests - previous.admin_requests,
+            errors: self.errors - previous.errors,
+        }
+    }
+}
+
+fn print_report(
+    label: &str,
+    stats: &Stats,
+    counters: &Counters,
+    elapsed: Duration,
+    latencies: &Latencies,
+) {
+    let secs = elapsed.as_secs_f64().max(0.001);
+    println!(
+        "{} servers {} admins {} | server msgs out {} ({:.1}/s) in {} ({:.1}/s) | admin requests {} ({:.1}/s) | errors {}",
+        label,
+        stats.servers_connected.load(Ordering::Relaxed),
+        stats.admins_connected.load(Ordering::Relaxed),
+        counters.server_messages_sent,
+        counters.server_messages_sent as f64 / secs,
+        counters.server_messages_received,
+        counters.server_messages_received as f64 / secs,
+        counters.admin_requests,
+        counters.admin_requests as f64 / secs,
+        counters.errors,
+    );
+    print_latencies("admin_get_servers", &latencies.admin_response);
+    print_latencies("connect", &latencies.connect);
+}
+
+fn print_latencies(name: &str, samples: &[Duration]) {
+    if samples.is_empty() {
+        return;
+    }
+    let mut samples = samples.to_vec();
+    samples.sort();
+    let percentile = |p: f64| {
+        let index = ((samples.len() as f64 * p).ceil() as usize).clamp(1, samples.len()) - 1;
+        samples[index].as_secs_f64() * 1000.0
+    };
+    println!(
+        "    {:<18} n={:<6} p50 {:.2}ms p95 {:.2}ms p99 {:.2}ms max {:.2}ms",
+        name,
+        samples.len(),
+        percentile(0.50),
+        percentile(0.95),
+        percentile(0.99),
+        percentile(1.0),
+    );
+}
@@ -0,0 +1,578 @@
+use std::net::{IpAddr, SocketAddr};
+use std::path::Path;
+use std::str::FromStr;
+
+use anyhow::{anyhow, Context};
+use axum::http::HeaderValue;
+use serde::Deserialize;
+
+use crate::driver::db::DbKind;
+
+/// Used when `CONFIG_FILE` isn't set, a missing default file is not an error
+const DEFAULT_CONFIG_FILE: &str = "config.toml";
+/// Durations are added to timestamps and instants, which panics once they overflow
+const MAX_DURATION_SECS: u64 = 365 * 86400;
+
+/// Backend settings, read from a TOML file and overridden by environment variables.
+/// See `config.example.toml` for every key and the variable overriding it.
+#[derive(Deserialize, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub database: DatabaseConfig,
+    pub steam: SteamConfig,
+    pub auth: AuthConfig,
+    pub websocket: WebsocketConfig,
+    pub scheduler: SchedulerConfig,
+    pub command_queue: CommandQueueConfig,
+    pub game_servers: GameServersConfig,
+    pub log_listener: LogListenerConfig,
+    pub server_query: ServerQueryConfig,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub host: String,
+    pub port: u16,
+    /// Public URL of the API, Steam redirects back to it after a login
+    pub api_url: String,
+    /// `*` allows any origin
+    pub cors_origins: Vec<String>,
+    /// Time left to in-flight requests, websocket clients and background tasks once a shutdown signal arrives
+    pub shutdown_grace_secs: u64,
+    /// Sent to websocket clients with `backend_shutting_down`, how long they should wait before reconnecting
+    pub reconnect_delay_secs: u64,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            host: "0.0.0.0".to_string(),
+            port: 1337,
+            api_url: "http://localhost:1337".to_string(),
+            cors_origins: vec!["*".to_string()],
+            shutdown_grace_secs: 30,
+            reconnect_delay_secs: 5,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    pub url: String,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct SteamConfig {
+    pub api_key: String,
+    /// Overridable so the Steam Web API can be replaced by a local mock
+    pub api_url: String,
+    pub trust_cache_secs: u64,
+}
+
+impl Default for SteamConfig {
+    fn default() -> Self {
+        SteamConfig {
+            api_key: String::new(),
+            api_url: "https://api.steampowered.com".to_string(),
+            trust_cache_secs: 86400,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthConfig {
+    pub jwt_key: String,
+    pub access_token_lifetime_secs: u64,
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        AuthConfig {
+            jwt_key: String::new(),
+            access_token_lifetime_secs: 3600,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct WebsocketConfig {
+    pub max_message_size: usize,
+    pub max_frame_size: usize,
+    /// 0 means no limit
+    pub max_spectators_per_match: usize,
+    pub spectator_throttle_ms: u64,
+    /// Servers waiting for approval are closed after this long without sending anything
+    pub pending_idle_timeout_secs: u64,
+}
+
+impl Default for WebsocketConfig {
+    fn default() -> Self {
+        WebsocketConfig {
+            max_message_size: 64 << 10,
+            max_frame_size: 16 << 10,
+            max_spectators_per_match: 0,
+            spectator_throttle_ms: 1000,
+            pending_idle_timeout_secs: 120,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct SchedulerConfig {
+    pub tick_secs: u64,
+    /// How long before the scheduled start a server gets reserved for a match
+    pub reserve_ahead_secs: u64,
+    /// How long the teams have to show up once a scheduled match was pushed to its server
+    pub forfeit_timeout_secs: u64,
+}
+
+impl Default for SchedulerConfig {
+    fn default() -> Self {
+        SchedulerConfig {
+            tick_secs: 10,
+            reserve_ahead_secs: 600,
+            forfeit_timeout_secs: 900,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct CommandQueueConfig {
+    pub tick_secs: u64,
+    /// Delay before the first retry of an unacknowledged command, doubled on every attempt
+    pub retry_base_secs: u64,
+    pub retry_max_secs: u64,
+    /// Sends after which a command is dead-lettered
+    pub max_attempts: u32,
+    /// Commands not acknowledged by then are dead-lettered, even while their server is offline
+    pub expiry_secs: u64,
+}
+
+impl Default for CommandQueueConfig {
+    fn default() -> Self {
+        CommandQueueConfig {
+            tick_secs: 2,
+            retry_base_secs: 2,
+            retry_max_secs: 60,
+            max_attempts: 10,
+            expiry_secs: 900,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct GameServersConfig {
+    /// IPs game servers may connect from on top of being registered, empty allows any registered server
+    pub allowlist: Vec<String>,
+    /// How often servers registered by hostname get their address resolved again
+    pub resolve_interval_secs: u64,
+}
+
+impl Default for GameServersConfig {
+    fn default() -> Self {
+        GameServersConfig {
+            allowlist: Vec::new(),
+            resolve_interval_secs: 300,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogListenerConfig {
+    /// Listens for `logaddress_add` packets, servers posting over HTTP don't need it
+    pub enabled: bool,
+    pub bind: String,
+    /// `sv_logsecret` of the servers, packets without it are dropped once it is set
+    pub secret: Option<String>,
+}
+
+impl Default for LogListenerConfig {
+    fn default() -> Self {
+        LogListenerConfig {
+            enabled: false,
+            bind: "0.0.0.0:27500".to_string(),
+            secret: None,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerQueryConfig {
+    /// Probes every registered server with A2S queries
+    pub enabled: bool,
+    pub interval_secs: u64,
+    /// How long a server has to answer each query
+    pub timeout_ms: u64,
+}
+
+impl Default for ServerQueryConfig {
+    fn default() -> Self {
+        ServerQueryConfig {
+            enabled: true,
+            interval_secs: 30,
+            timeout_ms: 1000,
+        }
+    }
+}
+
+impl Config {
+    /// Reads the file named by `CONFIG_FILE` (or `config.toml` when it exists), applies the environment
+    /// overrides and validates the result. Every problem is reported at once.
+    pub fn load() -> anyhow::Result<Config> {
+        let mut config = match std::env::var("CONFIG_FILE") {
+            Ok(path) => Config::from_file(Path::new(&path))?,
+            Err(_) if Path::new(DEFAULT_CONFIG_FILE).exists() => {
+                Config::from_file(Path::new(DEFAULT_CONFIG_FILE))?
+            }
+            Err(_) => Config::default(),
+        };
+
+        let mut errors = Vec::new();
+        config.apply_env(&mut errors);
+        config.validate(&mut errors);
+        if !errors.is_empty() {
+            return Err(anyhow!(
+                "Invalid configuration:\n  - {}",
+                errors.join("\n  - ")
+            ));
+        }
+        Ok(config)
+    }
+
+    fn from_file(path: &Path) -> anyhow::Result<Config> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Couldn't read config file {}", path.display()))?;
+        toml::from_str(&content)
+            .with_context(|| format!("Couldn't parse config file {}", path.display()))
+    }
+
+    fn apply_env(&mut self, errors: &mut Vec<String>) {
+        env_override("HOST", &mut self.server.host, errors);
+        env_override("PORT", &mut self.server.port, errors);
+        env_override("API_URL", &mut self.server.api_url, errors);
+        if let Ok(origins) = std::env::var("CORS_ORIGINS") {
+            self.server.cors_origins = split_list(&origins);
+        }
+        env_override(
+            "SHUTDOWN_GRACE_SECS",
+            &mut self.server.shutdown_grace_secs,
+            errors,
+        );
+        env_override(
+            "RECONNECT_DELAY_SECS",
+            &mut self.server.reconnect_delay_secs,
+            errors,
+        );
+        env_override("DATABASE_URL", &mut self.database.url, errors);
+        env_override("STEAM_KEY", &mut self.steam.api_key, errors);
+        env_override("STEAM_API_URL", &mut self.steam.api_url, errors);
+        env_override(
+            "STEAM_TRUST_CACHE_SECS",
+            &mut self.steam.trust_cache_secs,
+            errors,
+        );
+        env_override("JWT_KEY", &mut self.auth.jwt_key, errors);
+        env_override(
+            "ACCESS_TOKEN_LIFETIME_SECS",
+            &mut self.auth.access_token_lifetime_secs,
+            errors,
+        );
+        env_override(
+            "WS_MAX_MESSAGE_SIZE",
+            &mut self.websocket.max_message_size,
+            errors,
+        );
+        env_override(
+            "WS_MAX_FRAME_SIZE",
+            &mut self.websocket.max_frame_size,
+            errors,
+        );
+        env_override(
+            "WS_MAX_SPECTATORS_PER_MATCH",
+            &mut self.websocket.max_spectators_per_match,
+            errors,
+        );
+        env_override(
+            "SPECTATOR_THROTTLE_MS",
+            &mut self.websocket.spectator_throttle_ms,
+            errors,
+        );
+        env_override(
+            "WS_PENDING_IDLE_TIMEOUT_SECS",
+            &mut self.websocket.pending_idle_timeout_secs,
+            errors,
+        );
+        env_override("SCHEDULER_TICK_SECS", &mut self.scheduler.tick_secs, errors);
+        env_override(
+            "SERVER_RESERVE_AHEAD_SECS",
+            &mut self.scheduler.reserve_ahead_secs,
+            errors,
+        );
+        env_override(
+            "FORFEIT_TIMEOUT_SECS",
+            &mut self.scheduler.forfeit_timeout_secs,
+            errors,
+        );
+        env_override(
+            "COMMAND_QUEUE_TICK_SECS",
+            &mut self.command_queue.tick_secs,
+            errors,
+        );
+        env_override(
+            "COMMAND_RETRY_BASE_SECS",
+            &mut self.command_queue.retry_base_secs,
+            errors,
+        );
+        env_override(
+            "COMMAND_RETRY_MAX_SECS",
+            &mut self.command_queue.retry_max_secs,
+            errors,
+        );
+        env_override(
+            "COMMAND_MAX_ATTEMPTS",
+            &mut self.command_queue.max_attempts,
+            errors,
+        );
+        env_override(
+            "COMMAND_EXPIRY_SECS",
+            &mut self.command_queue.expiry_secs,
+            errors,
+        );
+        if let Ok(allowlist) = std::env::var("SERVER_ALLOWLIST") {
+            self.game_servers.allowlist = split_list(&allowlist);
+        }
+        env_override(
+            "SERVER_RESOLVE_INTERVAL_SECS",
+            &mut self.game_servers.resolve_interval_secs,
+            errors,
+        );
+        env_override(
+            "LOG_LISTENER_ENABLED",
+            &mut self.log_listener.enabled,
+            errors,
+        );
+        env_override("LOG_LISTENER_BIND", &mut self.log_listener.bind, errors);
+        if let Ok(secret) = std::env::var("LOG_SECRET") {
+            self.log_listener.secret = Some(secret).filter(|secret| !secret.is_empty());
+        }
+        env_override(
+            "SERVER_QUERY_ENABLED",
+            &mut self.server_query.enabled,
+            errors,
+        );
+        env_override(
+            "SERVER_QUERY_INTERVAL_SECS",
+            &mut self.server_query.interval_secs,
+            errors,
+        );
+        env_override(
+            "SERVER_QUERY_TIMEOUT_MS",
+            &mut self.server_query.timeout_ms,
+            errors,
+        );
+    }
+
+    fn validate(&self, errors: &mut Vec<String>) {
+        if IpAddr::from_str(&self.server.host).is_err() {
+            errors.push(format!(
+                "server.host (HOST) must be an IP address, got {}",
+                self.server.host
+            ));
+        }
+        if !is_http_url(&self.server.api_url) {
+            errors.push("server.api_url (API_URL) must be an http(s) URL".to_string());
+        }
+        if self.server.cors_origins.len() > 1 && self.server.cors_origins.iter().any(|o| o == "*") {
+            errors.push("server.cors_origins (CORS_ORIGINS) can't mix * with origins".to_string());
+        }
+        for origin in &self.server.cors_origins {
+            if origin != "*" && (!is_http_url(origin) || HeaderValue::from_str(origin).is_err()) {
+                errors.push(format!(
+                    "server.cors_origins (CORS_ORIGINS) has an invalid origin {}",
+                    origin
+                ));
+            }
+        }
+        if DbKind::from_url(&self.database.url).is_none() {
+            errors.push(
+                "database.url (DATABASE_URL) must be a postgres:// or sqlite:// URL".to_string(),
+            );
+        }
+        if self.steam.api_key.is_empty() {
+            errors.push("steam.api_key (STEAM_KEY) is not set".to_string());
+        }
+        if !is_http_url(&self.steam.api_url) {
+            errors.push("steam.api_url (STEAM_API_URL) must be an http(s) URL".to_string());
+        }
+        if self.auth.jwt_key.is_empty() {
+            errors.push("auth.jwt_key (JWT_KEY) is not set".to_string());
+        }
+        if self.auth.access_token_lifetime_secs == 0 {
+            errors.push(
+                "auth.access_token_lifetime_secs (ACCESS_TOKEN_LIFETIME_SECS) must be positive"
+                    .to_string(),
+            );
+        }
+        if self.websocket.max_frame_size == 0
+            || self.websocket.max_frame_size > self.websocket.max_message_size
+        {
+            errors.push(
+                "websocket.max_frame_size must be positive and at most websocket.max_message_size"
+                    .to_string(),
+            );
+        }
+        if self.scheduler.tick_secs == 0 {
+            errors.push("scheduler.tick_secs (SCHEDULER_TICK_SECS) must be positive".to_string());
+        }
+        if self.command_queue.tick_secs == 0 {
+            errors.push(
+                "command_queue.tick_secs (COMMAND_QUEUE_TICK_SECS) must be positive".to_string(),
+            );
+        }
+        if self.command_queue.retry_base_secs == 0
+            || self.command_queue.retry_base_secs > self.command_queue.retry_max_secs
+        {
+            errors.push(
+                "command_queue.retry_base_secs must be positive and at most command_queue.retry_max_secs"
+                    .to_string(),
+            );
+        }
+        if self.command_queue.max_attempts == 0 {
+            errors.push(
+                "command_queue.max_attempts (COMMAND_MAX_ATTEMPTS) must be positive".to_string(),
+            );
+        }
+        for ip in &self.game_servers.allowlist {
+            if IpAddr::from_str(ip).is_err() {
+                errors.push(format!(
+                    "game_servers.allowlist (SERVER_ALLOWLIST) has an invalid IP {}",
+                    ip
+                ));
+            }
+        }
+        if self.game_servers.resolve_interval_secs == 0 {
+            errors.push(
+                "game_servers.resolve_interval_secs (SERVER_RESOLVE_INTERVAL_SECS) must be positive"
+                    .to_string(),
+            );
+        }
+        if self.server_query.interval_secs == 0 {
+            errors.push(
+                "server_query.interval_secs (SERVER_QUERY_INTERVAL_SECS) must be positive"
+                    .to_string(),
+            );
+        }
+        if self.server_query.timeout_ms == 0 {
+            errors.push(
+                "server_query.timeout_ms (SERVER_QUERY_TIMEOUT_MS) must be positive".to_string(),
+            );
+        }
+        for (name, secs) in [
+            (
+                "server.shutdown_grace_secs (SHUTDOWN_GRACE_SECS)",
+                self.server.shutdown_grace_secs,
+            ),
+            (
+                "server.reconnect_delay_secs (RECONNECT_DELAY_SECS)",
+                self.server.reconnect_delay_secs,
+            ),
+            (
+                "steam.trust_cache_secs (STEAM_TRUST_CACHE_SECS)",
+                self.steam.trust_cache_secs,
+            ),
+            (
+                "auth.access_token_lifetime_secs (ACCESS_TOKEN_LIFETIME_SECS)",
+                self.auth.access_token_lifetime_secs,
+            ),
+            (
+                "websocket.pending_idle_timeout_secs (WS_PENDING_IDLE_TIMEOUT_SECS)",
+                self.websocket.pending_idle_timeout_secs,
+            ),
+            (
+                "scheduler.tick_secs (SCHEDULER_TICK_SECS)",
+                self.scheduler.tick_secs,
+            ),
+            (
+                "scheduler.reserve_ahead_secs (SERVER_RESERVE_AHEAD_SECS)",
+                self.scheduler.reserve_ahead_secs,
+            ),
+            (
+                "scheduler.forfeit_timeout_secs (FORFEIT_TIMEOUT_SECS)",
+                self.scheduler.forfeit_timeout_secs,
+            ),
+            (
+                "command_queue.tick_secs (COMMAND_QUEUE_TICK_SECS)",
+                self.command_queue.tick_secs,
+            ),
+            (
+                "command_queue.retry_max_secs (COMMAND_RETRY_MAX_SECS)",
+                self.command_queue.retry_max_secs,
+            ),
+            (
+                "command_queue.expiry_secs (COMMAND_EXPIRY_SECS)",
+                self.command_queue.expiry_secs,
+            ),
+            (
+                "game_servers.resolve_interval_secs (SERVER_RESOLVE_INTERVAL_SECS)",
+                self.game_servers.resolve_interval_secs,
+            ),
+            (
+                "server_query.interval_secs (SERVER_QUERY_INTERVAL_SECS)",
+                self.server_query.interval_secs,
+            ),
+        ] {
+            if secs > MAX_DURATION_SECS {
+                errors.push(format!("{} must be at most {}", name, MAX_DURATION_SECS));
+            }
+        }
+        if SocketAddr::from_str(&self.log_listener.bind).is_err() {
+            errors.push(format!(
+                "log_listener.bind (LOG_LISTENER_BIND) must be an IP and port, got {}",
+                self.log_listener.bind
+            ));
+        }
+    }
+
+    /// Game servers connecting from other addresses are refused, even when they are registered.
+    /// IPv4-mapped IPv6 addresses match their IPv4 entry.
+    pub fn is_server_allowed(&self, ip: &IpAddr) -> bool {
+        let ip = ip.to_canonical();
+        self.game_servers.allowlist.is_empty()
+            || self.game_servers.allowlist.iter().any(|allowed| {
+                IpAddr::from_str(allowed).is_ok_and(|allowed| allowed.to_canonical() == ip)
+            })
+    }
+}
+
+fn env_override<T: FromStr>(name: &str, target: &mut T, errors: &mut Vec<String>) {
+    if let Ok(value) = std::env::var(name) {
+        match value.parse() {
+            Ok(value) => *target = value,
+            Err(_) => errors.push(format!("{} has an invalid value {}", name, value)),
+        }
+    }
+}
+
+/// Comma separated environment lists, blank entries are ignored
+fn split_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(|item| item.trim().to_string())
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+fn is_http_url(url: &str) -> bool {
+    url.starts_with("http://") || url.starts_with("https://")
+}
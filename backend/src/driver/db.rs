@@ -2,50 +2,256 @@ mod embedded {
     use refinery::embed_migrations;
     embed_migrations!("db/migrations");
 }
+mod embedded_sqlite {
+    use refinery::embed_migrations;
+    embed_migrations!("db/migrations_sqlite");
+}
+use anyhow::Context;
+use async_trait::async_trait;
+use rbatis::executor::RBatisTxExecutorGuard;
+use refinery_core::traits::r#async::{AsyncMigrate, AsyncQuery, AsyncTransaction};
+use refinery_core::{Migration, Report, Runner, Target};
+use serde::Deserialize;
+
+use crate::metrics::TimedDriver;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum DbKind {
     Postgres,
+    Sqlite,
 }
 
-pub async fn migrate(db_kind: DbKind) {
-    match db_kind {
+impl DbKind {
+    /// Picked from the scheme of `database.url`, `None` for anything else
+    pub fn from_url(url: &str) -> Option<DbKind> {
+        if url.starts_with("postgres://") || url.starts_with("postgresql://") {
+            Some(DbKind::Postgres)
+        } else if url.starts_with("sqlite:") {
+            Some(DbKind::Sqlite)
+        } else {
+            None
+        }
+    }
+
+    fn runner(&self) -> Runner {
+        match self {
+            DbKind::Postgres => embedded::migrations::runner(),
+            DbKind::Sqlite => embedded_sqlite::migrations::runner(),
+        }
+    }
+}
+
+/// Kind of the database behind the pool, panics when called before `init`
+pub fn db_kind() -> DbKind {
+    match crate::global::RB.driver_type() {
+        Ok("postgres") => DbKind::Postgres,
+        Ok("sqlite") => DbKind::Sqlite,
+        other => panic!(
+            "The database was used before being initialized: {:?}",
+            other
+        ),
+    }
+}
+
+/// Applies the embedded migrations up to `target`, the migrations already applied are verified first
+/// and any drift aborts the run
+pub async fn migrate(db_kind: DbKind, url: &str, target: Target) -> anyhow::Result<Report> {
+    println!("Running DB migrations...");
+    let runner = db_kind.runner().set_target(target);
+    let migration_report = match db_kind {
         DbKind::Postgres => {
-            println!("Running DB migrations...");
-            let (mut client, con) = tokio_postgres::connect(
-                crate::global::DATABASE_URL.as_str(),
-                tokio_postgres::NoTls,
-            )
-            .await
-            .expect("Failed to create a connection to the database");
+            let (mut client, con) = tokio_postgres::connect(url, tokio_postgres::NoTls)
+                .await
+                .context("Failed to create a connection to the database")?;
 
             tokio::spawn(async move {
                 if let Err(e) = con.await {
                     eprintln!("connection error: {}", e);
                 }
             });
-            let migration_report = embedded::migrations::runner()
-                .run_async(&mut client)
-                .await
-                .expect("Failed to run database migrations");
-
-            for migration in migration_report.applied_migrations() {
-                println!(
-                    "Migration Applied -  Name: {}, Version: {}",
-                    migration.name(),
-                    migration.version()
-                );
-            }
+            runner.run_async(&mut client).await
+        }
+        DbKind::Sqlite => {
+            let mut migrator = SqliteMigrator {
+                migrations: runner.get_migrations().clone(),
+            };
+            runner.run_async(&mut migrator).await
+        }
+    }
+    .context("Failed to run database migrations")?;
+
+    for migration in migration_report.applied_migrations() {
+        println!(
+            "Migration Applied -  Name: {}, Version: {}",
+            migration.name(),
+            migration.version()
+        );
+    }
+    println!("DB migrations finished!");
+    Ok(migration_report)
+}
 
-            println!("DB migrations finished!");
+/// Sets up the connection pool, nothing is sent to the database until the first query
+pub fn init(url: &str) -> anyhow::Result<()> {
+    let db_kind = DbKind::from_url(url).context("database.url must be a postgres or sqlite URL")?;
+    match db_kind {
+        DbKind::Postgres => crate::global::RB.init(TimedDriver(rbdc_pg::driver::PgDriver {}), url),
+        DbKind::Sqlite => {
+            crate::global::RB.init(TimedDriver(rbdc_sqlite::driver::SqliteDriver {}), url)
         }
     }
+    .context("Failed to initialize database connection")
+}
+
+pub async fn init_and_migrate(url: &str) -> anyhow::Result<()> {
+    init(url)?;
+    migrate(db_kind(), url, Target::Latest).await?;
+    Ok(())
+}
+
+/// Migrations embedded in the binary for the configured database, ordered by version
+pub fn embedded_migrations() -> Vec<Migration> {
+    db_kind().runner().get_migrations().clone()
 }
 
-pub async fn init_and_migrate() {
+/// Rows of refinery's history table, empty when no migration ran yet
+pub async fn schema_history() -> Result<Vec<SchemaHistoryRow>, rbatis::Error> {
+    let exists_query = match db_kind() {
+        DbKind::Postgres => {
+            "select count(1) from information_schema.tables where table_name = 'refinery_schema_history'"
+        }
+        DbKind::Sqlite => {
+            "select count(1) from sqlite_master where type = 'table' and name = 'refinery_schema_history'"
+        }
+    };
+    let exists: u64 = crate::global::RB.fetch_decode(exists_query, vec![]).await?;
+    if exists == 0 {
+        return Ok(Vec::new());
+    }
     crate::global::RB
-        .init(
-            rbdc_pg::driver::PgDriver {},
-            crate::global::DATABASE_URL.as_str(),
+        .fetch_decode(
+            "select version, name, applied_on, checksum from refinery_schema_history order by version",
+            vec![],
         )
-        .expect("Failed to initialize database connection");
-    migrate(DbKind::Postgres).await;
+        .await
+}
+
+/// refinery's own SQLite driver needs a libsqlite3-sys that can't be linked next to the one of
+/// rbdc-sqlite, so SQLite migrations run through the rbatis pool instead
+struct SqliteMigrator {
+    /// Embedded migrations, rows of the history table are matched back to them
+    migrations: Vec<Migration>,
+}
+
+#[derive(Deserialize)]
+pub struct SchemaHistoryRow {
+    pub version: i32,
+    pub name: String,
+    pub applied_on: String,
+    /// refinery stores the `u64` checksum as text
+    pub checksum: String,
+}
+
+#[async_trait]
+impl AsyncTransaction for SqliteMigrator {
+    type Error = rbatis::Error;
+
+    /// SQLite can't alter the type of a column, such migrations rebuild the table. Foreign keys are
+    /// off meanwhile so dropping a referenced table doesn't cascade, they are checked before the
+    /// commit instead. The pragma has no effect inside a transaction, hence the manual one.
+    async fn execute(&mut self, queries: &[&str]) -> Result<usize, Self::Error> {
+        let mut conn = crate::global::RB.acquire().await?;
+        conn.exec("PRAGMA foreign_keys = OFF", vec![]).await?;
+        //the pooled connection is reused, every exit from here on turns the pragma back on
+        let result = async {
+            conn.exec("BEGIN", vec![]).await?;
+            let mut rows_affected = 0;
+            for query in queries {
+                rows_affected += conn.exec(query, vec![]).await?.rows_affected as usize;
+            }
+            let violations = conn.fetch("PRAGMA foreign_key_check", vec![]).await?;
+            if violations.as_array().is_some_and(|rows| !rows.is_empty()) {
+                return Err(rbatis::Error::from(format!(
+                    "The migration breaks foreign keys: {}",
+                    violations
+                )));
+            }
+            conn.exec("COMMIT", vec![]).await?;
+            Ok(rows_affected)
+        }
+        .await;
+        if result.is_err() {
+            conn.exec("ROLLBACK", vec![]).await.ok();
+        }
+        conn.exec("PRAGMA foreign_keys = ON", vec![]).await?;
+        result
+    }
+}
+
+#[async_trait]
+impl AsyncQuery<Vec<Migration>> for SqliteMigrator {
+    async fn query(&mut self, query: &str) -> Result<Vec<Migration>, Self::Error> {
+        let rows: Vec<SchemaHistoryRow> = crate::global::RB.fetch_decode(query, vec![]).await?;
+        // refinery can't build an applied migration outside of its own drivers, an identical
+        // embedded one compares equal and anything else becomes a placeholder that can't match
+        rows.into_iter()
+            .map(|row| {
+                match self.migrations.iter().find(|migration| {
+                    migration.version() as i32 == row.version
+                        && migration.name() == row.name
+                        && migration.checksum().to_string() == row.checksum
+                }) {
+                    Some(migration) => Ok(migration.clone()),
+                    None => Migration::unapplied(&format!("V{}__{}", row.version, row.name), "")
+                        .map_err(|e| rbatis::Error::from(e.to_string())),
+                }
+            })
+            .collect()
+    }
+}
+
+impl AsyncMigrate for SqliteMigrator {}
+
+/// Version of the newest migration embedded in the binary
+pub fn expected_migration_version() -> i32 {
+    db_kind()
+        .runner()
+        .get_migrations()
+        .iter()
+        .map(|migration| migration.version() as i32)
+        .max()
+        .unwrap_or(0)
+}
+
+/// Version of the newest migration applied to the database, `None` when none ran yet
+pub async fn applied_migration_version() -> Result<Option<i32>, rbatis::Error> {
+    crate::global::RB
+        .fetch_decode(
+            "select max(version) as version from refinery_schema_history",
+            vec![],
+        )
+        .await
+}
+
+/// Starts a transaction that is rolled back if it gets dropped before being committed.
+/// Commit it through `commit_tx` so the connection goes back to the pool right away.
+pub async fn begin_tx() -> Result<RBatisTxExecutorGuard, rbatis::Error> {
+    let tx = crate::global::RB.acquire_begin().await?;
+    Ok(tx.defer_async(|mut tx| async move {
+        if !tx.done {
+            tx.rollback().await.ok();
+        }
+    }))
+}
+
+pub async fn commit_tx(mut tx: RBatisTxExecutorGuard) -> Result<(), rbatis::Error> {
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Unique and primary key violations, rbdc only reports them through the message of the database
+pub fn is_unique_violation(e: &rbatis::Error) -> bool {
+    let message = e.to_string();
+    message.contains("duplicate key value violates unique constraint")
+        || message.contains("UNIQUE constraint failed")
 }
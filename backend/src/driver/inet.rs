@@ -0,0 +1,126 @@
+//! IP address columns, `inet` on Postgres and text on SQLite. rbdc binds strings as `varchar`, which
+//! Postgres won't compare with or store in an `inet`, so addresses are sent in its binary format.
+
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use super::db::{db_kind, DbKind};
+
+/// `PGSQL_AF_INET` and `PGSQL_AF_INET6` of the binary format
+const PG_AF_INET: u8 = 2;
+const PG_AF_INET6: u8 = 3;
+
+/// Address as stored in the database, IPv4-mapped IPv6 addresses are stored as IPv4 so a server
+/// matches whichever way its connection arrives. Only meant for rows, API types carry the `IpAddr`.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct InetAddr(IpAddr);
+
+impl InetAddr {
+    pub fn new(ip: IpAddr) -> Self {
+        InetAddr(ip.to_canonical())
+    }
+
+    pub fn ip(&self) -> IpAddr {
+        self.0
+    }
+
+    /// Family, prefix bits, cidr flag, address length and the address
+    fn to_pg_binary(self) -> Vec<u8> {
+        let (family, bits, octets) = match self.0 {
+            IpAddr::V4(ip) => (PG_AF_INET, 32, ip.octets().to_vec()),
+            IpAddr::V6(ip) => (PG_AF_INET6, 128, ip.octets().to_vec()),
+        };
+        let mut buf = vec![family, bits, 0, octets.len() as u8];
+        buf.extend(octets);
+        buf
+    }
+
+    fn from_pg_binary(buf: &[u8]) -> Option<Self> {
+        let (header, octets) = buf.split_at_checked(4)?;
+        if usize::from(header[3]) != octets.len() {
+            return None;
+        }
+        let ip = match header[0] {
+            PG_AF_INET => IpAddr::from(<[u8; 4]>::try_from(octets).ok()?),
+            PG_AF_INET6 => IpAddr::from(<[u8; 16]>::try_from(octets).ok()?),
+            _ => return None,
+        };
+        Some(InetAddr::new(ip))
+    }
+}
+
+impl From<IpAddr> for InetAddr {
+    fn from(ip: IpAddr) -> Self {
+        InetAddr::new(ip)
+    }
+}
+
+impl fmt::Display for InetAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// Takes the `/32` or `/128` Postgres adds to host addresses in text form
+impl FromStr for InetAddr {
+    type Err = std::net::AddrParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let ip = s.split_once('/').map_or(s, |(ip, _)| ip);
+        Ok(InetAddr::new(ip.trim().parse()?))
+    }
+}
+
+impl Serialize for InetAddr {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match db_kind() {
+            //rbs turns the newtype into `Value::Ext("Inet", ..)`, bound as an `inet`
+            DbKind::Postgres => {
+                serializer.serialize_newtype_struct("Inet", &PgBinary(self.to_pg_binary()))
+            }
+            DbKind::Sqlite => serializer.collect_str(self),
+        }
+    }
+}
+
+struct PgBinary(Vec<u8>);
+
+impl Serialize for PgBinary {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for InetAddr {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(InetAddrVisitor)
+    }
+}
+
+struct InetAddrVisitor;
+
+impl Visitor<'_> for InetAddrVisitor {
+    type Value = InetAddr;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("an IP address as text or in the binary format of Postgres")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<InetAddr, E> {
+        v.parse().map_err(E::custom)
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<InetAddr, E> {
+        match InetAddr::from_pg_binary(v) {
+            Some(inet) => Ok(inet),
+            //text results hold the address as written
+            None => std::str::from_utf8(v)
+                .map_err(E::custom)
+                .and_then(|v| self.visit_str(v)),
+        }
+    }
+}
@@ -3,69 +3,147 @@ use axum::response::{IntoResponse, Response};
 use axum::Json;
 use serde::Serialize;
 
+use crate::middleware::current_request_id;
+
+#[derive(Debug)]
 pub enum AppError {
     Unauthorized,
+    Forbidden,
+    NotFound(String),
     BadRequest(String),
+    /// The request is valid but clashes with the current state of the resource
+    Conflict(String),
+    Validation(Vec<FieldError>),
+    TooManyRequests(String),
+    PayloadTooLarge(String),
     SteamError(steam_auth::Error),
     JwtError(jsonwebtoken::errors::Error),
     DatabaseError(rbatis::Error),
     SteamVerifierError(steam_auth::Error),
     SteamApiError(reqwest::Error),
+    /// Steam answered with a body that isn't what its API documents
+    SteamResponseError(serde_json::Error),
     ReqwestError(reqwest::Error),
     JsonParseError(serde_json::Error),
 }
 
+#[derive(Serialize, Debug)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+impl AppError {
+    /// Shorthand for a validation error on a single field
+    pub fn invalid_field(field: &str, message: &str) -> Self {
+        AppError::Validation(vec![FieldError {
+            field: field.to_string(),
+            message: message.to_string(),
+        }])
+    }
+}
+
+/// Every error leaves the API as `{"error": {...}}`, clients branch on `code` which never changes once released
 #[derive(Serialize)]
-struct JsonError {
-    pub error: String,
+pub struct ErrorBody {
+    pub error: ErrorDetails,
 }
 
-impl From<String> for JsonError {
-    fn from(error: String) -> Self {
-        JsonError { error }
+#[derive(Serialize)]
+pub struct ErrorDetails {
+    pub code: &'static str,
+    pub message: String,
+    pub request_id: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub details: Vec<FieldError>,
+}
+
+/// Builds the error envelope for the request being handled
+pub fn error_response(
+    status: StatusCode,
+    code: &'static str,
+    message: String,
+    details: Vec<FieldError>,
+) -> Response {
+    let body = ErrorBody {
+        error: ErrorDetails {
+            code,
+            message,
+            request_id: current_request_id(),
+            details,
+        },
+    };
+    (status, Json(body)).into_response()
+}
+
+/// Stable code for responses that weren't produced by an `AppError`, such as extractor rejections
+pub fn status_code_name(status: StatusCode) -> &'static str {
+    match status {
+        StatusCode::BAD_REQUEST => "bad_request",
+        StatusCode::UNAUTHORIZED => "unauthorized",
+        StatusCode::FORBIDDEN => "forbidden",
+        StatusCode::NOT_FOUND => "not_found",
+        StatusCode::METHOD_NOT_ALLOWED => "method_not_allowed",
+        StatusCode::CONFLICT => "conflict",
+        StatusCode::PAYLOAD_TOO_LARGE => "payload_too_large",
+        StatusCode::UNSUPPORTED_MEDIA_TYPE => "unsupported_media_type",
+        StatusCode::UNPROCESSABLE_ENTITY => "validation_failed",
+        StatusCode::TOO_MANY_REQUESTS => "too_many_requests",
+        StatusCode::BAD_GATEWAY => "upstream_error",
+        s if s.is_client_error() => "bad_request",
+        _ => "internal_error",
     }
 }
 
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
-        match self {
-            AppError::Unauthorized => (StatusCode::UNAUTHORIZED, "Unauthorized").into_response(),
-            AppError::BadRequest(e) => (StatusCode::BAD_REQUEST, e).into_response(),
-            AppError::SteamError(e) => (
+        let request_id = current_request_id();
+        let internal = |cause: &dyn std::fmt::Debug| {
+            tracing::error!(request_id = ?request_id, cause = ?cause, "Internal error");
+            (
                 StatusCode::INTERNAL_SERVER_ERROR,
-                Json(JsonError::from(e.to_string())),
+                "internal_error",
+                "Internal server error".to_string(),
             )
-                .into_response(),
-            AppError::JwtError(e) => (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(JsonError::from(e.to_string())),
+        };
+        let upstream = |cause: &dyn std::fmt::Debug| {
+            tracing::error!(request_id = ?request_id, cause = ?cause, "Steam request failed");
+            (
+                StatusCode::BAD_GATEWAY,
+                "upstream_error",
+                "Steam couldn't be reached".to_string(),
             )
-                .into_response(),
-            AppError::DatabaseError(e) => (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(JsonError::from(e.to_string())),
-            )
-                .into_response(),
-            AppError::SteamVerifierError(e) => (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(JsonError::from(e.to_string())),
-            )
-                .into_response(),
-            AppError::ReqwestError(e) => (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(JsonError::from(e.to_string())),
-            )
-                .into_response(),
-            AppError::SteamApiError(e) => (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(JsonError::from(e.to_string())),
-            )
-                .into_response(),
-            AppError::JsonParseError(e) => (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(JsonError::from(e.to_string())),
-            )
-                .into_response(),
-        }
+        };
+
+        let (status, code, message) = match self {
+            AppError::Unauthorized => (
+                StatusCode::UNAUTHORIZED,
+                "unauthorized",
+                "Unauthorized".to_string(),
+            ),
+            AppError::Forbidden => (StatusCode::FORBIDDEN, "forbidden", "Forbidden".to_string()),
+            AppError::NotFound(e) => (StatusCode::NOT_FOUND, "not_found", e),
+            AppError::BadRequest(e) => (StatusCode::BAD_REQUEST, "bad_request", e),
+            AppError::Conflict(e) => (StatusCode::CONFLICT, "conflict", e),
+            AppError::Validation(details) => {
+                return error_response(
+                    StatusCode::UNPROCESSABLE_ENTITY,
+                    "validation_failed",
+                    "Some fields are invalid".to_string(),
+                    details,
+                )
+            }
+            AppError::TooManyRequests(e) => (StatusCode::TOO_MANY_REQUESTS, "too_many_requests", e),
+            AppError::PayloadTooLarge(e) => (StatusCode::PAYLOAD_TOO_LARGE, "payload_too_large", e),
+            AppError::SteamVerifierError(e) => upstream(&e),
+            AppError::SteamApiError(e) => upstream(&e),
+            AppError::SteamResponseError(e) => upstream(&e),
+            AppError::ReqwestError(e) => upstream(&e),
+            AppError::SteamError(e) => internal(&e),
+            AppError::JwtError(e) => internal(&e),
+            AppError::DatabaseError(e) => internal(&e),
+            AppError::JsonParseError(e) => internal(&e),
+        };
+        error_response(status, code, message, Vec::new())
     }
 }
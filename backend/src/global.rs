@@ -1,23 +1,29 @@
+use std::time::Instant;
+
 use lazy_static::lazy_static;
 use rbatis::Rbatis;
+use tokio::sync::{watch, Mutex};
 
+use crate::service::enrollment::PendingEnrollmentList;
+use crate::service::server_command::PendingCommandList;
+use crate::service::server_query::ServerQueryList;
 use crate::ws::server::ServerList;
+use crate::ws::spectator::MatchFeedList;
 use crate::ws::user::UserList;
 
 lazy_static! {
     pub static ref ONLINE_SERVERS: ServerList = ServerList::default();
     pub static ref ONLINE_USERS: UserList = UserList::default();
-    pub static ref DATABASE_URL: String = std::env::var("DATABASE_URL")
-        .expect("DATABASE_URL is not set in the environment variables");
-    pub static ref STEAM_KEY: String =
-        std::env::var("STEAM_KEY").expect("STEAM_KEY is not set in the environment variables");
-    pub static ref JWT_KEY: String =
-        std::env::var("JWT_KEY").expect("JWT_KEY is not set in the environment variables");
-    pub static ref PORT: String = std::env::var("PORT").unwrap_or("1337".to_string());
-    pub static ref HOST: String = std::env::var("HOST").unwrap_or("0.0.0.0".to_string());
-    pub static ref API_URL: String =
-        std::env::var("API_URL").unwrap_or(format!("http://localhost:{}", *PORT));
+    pub static ref LIVE_MATCHES: MatchFeedList = MatchFeedList::default();
+    /// Commands sent to servers and waiting for their ack, keyed by correlation ID
+    pub static ref PENDING_COMMANDS: PendingCommandList = PendingCommandList::default();
+    /// Last A2S answer of every registered server, keyed by server id
+    pub static ref SERVER_QUERIES: ServerQueryList = ServerQueryList::default();
+    /// Connections of enrolled servers waiting for an admin, keyed by pending server id
+    pub static ref PENDING_ENROLLMENTS: PendingEnrollmentList = PendingEnrollmentList::default();
     pub static ref RB: Rbatis = Rbatis::new();
+    /// Serializes bracket updates so two matches finishing at once can't overwrite each other
+    pub static ref TOURNAMENT_LOCK: Mutex<()> = Mutex::new(());
+    /// Holds when the shutdown started, `None` while the backend is running
+    pub static ref SHUTDOWN: watch::Sender<Option<Instant>> = watch::channel(None).0;
 }
-
-pub const AUTHORIZED_SERVERS: [&str; 1] = ["192.168.0.13"];
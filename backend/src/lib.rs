@@ -1,6 +1,9 @@
+pub mod app;
+pub mod config;
 pub mod driver;
 pub mod error;
 pub mod global;
+pub mod metrics;
 pub mod middleware;
 pub mod model;
 pub mod response;
@@ -1,69 +1,202 @@
 use std::net::SocketAddr;
 
-use axum::{
-    http::header,
-    routing::{get, post},
-    Router,
-};
+use anyhow::bail;
+use axum::Router;
+use clap::{Parser, Subcommand};
 use dotenv::dotenv;
-use noname::{global, middleware::{with_admin, with_admin_qs} , routes, ws};
-use tower_http::cors::{Any, CorsLayer};
+use noname::{
+    app::build_router,
+    config::Config,
+    driver::db,
+    service::migration::{self, MigrationState},
+    service::shutdown,
+};
+use refinery_core::Target;
+
+#[derive(Parser)]
+#[command(
+    version,
+    about = "noname backend, serves the API when no command is given"
+)]
+struct Cli {
+    /// Start without applying pending migrations, for deploys running `migrate up` as a separate step
+    #[arg(long)]
+    no_migrate: bool,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Manage the database schema
+    #[command(subcommand)]
+    Migrate(MigrateCommand),
+}
+
+#[derive(Subcommand)]
+enum MigrateCommand {
+    /// List applied and pending migrations with their checksums
+    Status,
+    /// Apply pending migrations
+    Up {
+        /// Stop once this version is applied instead of applying every pending migration
+        #[arg(long)]
+        to: Option<u32>,
+    },
+    /// Fail when the applied migrations differ from the ones embedded in this binary
+    Verify,
+}
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
     dotenv().ok();
-    tracing_subscriber::fmt::init();
-
-    noname::driver::db::init_and_migrate().await;
 
-    let listen_addr = format!("0.0.0.0:{}", *global::PORT);
+    if let Some(Command::Migrate(command)) = cli.command {
+        //query logs would drown the command output
+        tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::WARN)
+            .init();
+        let config = Config::load()?;
+        db::init(&config.database.url)?;
+        return run_migrate_command(&config, command).await;
+    }
 
-    let ws_router = Router::new()
-        .route(
-            "/user",
-        get(ws::user::on_user_connection).route_layer(axum::middleware::from_fn(with_admin_qs)),
-        )
-        .route("/server", get(ws::server::on_server_connection));
-
-    let auth_router = Router::new()
-        .route("/login", get(routes::auth::login))
-        .route("/steam_callback", get(routes::auth::steam_callback));
+    tracing_subscriber::fmt::init();
+    //shared by the router and the background tasks for the lifetime of the process
+    let config: &'static Config = Box::leak(Box::new(Config::load()?));
 
-    let server_router = Router::new().route(
-        "/",
-        post(routes::server::create_server).route_layer(axum::middleware::from_fn(with_admin)),
-    );
+    if cli.no_migrate {
+        db::init(&config.database.url)?;
+        let applied = db::applied_migration_version().await.ok().flatten();
+        if applied.is_none_or(|version| version < db::expected_migration_version()) {
+            tracing::warn!(
+                "Started with --no-migrate but migrations are pending, /readyz reports not ready until they are applied"
+            );
+        }
+    } else {
+        db::init_and_migrate(&config.database.url).await?;
+    }
+    if let Err(e) = shutdown::restore_live_matches().await {
+        tracing::error!(error = ?e, "Couldn't restore the live matches");
+    }
+    let mut background_tasks = vec![
+        noname::service::scheduler::spawn_scheduler(config),
+        noname::service::command_queue::spawn_command_queue(config),
+        noname::service::server::spawn_address_resolver(config),
+    ];
+    if config.server_query.enabled {
+        background_tasks.push(noname::service::server_query::spawn_server_query(config));
+    }
+    if config.log_listener.enabled {
+        let socket = std::net::UdpSocket::bind(&config.log_listener.bind)?;
+        background_tasks.push(noname::service::log_listener::spawn_log_listener(
+            config, socket,
+        )?);
+    }
 
-    let router = Router::new()
-        .nest(
-            "/api",
-            Router::new()
-                .nest("/auth", auth_router)
-                .nest("/servers", server_router),
-        )
-        .nest("/ws", ws_router)
-        .layer(build_cors());
+    let listen_addr = SocketAddr::new(config.server.host.parse()?, config.server.port);
+    let router = build_router(config);
 
     tracing::info!("Server started at http://{}/", listen_addr);
 
-    start_server(router).await?;
+    start_server(config, router, listen_addr).await?;
+    shutdown::finish(config, background_tasks).await;
+    tracing::info!("Server stopped");
     Ok(())
 }
 
-async fn start_server(router: Router) -> anyhow::Result<()> {
-    axum::Server::bind(&SocketAddr::from((
-        [0, 0, 0, 0],
-        global::PORT.parse::<u16>().unwrap(),
-    )))
-    .serve(router.into_make_service_with_connect_info::<SocketAddr>())
-    .await?;
+/// Serves until a shutdown signal, then waits for in-flight requests as long as the grace period allows
+async fn start_server(
+    config: &Config,
+    router: Router,
+    listen_addr: SocketAddr,
+) -> anyhow::Result<()> {
+    let server = axum::Server::bind(&listen_addr)
+        .serve(router.into_make_service_with_connect_info::<SocketAddr>())
+        .with_graceful_shutdown(shutdown::on_signal(config));
 
+    tokio::select! {
+        result = server => result?,
+        _ = shutdown::grace_period_elapsed(config) => {
+            tracing::warn!("Grace period elapsed, dropping in-flight requests");
+        }
+    }
     Ok(())
 }
 
-fn build_cors() -> CorsLayer {
-    CorsLayer::new()
-        .allow_origin(Any)
-        .allow_methods(Any)
-        .allow_headers(vec![header::AUTHORIZATION, header::CONTENT_TYPE])
+async fn run_migrate_command(config: &Config, command: MigrateCommand) -> anyhow::Result<()> {
+    match command {
+        MigrateCommand::Status => {
+            let statuses = migration::migration_status().await?;
+            println!(
+                "{:<8} {:<24} {:<10} {:<42} APPLIED ON",
+                "VERSION", "NAME", "STATE", "CHECKSUM"
+            );
+            for status in &statuses {
+                let checksum = match (&status.applied_checksum, &status.embedded_checksum) {
+                    (Some(applied), Some(embedded)) if applied != embedded => {
+                        format!("{} (embedded {})", applied, embedded)
+                    }
+                    (Some(checksum), _) | (None, Some(checksum)) => checksum.clone(),
+                    (None, None) => String::new(),
+                };
+                println!(
+                    "{:<8} {:<24} {:<10} {:<42} {}",
+                    status.version,
+                    status.name,
+                    status.state.as_str(),
+                    checksum,
+                    status.applied_on.as_deref().unwrap_or("-")
+                );
+            }
+            let count = |state| statuses.iter().filter(|s| s.state == state).count();
+            println!(
+                "\n{} applied, {} pending, {} divergent, {} missing",
+                count(MigrationState::Applied),
+                count(MigrationState::Pending),
+                count(MigrationState::Divergent),
+                count(MigrationState::Missing)
+            );
+        }
+        MigrateCommand::Up { to } => {
+            let target = match to {
+                Some(version) => {
+                    if !db::embedded_migrations()
+                        .iter()
+                        .any(|migration| migration.version() == version)
+                    {
+                        bail!("There is no migration with version {}", version);
+                    }
+                    Target::Version(version)
+                }
+                None => Target::Latest,
+            };
+            db::migrate(db::db_kind(), &config.database.url, target).await?;
+        }
+        MigrateCommand::Verify => {
+            let statuses = migration::migration_status().await?;
+            let drifted: Vec<_> = statuses.iter().filter(|s| s.state.is_drift()).collect();
+            for status in &drifted {
+                println!(
+                    "V{}__{} is {}",
+                    status.version,
+                    status.name,
+                    status.state.as_str()
+                );
+            }
+            if !drifted.is_empty() {
+                bail!(
+                    "{} applied migrations differ from the ones embedded in this binary",
+                    drifted.len()
+                );
+            }
+            let pending = statuses
+                .iter()
+                .filter(|s| s.state == MigrationState::Pending)
+                .count();
+            println!("No drift, {} migrations pending", pending);
+        }
+    }
+    Ok(())
 }
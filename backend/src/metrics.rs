@@ -0,0 +1,238 @@
+use std::future::Future;
+use std::time::Instant;
+
+use futures_util::future::BoxFuture;
+use lazy_static::lazy_static;
+use prometheus::{
+    register_histogram_vec, register_int_counter_vec, register_int_gauge_vec, Encoder,
+    HistogramVec, IntCounterVec, IntGaugeVec, TextEncoder,
+};
+use rbatis::rbdc::{
+    self,
+    db::{ConnectOptions, Connection, Driver, ExecResult, Row},
+};
+use rbs::Value;
+use serde::Serialize;
+
+use crate::global::{LIVE_MATCHES, ONLINE_SERVERS, ONLINE_USERS};
+
+lazy_static! {
+    static ref HTTP_REQUEST_DURATION: HistogramVec = register_histogram_vec!(
+        "http_request_duration_seconds",
+        "HTTP request latency by route",
+        &["method", "route", "status"]
+    )
+    .unwrap();
+    static ref WEBSOCKET_CONNECTIONS: IntGaugeVec = register_int_gauge_vec!(
+        "websocket_connections",
+        "Open websocket connections by peer",
+        &["peer"]
+    )
+    .unwrap();
+    static ref WEBSOCKET_MESSAGES: IntCounterVec = register_int_counter_vec!(
+        "websocket_messages_total",
+        "Websocket messages by peer, direction and action",
+        &["peer", "direction", "action"]
+    )
+    .unwrap();
+    static ref LOG_PACKETS: IntCounterVec = register_int_counter_vec!(
+        "srcds_log_packets_total",
+        "UDP log packets by outcome",
+        &["outcome"]
+    )
+    .unwrap();
+    static ref DB_QUERY_DURATION: HistogramVec = register_histogram_vec!(
+        "db_query_duration_seconds",
+        "Database query latency by statement kind",
+        &["statement", "outcome"]
+    )
+    .unwrap();
+}
+
+#[derive(Clone, Copy)]
+pub enum Peer {
+    Server,
+    User,
+}
+
+impl Peer {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Peer::Server => "server",
+            Peer::User => "user",
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum Direction {
+    In,
+    Out,
+}
+
+impl Direction {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Direction::In => "in",
+            Direction::Out => "out",
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum LogPacketOutcome {
+    Accepted,
+    /// Malformed, without the log secret, from outside the allowlist or over the buffer limit
+    Dropped,
+    /// From an address that isn't a registered server
+    Unattributed,
+}
+
+impl LogPacketOutcome {
+    fn as_str(&self) -> &'static str {
+        match self {
+            LogPacketOutcome::Accepted => "accepted",
+            LogPacketOutcome::Dropped => "dropped",
+            LogPacketOutcome::Unattributed => "unattributed",
+        }
+    }
+}
+
+pub fn count_log_packet(outcome: LogPacketOutcome) {
+    LOG_PACKETS.with_label_values(&[outcome.as_str()]).inc();
+}
+
+pub fn observe_http_request(method: &str, route: &str, status: u16, started: Instant) {
+    HTTP_REQUEST_DURATION
+        .with_label_values(&[method, route, &status.to_string()])
+        .observe(started.elapsed().as_secs_f64());
+}
+
+/// Actions are serde enums or plain strings, anything that doesn't look like an action name is counted as
+/// `invalid`. Actions read from clients have to be mapped to the known ones first, every distinct
+/// name is a new series
+pub fn count_ws_message<A: Serialize>(peer: Peer, direction: Direction, action: &A) {
+    let action = serde_json::to_value(action)
+        .ok()
+        .and_then(|value| value.as_str().map(|action| action.to_string()))
+        .filter(|action| {
+            !action.is_empty()
+                && action.len() <= 64
+                && action
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+        })
+        .unwrap_or_else(|| "invalid".to_string());
+    WEBSOCKET_MESSAGES
+        .with_label_values(&[peer.as_str(), direction.as_str(), &action])
+        .inc();
+}
+
+/// Renders every metric in the Prometheus text format, connection gauges are read from the online lists
+pub async fn render() -> String {
+    let spectators: usize = LIVE_MATCHES
+        .read()
+        .await
+        .values()
+        .map(|feed| feed.receiver_count())
+        .sum();
+    for (peer, count) in [
+        ("server", ONLINE_SERVERS.read().await.len()),
+        ("user", ONLINE_USERS.read().await.len()),
+        ("spectator", spectators),
+    ] {
+        WEBSOCKET_CONNECTIONS
+            .with_label_values(&[peer])
+            .set(count as i64);
+    }
+
+    let mut buffer = Vec::new();
+    if let Err(e) = TextEncoder::new().encode(&prometheus::gather(), &mut buffer) {
+        tracing::error!("Couldn't encode metrics {}", e);
+    }
+    String::from_utf8_lossy(&buffer).into_owned()
+}
+
+/// First keyword of the statement, the full SQL would make one series per query
+fn statement_kind(sql: &str) -> &'static str {
+    let keyword = sql.split_whitespace().next().unwrap_or_default();
+    [
+        "select", "insert", "update", "delete", "begin", "commit", "rollback",
+    ]
+    .into_iter()
+    .find(|kind| keyword.eq_ignore_ascii_case(kind))
+    .unwrap_or("other")
+}
+
+async fn timed<T, F: Future<Output = Result<T, rbdc::Error>>>(
+    statement: &'static str,
+    query: F,
+) -> Result<T, rbdc::Error> {
+    let started = Instant::now();
+    let result = query.await;
+    DB_QUERY_DURATION
+        .with_label_values(&[statement, if result.is_ok() { "ok" } else { "error" }])
+        .observe(started.elapsed().as_secs_f64());
+    result
+}
+
+/// Wraps the database driver so every statement going through `global::RB` is timed
+#[derive(Debug)]
+pub struct TimedDriver<D: Driver>(pub D);
+
+impl<D: Driver> Driver for TimedDriver<D> {
+    fn name(&self) -> &str {
+        self.0.name()
+    }
+
+    fn connect(&self, url: &str) -> BoxFuture<'_, Result<Box<dyn Connection>, rbdc::Error>> {
+        let connect = self.0.connect(url);
+        Box::pin(
+            async move { Ok(Box::new(TimedConnection(connect.await?)) as Box<dyn Connection>) },
+        )
+    }
+
+    fn connect_opt<'a>(
+        &'a self,
+        opt: &'a dyn ConnectOptions,
+    ) -> BoxFuture<'a, Result<Box<dyn Connection>, rbdc::Error>> {
+        let connect = self.0.connect_opt(opt);
+        Box::pin(
+            async move { Ok(Box::new(TimedConnection(connect.await?)) as Box<dyn Connection>) },
+        )
+    }
+
+    fn default_option(&self) -> Box<dyn ConnectOptions> {
+        self.0.default_option()
+    }
+}
+
+struct TimedConnection(Box<dyn Connection>);
+
+impl Connection for TimedConnection {
+    fn get_rows(
+        &mut self,
+        sql: &str,
+        params: Vec<Value>,
+    ) -> BoxFuture<'_, Result<Vec<Box<dyn Row>>, rbdc::Error>> {
+        let statement = statement_kind(sql);
+        Box::pin(timed(statement, self.0.get_rows(sql, params)))
+    }
+
+    fn exec(
+        &mut self,
+        sql: &str,
+        params: Vec<Value>,
+    ) -> BoxFuture<'_, Result<ExecResult, rbdc::Error>> {
+        let statement = statement_kind(sql);
+        Box::pin(timed(statement, self.0.exec(sql, params)))
+    }
+
+    fn close(&mut self) -> BoxFuture<'_, Result<(), rbdc::Error>> {
+        self.0.close()
+    }
+
+    fn ping(&mut self) -> BoxFuture<'_, Result<(), rbdc::Error>> {
+        self.0.ping()
+    }
+}
@@ -1,21 +1,113 @@
+use std::net::SocketAddr;
+use std::time::Instant;
+
 use axum::{
-    extract::{Query, RequestParts},
-    http::Request,
+    body::{boxed, Body, Full},
+    extract::{ConnectInfo, MatchedPath, OriginalUri, Query, RequestParts},
+    http::{header, HeaderValue, Method, Request},
     middleware::Next,
-    response::IntoResponse,
+    response::{IntoResponse, Response},
 };
 use serde::Deserialize;
+use serde_json::Value;
 
 use crate::{
-    error::AppError,
-    global,
+    config::Config,
+    error::{error_response, status_code_name, AppError},
+    global, metrics,
+    service::audit::{self, AuditBefore, AuditSource},
     service::auth::{decode_token, TokenData},
 };
 
-///	Extracts the authorization header and checks if the access should be granted
-pub async fn with_admin<B>(
-    mut req: Request<B>,
-    next: Next<B>,
+const REQUEST_ID_HEADER: &str = "x-request-id";
+/// Leaves room for a config template of the maximum length once JSON escaped
+const MAX_ADMIN_BODY_SIZE: usize = 256 << 10;
+
+/// The config layer wraps every route, so it's there before any of the middlewares below run
+fn request_config<B>(req: &Request<B>) -> &'static Config {
+    req.extensions()
+        .get::<&'static Config>()
+        .copied()
+        .expect("The config is added to every request")
+}
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// ID of the request handled by the current task, `None` outside of `with_request_id`
+pub fn current_request_id() -> Option<String> {
+    REQUEST_ID.try_with(|id| id.clone()).ok()
+}
+
+/// Tags every request with an ID, taken from `X-Request-ID` when the caller sent a sane one, and echoes it back.
+/// Error responses that weren't built from an `AppError`, such as extractor rejections or unknown routes,
+/// are rewritten into the JSON error envelope.
+pub async fn with_request_id<B>(req: Request<B>, next: Next<B>) -> Response {
+    let request_id = req
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|header| header.to_str().ok())
+        .filter(|id| !id.is_empty() && id.len() <= 64)
+        .filter(|id| {
+            id.chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        })
+        .map(|id| id.to_string())
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+
+    REQUEST_ID
+        .scope(request_id.clone(), async move {
+            let response = next.run(req).await;
+            let mut response = if response.status().as_u16() >= 400 && !is_json(&response) {
+                let status = response.status();
+                let body = hyper::body::to_bytes(response.into_body())
+                    .await
+                    .unwrap_or_default();
+                let message = match String::from_utf8_lossy(&body).trim() {
+                    "" => status.canonical_reason().unwrap_or("Error").to_string(),
+                    message => message.to_string(),
+                };
+                error_response(status, status_code_name(status), message, Vec::new())
+            } else {
+                response
+            };
+            if let Ok(value) = HeaderValue::from_str(&request_id) {
+                response.headers_mut().insert(REQUEST_ID_HEADER, value);
+            }
+            response
+        })
+        .await
+}
+
+/// Records the latency of every request, labelled with the route pattern rather than the raw path
+pub async fn with_metrics<B>(req: Request<B>, next: Next<B>) -> Response {
+    let started = Instant::now();
+    let method = req.method().to_string();
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".to_string());
+    let response = next.run(req).await;
+    metrics::observe_http_request(&method, &route, response.status().as_u16(), started);
+    response
+}
+
+fn is_json(response: &Response) -> bool {
+    response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/json"))
+}
+
+/// Extracts the authorization header and checks if the access should be granted.
+/// Every granted call is written to the audit log along with its response status, changes also
+/// store what they changed with the secrets redacted.
+pub async fn with_admin(
+    mut req: Request<Body>,
+    next: Next<Body>,
 ) -> Result<impl IntoResponse, AppError> {
     let header_token = req
         .headers()
@@ -23,15 +115,74 @@ pub async fn with_admin<B>(
         .and_then(|header| header.to_str().ok())
         .ok_or(AppError::Unauthorized)?
         .replace("Bearer ", "");
-    let token_data = decode_token(&header_token).map_err(|_| AppError::Unauthorized)?;
-
-    match user_is_admin(&token_data).await? {
-        true => {
-            req.extensions_mut().insert(token_data);
-            Ok(next.run(req).await)
-        }
-        false => Err(AppError::Unauthorized),
+    let token_data =
+        decode_token(request_config(&req), &header_token).map_err(|_| AppError::Unauthorized)?;
+
+    if !user_is_admin(&token_data).await? {
+        return Err(AppError::Unauthorized);
     }
+    req.extensions_mut().insert(token_data.clone());
+
+    let (parts, body) = req.into_parts();
+    //the body is buffered so it can't be allowed to grow without bounds
+    let body = hyper::body::to_bytes(http_body::Limited::new(body, MAX_ADMIN_BODY_SIZE))
+        .await
+        .map_err(|e| {
+            if e.is::<http_body::LengthLimitError>() {
+                AppError::PayloadTooLarge(format!(
+                    "The request body must be at most {} bytes",
+                    MAX_ADMIN_BODY_SIZE
+                ))
+            } else {
+                AppError::BadRequest("Couldn't read the request body".to_string())
+            }
+        })?;
+    let route = parts
+        .extensions
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| parts.uri.path().to_string());
+    let action = format!("{} {}", parts.method, route);
+    let is_change = parts.method != Method::GET && parts.method != Method::HEAD;
+    let target = parts
+        .extensions
+        .get::<OriginalUri>()
+        .map(|OriginalUri(uri)| uri.to_string());
+    let ip = parts
+        .extensions
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip().to_string());
+
+    let response = next.run(Request::from_parts(parts, Body::from(body))).await;
+    let status = response.status();
+    let (response, payload) = if is_change && status.is_success() {
+        let (mut parts, body) = response.into_parts();
+        let before = parts
+            .extensions
+            .remove::<AuditBefore>()
+            .map(|AuditBefore(before)| before);
+        let body = hyper::body::to_bytes(body).await.unwrap_or_default();
+        let after = serde_json::from_slice::<Value>(&body)
+            .ok()
+            .and_then(|mut body| body.get_mut("data").map(Value::take));
+        (
+            Response::from_parts(parts, boxed(Full::from(body))),
+            audit::change_payload(before, after),
+        )
+    } else {
+        (response, None)
+    };
+    let mut entry = audit::audit_entry(
+        &token_data.steamid64,
+        AuditSource::Rest,
+        action,
+        target,
+        payload,
+        ip,
+    );
+    entry.status = Some(status.as_u16());
+    audit::record(entry).await;
+    Ok(response)
 }
 
 #[derive(Deserialize)]
@@ -40,28 +191,26 @@ struct TokenQueryString {
 }
 
 /// The authorization token from a browser connection must be sent as a query string parameter because the browsers implementation of websockets doesn't support custom headers.
-pub async fn with_admin_qs<B: Send>(
+/// Any user may connect, admin messages are checked on the socket.
+pub async fn with_auth_qs<B: Send>(
     req: Request<B>,
     next: Next<B>,
 ) -> Result<impl IntoResponse, AppError> {
+    let config = request_config(&req);
     let mut request_parts = RequestParts::new(req);
     let token_query = request_parts
         .extract::<Query<TokenQueryString>>()
         .await
         .map_err(|_| AppError::Unauthorized)?;
-    let token_data = decode_token(&token_query.token).map_err(|_| AppError::Unauthorized)?;
-
-    match user_is_admin(&token_data).await? {
-        true => {
-            request_parts.extensions_mut().insert(token_data);
-            let request = request_parts.try_into_request().expect("body extracted");
-            Ok(next.run(request).await)
-        }
-        false => Err(AppError::Unauthorized),
-    }
+    let token_data =
+        decode_token(config, &token_query.token).map_err(|_| AppError::Unauthorized)?;
+
+    request_parts.extensions_mut().insert(token_data);
+    let request = request_parts.try_into_request().expect("body extracted");
+    Ok(next.run(request).await)
 }
 
-///	Checks the authorization header for a valid token
+/// Checks the authorization header for a valid token
 pub async fn with_auth<B>(
     mut req: Request<B>,
     next: Next<B>,
@@ -72,7 +221,8 @@ pub async fn with_auth<B>(
         .and_then(|header| header.to_str().ok())
         .ok_or(AppError::Unauthorized)?
         .replace("Bearer ", "");
-    let token_data = decode_token(&header_token).map_err(|_| AppError::Unauthorized)?;
+    let token_data =
+        decode_token(request_config(&req), &header_token).map_err(|_| AppError::Unauthorized)?;
     req.extensions_mut().insert(token_data);
     Ok(next.run(req).await)
 }
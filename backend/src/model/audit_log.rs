@@ -0,0 +1,44 @@
+use rbatis::{crud, rbdc::datetime::FastDateTime, sql, Rbatis};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct AuditLog {
+    pub id: Option<u32>,
+    pub actor_steamid64: String,
+    pub source: String,
+    pub action: String,
+    pub target: Option<String>,
+    /// Request body of REST calls, raw message of websocket actions
+    pub payload: Option<String>,
+    pub status: Option<u16>,
+    pub ip: Option<String>,
+    pub created_at: FastDateTime,
+}
+crud!(AuditLog {});
+
+/// Every filter is optional, `action` is matched with `like`
+#[sql("select * from audit_log where actor_steamid64 = coalesce(?, actor_steamid64) and source = coalesce(?, source) and action like coalesce(?, action) and created_at >= coalesce(?, created_at) and created_at <= coalesce(?, created_at) order by created_at desc, id desc limit ? offset ?")]
+pub async fn select_page(
+    rb: &Rbatis,
+    actor_steamid64: &Option<String>,
+    source: &Option<String>,
+    action: &Option<String>,
+    from: &Option<FastDateTime>,
+    to: &Option<FastDateTime>,
+    limit: u64,
+    offset: u64,
+) -> rbatis::Result<Vec<AuditLog>> {
+    impled!()
+}
+
+#[sql("select count(1) from audit_log where actor_steamid64 = coalesce(?, actor_steamid64) and source = coalesce(?, source) and action like coalesce(?, action) and created_at >= coalesce(?, created_at) and created_at <= coalesce(?, created_at)")]
+pub async fn count(
+    rb: &Rbatis,
+    actor_steamid64: &Option<String>,
+    source: &Option<String>,
+    action: &Option<String>,
+    from: &Option<FastDateTime>,
+    to: &Option<FastDateTime>,
+) -> rbatis::Result<u64> {
+    impled!()
+}
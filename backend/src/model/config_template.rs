@@ -0,0 +1,93 @@
+use rbatis::{crud, executor::Executor, rbdc::datetime::FastDateTime, sql, Rbatis};
+use serde::{Deserialize, Serialize};
+
+/// Current template of a phase, its content is also kept in `config_template_version`
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ConfigTemplate {
+    pub phase: String,
+    pub version: u32,
+    pub content: String,
+    pub updated_by: String,
+    pub updated_at: FastDateTime,
+}
+crud!(ConfigTemplate {});
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ConfigTemplateVersion {
+    pub phase: String,
+    pub version: u32,
+    pub content: String,
+    pub created_by: String,
+    pub created_at: FastDateTime,
+}
+crud!(ConfigTemplateVersion {});
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ConfigVariable {
+    pub name: String,
+    pub value: String,
+    pub updated_by: String,
+    pub updated_at: FastDateTime,
+}
+crud!(ConfigVariable {});
+
+#[sql("select * from config_template order by phase")]
+pub async fn select_templates(rb: &Rbatis) -> rbatis::Result<Vec<ConfigTemplate>> {
+    impled!()
+}
+
+#[sql("select * from config_template where phase = ? limit 1")]
+pub async fn select_template(rb: &Rbatis, phase: &str) -> rbatis::Result<Option<ConfigTemplate>> {
+    impled!()
+}
+
+#[sql("select * from config_template_version where phase = ? order by version desc")]
+pub async fn select_versions(
+    rb: &Rbatis,
+    phase: &str,
+) -> rbatis::Result<Vec<ConfigTemplateVersion>> {
+    impled!()
+}
+
+#[sql("select * from config_template_version where phase = ? and version = ? limit 1")]
+pub async fn select_version(
+    rb: &Rbatis,
+    phase: &str,
+    version: u32,
+) -> rbatis::Result<Option<ConfigTemplateVersion>> {
+    impled!()
+}
+
+/// Versions keep counting after a template is deleted so an old version number is never reused
+#[sql("select coalesce(max(version), 0) from config_template_version where phase = ?")]
+pub async fn select_latest_version(rb: &mut dyn Executor, phase: &str) -> rbatis::Result<u32> {
+    impled!()
+}
+
+#[sql("insert into config_template (phase, version, content, updated_by, updated_at) values (?, ?, ?, ?, ?) on conflict (phase) do update set version = excluded.version, content = excluded.content, updated_by = excluded.updated_by, updated_at = excluded.updated_at")]
+pub async fn upsert_template(
+    rb: &mut dyn Executor,
+    phase: &str,
+    version: u32,
+    content: &str,
+    updated_by: &str,
+    updated_at: &FastDateTime,
+) -> rbatis::Result<rbatis::rbdc::db::ExecResult> {
+    impled!()
+}
+
+#[sql("select * from config_variable order by name")]
+pub async fn select_variables(rb: &Rbatis) -> rbatis::Result<Vec<ConfigVariable>> {
+    impled!()
+}
+
+#[sql("insert into config_variable (name, value, updated_by, updated_at) values (?, ?, ?, ?) on conflict (name) do update set value = excluded.value, updated_by = excluded.updated_by, updated_at = excluded.updated_at")]
+pub async fn upsert_variable(
+    rb: &Rbatis,
+    name: &str,
+    value: &str,
+    updated_by: &str,
+    updated_at: &FastDateTime,
+) -> rbatis::Result<rbatis::rbdc::db::ExecResult> {
+    impled!()
+}
@@ -0,0 +1,105 @@
+use rbatis::{crud, executor::Executor, rbdc::datetime::FastDateTime, sql, Rbatis};
+use serde::{Deserialize, Serialize};
+
+use crate::driver::inet::InetAddr;
+
+/// Lets unknown game servers ask to be registered, only the hash of its secret is stored
+#[derive(Serialize, Deserialize, Clone)]
+pub struct EnrollmentToken {
+    pub id: Option<u32>,
+    pub token_hash: String,
+    pub label: Option<String>,
+    pub created_by: String,
+    pub created_at: FastDateTime,
+    pub expires_at: FastDateTime,
+    pub revoked_at: Option<FastDateTime>,
+}
+crud!(EnrollmentToken {});
+
+/// Game server that enrolled from an unknown address, waiting for an admin
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PendingServer {
+    pub id: u32,
+    pub ip: InetAddr,
+    pub port: u16,
+    pub enrollment_token_id: u32,
+    pub status: String,
+    /// Registered server once approved
+    pub server_id: Option<u32>,
+    pub decided_by: Option<String>,
+    pub created_at: FastDateTime,
+    pub last_seen_at: FastDateTime,
+    pub decided_at: Option<FastDateTime>,
+}
+crud!(PendingServer {});
+
+#[sql("insert into enrollment_token (token_hash, label, created_by, created_at, expires_at) values (?, ?, ?, ?, ?) returning *")]
+pub async fn insert_token_returning(
+    rb: &Rbatis,
+    token_hash: &str,
+    label: Option<&str>,
+    created_by: &str,
+    created_at: &FastDateTime,
+    expires_at: &FastDateTime,
+) -> rbatis::Result<EnrollmentToken> {
+    impled!()
+}
+
+#[sql("select * from enrollment_token where id = ? limit 1")]
+pub async fn select_token(rb: &Rbatis, id: u32) -> rbatis::Result<Option<EnrollmentToken>> {
+    impled!()
+}
+
+#[sql("select * from enrollment_token order by id desc")]
+pub async fn select_tokens(rb: &Rbatis) -> rbatis::Result<Vec<EnrollmentToken>> {
+    impled!()
+}
+
+#[sql("update enrollment_token set revoked_at = ? where id = ? and revoked_at is null")]
+pub async fn update_token_revoked(
+    rb: &Rbatis,
+    revoked_at: &FastDateTime,
+    id: u32,
+) -> rbatis::Result<rbatis::rbdc::db::ExecResult> {
+    impled!()
+}
+
+/// A server enrolling again only refreshes `last_seen_at`, a decision stays as it is
+#[sql("insert into pending_server (ip, port, enrollment_token_id, status, created_at, last_seen_at) values (?, ?, ?, ?, ?, ?) on conflict (ip, port) do update set enrollment_token_id = excluded.enrollment_token_id, last_seen_at = excluded.last_seen_at returning *")]
+pub async fn upsert_pending_returning(
+    rb: &Rbatis,
+    ip: &InetAddr,
+    port: u16,
+    enrollment_token_id: u32,
+    status: &str,
+    created_at: &FastDateTime,
+    last_seen_at: &FastDateTime,
+) -> rbatis::Result<PendingServer> {
+    impled!()
+}
+
+#[sql("select * from pending_server where id = ? limit 1")]
+pub async fn select_pending(rb: &Rbatis, id: u32) -> rbatis::Result<Option<PendingServer>> {
+    impled!()
+}
+
+#[sql("select * from pending_server where status = ? order by created_at")]
+pub async fn select_pending_by_status(
+    rb: &Rbatis,
+    status: &str,
+) -> rbatis::Result<Vec<PendingServer>> {
+    impled!()
+}
+
+#[sql("update pending_server set status = ?, server_id = ?, decided_by = ?, decided_at = ? where id = ? and status = ?")]
+pub async fn update_decision(
+    rb: &mut dyn Executor,
+    status: &str,
+    server_id: Option<u32>,
+    decided_by: &str,
+    decided_at: &FastDateTime,
+    id: u32,
+    previous_status: &str,
+) -> rbatis::Result<rbatis::rbdc::db::ExecResult> {
+    impled!()
+}
@@ -0,0 +1,70 @@
+use rbatis::{crud, executor::Executor, rbdc::datetime::FastDateTime, sql, Rbatis};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct GameMatch {
+    pub id: Option<u32>,
+    pub team1_id: u32,
+    pub team2_id: u32,
+    pub server_id: Option<u32>,
+    pub winner_team_id: Option<u32>,
+    pub finished_at: Option<FastDateTime>,
+    pub created_at: FastDateTime,
+}
+crud!(GameMatch {});
+
+#[sql("insert into game_match (team1_id, team2_id, server_id, created_at) values (?, ?, ?, ?) returning *")]
+pub async fn insert_returning(
+    rb: &mut dyn Executor,
+    team1_id: u32,
+    team2_id: u32,
+    server_id: Option<u32>,
+    created_at: &FastDateTime,
+) -> rbatis::Result<GameMatch> {
+    impled!()
+}
+
+#[sql("select * from game_match where id = ? limit 1")]
+pub async fn select_by_id(rb: &Rbatis, id: u32) -> rbatis::Result<Option<GameMatch>> {
+    impled!()
+}
+
+#[sql("select * from game_match where team1_id = ? or team2_id = ? order by created_at desc")]
+pub async fn select_by_team(
+    rb: &Rbatis,
+    team1_id: u32,
+    team2_id: u32,
+) -> rbatis::Result<Vec<GameMatch>> {
+    impled!()
+}
+
+/// Only the first report is recorded, the server keeps sending its final state while the match is ending
+#[sql("update game_match set winner_team_id = ?, finished_at = ? where id = ? and winner_team_id is null")]
+pub async fn update_winner(
+    rb: &mut dyn Executor,
+    winner_team_id: u32,
+    finished_at: &FastDateTime,
+    id: u32,
+) -> rbatis::Result<rbatis::rbdc::db::ExecResult> {
+    impled!()
+}
+
+/// The match a server is hosting: a scheduled match that holds its reservation, otherwise the latest
+/// unfinished match that was assigned to the server by hand
+#[sql("select gm.* from game_match gm left join match_schedule ms on ms.game_match_id = gm.id where gm.winner_team_id is null and ((ms.server_id = ? and ms.status in ('reserved', 'started', 'live')) or (ms.game_match_id is null and gm.server_id = ?)) order by ms.game_match_id is null, gm.created_at desc limit 1")]
+pub async fn select_active_by_server(
+    rb: &Rbatis,
+    scheduled_server_id: u32,
+    server_id: u32,
+) -> rbatis::Result<Option<GameMatch>> {
+    impled!()
+}
+
+#[sql("update game_match set server_id = ? where id = ?")]
+pub async fn update_server(
+    rb: &mut dyn Executor,
+    server_id: u32,
+    id: u32,
+) -> rbatis::Result<rbatis::rbdc::db::ExecResult> {
+    impled!()
+}
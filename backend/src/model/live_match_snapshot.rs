@@ -0,0 +1,11 @@
+use rbatis::{crud, rbdc::datetime::FastDateTime};
+use serde::{Deserialize, Serialize};
+
+/// Live match state written on shutdown and loaded back on the next start, `state` is the JSON of a `LiveMatch`
+#[derive(Serialize, Deserialize, Clone)]
+pub struct LiveMatchSnapshot {
+    pub game_match_id: u32,
+    pub state: String,
+    pub created_at: FastDateTime,
+}
+crud!(LiveMatchSnapshot {});
@@ -0,0 +1,90 @@
+use rbatis::{crud, executor::Executor, rbdc::datetime::FastDateTime, sql, Rbatis};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct MatchSchedule {
+    pub game_match_id: u32,
+    pub scheduled_at: FastDateTime,
+    pub status: String,
+    pub server_id: Option<u32>,
+    pub reserved_at: Option<FastDateTime>,
+    pub started_at: Option<FastDateTime>,
+    pub created_at: FastDateTime,
+}
+crud!(MatchSchedule {});
+
+#[sql("select * from match_schedule where game_match_id = ? limit 1")]
+pub async fn select_by_match(
+    rb: &Rbatis,
+    game_match_id: u32,
+) -> rbatis::Result<Option<MatchSchedule>> {
+    impled!()
+}
+
+#[sql("select * from match_schedule where status in ('scheduled', 'reserved', 'started') order by scheduled_at")]
+pub async fn select_pending(rb: &Rbatis) -> rbatis::Result<Vec<MatchSchedule>> {
+    impled!()
+}
+
+#[sql("update match_schedule set scheduled_at = ?, status = ?, server_id = null, reserved_at = null, started_at = null where game_match_id = ?")]
+pub async fn update_scheduled_at(
+    rb: &Rbatis,
+    scheduled_at: &FastDateTime,
+    status: &str,
+    game_match_id: u32,
+) -> rbatis::Result<rbatis::rbdc::db::ExecResult> {
+    impled!()
+}
+
+#[sql("update match_schedule set status = ?, server_id = ?, reserved_at = ? where game_match_id = ? and status = ?")]
+pub async fn update_reserved(
+    rb: &Rbatis,
+    status: &str,
+    server_id: u32,
+    reserved_at: &FastDateTime,
+    game_match_id: u32,
+    previous_status: &str,
+) -> rbatis::Result<rbatis::rbdc::db::ExecResult> {
+    impled!()
+}
+
+#[sql(
+    "update match_schedule set status = ?, started_at = ? where game_match_id = ? and status = ?"
+)]
+pub async fn update_started(
+    rb: &Rbatis,
+    status: &str,
+    started_at: &FastDateTime,
+    game_match_id: u32,
+    previous_status: &str,
+) -> rbatis::Result<rbatis::rbdc::db::ExecResult> {
+    impled!()
+}
+
+#[sql("update match_schedule set status = ? where game_match_id = ?")]
+pub async fn update_status(
+    rb: &Rbatis,
+    status: &str,
+    game_match_id: u32,
+) -> rbatis::Result<rbatis::rbdc::db::ExecResult> {
+    impled!()
+}
+
+/// Forfeited and cancelled schedules keep their status once the match gets its result
+#[sql("update match_schedule set status = ? where game_match_id = ? and status in ('scheduled', 'reserved', 'started', 'live')")]
+pub async fn update_finished(
+    rb: &mut dyn Executor,
+    status: &str,
+    game_match_id: u32,
+) -> rbatis::Result<rbatis::rbdc::db::ExecResult> {
+    impled!()
+}
+
+#[sql("update match_schedule set server_id = ? where game_match_id = ?")]
+pub async fn update_server(
+    rb: &mut dyn Executor,
+    server_id: u32,
+    game_match_id: u32,
+) -> rbatis::Result<rbatis::rbdc::db::ExecResult> {
+    impled!()
+}
@@ -1,3 +1,58 @@
+#[allow(clippy::too_many_arguments)]
+pub mod audit_log;
+#[allow(clippy::too_many_arguments)]
+pub mod config_template;
+#[allow(clippy::too_many_arguments)]
+pub mod enrollment;
+pub mod game_match;
+pub mod live_match_snapshot;
+pub mod match_schedule;
+pub mod player_admission;
+#[allow(clippy::too_many_arguments)]
+pub mod player_ban;
+#[allow(clippy::too_many_arguments)]
+pub mod player_match_stats;
+#[allow(clippy::too_many_arguments)]
+pub mod queued_command;
+#[allow(clippy::too_many_arguments)]
+pub mod round_backup;
 pub mod server;
+pub mod server_log_token;
+pub mod team;
+#[allow(clippy::too_many_arguments)]
+pub mod tournament;
+#[allow(clippy::too_many_arguments)]
+pub mod trust;
 pub mod user;
+pub use game_match::GameMatch;
 pub use server::Server;
+pub use team::Team;
+
+use serde::de::{self, Deserializer, Visitor};
+
+/// SQLite has no boolean type and hands flags back as 0 or 1, use on every `bool` column
+pub fn deserialize_bool<'de, D: Deserializer<'de>>(deserializer: D) -> Result<bool, D::Error> {
+    struct BoolVisitor;
+
+    impl<'de> Visitor<'de> for BoolVisitor {
+        type Value = bool;
+
+        fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+            formatter.write_str("a boolean or 0/1")
+        }
+
+        fn visit_bool<E: de::Error>(self, value: bool) -> Result<bool, E> {
+            Ok(value)
+        }
+
+        fn visit_i64<E: de::Error>(self, value: i64) -> Result<bool, E> {
+            Ok(value != 0)
+        }
+
+        fn visit_u64<E: de::Error>(self, value: u64) -> Result<bool, E> {
+            Ok(value != 0)
+        }
+    }
+
+    deserializer.deserialize_any(BoolVisitor)
+}
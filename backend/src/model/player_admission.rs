@@ -0,0 +1,16 @@
+use rbatis::{crud, rbdc::datetime::FastDateTime};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PlayerAdmission {
+    pub id: Option<u32>,
+    pub server_id: u32,
+    pub game_match_id: Option<u32>,
+    pub steamid64: String,
+    #[serde(deserialize_with = "super::deserialize_bool")]
+    pub allowed: bool,
+    pub team_id: Option<u32>,
+    pub reason: String,
+    pub created_at: FastDateTime,
+}
+crud!(PlayerAdmission {});
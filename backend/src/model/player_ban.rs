@@ -0,0 +1,108 @@
+use rbatis::{crud, executor::Executor, rbdc::datetime::FastDateTime, sql, Rbatis};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PlayerBan {
+    pub id: Option<u32>,
+    pub steamid64: String,
+    pub kind: String,
+    pub scope: String,
+    pub server_id: Option<u32>,
+    pub reason: String,
+    pub banned_by: Option<String>,
+    pub expires_at: Option<FastDateTime>,
+    pub lifted_at: Option<FastDateTime>,
+    pub lifted_by: Option<String>,
+    pub lift_reason: Option<String>,
+    pub created_at: FastDateTime,
+}
+crud!(PlayerBan {});
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PlayerBanEvent {
+    pub id: Option<u32>,
+    pub ban_id: u32,
+    pub event: String,
+    pub actor_steamid64: String,
+    pub reason: Option<String>,
+    pub created_at: FastDateTime,
+}
+crud!(PlayerBanEvent {});
+
+#[sql("insert into player_ban (steamid64, kind, scope, server_id, reason, banned_by, expires_at, created_at) values (?, ?, ?, ?, ?, ?, ?, ?) returning *")]
+pub async fn insert_returning(
+    rb: &mut dyn Executor,
+    steamid64: &str,
+    kind: &str,
+    scope: &str,
+    server_id: Option<u32>,
+    reason: &str,
+    banned_by: &str,
+    expires_at: &Option<FastDateTime>,
+    created_at: &FastDateTime,
+) -> rbatis::Result<PlayerBan> {
+    impled!()
+}
+
+#[sql("select * from player_ban where id = ? limit 1")]
+pub async fn select_by_id(rb: &Rbatis, id: u32) -> rbatis::Result<Option<PlayerBan>> {
+    impled!()
+}
+
+/// Every filter is optional, `state` is `active` or `inactive` as of `now`
+#[sql("select * from player_ban where steamid64 = coalesce(?, steamid64) and kind = coalesce(?, kind) and (case when lifted_at is null and (expires_at is null or expires_at > ?) then 'active' else 'inactive' end) = coalesce(?, case when lifted_at is null and (expires_at is null or expires_at > ?) then 'active' else 'inactive' end) order by created_at desc, id desc limit ? offset ?")]
+pub async fn select_page(
+    rb: &Rbatis,
+    steamid64: &Option<String>,
+    kind: Option<&str>,
+    now: &FastDateTime,
+    state: Option<&str>,
+    now_again: &FastDateTime,
+    limit: u64,
+    offset: u64,
+) -> rbatis::Result<Vec<PlayerBan>> {
+    impled!()
+}
+
+#[sql("select count(1) from player_ban where steamid64 = coalesce(?, steamid64) and kind = coalesce(?, kind) and (case when lifted_at is null and (expires_at is null or expires_at > ?) then 'active' else 'inactive' end) = coalesce(?, case when lifted_at is null and (expires_at is null or expires_at > ?) then 'active' else 'inactive' end)")]
+pub async fn count(
+    rb: &Rbatis,
+    steamid64: &Option<String>,
+    kind: Option<&str>,
+    now: &FastDateTime,
+    state: Option<&str>,
+    now_again: &FastDateTime,
+) -> rbatis::Result<u64> {
+    impled!()
+}
+
+/// Bans without an expiration date are permanent
+#[sql("select * from player_ban where steamid64 = ? and lifted_at is null and (expires_at is null or expires_at > ?) order by created_at desc")]
+pub async fn select_active_by_steamid(
+    rb: &Rbatis,
+    steamid64: &str,
+    now: &FastDateTime,
+) -> rbatis::Result<Vec<PlayerBan>> {
+    impled!()
+}
+
+#[sql("select * from player_ban where lifted_at is null and (expires_at is null or expires_at > ?) order by created_at desc")]
+pub async fn select_active(rb: &Rbatis, now: &FastDateTime) -> rbatis::Result<Vec<PlayerBan>> {
+    impled!()
+}
+
+#[sql("update player_ban set lifted_at = ?, lifted_by = ?, lift_reason = ? where id = ? and lifted_at is null")]
+pub async fn update_lifted(
+    rb: &mut dyn Executor,
+    lifted_at: &FastDateTime,
+    lifted_by: &str,
+    lift_reason: &Option<String>,
+    id: u32,
+) -> rbatis::Result<rbatis::rbdc::db::ExecResult> {
+    impled!()
+}
+
+#[sql("select * from player_ban_event where ban_id = ? order by created_at, id")]
+pub async fn select_events(rb: &Rbatis, ban_id: u32) -> rbatis::Result<Vec<PlayerBanEvent>> {
+    impled!()
+}
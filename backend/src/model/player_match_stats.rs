@@ -0,0 +1,48 @@
+use rbatis::{crud, rbdc::datetime::FastDateTime, sql, Rbatis};
+use serde::{Deserialize, Serialize};
+
+/// Totals of a player in a match, built from the server logs
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PlayerMatchStats {
+    pub id: Option<u32>,
+    pub game_match_id: u32,
+    pub steamid64: String,
+    pub name: String,
+    /// Last side the player was seen on
+    pub side: Option<String>,
+    pub kills: u32,
+    pub deaths: u32,
+    pub assists: u32,
+    pub headshots: u32,
+    pub bomb_plants: u32,
+    pub bomb_defuses: u32,
+    pub updated_at: FastDateTime,
+}
+crud!(PlayerMatchStats {});
+
+/// Adds the counters to the stored totals
+#[sql("insert into player_match_stats (game_match_id, steamid64, name, side, kills, deaths, assists, headshots, bomb_plants, bomb_defuses, updated_at) values (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?) on conflict (game_match_id, steamid64) do update set name = excluded.name, side = coalesce(excluded.side, player_match_stats.side), kills = player_match_stats.kills + excluded.kills, deaths = player_match_stats.deaths + excluded.deaths, assists = player_match_stats.assists + excluded.assists, headshots = player_match_stats.headshots + excluded.headshots, bomb_plants = player_match_stats.bomb_plants + excluded.bomb_plants, bomb_defuses = player_match_stats.bomb_defuses + excluded.bomb_defuses, updated_at = excluded.updated_at")]
+pub async fn upsert_increment(
+    rb: &Rbatis,
+    game_match_id: u32,
+    steamid64: &str,
+    name: &str,
+    side: Option<&str>,
+    kills: u32,
+    deaths: u32,
+    assists: u32,
+    headshots: u32,
+    bomb_plants: u32,
+    bomb_defuses: u32,
+    updated_at: &FastDateTime,
+) -> rbatis::Result<rbatis::rbdc::db::ExecResult> {
+    impled!()
+}
+
+#[sql("select * from player_match_stats where game_match_id = ? order by kills desc, deaths, steamid64")]
+pub async fn select_by_match(
+    rb: &Rbatis,
+    game_match_id: u32,
+) -> rbatis::Result<Vec<PlayerMatchStats>> {
+    impled!()
+}
@@ -0,0 +1,127 @@
+use rbatis::{crud, rbdc::datetime::FastDateTime, sql, Rbatis};
+use serde::{Deserialize, Serialize};
+
+/// Backend to server message kept until the server acknowledges its `seq`
+#[derive(Serialize, Deserialize, Clone)]
+pub struct QueuedCommand {
+    pub seq: u32,
+    pub server_id: u32,
+    pub action: String,
+    /// JSON `data` of the message
+    pub payload: String,
+    pub status: String,
+    pub attempts: u32,
+    pub next_attempt_at: FastDateTime,
+    pub expires_at: FastDateTime,
+    pub last_error: Option<String>,
+    pub created_at: FastDateTime,
+    pub acked_at: Option<FastDateTime>,
+}
+crud!(QueuedCommand {});
+
+#[sql("insert into queued_command (server_id, action, payload, status, next_attempt_at, expires_at, created_at) values (?, ?, ?, ?, ?, ?, ?) returning *")]
+pub async fn insert_returning(
+    rb: &Rbatis,
+    server_id: u32,
+    action: &str,
+    payload: &str,
+    status: &str,
+    next_attempt_at: &FastDateTime,
+    expires_at: &FastDateTime,
+    created_at: &FastDateTime,
+) -> rbatis::Result<QueuedCommand> {
+    impled!()
+}
+
+#[sql("select * from queued_command where seq = ? and server_id = ? limit 1")]
+pub async fn select_by_seq(
+    rb: &Rbatis,
+    seq: u32,
+    server_id: u32,
+) -> rbatis::Result<Option<QueuedCommand>> {
+    impled!()
+}
+
+#[sql("select * from queued_command where server_id = ? and status = ? order by seq")]
+pub async fn select_by_server(
+    rb: &Rbatis,
+    server_id: u32,
+    status: &str,
+) -> rbatis::Result<Vec<QueuedCommand>> {
+    impled!()
+}
+
+#[sql("select * from queued_command where server_id = ? and status != ? order by seq")]
+pub async fn select_unacked_by_server(
+    rb: &Rbatis,
+    server_id: u32,
+    acked_status: &str,
+) -> rbatis::Result<Vec<QueuedCommand>> {
+    impled!()
+}
+
+#[sql("select * from queued_command where status = ? and next_attempt_at <= ? order by seq")]
+pub async fn select_due(
+    rb: &Rbatis,
+    status: &str,
+    now: &FastDateTime,
+) -> rbatis::Result<Vec<QueuedCommand>> {
+    impled!()
+}
+
+#[sql("update queued_command set attempts = ?, next_attempt_at = ? where seq = ? and status = ?")]
+pub async fn update_attempt(
+    rb: &Rbatis,
+    attempts: u32,
+    next_attempt_at: &FastDateTime,
+    seq: u32,
+    status: &str,
+) -> rbatis::Result<rbatis::rbdc::db::ExecResult> {
+    impled!()
+}
+
+#[sql("update queued_command set status = ?, acked_at = ? where seq = ? and server_id = ? and status = ?")]
+pub async fn update_acked(
+    rb: &Rbatis,
+    status: &str,
+    acked_at: &FastDateTime,
+    seq: u32,
+    server_id: u32,
+    previous_status: &str,
+) -> rbatis::Result<rbatis::rbdc::db::ExecResult> {
+    impled!()
+}
+
+#[sql("update queued_command set status = ?, last_error = ? where seq = ? and status = ?")]
+pub async fn update_dead(
+    rb: &Rbatis,
+    status: &str,
+    last_error: &str,
+    seq: u32,
+    previous_status: &str,
+) -> rbatis::Result<rbatis::rbdc::db::ExecResult> {
+    impled!()
+}
+
+/// Gives a dead command a fresh set of attempts
+#[sql("update queued_command set status = ?, attempts = 0, next_attempt_at = ?, expires_at = ?, last_error = null where seq = ? and server_id = ? and status = ?")]
+pub async fn update_requeued(
+    rb: &Rbatis,
+    status: &str,
+    next_attempt_at: &FastDateTime,
+    expires_at: &FastDateTime,
+    seq: u32,
+    server_id: u32,
+    previous_status: &str,
+) -> rbatis::Result<rbatis::rbdc::db::ExecResult> {
+    impled!()
+}
+
+#[sql("delete from queued_command where status = ? and acked_at < ?")]
+pub async fn delete_acked_before(
+    rb: &Rbatis,
+    status: &str,
+    acked_before: &FastDateTime,
+) -> rbatis::Result<rbatis::rbdc::db::ExecResult> {
+    impled!()
+}
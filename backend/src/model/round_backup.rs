@@ -0,0 +1,58 @@
+use rbatis::{crud, rbdc::datetime::FastDateTime, sql, Rbatis};
+use serde::{Deserialize, Serialize};
+
+/// Round backup file written by the game server, one per match and round
+#[derive(Serialize, Deserialize, Clone)]
+pub struct RoundBackup {
+    pub id: Option<u32>,
+    pub game_match_id: u32,
+    pub server_id: u32,
+    pub round: u32,
+    pub file_name: String,
+    pub content: String,
+    pub created_at: FastDateTime,
+}
+crud!(RoundBackup {});
+
+/// Listing without the file contents
+#[derive(Serialize, Deserialize, Clone)]
+pub struct RoundBackupSummary {
+    pub id: u32,
+    pub game_match_id: u32,
+    pub server_id: u32,
+    pub round: u32,
+    pub file_name: String,
+    pub size: u32,
+    pub created_at: FastDateTime,
+}
+
+/// A server uploading a round again (after a restore) replaces the older file
+#[sql("insert into round_backup (game_match_id, server_id, round, file_name, content, created_at) values (?, ?, ?, ?, ?, ?) on conflict (game_match_id, round) do update set server_id = excluded.server_id, file_name = excluded.file_name, content = excluded.content, created_at = excluded.created_at")]
+pub async fn upsert(
+    rb: &Rbatis,
+    game_match_id: u32,
+    server_id: u32,
+    round: u32,
+    file_name: &str,
+    content: &str,
+    created_at: &FastDateTime,
+) -> rbatis::Result<rbatis::rbdc::db::ExecResult> {
+    impled!()
+}
+
+#[sql("select id, game_match_id, server_id, round, file_name, length(content) as size, created_at from round_backup where game_match_id = ? order by round")]
+pub async fn select_by_match(
+    rb: &Rbatis,
+    game_match_id: u32,
+) -> rbatis::Result<Vec<RoundBackupSummary>> {
+    impled!()
+}
+
+#[sql("select * from round_backup where game_match_id = ? and round = ? limit 1")]
+pub async fn select_round(
+    rb: &Rbatis,
+    game_match_id: u32,
+    round: u32,
+) -> rbatis::Result<Option<RoundBackup>> {
+    impled!()
+}
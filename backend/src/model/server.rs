@@ -1,20 +1,80 @@
-use rbatis::{crud, rbdc::datetime::FastDateTime, sql, Rbatis};
+use std::net::SocketAddr;
+
+use rbatis::{crud, executor::Executor, rbdc::datetime::FastDateTime, sql, Rbatis};
 use serde::{Deserialize, Serialize};
 
+use crate::driver::inet::InetAddr;
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Server {
     pub id: Option<u32>,
-    pub ip: String,
-    pub port: String,
+    pub ip: InetAddr,
+    pub port: u16,
+    /// Servers registered by name get `ip` refreshed from it
+    pub hostname: Option<String>,
+    pub resolved_at: Option<FastDateTime>,
     pub created_at: FastDateTime,
 }
 crud!(Server {});
 
+impl Server {
+    pub fn socket_addr(&self) -> SocketAddr {
+        SocketAddr::new(self.ip.ip(), self.port)
+    }
+}
+
 #[sql("select * from server where ip = ? and port = ? limit 1")]
-pub async fn select_by_full_ip(
+pub async fn select_by_address(
     rb: &Rbatis,
-    ip: String,
-    port: String,
+    ip: &InetAddr,
+    port: u16,
 ) -> rbatis::Result<Option<Server>> {
     impled!()
 }
+
+#[sql("select * from server where hostname is not null")]
+pub async fn select_with_hostname(rb: &Rbatis) -> rbatis::Result<Vec<Server>> {
+    impled!()
+}
+
+#[sql("update server set ip = ?, resolved_at = ? where id = ?")]
+pub async fn update_resolved_ip(
+    rb: &Rbatis,
+    ip: &InetAddr,
+    resolved_at: &FastDateTime,
+    id: u32,
+) -> rbatis::Result<rbatis::rbdc::db::ExecResult> {
+    impled!()
+}
+
+#[sql("insert into server (ip, port, created_at) values (?, ?, ?) returning *")]
+pub async fn insert_returning(
+    rb: &mut dyn Executor,
+    ip: &InetAddr,
+    port: u16,
+    created_at: &FastDateTime,
+) -> rbatis::Result<Server> {
+    impled!()
+}
+
+#[sql("select * from server where id = ? limit 1")]
+pub async fn select_by_id(rb: &Rbatis, id: u32) -> rbatis::Result<Option<Server>> {
+    impled!()
+}
+
+/// Token a server has to present once it was issued one, only its hash is stored
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ServerCredential {
+    pub server_id: u32,
+    pub token_hash: String,
+    pub created_at: FastDateTime,
+}
+crud!(ServerCredential {});
+
+#[sql("select * from server_credential where server_id = ? limit 1")]
+pub async fn select_credential(
+    rb: &Rbatis,
+    server_id: u32,
+) -> rbatis::Result<Option<ServerCredential>> {
+    impled!()
+}
@@ -0,0 +1,32 @@
+use rbatis::{crud, rbdc::datetime::FastDateTime, sql, Rbatis};
+use serde::{Deserialize, Serialize};
+
+/// Argon2 hash of the token a server appends to its `logaddress_add_http` url
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ServerLogToken {
+    pub server_id: u32,
+    pub token_hash: String,
+    pub created_by: String,
+    pub created_at: FastDateTime,
+}
+crud!(ServerLogToken {});
+
+/// Issuing a new token revokes the previous one
+#[sql("insert into server_log_token (server_id, token_hash, created_by, created_at) values (?, ?, ?, ?) on conflict (server_id) do update set token_hash = excluded.token_hash, created_by = excluded.created_by, created_at = excluded.created_at")]
+pub async fn upsert(
+    rb: &Rbatis,
+    server_id: u32,
+    token_hash: &str,
+    created_by: &str,
+    created_at: &FastDateTime,
+) -> rbatis::Result<rbatis::rbdc::db::ExecResult> {
+    impled!()
+}
+
+#[sql("select * from server_log_token where server_id = ? limit 1")]
+pub async fn select_by_server(
+    rb: &Rbatis,
+    server_id: u32,
+) -> rbatis::Result<Option<ServerLogToken>> {
+    impled!()
+}
@@ -0,0 +1,129 @@
+use rbatis::{crud, executor::Executor, rbdc::datetime::FastDateTime, sql, Rbatis};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Team {
+    pub id: Option<u32>,
+    pub name: String,
+    pub tag: String,
+    pub logo: Option<String>,
+    pub captain_steamid64: String,
+    pub created_at: FastDateTime,
+}
+crud!(Team {});
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TeamMember {
+    pub team_id: u32,
+    pub steamid64: String,
+    pub role: String,
+    pub joined_at: FastDateTime,
+}
+crud!(TeamMember {});
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TeamInvite {
+    pub id: Option<u32>,
+    pub team_id: u32,
+    pub steamid64: String,
+    pub role: String,
+    pub invited_by: String,
+    pub created_at: FastDateTime,
+}
+crud!(TeamInvite {});
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TeamRosterEvent {
+    pub id: Option<u32>,
+    pub team_id: u32,
+    pub steamid64: String,
+    pub event: String,
+    pub role: Option<String>,
+    pub actor_steamid64: String,
+    pub created_at: FastDateTime,
+}
+crud!(TeamRosterEvent {});
+
+#[sql("insert into team (name, tag, logo, captain_steamid64, created_at) values (?, ?, ?, ?, ?) returning *")]
+pub async fn insert_returning(
+    rb: &mut dyn Executor,
+    name: &str,
+    tag: &str,
+    logo: &Option<String>,
+    captain_steamid64: &str,
+    created_at: &FastDateTime,
+) -> rbatis::Result<Team> {
+    impled!()
+}
+
+#[sql("select * from team where id = ? limit 1")]
+pub async fn select_by_id(rb: &Rbatis, id: u32) -> rbatis::Result<Option<Team>> {
+    impled!()
+}
+
+#[sql("select * from team where name = ? limit 1")]
+pub async fn select_by_name(rb: &Rbatis, name: &str) -> rbatis::Result<Option<Team>> {
+    impled!()
+}
+
+#[sql("select * from team_member where team_id = ? order by joined_at")]
+pub async fn select_members(rb: &Rbatis, team_id: u32) -> rbatis::Result<Vec<TeamMember>> {
+    impled!()
+}
+
+#[sql("select * from team_member where team_id = ? and steamid64 = ? limit 1")]
+pub async fn select_member(
+    rb: &Rbatis,
+    team_id: u32,
+    steamid64: &str,
+) -> rbatis::Result<Option<TeamMember>> {
+    impled!()
+}
+
+#[sql("delete from team_member where team_id = ? and steamid64 = ?")]
+pub async fn delete_member(
+    rb: &mut dyn Executor,
+    team_id: u32,
+    steamid64: &str,
+) -> rbatis::Result<rbatis::rbdc::db::ExecResult> {
+    impled!()
+}
+
+#[sql("update team set captain_steamid64 = ? where id = ?")]
+pub async fn update_captain(
+    rb: &mut dyn Executor,
+    captain_steamid64: &str,
+    id: u32,
+) -> rbatis::Result<rbatis::rbdc::db::ExecResult> {
+    impled!()
+}
+
+#[sql("select * from team_invite where id = ? limit 1")]
+pub async fn select_invite(rb: &Rbatis, id: u32) -> rbatis::Result<Option<TeamInvite>> {
+    impled!()
+}
+
+#[sql("select * from team_invite where team_id = ? and steamid64 = ? limit 1")]
+pub async fn select_invite_for(
+    rb: &Rbatis,
+    team_id: u32,
+    steamid64: &str,
+) -> rbatis::Result<Option<TeamInvite>> {
+    impled!()
+}
+
+#[sql("select * from team_invite where steamid64 = ? order by created_at desc")]
+pub async fn select_invites_by_steamid(
+    rb: &Rbatis,
+    steamid64: &str,
+) -> rbatis::Result<Vec<TeamInvite>> {
+    impled!()
+}
+
+#[sql("select * from team_roster_event where team_id = ? order by created_at desc, id desc")]
+pub async fn select_roster_history(
+    rb: &Rbatis,
+    team_id: u32,
+) -> rbatis::Result<Vec<TeamRosterEvent>> {
+    impled!()
+}
@@ -0,0 +1,145 @@
+use rbatis::{crud, executor::Executor, rbdc::datetime::FastDateTime, sql, Rbatis};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Tournament {
+    pub id: Option<u32>,
+    pub name: String,
+    pub format: String,
+    pub status: String,
+    pub swiss_rounds: Option<u32>,
+    pub winner_team_id: Option<u32>,
+    pub created_at: FastDateTime,
+}
+crud!(Tournament {});
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TournamentTeam {
+    pub tournament_id: u32,
+    pub team_id: u32,
+    pub seed: u32,
+}
+crud!(TournamentTeam {});
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TournamentMatch {
+    pub id: Option<u32>,
+    pub tournament_id: u32,
+    pub bracket: String,
+    pub round: u32,
+    pub position: u32,
+    pub status: String,
+    pub team1_id: Option<u32>,
+    pub team2_id: Option<u32>,
+    pub winner_team_id: Option<u32>,
+    pub loser_team_id: Option<u32>,
+    pub game_match_id: Option<u32>,
+    pub next_match_id: Option<u32>,
+    pub next_match_slot: Option<u8>,
+    pub loser_next_match_id: Option<u32>,
+    pub loser_next_match_slot: Option<u8>,
+}
+crud!(TournamentMatch {});
+
+#[sql("insert into tournament (name, format, status, swiss_rounds, created_at) values (?, ?, ?, ?, ?) returning *")]
+pub async fn insert_returning(
+    rb: &Rbatis,
+    name: &str,
+    format: &str,
+    status: &str,
+    swiss_rounds: Option<u32>,
+    created_at: &FastDateTime,
+) -> rbatis::Result<Tournament> {
+    impled!()
+}
+
+#[sql("select * from tournament where id = ? limit 1")]
+pub async fn select_by_id(rb: &mut dyn Executor, id: u32) -> rbatis::Result<Option<Tournament>> {
+    impled!()
+}
+
+#[sql("select * from tournament_team where tournament_id = ? order by seed")]
+pub async fn select_teams(rb: &Rbatis, tournament_id: u32) -> rbatis::Result<Vec<TournamentTeam>> {
+    impled!()
+}
+
+#[sql("delete from tournament_team where tournament_id = ? and team_id = ?")]
+pub async fn delete_team(
+    rb: &Rbatis,
+    tournament_id: u32,
+    team_id: u32,
+) -> rbatis::Result<rbatis::rbdc::db::ExecResult> {
+    impled!()
+}
+
+#[sql("update tournament_team set seed = ? where tournament_id = ? and team_id = ?")]
+pub async fn update_seed(
+    rb: &mut dyn Executor,
+    seed: u32,
+    tournament_id: u32,
+    team_id: u32,
+) -> rbatis::Result<rbatis::rbdc::db::ExecResult> {
+    impled!()
+}
+
+#[sql("update tournament set status = ?, winner_team_id = ? where id = ?")]
+pub async fn update_status(
+    rb: &mut dyn Executor,
+    status: &str,
+    winner_team_id: Option<u32>,
+    id: u32,
+) -> rbatis::Result<rbatis::rbdc::db::ExecResult> {
+    impled!()
+}
+
+#[sql("update tournament set status = ?, swiss_rounds = ? where id = ?")]
+pub async fn update_started(
+    rb: &mut dyn Executor,
+    status: &str,
+    swiss_rounds: Option<u32>,
+    id: u32,
+) -> rbatis::Result<rbatis::rbdc::db::ExecResult> {
+    impled!()
+}
+
+#[sql("insert into tournament_match (tournament_id, bracket, round, position, status, team1_id, team2_id) values (?, ?, ?, ?, ?, ?, ?) returning id")]
+pub async fn insert_match_returning_id(
+    rb: &mut dyn Executor,
+    tournament_id: u32,
+    bracket: &str,
+    round: u32,
+    position: u32,
+    status: &str,
+    team1_id: Option<u32>,
+    team2_id: Option<u32>,
+) -> rbatis::Result<u32> {
+    impled!()
+}
+
+#[sql("update tournament_match set next_match_id = ?, next_match_slot = ?, loser_next_match_id = ?, loser_next_match_slot = ? where id = ?")]
+pub async fn update_match_links(
+    rb: &mut dyn Executor,
+    next_match_id: Option<u32>,
+    next_match_slot: Option<u8>,
+    loser_next_match_id: Option<u32>,
+    loser_next_match_slot: Option<u8>,
+    id: u32,
+) -> rbatis::Result<rbatis::rbdc::db::ExecResult> {
+    impled!()
+}
+
+#[sql("select * from tournament_match where tournament_id = ? order by id")]
+pub async fn select_matches(
+    rb: &mut dyn Executor,
+    tournament_id: u32,
+) -> rbatis::Result<Vec<TournamentMatch>> {
+    impled!()
+}
+
+#[sql("select * from tournament_match where game_match_id = ? limit 1")]
+pub async fn select_match_by_game_match(
+    rb: &mut dyn Executor,
+    game_match_id: u32,
+) -> rbatis::Result<Option<TournamentMatch>> {
+    impled!()
+}
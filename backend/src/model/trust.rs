@@ -0,0 +1,67 @@
+use rbatis::{crud, rbdc::datetime::FastDateTime, sql, Rbatis};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SteamTrustCheck {
+    pub steamid64: String,
+    #[serde(deserialize_with = "super::deserialize_bool")]
+    pub vac_banned: bool,
+    pub number_of_vac_bans: u32,
+    pub number_of_game_bans: u32,
+    pub days_since_last_ban: u32,
+    #[serde(deserialize_with = "super::deserialize_bool")]
+    pub community_banned: bool,
+    pub economy_ban: String,
+    pub time_created: Option<i64>,
+    #[serde(deserialize_with = "super::deserialize_bool")]
+    pub public_profile: bool,
+    pub verdict: String,
+    /// Comma separated list of the rules that matched
+    pub reasons: String,
+    pub checked_at: FastDateTime,
+}
+crud!(SteamTrustCheck {});
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TrustPolicy {
+    pub id: u32,
+    pub vac_ban_action: String,
+    pub game_ban_action: String,
+    pub community_ban_action: String,
+    pub economy_ban_action: String,
+    pub private_profile_action: String,
+    pub new_account_action: String,
+    pub min_account_age_days: u32,
+    pub ignore_bans_older_than_days: Option<u32>,
+    pub updated_by: Option<String>,
+    pub updated_at: FastDateTime,
+}
+crud!(TrustPolicy {});
+
+#[sql("select * from steam_trust_check where steamid64 = ? limit 1")]
+pub async fn select_check(rb: &Rbatis, steamid64: &str) -> rbatis::Result<Option<SteamTrustCheck>> {
+    impled!()
+}
+
+#[sql("select * from trust_policy where id = 1 limit 1")]
+pub async fn select_policy(rb: &Rbatis) -> rbatis::Result<Option<TrustPolicy>> {
+    impled!()
+}
+
+/// Written by hand because `update_by_column` skips null columns, which would keep an old ban grace period
+#[sql("update trust_policy set vac_ban_action = ?, game_ban_action = ?, community_ban_action = ?, economy_ban_action = ?, private_profile_action = ?, new_account_action = ?, min_account_age_days = ?, ignore_bans_older_than_days = ?, updated_by = ?, updated_at = ? where id = 1")]
+pub async fn update_policy(
+    rb: &Rbatis,
+    vac_ban_action: &str,
+    game_ban_action: &str,
+    community_ban_action: &str,
+    economy_ban_action: &str,
+    private_profile_action: &str,
+    new_account_action: &str,
+    min_account_age_days: u32,
+    ignore_bans_older_than_days: Option<u32>,
+    updated_by: &str,
+    updated_at: &FastDateTime,
+) -> rbatis::Result<rbatis::rbdc::db::ExecResult> {
+    impled!()
+}
@@ -4,7 +4,10 @@ use serde::{Deserialize, Serialize};
 #[derive(Serialize, Deserialize, Clone)]
 pub struct User {
     pub steamid64: String,
+    #[serde(deserialize_with = "super::deserialize_bool")]
     pub is_admin: bool,
+    #[serde(deserialize_with = "super::deserialize_bool")]
+    pub is_caster: bool,
     pub created_at: FastDateTime,
 }
 crud!(User {}, "app_user");
@@ -14,11 +17,21 @@ pub async fn select_by_steamid(rb: &Rbatis, steamid64: String) -> rbatis::Result
     impled!()
 }
 
+#[sql("update app_user set is_caster = ? where steamid64 = ?")]
+pub async fn update_caster(
+    rb: &Rbatis,
+    is_caster: bool,
+    steamid64: &str,
+) -> rbatis::Result<rbatis::rbdc::db::ExecResult> {
+    impled!()
+}
+
 impl User {
     pub fn from_steamid64(steamid64: u64) -> Self {
         Self {
             steamid64: steamid64.to_string(),
             is_admin: false,
+            is_caster: false,
             created_at: FastDateTime::now(),
         }
     }
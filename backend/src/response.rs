@@ -5,9 +5,12 @@ use axum::{
 };
 use serde::Serialize;
 
+use crate::service::audit::AuditBefore;
+
 pub struct AppResponse<T: Serialize> {
     status_code: StatusCode,
     body: T,
+    audit_before: Option<serde_json::Value>,
 }
 
 #[derive(Serialize)]
@@ -26,6 +29,7 @@ impl<T: Serialize> AppResponse<T> {
         Self {
             status_code: StatusCode::OK,
             body,
+            audit_before: None,
         }
     }
 
@@ -33,12 +37,23 @@ impl<T: Serialize> AppResponse<T> {
         Self {
             status_code: StatusCode::CREATED,
             body,
+            audit_before: None,
         }
     }
+
+    /// Hands the state of the target before the change to the audit log, which stores the diff
+    pub fn audited<B: Serialize>(mut self, before: Option<B>) -> Self {
+        self.audit_before = before.and_then(|before| serde_json::to_value(before).ok());
+        self
+    }
 }
 
 impl<T: Serialize> IntoResponse for AppResponse<T> {
     fn into_response(self) -> Response {
-        (self.status_code, Json(ResponseBody::new(self.body))).into_response()
+        let mut response = (self.status_code, Json(ResponseBody::new(self.body))).into_response();
+        if let Some(before) = self.audit_before {
+            response.extensions_mut().insert(AuditBefore(before));
+        }
+        response
     }
 }
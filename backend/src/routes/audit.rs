@@ -0,0 +1,24 @@
+use axum::extract::Query;
+use rbatis::rbdc::datetime::FastDateTime;
+use serde::Deserialize;
+
+use crate::error::AppError;
+use crate::response::AppResponse;
+use crate::service::audit::{self, AuditLogPage};
+
+#[derive(Deserialize)]
+pub struct AuditLogQuery {
+    pub actor: Option<String>,
+    pub source: Option<String>,
+    pub action: Option<String>,
+    pub from: Option<FastDateTime>,
+    pub to: Option<FastDateTime>,
+    pub page: Option<u64>,
+    pub page_size: Option<u64>,
+}
+
+pub async fn get_audit_log(
+    Query(query): Query<AuditLogQuery>,
+) -> Result<AppResponse<AuditLogPage>, AppError> {
+    Ok(AppResponse::ok(audit::get_audit_log(query).await?))
+}
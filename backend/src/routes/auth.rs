@@ -1,5 +1,6 @@
 use std::str::FromStr;
 
+use axum::Extension;
 use axum::{
     body::Body,
     headers::HeaderName,
@@ -9,7 +10,7 @@ use axum::{
 use reqwest::StatusCode;
 use serde::Serialize;
 
-use crate::{error::AppError, service::auth::*};
+use crate::{config::Config, error::AppError, service::auth::*};
 #[derive(Serialize)]
 pub struct LoginResponse {
     token: String,
@@ -27,8 +28,10 @@ impl LoginResponse {
     }
 }
 
-pub async fn login(_: Request<Body>) -> Result<impl IntoResponse, AppError> {
-    let redirector = generate_steam_redirector()?;
+pub async fn login(
+    Extension(config): Extension<&'static Config>,
+) -> Result<impl IntoResponse, AppError> {
+    let redirector = generate_steam_redirector(config)?;
     let res = axum::response::Response::builder()
         .header(
             HeaderName::from_str("Location").unwrap(),
@@ -49,9 +52,12 @@ pub struct CurrentUserResponse {
     is_admin: bool,
 }
 
-pub async fn steam_callback(req: Request<Body>) -> Result<impl IntoResponse, AppError> {
+pub async fn steam_callback(
+    Extension(config): Extension<&'static Config>,
+    req: Request<Body>,
+) -> Result<impl IntoResponse, AppError> {
     let qs = req.uri().query().ok_or(AppError::Unauthorized)?;
-    let (token, steam_user) = on_steam_callback(qs).await?;
+    let (token, steam_user) = on_steam_callback(config, qs).await?;
     let user_in_db =
         crate::model::user::select_by_steamid(&mut crate::global::RB.clone(), steam_user.steamid)
             .await
@@ -0,0 +1,68 @@
+use axum::extract::{Path, Query};
+use axum::{Extension, Json};
+use serde::Deserialize;
+
+use crate::error::AppError;
+use crate::model::player_ban::PlayerBan;
+use crate::response::AppResponse;
+use crate::service::auth::TokenData;
+use crate::service::ban::{self, BanPage, BanWithEvents};
+
+#[derive(Deserialize)]
+pub struct IssueBanPayload {
+    pub steamid64: String,
+    pub kind: String,
+    pub scope: Option<String>,
+    pub server_id: Option<u32>,
+    pub reason: String,
+    /// Permanent when empty
+    pub duration_secs: Option<u64>,
+}
+
+#[derive(Deserialize)]
+pub struct LiftBanPayload {
+    pub reason: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ListBansQuery {
+    pub steamid64: Option<String>,
+    pub kind: Option<String>,
+    pub active: Option<bool>,
+    pub page: Option<u64>,
+    pub page_size: Option<u64>,
+}
+
+pub async fn issue_ban(
+    Json(body): Json<IssueBanPayload>,
+    Extension(token_data): Extension<TokenData>,
+) -> Result<AppResponse<PlayerBan>, AppError> {
+    tracing::info!(
+        "Issuing ban to {} for user {}",
+        body.steamid64,
+        token_data.steamid64
+    );
+    let ban = ban::issue_ban(body, token_data.steamid64).await?;
+    Ok(AppResponse::created(ban))
+}
+
+pub async fn lift_ban(
+    Path(ban_id): Path<u32>,
+    Json(body): Json<LiftBanPayload>,
+    Extension(token_data): Extension<TokenData>,
+) -> Result<AppResponse<PlayerBan>, AppError> {
+    tracing::info!("Lifting ban {} for user {}", ban_id, token_data.steamid64);
+    let before = ban::get_ban(ban_id).await.ok().map(|before| before.ban);
+    let ban = ban::lift_ban(ban_id, body, token_data.steamid64).await?;
+    Ok(AppResponse::ok(ban).audited(before))
+}
+
+pub async fn get_bans(
+    Query(query): Query<ListBansQuery>,
+) -> Result<AppResponse<BanPage>, AppError> {
+    Ok(AppResponse::ok(ban::get_bans(query).await?))
+}
+
+pub async fn get_ban(Path(ban_id): Path<u32>) -> Result<AppResponse<BanWithEvents>, AppError> {
+    Ok(AppResponse::ok(ban::get_ban(ban_id).await?))
+}
@@ -0,0 +1,90 @@
+use std::str::FromStr;
+
+use axum::{extract::Path, Extension, Json};
+use serde::Deserialize;
+
+use crate::error::AppError;
+use crate::model::config_template::{ConfigTemplate, ConfigTemplateVersion, ConfigVariable};
+use crate::response::AppResponse;
+use crate::service::auth::TokenData;
+use crate::service::config_template::{self, ConfigPhase, ConfigTemplateDetails};
+
+/// Placeholders are written `{{name}}`, see `BUILTIN_VARIABLES` and the custom variables
+#[derive(Deserialize)]
+pub struct SaveConfigTemplatePayload {
+    pub content: String,
+}
+
+#[derive(Deserialize)]
+pub struct SetConfigVariablePayload {
+    pub value: String,
+}
+
+pub async fn get_templates() -> Result<AppResponse<Vec<ConfigTemplate>>, AppError> {
+    Ok(AppResponse::ok(config_template::get_templates().await?))
+}
+
+pub async fn get_template(
+    Path(phase): Path<String>,
+) -> Result<AppResponse<ConfigTemplateDetails>, AppError> {
+    let phase = ConfigPhase::from_str(&phase)?;
+    Ok(AppResponse::ok(config_template::get_template(phase).await?))
+}
+
+pub async fn get_template_version(
+    Path((phase, version)): Path<(String, u32)>,
+) -> Result<AppResponse<ConfigTemplateVersion>, AppError> {
+    let phase = ConfigPhase::from_str(&phase)?;
+    let version = config_template::get_template_version(phase, version).await?;
+    Ok(AppResponse::ok(version))
+}
+
+pub async fn save_template(
+    Path(phase): Path<String>,
+    Json(body): Json<SaveConfigTemplatePayload>,
+    Extension(token_data): Extension<TokenData>,
+) -> Result<AppResponse<ConfigTemplateDetails>, AppError> {
+    let phase = ConfigPhase::from_str(&phase)?;
+    tracing::info!(
+        "Saving the {} config for user {}",
+        phase.as_str(),
+        token_data.steamid64
+    );
+    let before = config_template::get_template(phase).await.ok();
+    let template =
+        config_template::save_template(phase, body.content, token_data.steamid64).await?;
+    Ok(AppResponse::ok(template).audited(before))
+}
+
+pub async fn delete_template(
+    Path(phase): Path<String>,
+    Extension(token_data): Extension<TokenData>,
+) -> Result<AppResponse<()>, AppError> {
+    let phase = ConfigPhase::from_str(&phase)?;
+    let before = config_template::get_template(phase).await.ok();
+    config_template::delete_template(phase, token_data.steamid64).await?;
+    Ok(AppResponse::ok(()).audited(before))
+}
+
+pub async fn get_variables() -> Result<AppResponse<Vec<ConfigVariable>>, AppError> {
+    Ok(AppResponse::ok(config_template::get_variables().await?))
+}
+
+pub async fn set_variable(
+    Path(name): Path<String>,
+    Json(body): Json<SetConfigVariablePayload>,
+    Extension(token_data): Extension<TokenData>,
+) -> Result<AppResponse<ConfigVariable>, AppError> {
+    let before = config_template::find_variable(&name).await?;
+    let variable = config_template::set_variable(name, body.value, token_data.steamid64).await?;
+    Ok(AppResponse::ok(variable).audited(before))
+}
+
+pub async fn delete_variable(
+    Path(name): Path<String>,
+    Extension(token_data): Extension<TokenData>,
+) -> Result<AppResponse<()>, AppError> {
+    let before = config_template::find_variable(&name).await?;
+    config_template::delete_variable(name, token_data.steamid64).await?;
+    Ok(AppResponse::ok(()).audited(before))
+}
@@ -0,0 +1,50 @@
+use axum::{extract::Path, Extension, Json};
+
+use crate::error::AppError;
+use crate::response::AppResponse;
+use crate::service::auth::TokenData;
+use crate::service::enrollment::{
+    self, ApprovedServer, CreateEnrollmentTokenPayload, EnrollmentTokenSummary,
+    IssuedEnrollmentToken, PendingServerEntry,
+};
+
+/// The token is only shown in this response
+pub async fn create_enrollment_token(
+    Json(body): Json<CreateEnrollmentTokenPayload>,
+    Extension(token_data): Extension<TokenData>,
+) -> Result<AppResponse<IssuedEnrollmentToken>, AppError> {
+    let token = enrollment::create_enrollment_token(body, token_data.steamid64).await?;
+    Ok(AppResponse::created(token))
+}
+
+pub async fn get_enrollment_tokens() -> Result<AppResponse<Vec<EnrollmentTokenSummary>>, AppError> {
+    Ok(AppResponse::ok(enrollment::get_enrollment_tokens().await?))
+}
+
+pub async fn revoke_enrollment_token(
+    Path(token_id): Path<u32>,
+    Extension(token_data): Extension<TokenData>,
+) -> Result<AppResponse<()>, AppError> {
+    enrollment::revoke_enrollment_token(token_id, token_data.steamid64).await?;
+    Ok(AppResponse::ok(()))
+}
+
+pub async fn get_pending_servers() -> Result<AppResponse<Vec<PendingServerEntry>>, AppError> {
+    Ok(AppResponse::ok(enrollment::get_pending_servers().await?))
+}
+
+pub async fn approve_server(
+    Path(pending_id): Path<u32>,
+    Extension(token_data): Extension<TokenData>,
+) -> Result<AppResponse<ApprovedServer>, AppError> {
+    let approved = enrollment::approve(pending_id, token_data.steamid64).await?;
+    Ok(AppResponse::ok(approved))
+}
+
+pub async fn reject_server(
+    Path(pending_id): Path<u32>,
+    Extension(token_data): Extension<TokenData>,
+) -> Result<AppResponse<PendingServerEntry>, AppError> {
+    let rejected = enrollment::reject(pending_id, token_data.steamid64).await?;
+    Ok(AppResponse::ok(rejected))
+}
@@ -0,0 +1,132 @@
+use axum::extract::Path;
+use axum::{Extension, Json};
+use rbatis::rbdc::datetime::FastDateTime;
+use serde::Deserialize;
+
+use crate::config::Config;
+use crate::error::AppError;
+use crate::model::match_schedule::MatchSchedule;
+use crate::model::round_backup::{RoundBackup, RoundBackupSummary};
+use crate::model::GameMatch;
+use crate::response::AppResponse;
+use crate::service::auth::TokenData;
+use crate::service::game_match::{self, MatchDetails};
+use crate::service::round_backup::{self, RoundRestore};
+use crate::service::scheduler;
+
+#[derive(Deserialize)]
+pub struct CreateMatchPayload {
+    pub team1_id: u32,
+    pub team2_id: u32,
+    pub server_id: Option<u32>,
+    pub scheduled_at: Option<FastDateTime>,
+}
+
+pub async fn create_match(
+    Json(body): Json<CreateMatchPayload>,
+    Extension(token_data): Extension<TokenData>,
+) -> Result<AppResponse<GameMatch>, AppError> {
+    tracing::info!("Creating match for user {}", token_data.steamid64);
+    let created_match = game_match::create_match(body).await?;
+    Ok(AppResponse::created(created_match))
+}
+
+#[derive(Deserialize)]
+pub struct ReportResultPayload {
+    pub winner_team_id: u32,
+}
+
+/// Manual result entry for matches played on servers that can't report it themselves
+pub async fn report_result(
+    Path(match_id): Path<u32>,
+    Json(body): Json<ReportResultPayload>,
+    Extension(token_data): Extension<TokenData>,
+) -> Result<AppResponse<MatchDetails>, AppError> {
+    tracing::info!(
+        "Reporting result of match {} for user {}",
+        match_id,
+        token_data.steamid64
+    );
+    game_match::finish_match(match_id, body.winner_team_id).await?;
+    Ok(AppResponse::ok(game_match::get_match(match_id).await?))
+}
+
+#[derive(Deserialize)]
+pub struct ScheduleMatchPayload {
+    pub scheduled_at: FastDateTime,
+}
+
+pub async fn schedule_match(
+    Path(match_id): Path<u32>,
+    Json(body): Json<ScheduleMatchPayload>,
+    Extension(token_data): Extension<TokenData>,
+) -> Result<AppResponse<MatchSchedule>, AppError> {
+    tracing::info!(
+        "Scheduling match {} for user {}",
+        match_id,
+        token_data.steamid64
+    );
+    let schedule = scheduler::schedule_match(match_id, body.scheduled_at).await?;
+    Ok(AppResponse::ok(schedule))
+}
+
+pub async fn cancel_schedule(
+    Path(match_id): Path<u32>,
+    Extension(token_data): Extension<TokenData>,
+    Extension(config): Extension<&'static Config>,
+) -> Result<AppResponse<()>, AppError> {
+    tracing::info!(
+        "Cancelling scheduled match {} for user {}",
+        match_id,
+        token_data.steamid64
+    );
+    scheduler::cancel_schedule(config, match_id).await?;
+    Ok(AppResponse::ok(()))
+}
+
+pub async fn get_match(Path(match_id): Path<u32>) -> Result<AppResponse<MatchDetails>, AppError> {
+    Ok(AppResponse::ok(game_match::get_match(match_id).await?))
+}
+
+pub async fn get_backups(
+    Path(match_id): Path<u32>,
+) -> Result<AppResponse<Vec<RoundBackupSummary>>, AppError> {
+    Ok(AppResponse::ok(round_backup::get_backups(match_id).await?))
+}
+
+pub async fn get_backup(
+    Path((match_id, round)): Path<(u32, u32)>,
+) -> Result<AppResponse<RoundBackup>, AppError> {
+    Ok(AppResponse::ok(
+        round_backup::get_backup(match_id, round).await?,
+    ))
+}
+
+/// Without `server_id` the match is restored on the server hosting it
+#[derive(Deserialize)]
+pub struct RestoreRoundPayload {
+    pub server_id: Option<u32>,
+}
+
+pub async fn restore_round(
+    Path((match_id, round)): Path<(u32, u32)>,
+    Json(body): Json<RestoreRoundPayload>,
+    Extension(token_data): Extension<TokenData>,
+    Extension(config): Extension<&'static Config>,
+) -> Result<AppResponse<RoundRestore>, AppError> {
+    tracing::info!(
+        "Restoring round {} of match {} for user {}",
+        round,
+        match_id,
+        token_data.steamid64
+    );
+    let restore = round_backup::restore_round(
+        config,
+        match_id,
+        round,
+        body.server_id,
+        token_data.steamid64,
+    )
+    .await?;
+    Ok(AppResponse::ok(restore))
+}
@@ -0,0 +1,31 @@
+use axum::{
+    http::{header, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use serde_json::json;
+
+use crate::metrics;
+use crate::response::ResponseBody;
+use crate::service::health;
+
+/// Liveness only, it doesn't touch the database
+pub async fn healthz() -> impl IntoResponse {
+    Json(ResponseBody::new(json!({ "status": "ok" })))
+}
+
+pub async fn readyz() -> impl IntoResponse {
+    let readiness = health::check_readiness().await;
+    let status = match readiness.ready {
+        true => StatusCode::OK,
+        false => StatusCode::SERVICE_UNAVAILABLE,
+    };
+    (status, Json(ResponseBody::new(readiness)))
+}
+
+pub async fn get_metrics() -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        metrics::render().await,
+    )
+}
@@ -1,2 +1,13 @@
+pub mod audit;
 pub mod auth;
+pub mod ban;
+pub mod config_template;
+pub mod enrollment;
+pub mod game_match;
+pub mod health;
 pub mod server;
+pub mod server_log;
+pub mod team;
+pub mod tournament;
+pub mod trust;
+pub mod user;
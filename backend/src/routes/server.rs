@@ -1,15 +1,21 @@
-use axum::{Extension, Json};
+use axum::{extract::Path, Extension, Json};
 use rbatis::rbdc::db::ExecResult;
 use serde::Deserialize;
 
+use crate::config::Config;
 use crate::error::AppError;
+use crate::model::queued_command::QueuedCommand;
 use crate::response::AppResponse;
 use crate::service::auth::TokenData;
+use crate::service::command_queue;
 use crate::service::server;
+use crate::service::server_command::{self, CommandResult, ServerCommand};
+/// Servers given by `hostname` alone get their ip resolved from it, and refreshed periodically
 #[derive(Deserialize)]
 pub struct CreateServerPayload {
-    pub ip: String,
-    pub port: String,
+    pub ip: Option<String>,
+    pub hostname: Option<String>,
+    pub port: u16,
 }
 
 pub async fn create_server(
@@ -20,3 +26,44 @@ pub async fn create_server(
     let created_server = server::create_server(body).await?;
     Ok(AppResponse::created(created_server))
 }
+
+/// Waits for the server to acknowledge the command, `status` tells whether it did
+pub async fn send_command(
+    Path(server_id): Path<u32>,
+    Json(body): Json<ServerCommand>,
+    Extension(token_data): Extension<TokenData>,
+) -> Result<AppResponse<CommandResult>, AppError> {
+    tracing::info!(
+        "Sending {} to server {} for user {}",
+        body.as_str(),
+        server_id,
+        token_data.steamid64
+    );
+    let issued = server_command::issue_command(server_id, body, token_data.steamid64).await?;
+    Ok(AppResponse::ok(
+        server_command::wait_for_result(issued).await,
+    ))
+}
+
+/// Commands of the server still waiting for an ack, or dead-lettered
+pub async fn get_queue(
+    Path(server_id): Path<u32>,
+) -> Result<AppResponse<Vec<QueuedCommand>>, AppError> {
+    Ok(AppResponse::ok(command_queue::get_queue(server_id).await?))
+}
+
+pub async fn retry_queued_command(
+    Path((server_id, seq)): Path<(u32, u32)>,
+    Extension(token_data): Extension<TokenData>,
+    Extension(config): Extension<&'static Config>,
+) -> Result<AppResponse<QueuedCommand>, AppError> {
+    tracing::info!(
+        "Retrying command {} of server {} for user {}",
+        seq,
+        server_id,
+        token_data.steamid64
+    );
+    Ok(AppResponse::ok(
+        command_queue::retry_dead(config, server_id, seq).await?,
+    ))
+}
@@ -0,0 +1,62 @@
+use std::net::SocketAddr;
+
+use axum::extract::{ConnectInfo, ContentLengthLimit, Path, Query};
+use axum::Extension;
+use serde::Deserialize;
+
+use crate::config::Config;
+use crate::error::AppError;
+use crate::model::player_match_stats::PlayerMatchStats;
+use crate::response::AppResponse;
+use crate::service::auth::TokenData;
+use crate::service::server_log::{self, IngestSummary, LogToken};
+
+/// srcds posts a few seconds of logs at a time
+const MAX_LOG_BATCH_BYTES: u64 = 1024 * 1024;
+
+#[derive(Deserialize)]
+pub struct IngestQuery {
+    pub token: String,
+}
+
+/// `logaddress_add_http` can't set headers, the token comes in the url
+pub async fn ingest_logs(
+    Path(server_id): Path<u32>,
+    Query(query): Query<IngestQuery>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Extension(config): Extension<&'static Config>,
+    ContentLengthLimit(body): ContentLengthLimit<String, MAX_LOG_BATCH_BYTES>,
+) -> Result<AppResponse<IngestSummary>, AppError> {
+    if !config.is_server_allowed(&addr.ip()) {
+        tracing::info!(
+            "Server outside of the allowlist tried to post logs: {}",
+            addr.ip()
+        );
+        return Err(AppError::Unauthorized);
+    }
+    server_log::authorize(server_id, &query.token).await?;
+    Ok(AppResponse::ok(server_log::ingest(server_id, &body).await?))
+}
+
+pub async fn create_log_token(
+    Path(server_id): Path<u32>,
+    Extension(token_data): Extension<TokenData>,
+    Extension(config): Extension<&'static Config>,
+) -> Result<AppResponse<LogToken>, AppError> {
+    tracing::info!(
+        "Issuing a log token for server {} for user {}",
+        server_id,
+        token_data.steamid64
+    );
+    Ok(AppResponse::created(
+        server_log::create_log_token(config, server_id, token_data.steamid64).await?,
+    ))
+}
+
+pub async fn get_match_stats(
+    Path(match_id): Path<u32>,
+) -> Result<AppResponse<Vec<PlayerMatchStats>>, AppError> {
+    Ok(AppResponse::ok(
+        server_log::get_match_stats(match_id).await?,
+    ))
+}
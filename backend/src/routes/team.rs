@@ -0,0 +1,107 @@
+use axum::extract::Path;
+use axum::{Extension, Json};
+use serde::Deserialize;
+
+use crate::error::AppError;
+use crate::model::team::{Team, TeamInvite, TeamMember, TeamRosterEvent};
+use crate::response::AppResponse;
+use crate::service::auth::TokenData;
+use crate::service::team::{self, TeamWithRoster};
+
+#[derive(Deserialize)]
+pub struct CreateTeamPayload {
+    pub name: String,
+    pub tag: String,
+    pub logo: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct InviteMemberPayload {
+    pub steamid64: String,
+    pub role: String,
+}
+
+#[derive(Deserialize)]
+pub struct TransferCaptaincyPayload {
+    pub steamid64: String,
+}
+
+pub async fn create_team(
+    Json(body): Json<CreateTeamPayload>,
+    Extension(token_data): Extension<TokenData>,
+) -> Result<AppResponse<Team>, AppError> {
+    let created_team = team::create_team(body, token_data.steamid64).await?;
+    Ok(AppResponse::created(created_team))
+}
+
+pub async fn get_teams() -> Result<AppResponse<Vec<Team>>, AppError> {
+    Ok(AppResponse::ok(team::get_teams().await?))
+}
+
+pub async fn get_team(Path(team_id): Path<u32>) -> Result<AppResponse<TeamWithRoster>, AppError> {
+    Ok(AppResponse::ok(team::get_team(team_id).await?))
+}
+
+pub async fn get_roster_history(
+    Path(team_id): Path<u32>,
+) -> Result<AppResponse<Vec<TeamRosterEvent>>, AppError> {
+    Ok(AppResponse::ok(team::get_roster_history(team_id).await?))
+}
+
+pub async fn invite_member(
+    Path(team_id): Path<u32>,
+    Json(body): Json<InviteMemberPayload>,
+    Extension(token_data): Extension<TokenData>,
+) -> Result<AppResponse<TeamInvite>, AppError> {
+    let invite = team::invite_member(team_id, body, token_data.steamid64).await?;
+    Ok(AppResponse::created(invite))
+}
+
+pub async fn get_my_invites(
+    Extension(token_data): Extension<TokenData>,
+) -> Result<AppResponse<Vec<TeamInvite>>, AppError> {
+    Ok(AppResponse::ok(
+        team::get_invites(token_data.steamid64).await?,
+    ))
+}
+
+pub async fn accept_invite(
+    Path(invite_id): Path<u32>,
+    Extension(token_data): Extension<TokenData>,
+) -> Result<AppResponse<TeamMember>, AppError> {
+    let member = team::accept_invite(invite_id, token_data.steamid64).await?;
+    Ok(AppResponse::created(member))
+}
+
+pub async fn delete_invite(
+    Path(invite_id): Path<u32>,
+    Extension(token_data): Extension<TokenData>,
+) -> Result<AppResponse<()>, AppError> {
+    team::delete_invite(invite_id, token_data.steamid64).await?;
+    Ok(AppResponse::ok(()))
+}
+
+pub async fn leave_team(
+    Path(team_id): Path<u32>,
+    Extension(token_data): Extension<TokenData>,
+) -> Result<AppResponse<()>, AppError> {
+    team::leave_team(team_id, token_data.steamid64).await?;
+    Ok(AppResponse::ok(()))
+}
+
+pub async fn kick_member(
+    Path((team_id, steamid64)): Path<(u32, String)>,
+    Extension(token_data): Extension<TokenData>,
+) -> Result<AppResponse<()>, AppError> {
+    team::kick_member(team_id, steamid64, token_data.steamid64).await?;
+    Ok(AppResponse::ok(()))
+}
+
+pub async fn transfer_captaincy(
+    Path(team_id): Path<u32>,
+    Json(body): Json<TransferCaptaincyPayload>,
+    Extension(token_data): Extension<TokenData>,
+) -> Result<AppResponse<()>, AppError> {
+    team::transfer_captaincy(team_id, body.steamid64, token_data.steamid64).await?;
+    Ok(AppResponse::ok(()))
+}
@@ -0,0 +1,93 @@
+use axum::extract::Path;
+use axum::{Extension, Json};
+use serde::Deserialize;
+
+use crate::error::AppError;
+use crate::model::tournament::{Tournament, TournamentTeam};
+use crate::response::AppResponse;
+use crate::service::auth::TokenData;
+use crate::service::tournament::{self, StandingResponse, TournamentDetails};
+
+#[derive(Deserialize)]
+pub struct CreateTournamentPayload {
+    pub name: String,
+    pub format: String,
+    pub swiss_rounds: Option<u32>,
+}
+
+#[derive(Deserialize)]
+pub struct RegisterTeamPayload {
+    pub team_id: u32,
+    pub seed: Option<u32>,
+}
+
+#[derive(Deserialize)]
+pub struct SeedTeamsPayload {
+    pub team_ids: Vec<u32>,
+}
+
+pub async fn create_tournament(
+    Json(body): Json<CreateTournamentPayload>,
+    Extension(token_data): Extension<TokenData>,
+) -> Result<AppResponse<Tournament>, AppError> {
+    tracing::info!("Creating tournament for user {}", token_data.steamid64);
+    let created_tournament = tournament::create_tournament(body).await?;
+    Ok(AppResponse::created(created_tournament))
+}
+
+pub async fn get_tournaments() -> Result<AppResponse<Vec<Tournament>>, AppError> {
+    Ok(AppResponse::ok(tournament::get_tournaments().await?))
+}
+
+pub async fn get_tournament(
+    Path(tournament_id): Path<u32>,
+) -> Result<AppResponse<TournamentDetails>, AppError> {
+    Ok(AppResponse::ok(
+        tournament::get_tournament(tournament_id).await?,
+    ))
+}
+
+pub async fn get_standings(
+    Path(tournament_id): Path<u32>,
+) -> Result<AppResponse<Vec<StandingResponse>>, AppError> {
+    Ok(AppResponse::ok(
+        tournament::get_standings(tournament_id).await?,
+    ))
+}
+
+pub async fn register_team(
+    Path(tournament_id): Path<u32>,
+    Json(body): Json<RegisterTeamPayload>,
+) -> Result<AppResponse<TournamentTeam>, AppError> {
+    let registered_team = tournament::register_team(tournament_id, body).await?;
+    Ok(AppResponse::created(registered_team))
+}
+
+pub async fn unregister_team(
+    Path((tournament_id, team_id)): Path<(u32, u32)>,
+) -> Result<AppResponse<()>, AppError> {
+    tournament::unregister_team(tournament_id, team_id).await?;
+    Ok(AppResponse::ok(()))
+}
+
+pub async fn seed_teams(
+    Path(tournament_id): Path<u32>,
+    Json(body): Json<SeedTeamsPayload>,
+) -> Result<AppResponse<()>, AppError> {
+    tournament::seed_teams(tournament_id, body.team_ids).await?;
+    Ok(AppResponse::ok(()))
+}
+
+pub async fn start_tournament(
+    Path(tournament_id): Path<u32>,
+    Extension(token_data): Extension<TokenData>,
+) -> Result<AppResponse<TournamentDetails>, AppError> {
+    tracing::info!(
+        "Starting tournament {} for user {}",
+        tournament_id,
+        token_data.steamid64
+    );
+    Ok(AppResponse::ok(
+        tournament::start_tournament(tournament_id).await?,
+    ))
+}
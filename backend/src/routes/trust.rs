@@ -0,0 +1,35 @@
+use axum::{Extension, Json};
+use serde::Deserialize;
+
+use crate::error::AppError;
+use crate::model::trust::TrustPolicy;
+use crate::response::AppResponse;
+use crate::service::auth::TokenData;
+use crate::service::trust;
+
+/// Every action is one of `allow`, `flag` or `block`
+#[derive(Deserialize)]
+pub struct UpdateTrustPolicyPayload {
+    pub vac_ban_action: String,
+    pub game_ban_action: String,
+    pub community_ban_action: String,
+    pub economy_ban_action: String,
+    pub private_profile_action: String,
+    pub new_account_action: String,
+    pub min_account_age_days: u32,
+    pub ignore_bans_older_than_days: Option<u32>,
+}
+
+pub async fn get_policy() -> Result<AppResponse<TrustPolicy>, AppError> {
+    Ok(AppResponse::ok(trust::get_policy().await?))
+}
+
+pub async fn update_policy(
+    Json(body): Json<UpdateTrustPolicyPayload>,
+    Extension(token_data): Extension<TokenData>,
+) -> Result<AppResponse<TrustPolicy>, AppError> {
+    tracing::info!("Updating trust policy for user {}", token_data.steamid64);
+    let before = trust::get_policy().await.ok();
+    let policy = trust::update_policy(body, token_data.steamid64).await?;
+    Ok(AppResponse::ok(policy).audited(before))
+}
@@ -0,0 +1,59 @@
+use axum::extract::{Path, Query};
+use axum::{Extension, Json};
+use serde::Deserialize;
+
+use crate::config::Config;
+use crate::error::AppError;
+use crate::model::trust::SteamTrustCheck;
+use crate::model::user::User;
+use crate::response::AppResponse;
+use crate::service::auth::TokenData;
+use crate::service::trust;
+use crate::service::user::{self, UserWithTrust};
+
+#[derive(Deserialize)]
+pub struct SetCasterPayload {
+    pub is_caster: bool,
+}
+
+#[derive(Deserialize)]
+pub struct ListUsersQuery {
+    pub verdict: Option<String>,
+}
+
+pub async fn get_users(
+    Query(query): Query<ListUsersQuery>,
+) -> Result<AppResponse<Vec<UserWithTrust>>, AppError> {
+    Ok(AppResponse::ok(user::get_users(query).await?))
+}
+
+pub async fn refresh_trust(
+    Path(steamid64): Path<String>,
+    Extension(token_data): Extension<TokenData>,
+    Extension(config): Extension<&'static Config>,
+) -> Result<AppResponse<SteamTrustCheck>, AppError> {
+    tracing::info!(
+        "Refreshing trust check of {} for user {}",
+        steamid64,
+        token_data.steamid64
+    );
+    Ok(AppResponse::ok(
+        trust::refresh_check(config, steamid64).await?,
+    ))
+}
+
+pub async fn set_caster(
+    Path(steamid64): Path<String>,
+    Json(body): Json<SetCasterPayload>,
+    Extension(token_data): Extension<TokenData>,
+) -> Result<AppResponse<User>, AppError> {
+    tracing::info!(
+        "Setting caster role of {} to {} for user {}",
+        steamid64,
+        body.is_caster,
+        token_data.steamid64
+    );
+    let before = user::find_user(&steamid64).await.ok();
+    let user = user::set_caster(steamid64, body.is_caster).await?;
+    Ok(AppResponse::ok(user).audited(before))
+}
@@ -0,0 +1,251 @@
+//! Client for the Source engine server queries (A2S_INFO, A2S_PLAYER and A2S_RULES), answered by
+//! every srcds on its game port whether or not it runs the plugin.
+
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, bail};
+use serde::Serialize;
+use tokio::net::UdpSocket;
+
+const SINGLE_PACKET: &[u8] = b"\xFF\xFF\xFF\xFF";
+const SPLIT_PACKET: &[u8] = b"\xFE\xFF\xFF\xFF";
+const A2S_INFO: u8 = 0x54;
+const A2S_PLAYER: u8 = 0x55;
+const A2S_RULES: u8 = 0x56;
+const S2C_CHALLENGE: u8 = 0x41;
+const S2A_INFO: u8 = 0x49;
+const S2A_PLAYER: u8 = 0x44;
+const S2A_RULES: u8 = 0x45;
+/// Servers may ask for a fresh challenge again, give up after that many
+const MAX_CHALLENGES: usize = 3;
+const MAX_PACKET_SIZE: usize = 1400;
+
+#[derive(Serialize, Clone)]
+pub struct A2sInfo {
+    pub name: String,
+    pub map: String,
+    pub folder: String,
+    pub game: String,
+    pub players: u8,
+    pub max_players: u8,
+    pub bots: u8,
+    pub vac: bool,
+    pub version: String,
+    /// Round trip of the answered request
+    pub ping_ms: u32,
+}
+
+#[derive(Serialize, Clone)]
+pub struct A2sPlayer {
+    pub name: String,
+    pub score: i32,
+    pub duration_secs: f32,
+}
+
+pub async fn query_info(addr: SocketAddr, timeout: Duration) -> anyhow::Result<A2sInfo> {
+    let (response, ping) = query(addr, A2S_INFO, S2A_INFO, timeout).await?;
+
+    let mut reader = Reader::new(&response);
+    let _protocol = reader.u8()?;
+    let name = reader.string()?;
+    let map = reader.string()?;
+    let folder = reader.string()?;
+    let game = reader.string()?;
+    let _app_id = reader.u16()?;
+    let players = reader.u8()?;
+    let max_players = reader.u8()?;
+    let bots = reader.u8()?;
+    let _server_type = reader.u8()?;
+    let _environment = reader.u8()?;
+    let _visibility = reader.u8()?;
+    let vac = reader.u8()? == 1;
+    let version = reader.string()?;
+    Ok(A2sInfo {
+        name,
+        map,
+        folder,
+        game,
+        players,
+        max_players,
+        bots,
+        vac,
+        version,
+        ping_ms: ping.as_millis().try_into().unwrap_or(u32::MAX),
+    })
+}
+
+pub async fn query_players(addr: SocketAddr, timeout: Duration) -> anyhow::Result<Vec<A2sPlayer>> {
+    let (response, _) = query(addr, A2S_PLAYER, S2A_PLAYER, timeout).await?;
+    let mut reader = Reader::new(&response);
+    let count = reader.u8()?;
+    let mut players = Vec::with_capacity(count.into());
+    for _ in 0..count {
+        let _index = reader.u8()?;
+        players.push(A2sPlayer {
+            name: reader.string()?,
+            score: reader.i32()?,
+            duration_secs: reader.f32()?,
+        });
+    }
+    Ok(players)
+}
+
+pub async fn query_rules(
+    addr: SocketAddr,
+    timeout: Duration,
+) -> anyhow::Result<BTreeMap<String, String>> {
+    let (response, _) = query(addr, A2S_RULES, S2A_RULES, timeout).await?;
+    let mut reader = Reader::new(&response);
+    let count = reader.u16()?;
+    let mut rules = BTreeMap::new();
+    for _ in 0..count {
+        rules.insert(reader.string()?, reader.string()?);
+    }
+    Ok(rules)
+}
+
+/// Info requests get the challenge appended once the server asks for one, player and rules
+/// requests send `-1` to get one first
+fn build_request(kind: u8, challenge: Option<[u8; 4]>) -> Vec<u8> {
+    let mut request = SINGLE_PACKET.to_vec();
+    request.push(kind);
+    if kind == A2S_INFO {
+        request.extend_from_slice(b"Source Engine Query\0");
+        request.extend(challenge.iter().flatten());
+    } else {
+        request.extend_from_slice(&challenge.unwrap_or([0xFF; 4]));
+    }
+    request
+}
+
+/// Sends the request, answering challenges by sending it again with the challenge. Returns the
+/// payload after the expected header and the round trip.
+async fn query(
+    addr: SocketAddr,
+    kind: u8,
+    expected: u8,
+    timeout: Duration,
+) -> anyhow::Result<(Vec<u8>, Duration)> {
+    let local: SocketAddr = if addr.is_ipv4() {
+        ([0, 0, 0, 0], 0).into()
+    } else {
+        ([0u16; 8], 0).into()
+    };
+    let socket = UdpSocket::bind(local).await?;
+    socket.connect(addr).await?;
+
+    let mut challenge = None;
+    for _ in 0..MAX_CHALLENGES {
+        let started = Instant::now();
+        socket.send(&build_request(kind, challenge)).await?;
+        let response = tokio::time::timeout(timeout, receive(&socket))
+            .await
+            .map_err(|_| anyhow!("{} didn't answer in time", addr))??;
+        let ping = started.elapsed();
+        match response.split_first() {
+            Some((&S2C_CHALLENGE, rest)) if rest.len() >= 4 => {
+                challenge = Some(rest[..4].try_into().unwrap());
+            }
+            Some((&header, payload)) if header == expected => return Ok((payload.to_vec(), ping)),
+            Some((header, _)) => bail!("{} answered with header {:#04x}", addr, header),
+            None => bail!("{} answered with an empty packet", addr),
+        }
+    }
+    bail!("{} kept asking for a challenge", addr)
+}
+
+/// Reads one response, reassembling split ones. The returned payload starts with its header byte.
+async fn receive(socket: &UdpSocket) -> anyhow::Result<Vec<u8>> {
+    let mut buf = vec![0; MAX_PACKET_SIZE];
+    let mut parts: Vec<Option<Vec<u8>>> = Vec::new();
+    let mut split_id = None;
+    loop {
+        let len = socket.recv(&mut buf).await?;
+        let packet = &buf[..len];
+        if let Some(payload) = packet.strip_prefix(SINGLE_PACKET) {
+            return Ok(payload.to_vec());
+        }
+        let mut reader = Reader::new(
+            packet
+                .strip_prefix(SPLIT_PACKET)
+                .ok_or_else(|| anyhow!("Unknown packet header"))?,
+        );
+        let id = reader.i32()?;
+        if id < 0 {
+            bail!("Compressed responses aren't supported");
+        }
+        let total = reader.u8()?;
+        let number = reader.u8()?;
+        let _size = reader.u16()?;
+        //leftovers of an earlier response are skipped
+        if split_id.is_some_and(|split_id| split_id != id) {
+            continue;
+        }
+        if total == 0 || number >= total {
+            bail!("Split packet {} of {}", number, total);
+        }
+        split_id = Some(id);
+        parts.resize(total.into(), None);
+        parts[usize::from(number)] = Some(reader.rest().to_vec());
+        if parts.iter().all(Option::is_some) {
+            let response: Vec<u8> = parts.into_iter().flatten().flatten().collect();
+            return response
+                .strip_prefix(SINGLE_PACKET)
+                .map(|payload| payload.to_vec())
+                .ok_or_else(|| anyhow!("Split response without a header"));
+        }
+    }
+}
+
+/// Little endian fields and nul terminated strings of the responses
+struct Reader<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(buf: &'a [u8]) -> Self {
+        Reader { buf }
+    }
+
+    fn take<const N: usize>(&mut self) -> anyhow::Result<[u8; N]> {
+        if self.buf.len() < N {
+            bail!("Truncated response");
+        }
+        let (bytes, rest) = self.buf.split_at(N);
+        self.buf = rest;
+        Ok(bytes.try_into().unwrap())
+    }
+
+    fn u8(&mut self) -> anyhow::Result<u8> {
+        Ok(self.take::<1>()?[0])
+    }
+
+    fn u16(&mut self) -> anyhow::Result<u16> {
+        Ok(u16::from_le_bytes(self.take()?))
+    }
+
+    fn i32(&mut self) -> anyhow::Result<i32> {
+        Ok(i32::from_le_bytes(self.take()?))
+    }
+
+    fn f32(&mut self) -> anyhow::Result<f32> {
+        Ok(f32::from_le_bytes(self.take()?))
+    }
+
+    fn string(&mut self) -> anyhow::Result<String> {
+        let end = self
+            .buf
+            .iter()
+            .position(|&b| b == 0)
+            .ok_or_else(|| anyhow!("Unterminated string"))?;
+        let string = String::from_utf8_lossy(&self.buf[..end]).into_owned();
+        self.buf = &self.buf[end + 1..];
+        Ok(string)
+    }
+
+    fn rest(&self) -> &'a [u8] {
+        self.buf
+    }
+}
@@ -0,0 +1,135 @@
+use rbatis::rbdc::datetime::FastDateTime;
+use serde::{Deserialize, Serialize};
+
+use crate::error::AppError;
+use crate::global;
+use crate::model::player_admission::PlayerAdmission;
+use crate::model::{game_match, team, user, GameMatch};
+use crate::service::ban::{self, BanKind};
+
+/// Sent by the plugin when a player starts connecting, before the player is put on a team
+#[derive(Deserialize)]
+pub struct PlayerConnecting {
+    pub steamid64: String,
+    pub name: Option<String>,
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AdmissionReason {
+    Banned,
+    Team1,
+    Team2,
+    Admin,
+    Caster,
+    NoActiveMatch,
+    NotOnRoster,
+}
+
+impl AdmissionReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AdmissionReason::Banned => "banned",
+            AdmissionReason::Team1 => "team1",
+            AdmissionReason::Team2 => "team2",
+            AdmissionReason::Admin => "admin",
+            AdmissionReason::Caster => "caster",
+            AdmissionReason::NoActiveMatch => "no_active_match",
+            AdmissionReason::NotOnRoster => "not_on_roster",
+        }
+    }
+}
+
+/// Answer to `server_2_backend_player_connecting`, the plugin kicks denied players and puts the others on
+/// `team_id`. Admins and casters are allowed without a team so they join as spectators.
+#[derive(Serialize)]
+pub struct PlayerAdmissionResponse {
+    pub steamid64: String,
+    pub allowed: bool,
+    pub match_id: Option<u32>,
+    pub team_id: Option<u32>,
+    pub reason: AdmissionReason,
+}
+
+/// Bans go first, then the roster of the match hosted by the server and finally the staff roles
+pub async fn admit_player(
+    server_id: u32,
+    player: PlayerConnecting,
+) -> Result<PlayerAdmissionResponse, AppError> {
+    let now = FastDateTime::now();
+    let active_match = game_match::select_active_by_server(&global::RB, server_id, server_id)
+        .await
+        .map_err(AppError::DatabaseError)?;
+    let (allowed, team_id, reason) =
+        decide(server_id, &player.steamid64, active_match.as_ref()).await?;
+
+    PlayerAdmission::insert(
+        &mut global::RB.clone(),
+        &PlayerAdmission {
+            id: None,
+            server_id,
+            game_match_id: active_match.as_ref().and_then(|m| m.id),
+            steamid64: player.steamid64.clone(),
+            allowed,
+            team_id,
+            reason: reason.as_str().to_string(),
+            created_at: now,
+        },
+    )
+    .await
+    .map_err(AppError::DatabaseError)?;
+    tracing::info!(
+        "Player {} ({}) {} on server {}: {}",
+        player.steamid64,
+        player.name.as_deref().unwrap_or("unknown"),
+        if allowed { "admitted" } else { "denied" },
+        server_id,
+        reason.as_str()
+    );
+
+    Ok(PlayerAdmissionResponse {
+        steamid64: player.steamid64,
+        allowed,
+        match_id: active_match.and_then(|m| m.id),
+        team_id,
+        reason,
+    })
+}
+
+async fn decide(
+    server_id: u32,
+    steamid64: &str,
+    active_match: Option<&GameMatch>,
+) -> Result<(bool, Option<u32>, AdmissionReason), AppError> {
+    if ban::find_active_ban(steamid64, &[BanKind::Game], Some(server_id))
+        .await?
+        .is_some()
+    {
+        return Ok((false, None, AdmissionReason::Banned));
+    }
+
+    if let Some(active_match) = active_match {
+        for (team_id, reason) in [
+            (active_match.team1_id, AdmissionReason::Team1),
+            (active_match.team2_id, AdmissionReason::Team2),
+        ] {
+            if team::select_member(&global::RB, team_id, steamid64)
+                .await
+                .map_err(AppError::DatabaseError)?
+                .is_some()
+            {
+                return Ok((true, Some(team_id), reason));
+            }
+        }
+    }
+
+    let user = user::select_by_steamid(&global::RB, steamid64.to_string())
+        .await
+        .map_err(AppError::DatabaseError)?;
+    match user {
+        Some(user) if user.is_admin => Ok((true, None, AdmissionReason::Admin)),
+        Some(user) if user.is_caster => Ok((true, None, AdmissionReason::Caster)),
+        _ if active_match.is_none() => Ok((false, None, AdmissionReason::NoActiveMatch)),
+        _ => Ok((false, None, AdmissionReason::NotOnRoster)),
+    }
+}
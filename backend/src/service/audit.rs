@@ -0,0 +1,182 @@
+use rbatis::rbdc::datetime::FastDateTime;
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::error::AppError;
+use crate::global;
+use crate::model::audit_log::{self, AuditLog};
+use crate::routes::audit::AuditLogQuery;
+use crate::service::pagination::Pagination;
+
+/// Longer payloads are truncated before being stored
+const MAX_PAYLOAD_LEN: usize = 4096;
+/// Keys ending with one of these never reach the audit log, config variables keep server
+/// passwords in `value` and the `log_url` of a server carries its log token
+const SECRET_KEYS: [&str; 7] = [
+    "password",
+    "secret",
+    "token",
+    "credential",
+    "hash",
+    "value",
+    "url",
+];
+const REDACTED: &str = "[redacted]";
+
+#[derive(Clone, Copy)]
+pub enum AuditSource {
+    Rest,
+    Ws,
+}
+
+impl AuditSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditSource::Rest => "rest",
+            AuditSource::Ws => "ws",
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct AuditLogPage {
+    pub entries: Vec<AuditLog>,
+    pub total: u64,
+    pub page: u64,
+    pub page_size: u64,
+}
+
+/// State of the target before a change, attached to the response by the handler so the audit
+/// log can store what changed
+#[derive(Clone)]
+pub struct AuditBefore(pub Value);
+
+pub fn audit_entry(
+    actor_steamid64: &str,
+    source: AuditSource,
+    action: String,
+    target: Option<String>,
+    payload: Option<String>,
+    ip: Option<String>,
+) -> AuditLog {
+    let payload = payload
+        .filter(|payload| !payload.is_empty())
+        .map(|mut payload| {
+            if payload.len() > MAX_PAYLOAD_LEN {
+                let mut end = MAX_PAYLOAD_LEN;
+                while !payload.is_char_boundary(end) {
+                    end -= 1;
+                }
+                payload.truncate(end);
+            }
+            payload
+        });
+    AuditLog {
+        id: None,
+        actor_steamid64: actor_steamid64.to_string(),
+        source: source.as_str().to_string(),
+        action,
+        target,
+        payload,
+        status: None,
+        ip,
+        created_at: FastDateTime::now(),
+    }
+}
+
+/// `{"before": ..., "after": ...}` with the fields both sides share only kept when they changed,
+/// secrets are redacted once compared so changing one still shows up
+pub fn change_payload(before: Option<Value>, after: Option<Value>) -> Option<String> {
+    let (before, after) = match (
+        before.filter(|v| !v.is_null()),
+        after.filter(|v| !v.is_null()),
+    ) {
+        (Some(Value::Object(mut before)), Some(Value::Object(mut after))) => {
+            let unchanged: Vec<String> = before
+                .iter()
+                .filter(|(key, value)| after.get(*key) == Some(*value))
+                .map(|(key, _)| key.clone())
+                .collect();
+            for key in unchanged {
+                before.remove(&key);
+                after.remove(&key);
+            }
+            (Some(Value::Object(before)), Some(Value::Object(after)))
+        }
+        (None, None) => return None,
+        sides => sides,
+    };
+    let mut payload = Map::new();
+    for (side, value) in [("before", before), ("after", after)] {
+        if let Some(mut value) = value {
+            redact(&mut value);
+            payload.insert(side.to_string(), value);
+        }
+    }
+    Some(Value::Object(payload).to_string())
+}
+
+/// Replaces the values of secret keys, at any depth
+pub fn redact(value: &mut Value) {
+    match value {
+        Value::Object(fields) => {
+            for (key, value) in fields.iter_mut() {
+                let last = key.rsplit('_').next().unwrap_or(key);
+                if SECRET_KEYS.contains(&last) && !value.is_null() {
+                    *value = Value::String(REDACTED.to_string());
+                } else {
+                    redact(value);
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(redact),
+        _ => {}
+    }
+}
+
+/// Failing to write the audit log never fails the audited action itself
+pub async fn record(entry: AuditLog) {
+    if let Err(e) = AuditLog::insert(&mut global::RB.clone(), &entry).await {
+        tracing::error!(
+            error = ?e,
+            "Couldn't write audit log entry {} by {}",
+            entry.action,
+            entry.actor_steamid64
+        );
+    }
+}
+
+pub async fn get_audit_log(query: AuditLogQuery) -> Result<AuditLogPage, AppError> {
+    let pagination = Pagination::new(query.page, query.page_size)?;
+    let action = query.action.map(|action| format!("%{}%", action));
+
+    let total = audit_log::count(
+        &global::RB,
+        &query.actor,
+        &query.source,
+        &action,
+        &query.from,
+        &query.to,
+    )
+    .await
+    .map_err(AppError::DatabaseError)?;
+    let entries = audit_log::select_page(
+        &global::RB,
+        &query.actor,
+        &query.source,
+        &action,
+        &query.from,
+        &query.to,
+        pagination.page_size,
+        pagination.offset,
+    )
+    .await
+    .map_err(AppError::DatabaseError)?;
+
+    Ok(AuditLogPage {
+        entries,
+        total,
+        page: pagination.page,
+        page_size: pagination.page_size,
+    })
+}
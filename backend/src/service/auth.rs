@@ -4,9 +4,11 @@ use jsonwebtoken::{decode, encode, Algorithm, DecodingKey, EncodingKey, Header};
 use serde::{Deserialize, Serialize};
 use steam_auth;
 
+use crate::config::Config;
 use crate::error::AppError;
 use crate::global;
 use crate::model::user::User;
+use crate::service::trust::{self, TrustVerdict};
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct TokenData {
@@ -19,21 +21,22 @@ pub struct TokenData {
 pub struct SteamUser {
     pub steamid: String,
     pub communityvisibilitystate: i32,
-    pub profilestate: i32,
+    pub profilestate: Option<i32>,
     pub personaname: String,
-    pub commentpermission: i32,
+    pub commentpermission: Option<i32>,
     pub profileurl: String,
     pub avatar: String,
     pub avatarmedium: String,
     pub avatarfull: String,
     pub avatarhash: String,
-    pub lastlogoff: i32,
+    pub lastlogoff: Option<i64>,
     pub personastate: i32,
-    pub realname: String,
-    pub primaryclanid: String,
-    pub timecreated: i32,
-    pub personastateflags: i32,
-    pub loccountrycode: String,
+    /// Private profiles only expose the fields above
+    pub realname: Option<String>,
+    pub primaryclanid: Option<String>,
+    pub timecreated: Option<i64>,
+    pub personastateflags: Option<i32>,
+    pub loccountrycode: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -45,23 +48,39 @@ struct SteamGetPlayerSummaryResponse {
     response: SteamResponsePlayers,
 }
 
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SteamPlayerBans {
+    pub community_banned: bool,
+    #[serde(rename = "VACBanned")]
+    pub vac_banned: bool,
+    #[serde(rename = "NumberOfVACBans")]
+    pub number_of_vac_bans: u32,
+    pub days_since_last_ban: u32,
+    pub number_of_game_bans: u32,
+    pub economy_ban: String,
+}
+
+#[derive(Deserialize)]
+struct SteamGetPlayerBansResponse {
+    players: Vec<SteamPlayerBans>,
+}
+
 ///	/login -> redirect to steam -> /steam_callback -> verify stuff -> create token -> send token and some data to client
-pub fn generate_steam_redirector() -> Result<steam_auth::Redirector, AppError> {
-    steam_auth::Redirector::new(global::API_URL.to_string(), "/api/auth/steam_callback").map_err(
-        |e| {
-            tracing::error!("Failed to create redirector: {}", e);
-            AppError::SteamError(e)
-        },
-    )
+pub fn generate_steam_redirector(config: &Config) -> Result<steam_auth::Redirector, AppError> {
+    steam_auth::Redirector::new(&config.server.api_url, "/api/auth/steam_callback").map_err(|e| {
+        tracing::error!("Failed to create redirector: {}", e);
+        AppError::SteamError(e)
+    })
 }
 
-pub fn create_access_token(steamid64: String) -> Result<String, AppError> {
+pub fn create_access_token(config: &Config, steamid64: String) -> Result<String, AppError> {
     let iat = Utc::now();
-    let exp = iat + Duration::seconds(3600);
+    let exp = iat + Duration::seconds(config.auth.access_token_lifetime_secs as i64);
     let iat = iat.timestamp_millis();
     let exp = exp.timestamp_millis();
 
-    let key = EncodingKey::from_secret(crate::global::JWT_KEY.as_bytes());
+    let key = EncodingKey::from_secret(config.auth.jwt_key.as_bytes());
     let claims = TokenData {
         steamid64,
         iat,
@@ -74,18 +93,31 @@ pub fn create_access_token(steamid64: String) -> Result<String, AppError> {
     })
 }
 
-pub async fn on_steam_callback(qs: &str) -> Result<(String, SteamUser), AppError> {
+pub async fn on_steam_callback(config: &Config, qs: &str) -> Result<(String, SteamUser), AppError> {
     let steamid64 = verify_steam_request(qs).await?;
+    let steam_user = query_steam_user(config, steamid64).await?;
 
-    let mut _token = String::new();
-    match crate::model::user::select_by_steamid(&mut global::RB.clone(), steamid64.to_string())
+    let user = crate::model::user::select_by_steamid(&global::RB, steamid64.to_string())
         .await
         .map_err(|e| {
             tracing::error!("Failed to select user: {}", e);
             AppError::DatabaseError(e)
-        })? {
+        })?;
+    //admins can't lock themselves out by tightening the policy
+    let check = trust::check_steam_user(config, &steam_user, false).await?;
+    if check.verdict == TrustVerdict::Block.as_str() && !user.as_ref().is_some_and(|u| u.is_admin) {
+        tracing::warn!(
+            "Login of {} blocked by the trust policy: {}",
+            steamid64,
+            check.reasons
+        );
+        return Err(AppError::Forbidden);
+    }
+
+    let mut _token = String::new();
+    match user {
         Some(user) => {
-            _token = create_access_token(user.steamid64)?;
+            _token = create_access_token(config, user.steamid64)?;
         }
         None => {
             let new_user = User::from_steamid64(steamid64);
@@ -95,18 +127,16 @@ pub async fn on_steam_callback(qs: &str) -> Result<(String, SteamUser), AppError
                     tracing::error!("Failed to insert user: {}", e);
                     AppError::DatabaseError(e)
                 })?;
-            _token = create_access_token(new_user.steamid64)?;
+            _token = create_access_token(config, new_user.steamid64)?;
         }
     }
-    let steam_user = query_steam_user(steamid64).await?;
     Ok((_token, steam_user))
 }
 
-pub async fn query_steam_user(steamid: u64) -> Result<SteamUser, AppError> {
+pub async fn query_steam_user(config: &Config, steamid: u64) -> Result<SteamUser, AppError> {
     let player_summary_api_url = format!(
-        "http://api.steampowered.com/ISteamUser/GetPlayerSummaries/v0002/?key={}&steamids={}",
-        *global::STEAM_KEY,
-        steamid
+        "{}/ISteamUser/GetPlayerSummaries/v0002/?key={}&steamids={}",
+        config.steam.api_url, config.steam.api_key, steamid
     );
     let resp = reqwest::get(&player_summary_api_url)
         .await
@@ -115,7 +145,7 @@ pub async fn query_steam_user(steamid: u64) -> Result<SteamUser, AppError> {
     let body = resp.text().await.map_err(|e| AppError::ReqwestError(e))?;
     let body: SteamGetPlayerSummaryResponse = serde_json::from_str(&body).map_err(|e| {
         tracing::error!("Failed to parse steam response: {}", e);
-        AppError::JsonParseError(e)
+        AppError::SteamResponseError(e)
     })?;
 
     if body.response.players.len() == 0 {
@@ -124,6 +154,30 @@ pub async fn query_steam_user(steamid: u64) -> Result<SteamUser, AppError> {
     Ok(body.response.players[0].clone())
 }
 
+pub async fn query_player_bans(
+    config: &Config,
+    steamid: &str,
+) -> Result<SteamPlayerBans, AppError> {
+    let player_bans_api_url = format!(
+        "{}/ISteamUser/GetPlayerBans/v1/?key={}&steamids={}",
+        config.steam.api_url, config.steam.api_key, steamid
+    );
+    let resp = reqwest::get(&player_bans_api_url)
+        .await
+        .map_err(AppError::SteamApiError)?;
+
+    let body = resp.text().await.map_err(AppError::ReqwestError)?;
+    let body: SteamGetPlayerBansResponse = serde_json::from_str(&body).map_err(|e| {
+        tracing::error!("Failed to parse steam bans response: {}", e);
+        AppError::SteamResponseError(e)
+    })?;
+
+    body.players
+        .into_iter()
+        .next()
+        .ok_or_else(|| AppError::NotFound(format!("Steam has no ban record for {}", steamid)))
+}
+
 pub async fn verify_steam_request(query_string: &str) -> Result<u64, AppError> {
     let client = reqwest::Client::new();
 
@@ -153,7 +207,31 @@ pub async fn verify_steam_request(query_string: &str) -> Result<u64, AppError> {
         .map_err(|_| AppError::Unauthorized)
 }
 
-pub fn decode_token(token: &str) -> Result<TokenData, jsonwebtoken::errors::Error> {
-    let key = DecodingKey::from_secret(global::JWT_KEY.as_bytes());
+pub fn decode_token(
+    config: &Config,
+    token: &str,
+) -> Result<TokenData, jsonwebtoken::errors::Error> {
+    let key = DecodingKey::from_secret(config.auth.jwt_key.as_bytes());
     decode::<TokenData>(&token, &key, &jsonwebtoken::Validation::default()).map(|data| data.claims)
 }
+
+/// Random token for machines (game server logs, enrollment, credentials) and the argon2 hash
+/// to store in its place
+pub fn generate_secret() -> (String, String) {
+    let secret = format!(
+        "{}{}",
+        uuid::Uuid::new_v4().simple(),
+        uuid::Uuid::new_v4().simple()
+    );
+    let hash = argon2::hash_encoded(
+        secret.as_bytes(),
+        uuid::Uuid::new_v4().as_bytes(),
+        &argon2::Config::default(),
+    )
+    .expect("argon2 accepts its default config and a 16 byte salt");
+    (secret, hash)
+}
+
+pub fn verify_secret(hash: &str, secret: &str) -> bool {
+    argon2::verify_encoded(hash, secret.as_bytes()).unwrap_or(false)
+}
@@ -0,0 +1,352 @@
+use std::str::FromStr;
+use std::time::Duration;
+
+use rbatis::rbdc::datetime::FastDateTime;
+use serde::Serialize;
+
+use crate::driver::db::{begin_tx, commit_tx};
+use crate::error::AppError;
+use crate::global;
+use crate::model::player_ban::{self, PlayerBan, PlayerBanEvent};
+use crate::model::{self, user};
+use crate::routes::ban::{IssueBanPayload, LiftBanPayload, ListBansQuery};
+use crate::service::pagination::Pagination;
+use crate::ws::server::{broadcast_to_servers, send_message_to_server, BackendAction};
+
+/// Longer bans are issued without a duration, as permanent bans
+const MAX_BAN_DURATION: Duration = Duration::from_secs(10 * 365 * 86400);
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum BanKind {
+    /// Can't join match servers nor queue
+    Game,
+    /// Can join but voice and chat are blocked by the plugin
+    Mute,
+    /// Can't queue until the ban expires
+    QueueCooldown,
+}
+
+impl BanKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BanKind::Game => "game",
+            BanKind::Mute => "mute",
+            BanKind::QueueCooldown => "queue_cooldown",
+        }
+    }
+}
+
+impl FromStr for BanKind {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "game" => Ok(BanKind::Game),
+            "mute" => Ok(BanKind::Mute),
+            "queue_cooldown" => Ok(BanKind::QueueCooldown),
+            _ => Err(AppError::BadRequest(format!("Invalid ban kind {}", s))),
+        }
+    }
+}
+
+/// Global bans apply everywhere, server bans only on the server they were issued for
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum BanScope {
+    Global,
+    Server,
+}
+
+impl BanScope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BanScope::Global => "global",
+            BanScope::Server => "server",
+        }
+    }
+}
+
+impl FromStr for BanScope {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "global" => Ok(BanScope::Global),
+            "server" => Ok(BanScope::Server),
+            _ => Err(AppError::BadRequest(format!("Invalid ban scope {}", s))),
+        }
+    }
+}
+
+pub enum BanEvent {
+    Issued,
+    Lifted,
+}
+
+impl BanEvent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BanEvent::Issued => "issued",
+            BanEvent::Lifted => "lifted",
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct BanWithEvents {
+    #[serde(flatten)]
+    pub ban: PlayerBan,
+    pub events: Vec<PlayerBanEvent>,
+}
+
+#[derive(Serialize)]
+pub struct BanPage {
+    pub bans: Vec<PlayerBan>,
+    pub total: u64,
+    pub page: u64,
+    pub page_size: u64,
+}
+
+/// Sent to the servers with `backend_2_server_unban`
+#[derive(Serialize)]
+struct LiftedBan {
+    id: u32,
+    steamid64: String,
+    kind: String,
+}
+
+fn ban_event(
+    ban_id: u32,
+    event: BanEvent,
+    actor_steamid64: &str,
+    reason: Option<String>,
+) -> PlayerBanEvent {
+    PlayerBanEvent {
+        id: None,
+        ban_id,
+        event: event.as_str().to_string(),
+        actor_steamid64: actor_steamid64.to_string(),
+        reason,
+        created_at: FastDateTime::now(),
+    }
+}
+
+fn applies_to(ban: &PlayerBan, server_id: Option<u32>) -> bool {
+    ban.scope == BanScope::Global.as_str() || (server_id.is_some() && ban.server_id == server_id)
+}
+
+async fn find_ban(ban_id: u32) -> Result<PlayerBan, AppError> {
+    player_ban::select_by_id(&global::RB, ban_id)
+        .await
+        .map_err(AppError::DatabaseError)?
+        .ok_or_else(|| AppError::NotFound(format!("Ban {} not found", ban_id)))
+}
+
+/// The most recent active ban of one of the given kinds, `server_id` also includes the bans scoped to that server
+pub async fn find_active_ban(
+    steamid64: &str,
+    kinds: &[BanKind],
+    server_id: Option<u32>,
+) -> Result<Option<PlayerBan>, AppError> {
+    let bans = player_ban::select_active_by_steamid(&global::RB, steamid64, &FastDateTime::now())
+        .await
+        .map_err(AppError::DatabaseError)?;
+    Ok(bans.into_iter().find(|ban| {
+        kinds.iter().any(|kind| kind.as_str() == ban.kind) && applies_to(ban, server_id)
+    }))
+}
+
+/// Active bans a server has to enforce, pushed when it connects so local ban lists can't diverge
+pub async fn get_active_bans_for_server(server_id: u32) -> Result<Vec<PlayerBan>, AppError> {
+    let bans = player_ban::select_active(&global::RB, &FastDateTime::now())
+        .await
+        .map_err(AppError::DatabaseError)?;
+    Ok(bans
+        .into_iter()
+        .filter(|ban| applies_to(ban, Some(server_id)))
+        .collect())
+}
+
+pub async fn issue_ban(payload: IssueBanPayload, steamid64: String) -> Result<PlayerBan, AppError> {
+    let kind = BanKind::from_str(&payload.kind)?;
+    let scope = match &payload.scope {
+        Some(scope) => BanScope::from_str(scope)?,
+        None => BanScope::Global,
+    };
+    let reason = payload.reason.trim();
+    if reason.is_empty() || reason.len() > 255 {
+        return Err(AppError::invalid_field(
+            "reason",
+            "Ban reason must have between 1 and 255 characters",
+        ));
+    }
+    let server_id = match (scope, payload.server_id) {
+        (BanScope::Global, None) => None,
+        (BanScope::Server, Some(server_id)) => {
+            model::Server::select_by_column(&mut global::RB.clone(), "id", server_id)
+                .await
+                .map_err(AppError::DatabaseError)?
+                .pop()
+                .ok_or_else(|| AppError::NotFound(format!("Server {} not found", server_id)))?;
+            Some(server_id)
+        }
+        (BanScope::Global, Some(_)) => {
+            return Err(AppError::invalid_field(
+                "server_id",
+                "Global bans can't target a server",
+            ))
+        }
+        (BanScope::Server, None) => {
+            return Err(AppError::invalid_field(
+                "server_id",
+                "Server bans need a server_id",
+            ))
+        }
+    };
+    if kind == BanKind::QueueCooldown && payload.duration_secs.is_none() {
+        return Err(AppError::invalid_field(
+            "duration_secs",
+            "Queue cooldowns need a duration",
+        ));
+    }
+    let duration = payload.duration_secs.map(Duration::from_secs);
+    if duration.is_some_and(|duration| duration.is_zero() || duration > MAX_BAN_DURATION) {
+        return Err(AppError::invalid_field(
+            "duration_secs",
+            &format!("must be between 1 and {}", MAX_BAN_DURATION.as_secs()),
+        ));
+    }
+    user::select_by_steamid(&global::RB, payload.steamid64.clone())
+        .await
+        .map_err(AppError::DatabaseError)?
+        .ok_or_else(|| AppError::NotFound(format!("User {} not found", payload.steamid64)))?;
+
+    let now = FastDateTime::now();
+    let expires_at = duration.map(|duration| now.clone() + duration);
+    let mut tx = begin_tx().await.map_err(AppError::DatabaseError)?;
+    let ban = player_ban::insert_returning(
+        &mut tx,
+        &payload.steamid64,
+        kind.as_str(),
+        scope.as_str(),
+        server_id,
+        reason,
+        &steamid64,
+        &expires_at,
+        &now,
+    )
+    .await
+    .map_err(AppError::DatabaseError)?;
+    PlayerBanEvent::insert(
+        &mut tx,
+        &ban_event(
+            ban.id.unwrap(),
+            BanEvent::Issued,
+            &steamid64,
+            Some(reason.to_string()),
+        ),
+    )
+    .await
+    .map_err(AppError::DatabaseError)?;
+    commit_tx(tx).await.map_err(AppError::DatabaseError)?;
+
+    match ban.server_id {
+        Some(server_id) => {
+            send_message_to_server(server_id, BackendAction::Backend2ServerBan, &ban).await;
+        }
+        None => broadcast_to_servers(BackendAction::Backend2ServerBan, &ban).await,
+    }
+    tracing::info!(
+        "{} ban {} issued to {} by {}",
+        ban.kind,
+        ban.id.unwrap(),
+        ban.steamid64,
+        steamid64
+    );
+    Ok(ban)
+}
+
+pub async fn lift_ban(
+    ban_id: u32,
+    payload: LiftBanPayload,
+    steamid64: String,
+) -> Result<PlayerBan, AppError> {
+    let ban = find_ban(ban_id).await?;
+    if ban.lifted_at.is_some() {
+        return Err(AppError::Conflict(format!(
+            "Ban {} was already lifted",
+            ban_id
+        )));
+    }
+
+    let mut tx = begin_tx().await.map_err(AppError::DatabaseError)?;
+    player_ban::update_lifted(
+        &mut tx,
+        &FastDateTime::now(),
+        &steamid64,
+        &payload.reason,
+        ban_id,
+    )
+    .await
+    .map_err(AppError::DatabaseError)?;
+    PlayerBanEvent::insert(
+        &mut tx,
+        &ban_event(ban_id, BanEvent::Lifted, &steamid64, payload.reason.clone()),
+    )
+    .await
+    .map_err(AppError::DatabaseError)?;
+    commit_tx(tx).await.map_err(AppError::DatabaseError)?;
+
+    let lifted = LiftedBan {
+        id: ban_id,
+        steamid64: ban.steamid64.clone(),
+        kind: ban.kind.clone(),
+    };
+    match ban.server_id {
+        Some(server_id) => {
+            send_message_to_server(server_id, BackendAction::Backend2ServerUnban, &lifted).await;
+        }
+        None => broadcast_to_servers(BackendAction::Backend2ServerUnban, &lifted).await,
+    }
+    tracing::info!("Ban {} lifted by {}", ban_id, steamid64);
+    find_ban(ban_id).await
+}
+
+pub async fn get_bans(query: ListBansQuery) -> Result<BanPage, AppError> {
+    let kind = query.kind.as_deref().map(BanKind::from_str).transpose()?;
+    let kind = kind.map(|kind| kind.as_str());
+    let state = query
+        .active
+        .map(|active| if active { "active" } else { "inactive" });
+    let pagination = Pagination::new(query.page, query.page_size)?;
+    let now = FastDateTime::now();
+
+    let total = player_ban::count(&global::RB, &query.steamid64, kind, &now, state, &now)
+        .await
+        .map_err(AppError::DatabaseError)?;
+    let bans = player_ban::select_page(
+        &global::RB,
+        &query.steamid64,
+        kind,
+        &now,
+        state,
+        &now,
+        pagination.page_size,
+        pagination.offset,
+    )
+    .await
+    .map_err(AppError::DatabaseError)?;
+    Ok(BanPage {
+        bans,
+        total,
+        page: pagination.page,
+        page_size: pagination.page_size,
+    })
+}
+
+pub async fn get_ban(ban_id: u32) -> Result<BanWithEvents, AppError> {
+    let ban = find_ban(ban_id).await?;
+    let events = player_ban::select_events(&global::RB, ban_id)
+        .await
+        .map_err(AppError::DatabaseError)?;
+    Ok(BanWithEvents { ban, events })
+}
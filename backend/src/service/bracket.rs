@@ -0,0 +1,464 @@
+//! Bracket generation and swiss pairing, kept free of any database access
+
+use std::collections::{HashMap, HashSet};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum BracketKind {
+    Winners,
+    Losers,
+    GrandFinal,
+    Swiss,
+}
+
+impl BracketKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BracketKind::Winners => "winners",
+            BracketKind::Losers => "losers",
+            BracketKind::GrandFinal => "grand_final",
+            BracketKind::Swiss => "swiss",
+        }
+    }
+}
+
+/// A match slot of a generated bracket, `next` and `loser_next` point to the node index and slot (1 or 2)
+/// the winner and the loser advance to
+pub struct BracketNode {
+    pub bracket: BracketKind,
+    pub round: u32,
+    pub position: u32,
+    pub team1_id: Option<u32>,
+    pub team2_id: Option<u32>,
+    pub next: Option<(usize, u8)>,
+    pub loser_next: Option<(usize, u8)>,
+}
+
+impl BracketNode {
+    fn new(bracket: BracketKind, round: u32, position: u32) -> Self {
+        Self {
+            bracket,
+            round,
+            position,
+            team1_id: None,
+            team2_id: None,
+            next: None,
+            loser_next: None,
+        }
+    }
+}
+
+/// Bracket positions of each seed so the top seeds only meet in the latest rounds, e.g. 8 -> [1, 8, 4, 5, 2, 7, 3, 6]
+pub fn seed_order(size: usize) -> Vec<usize> {
+    let mut order = vec![1];
+    while order.len() < size {
+        let sum = order.len() * 2 + 1;
+        order = order.iter().flat_map(|&seed| [seed, sum - seed]).collect();
+    }
+    order
+}
+
+/// Builds the winners bracket for teams sorted by seed, returning the node indexes of every round
+fn winners_bracket(teams: &[u32], nodes: &mut Vec<BracketNode>) -> Vec<Vec<usize>> {
+    let size = teams.len().next_power_of_two().max(2);
+    let order = seed_order(size);
+    let mut rounds: Vec<Vec<usize>> = Vec::new();
+
+    let mut matches_in_round = size / 2;
+    let mut round = 1;
+    while matches_in_round >= 1 {
+        let mut indexes = Vec::with_capacity(matches_in_round);
+        for position in 0..matches_in_round {
+            let mut node = BracketNode::new(BracketKind::Winners, round, position as u32);
+            if round == 1 {
+                node.team1_id = teams.get(order[position * 2] - 1).copied();
+                node.team2_id = teams.get(order[position * 2 + 1] - 1).copied();
+            }
+            indexes.push(nodes.len());
+            nodes.push(node);
+        }
+        rounds.push(indexes);
+        matches_in_round /= 2;
+        round += 1;
+    }
+
+    for pair in rounds.windows(2) {
+        for (position, &index) in pair[0].iter().enumerate() {
+            nodes[index].next = Some((pair[1][position / 2], (position % 2) as u8 + 1));
+        }
+    }
+    rounds
+}
+
+pub fn single_elimination(teams: &[u32]) -> Vec<BracketNode> {
+    let mut nodes = Vec::new();
+    winners_bracket(teams, &mut nodes);
+    nodes
+}
+
+/// Winners bracket, losers bracket and a single grand final between both bracket winners.
+/// Losers of the winners bracket drop into alternating rounds of the losers bracket.
+pub fn double_elimination(teams: &[u32]) -> Vec<BracketNode> {
+    let mut nodes = Vec::new();
+    let wb = winners_bracket(teams, &mut nodes);
+    let wb_rounds = wb.len();
+
+    let mut lb: Vec<Vec<usize>> = Vec::new();
+    let size = teams.len().next_power_of_two().max(2);
+    for round in 1..=2 * (wb_rounds - 1) {
+        let matches_in_round = if round == 1 {
+            size / 4
+        } else if round % 2 == 0 {
+            size >> (round / 2 + 1)
+        } else {
+            size >> (round / 2 + 2)
+        };
+        let mut indexes = Vec::with_capacity(matches_in_round);
+        for position in 0..matches_in_round {
+            indexes.push(nodes.len());
+            nodes.push(BracketNode::new(
+                BracketKind::Losers,
+                round as u32,
+                position as u32,
+            ));
+        }
+        lb.push(indexes);
+    }
+
+    let grand_final = nodes.len();
+    nodes.push(BracketNode::new(BracketKind::GrandFinal, 1, 0));
+    nodes[*wb[wb_rounds - 1].first().unwrap()].next = Some((grand_final, 1));
+
+    if lb.is_empty() {
+        nodes[wb[0][0]].loser_next = Some((grand_final, 2));
+        return nodes;
+    }
+
+    for (position, &index) in wb[0].iter().enumerate() {
+        nodes[index].loser_next = Some((lb[0][position / 2], (position % 2) as u8 + 1));
+    }
+    for (wb_round, indexes) in wb.iter().enumerate().skip(1) {
+        //losers of the winners round n + 1 meet the survivors of the losers round 2n, mirrored every other round to avoid early rematches
+        let target = &lb[2 * wb_round - 1];
+        for (position, &index) in indexes.iter().enumerate() {
+            let target_position = if wb_round % 2 == 1 {
+                target.len() - 1 - position
+            } else {
+                position
+            };
+            nodes[index].loser_next = Some((target[target_position], 2));
+        }
+    }
+    for (lb_round, indexes) in lb.iter().enumerate() {
+        let is_last = lb_round == lb.len() - 1;
+        for (position, &index) in indexes.iter().enumerate() {
+            nodes[index].next = Some(if is_last {
+                (grand_final, 2)
+            } else if lb_round % 2 == 0 {
+                (lb[lb_round + 1][position], 1)
+            } else {
+                (lb[lb_round + 1][position / 2], (position % 2) as u8 + 1)
+            });
+        }
+    }
+    nodes
+}
+
+/// Result of a finished swiss match, `loser_team_id` is empty for byes
+pub struct SwissResult {
+    pub winner_team_id: u32,
+    pub loser_team_id: Option<u32>,
+}
+
+pub struct SwissStanding {
+    pub team_id: u32,
+    pub seed: u32,
+    pub wins: u32,
+    pub losses: u32,
+    pub buchholz: u32,
+    pub opponents: Vec<u32>,
+    pub had_bye: bool,
+}
+
+/// Computes wins, losses and the Buchholz score (sum of the opponents wins) for every team, sorted by rank
+pub fn swiss_standings(seeds: &[(u32, u32)], results: &[SwissResult]) -> Vec<SwissStanding> {
+    let mut standings: HashMap<u32, SwissStanding> = seeds
+        .iter()
+        .map(|&(team_id, seed)| {
+            (
+                team_id,
+                SwissStanding {
+                    team_id,
+                    seed,
+                    wins: 0,
+                    losses: 0,
+                    buchholz: 0,
+                    opponents: Vec::new(),
+                    had_bye: false,
+                },
+            )
+        })
+        .collect();
+
+    for result in results {
+        if let Some(winner) = standings.get_mut(&result.winner_team_id) {
+            winner.wins += 1;
+            match result.loser_team_id {
+                Some(loser_team_id) => winner.opponents.push(loser_team_id),
+                None => winner.had_bye = true,
+            }
+        }
+        if let Some(loser_team_id) = result.loser_team_id {
+            if let Some(loser) = standings.get_mut(&loser_team_id) {
+                loser.losses += 1;
+                loser.opponents.push(result.winner_team_id);
+            }
+        }
+    }
+
+    let wins: HashMap<u32, u32> = standings.values().map(|s| (s.team_id, s.wins)).collect();
+    let mut standings: Vec<SwissStanding> = standings
+        .into_values()
+        .map(|mut standing| {
+            standing.buchholz = standing
+                .opponents
+                .iter()
+                .map(|opponent| wins.get(opponent).copied().unwrap_or(0))
+                .sum();
+            standing
+        })
+        .collect();
+    standings.sort_by(|a, b| {
+        b.wins
+            .cmp(&a.wins)
+            .then(b.buchholz.cmp(&a.buchholz))
+            .then(a.seed.cmp(&b.seed))
+    });
+    standings
+}
+
+fn pair_without_rematches(
+    remaining: &[u32],
+    played: &HashSet<(u32, u32)>,
+    pairs: &mut Vec<(u32, u32)>,
+) -> bool {
+    let Some((&first, rest)) = remaining.split_first() else {
+        return true;
+    };
+    for (i, &opponent) in rest.iter().enumerate() {
+        if played.contains(&(first, opponent)) {
+            continue;
+        }
+        let others: Vec<u32> = rest
+            .iter()
+            .enumerate()
+            .filter(|&(j, _)| j != i)
+            .map(|(_, &team)| team)
+            .collect();
+        pairs.push((first, opponent));
+        if pair_without_rematches(&others, played, pairs) {
+            return true;
+        }
+        pairs.pop();
+    }
+    false
+}
+
+/// Pairs the next swiss round from the current standings, top down and avoiding rematches whenever possible.
+/// With an odd number of teams the lowest ranked team that didn't have a bye yet gets one.
+pub fn swiss_pairings(standings: &[SwissStanding]) -> (Vec<(u32, u32)>, Option<u32>) {
+    let mut ranked: Vec<u32> = standings.iter().map(|s| s.team_id).collect();
+    let mut bye = None;
+    if ranked.len() % 2 == 1 {
+        let bye_index = standings
+            .iter()
+            .rposition(|s| !s.had_bye)
+            .unwrap_or(standings.len() - 1);
+        bye = Some(ranked.remove(bye_index));
+    }
+
+    let played: HashSet<(u32, u32)> = standings
+        .iter()
+        .flat_map(|s| s.opponents.iter().map(move |&o| (s.team_id, o)))
+        .collect();
+    let mut pairs = Vec::with_capacity(ranked.len() / 2);
+    if !pair_without_rematches(&ranked, &played, &mut pairs) {
+        pairs = ranked.chunks(2).map(|pair| (pair[0], pair[1])).collect();
+    }
+    (pairs, bye)
+}
+
+/// Enough rounds to leave a single undefeated team
+pub fn default_swiss_rounds(teams: usize) -> u32 {
+    teams.next_power_of_two().trailing_zeros().max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every slot past the first winners round is fed by exactly one earlier match
+    fn assert_slots_fed_once(nodes: &[BracketNode]) {
+        for (index, node) in nodes.iter().enumerate() {
+            for slot in [1, 2] {
+                let feeders = nodes
+                    .iter()
+                    .filter(|n| {
+                        n.next == Some((index, slot)) || n.loser_next == Some((index, slot))
+                    })
+                    .count();
+                let expected = match (node.bracket, node.round) {
+                    (BracketKind::Winners, 1) => 0,
+                    _ => 1,
+                };
+                assert_eq!(
+                    feeders,
+                    expected,
+                    "slot {} of {} round {} position {}",
+                    slot,
+                    node.bracket.as_str(),
+                    node.round,
+                    node.position
+                );
+            }
+        }
+    }
+
+    fn first_round(nodes: &[BracketNode]) -> Vec<(Option<u32>, Option<u32>)> {
+        nodes
+            .iter()
+            .filter(|n| n.bracket == BracketKind::Winners && n.round == 1)
+            .map(|n| (n.team1_id, n.team2_id))
+            .collect()
+    }
+
+    fn results(matches: &[(u32, u32)]) -> Vec<SwissResult> {
+        matches
+            .iter()
+            .map(|&(winner_team_id, loser_team_id)| SwissResult {
+                winner_team_id,
+                loser_team_id: Some(loser_team_id),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn seed_order_keeps_top_seeds_apart() {
+        assert_eq!(seed_order(1), vec![1]);
+        assert_eq!(seed_order(2), vec![1, 2]);
+        assert_eq!(seed_order(8), vec![1, 8, 4, 5, 2, 7, 3, 6]);
+    }
+
+    #[test]
+    fn single_elimination_gives_byes_to_top_seeds() {
+        let nodes = single_elimination(&[10, 20, 30, 40, 50]);
+        assert_eq!(nodes.len(), 7);
+        assert_eq!(
+            first_round(&nodes),
+            vec![
+                (Some(10), None),
+                (Some(40), Some(50)),
+                (Some(20), None),
+                (Some(30), None),
+            ]
+        );
+        assert_slots_fed_once(&nodes);
+        let finals: Vec<_> = nodes.iter().filter(|n| n.next.is_none()).collect();
+        assert_eq!(finals.len(), 1);
+        assert_eq!(finals[0].round, 3);
+    }
+
+    #[test]
+    fn single_elimination_of_two_teams_is_a_final() {
+        let nodes = single_elimination(&[10, 20]);
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(first_round(&nodes), vec![(Some(10), Some(20))]);
+        assert!(nodes[0].next.is_none());
+    }
+
+    #[test]
+    fn double_elimination_links_every_slot() {
+        for teams in [2, 3, 4, 6, 8, 16] {
+            let team_ids: Vec<u32> = (1..=teams).collect();
+            let nodes = double_elimination(&team_ids);
+            assert_slots_fed_once(&nodes);
+            let grand_finals: Vec<_> = nodes
+                .iter()
+                .filter(|n| n.bracket == BracketKind::GrandFinal)
+                .collect();
+            assert_eq!(grand_finals.len(), 1, "{} teams", teams);
+            assert!(grand_finals[0].next.is_none());
+            //every other match sends its winner on and the winners bracket its loser too
+            for node in nodes
+                .iter()
+                .filter(|n| n.bracket != BracketKind::GrandFinal)
+            {
+                assert!(node.next.is_some());
+                assert_eq!(
+                    node.loser_next.is_some(),
+                    node.bracket == BracketKind::Winners
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn double_elimination_of_two_teams_replays_in_the_grand_final() {
+        let nodes = double_elimination(&[10, 20]);
+        assert_eq!(nodes.len(), 2);
+        assert_eq!(nodes[0].next, Some((1, 1)));
+        assert_eq!(nodes[0].loser_next, Some((1, 2)));
+    }
+
+    #[test]
+    fn swiss_pairs_top_down_in_the_first_round() {
+        let standings = swiss_standings(&[(10, 1), (20, 2), (30, 3), (40, 4)], &[]);
+        assert_eq!(swiss_pairings(&standings), (vec![(10, 20), (30, 40)], None));
+    }
+
+    #[test]
+    fn swiss_byes_go_to_the_lowest_team_without_one() {
+        let seeds = [(10, 1), (20, 2), (30, 3)];
+        let standings = swiss_standings(&seeds, &[]);
+        assert_eq!(swiss_pairings(&standings), (vec![(10, 20)], Some(30)));
+
+        let mut played = results(&[(10, 20)]);
+        played.push(SwissResult {
+            winner_team_id: 30,
+            loser_team_id: None,
+        });
+        let standings = swiss_standings(&seeds, &played);
+        let (pairs, bye) = swiss_pairings(&standings);
+        assert_eq!(bye, Some(20));
+        assert_eq!(pairs.len(), 1);
+    }
+
+    #[test]
+    fn swiss_avoids_rematches() {
+        let seeds = [(10, 1), (20, 2), (30, 3), (40, 4)];
+        let played = results(&[(10, 20), (30, 40), (10, 30), (20, 40)]);
+        let standings = swiss_standings(&seeds, &played);
+        let ranked: Vec<u32> = standings.iter().map(|s| s.team_id).collect();
+        assert_eq!(ranked, vec![10, 20, 30, 40]);
+        assert_eq!(swiss_pairings(&standings), (vec![(10, 40), (20, 30)], None));
+    }
+
+    #[test]
+    fn swiss_falls_back_to_rematches_when_unavoidable() {
+        let played = HashSet::from([(10, 20), (20, 10)]);
+        let mut pairs = Vec::new();
+        assert!(!pair_without_rematches(&[10, 20], &played, &mut pairs));
+        assert!(pairs.is_empty());
+
+        let standings = swiss_standings(&[(10, 1), (20, 2)], &results(&[(10, 20)]));
+        assert_eq!(swiss_pairings(&standings), (vec![(10, 20)], None));
+    }
+
+    #[test]
+    fn default_swiss_rounds_leave_one_undefeated_team() {
+        assert_eq!(default_swiss_rounds(1), 1);
+        assert_eq!(default_swiss_rounds(2), 1);
+        assert_eq!(default_swiss_rounds(5), 3);
+        assert_eq!(default_swiss_rounds(8), 3);
+        assert_eq!(default_swiss_rounds(9), 4);
+    }
+}
@@ -0,0 +1,287 @@
+use std::collections::HashSet;
+use std::time::Duration;
+
+use rbatis::rbdc::datetime::FastDateTime;
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::error::AppError;
+use crate::global;
+use crate::model::queued_command::{self, QueuedCommand};
+use crate::ws::server::{get_online_servers, send_sequenced_to_server, BackendAction};
+
+/// Acknowledged commands are kept this long for inspection
+const ACKED_RETENTION: Duration = Duration::from_secs(86400);
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum QueueStatus {
+    /// Waiting for its ack, resent with backoff while the server is connected
+    Pending,
+    Acked,
+    /// Expired or out of attempts, only an admin sends it again
+    Dead,
+}
+
+impl QueueStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            QueueStatus::Pending => "pending",
+            QueueStatus::Acked => "acked",
+            QueueStatus::Dead => "dead",
+        }
+    }
+}
+
+/// Data of `server_2_backend_ack`. Servers remember the last `seq` they executed and only ack
+/// the commands replayed after a reconnect, so a command whose ack was lost doesn't run twice.
+#[derive(Deserialize)]
+pub struct SequenceAck {
+    pub seq: u32,
+}
+
+/// Stores the message and sends it right away when the server is connected. Until its ack
+/// arrives it is resent with backoff and replayed whenever the server reconnects.
+pub async fn enqueue<T: Serialize>(
+    config: &Config,
+    server_id: u32,
+    action: BackendAction,
+    data: T,
+) -> Result<QueuedCommand, AppError> {
+    let action = match serde_json::to_value(&action).map_err(AppError::JsonParseError)? {
+        serde_json::Value::String(action) => action,
+        _ => unreachable!("backend actions serialize to strings"),
+    };
+    let payload = serde_json::to_string(&data).map_err(AppError::JsonParseError)?;
+    let now = FastDateTime::now();
+    let expires_at = now.clone() + Duration::from_secs(config.command_queue.expiry_secs);
+    let command = queued_command::insert_returning(
+        &global::RB,
+        server_id,
+        &action,
+        &payload,
+        QueueStatus::Pending.as_str(),
+        &now,
+        &expires_at,
+        &now,
+    )
+    .await
+    .map_err(AppError::DatabaseError)?;
+    tracing::info!(
+        "Queued {} as command {} for server {}",
+        command.action,
+        command.seq,
+        server_id
+    );
+    deliver(config, &command, &now).await?;
+    Ok(command)
+}
+
+/// Sends every pending command of a server that just connected, oldest first
+pub async fn replay(config: &Config, server_id: u32) -> Result<(), AppError> {
+    let pending =
+        queued_command::select_by_server(&global::RB, server_id, QueueStatus::Pending.as_str())
+            .await
+            .map_err(AppError::DatabaseError)?;
+    if !pending.is_empty() {
+        tracing::info!(
+            "Replaying {} queued commands to server {}",
+            pending.len(),
+            server_id
+        );
+    }
+    let now = FastDateTime::now();
+    for command in pending {
+        deliver(config, &command, &now).await?;
+    }
+    Ok(())
+}
+
+/// Acks of commands that were already acknowledged or dead-lettered are ignored
+pub async fn acknowledge(server_id: u32, seq: u32) -> Result<(), AppError> {
+    let result = queued_command::update_acked(
+        &global::RB,
+        QueueStatus::Acked.as_str(),
+        &FastDateTime::now(),
+        seq,
+        server_id,
+        QueueStatus::Pending.as_str(),
+    )
+    .await
+    .map_err(AppError::DatabaseError)?;
+    if result.rows_affected == 0 {
+        tracing::debug!("Server {} acked command {} again", server_id, seq);
+    }
+    Ok(())
+}
+
+/// Pending and dead commands of a server, oldest first
+pub async fn get_queue(server_id: u32) -> Result<Vec<QueuedCommand>, AppError> {
+    queued_command::select_unacked_by_server(&global::RB, server_id, QueueStatus::Acked.as_str())
+        .await
+        .map_err(AppError::DatabaseError)
+}
+
+/// Puts a dead-lettered command back in the queue with a fresh expiry and set of attempts
+pub async fn retry_dead(
+    config: &Config,
+    server_id: u32,
+    seq: u32,
+) -> Result<QueuedCommand, AppError> {
+    let now = FastDateTime::now();
+    let expires_at = now.clone() + Duration::from_secs(config.command_queue.expiry_secs);
+    let result = queued_command::update_requeued(
+        &global::RB,
+        QueueStatus::Pending.as_str(),
+        &now,
+        &expires_at,
+        seq,
+        server_id,
+        QueueStatus::Dead.as_str(),
+    )
+    .await
+    .map_err(AppError::DatabaseError)?;
+    let command = queued_command::select_by_seq(&global::RB, seq, server_id)
+        .await
+        .map_err(AppError::DatabaseError)?
+        .ok_or_else(|| {
+            AppError::NotFound(format!("Command {} of server {} not found", seq, server_id))
+        })?;
+    if result.rows_affected == 0 {
+        return Err(AppError::Conflict(format!(
+            "Command {} is {}, only dead commands can be retried",
+            seq, command.status
+        )));
+    }
+    tracing::info!("Dead command {} of server {} requeued", seq, server_id);
+    deliver(config, &command, &now).await?;
+    Ok(command)
+}
+
+pub fn spawn_command_queue(config: &'static Config) -> tokio::task::JoinHandle<()> {
+    tokio::task::spawn(async move {
+        let mut interval =
+            tokio::time::interval(Duration::from_secs(config.command_queue.tick_secs));
+        let mut shutdown = global::SHUTDOWN.subscribe();
+        loop {
+            if shutdown.borrow_and_update().is_some() {
+                break;
+            }
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = shutdown.changed() => break,
+            }
+            if let Err(e) = retry_due(config).await {
+                tracing::error!(error = ?e, "Error while retrying the queued commands");
+            }
+        }
+    })
+}
+
+/// Resends the commands whose backoff is over, commands of offline servers wait for the replay
+/// but still expire
+async fn retry_due(config: &Config) -> Result<(), AppError> {
+    let now = FastDateTime::now();
+    queued_command::delete_acked_before(
+        &global::RB,
+        QueueStatus::Acked.as_str(),
+        &(now.clone() - ACKED_RETENTION),
+    )
+    .await
+    .map_err(AppError::DatabaseError)?;
+
+    let due = queued_command::select_due(&global::RB, QueueStatus::Pending.as_str(), &now)
+        .await
+        .map_err(AppError::DatabaseError)?;
+    if due.is_empty() {
+        return Ok(());
+    }
+    let online: HashSet<u32> = get_online_servers()
+        .await
+        .iter()
+        .map(|server| server.id)
+        .collect();
+    for command in due {
+        let result = if online.contains(&command.server_id) {
+            deliver(config, &command, &now).await
+        } else if now.0 >= command.expires_at.0 {
+            dead_letter(&command, "Expired while the server was offline").await
+        } else {
+            Ok(())
+        };
+        if let Err(e) = result {
+            tracing::error!(error = ?e, "Error while retrying queued command {}", command.seq);
+        }
+    }
+    Ok(())
+}
+
+/// Sends one attempt and schedules the next, unless the command expired or ran out of attempts.
+/// Nothing is counted when the server isn't connected.
+async fn deliver(
+    config: &Config,
+    command: &QueuedCommand,
+    now: &FastDateTime,
+) -> Result<(), AppError> {
+    let config = &config.command_queue;
+    if now.0 >= command.expires_at.0 {
+        return dead_letter(
+            command,
+            &format!("Expired after {} attempts", command.attempts),
+        )
+        .await;
+    }
+    if command.attempts >= config.max_attempts {
+        return dead_letter(
+            command,
+            &format!("No ack after {} attempts", command.attempts),
+        )
+        .await;
+    }
+    let action: BackendAction =
+        serde_json::from_value(serde_json::Value::String(command.action.clone()))
+            .map_err(AppError::JsonParseError)?;
+    let data: serde_json::Value =
+        serde_json::from_str(&command.payload).map_err(AppError::JsonParseError)?;
+    if !send_sequenced_to_server(command.server_id, action, command.seq, data).await {
+        return Ok(());
+    }
+
+    let attempts = command.attempts + 1;
+    let backoff = config
+        .retry_base_secs
+        .saturating_mul(1 << (attempts - 1).min(16))
+        .min(config.retry_max_secs);
+    //an ack arriving before this update leaves the command acknowledged
+    queued_command::update_attempt(
+        &global::RB,
+        attempts,
+        &(now.clone() + Duration::from_secs(backoff)),
+        command.seq,
+        QueueStatus::Pending.as_str(),
+    )
+    .await
+    .map_err(AppError::DatabaseError)?;
+    Ok(())
+}
+
+async fn dead_letter(command: &QueuedCommand, reason: &str) -> Result<(), AppError> {
+    let result = queued_command::update_dead(
+        &global::RB,
+        QueueStatus::Dead.as_str(),
+        reason,
+        command.seq,
+        QueueStatus::Pending.as_str(),
+    )
+    .await
+    .map_err(AppError::DatabaseError)?;
+    if result.rows_affected > 0 {
+        tracing::warn!(
+            "Command {} ({}) of server {} dead-lettered: {}",
+            command.seq,
+            command.action,
+            command.server_id,
+            reason
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,428 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use rbatis::rbdc::datetime::FastDateTime;
+use serde::Serialize;
+
+use crate::driver::db::{begin_tx, commit_tx};
+use crate::error::AppError;
+use crate::global;
+use crate::model::config_template::{self, ConfigTemplate, ConfigTemplateVersion, ConfigVariable};
+use crate::model::{game_match, server, team};
+use crate::ws::server::{send_message_to_server, BackendAction, ServerStatus};
+
+/// Game phase a template is executed for, servers get it when they report the matching status
+#[derive(Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ConfigPhase {
+    Warmup,
+    Knife,
+    Live,
+    Overtime,
+}
+
+impl ConfigPhase {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ConfigPhase::Warmup => "warmup",
+            ConfigPhase::Knife => "knife",
+            ConfigPhase::Live => "live",
+            ConfigPhase::Overtime => "overtime",
+        }
+    }
+
+    /// Statuses without a phase keep whatever config the server already runs
+    pub fn for_status(status: ServerStatus) -> Option<ConfigPhase> {
+        match status {
+            ServerStatus::WaitingForPlayers => Some(ConfigPhase::Warmup),
+            ServerStatus::KnifeRound => Some(ConfigPhase::Knife),
+            ServerStatus::Live => Some(ConfigPhase::Live),
+            ServerStatus::Overtime => Some(ConfigPhase::Overtime),
+            ServerStatus::Idle | ServerStatus::Starting | ServerStatus::Ending => None,
+        }
+    }
+}
+
+impl FromStr for ConfigPhase {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "warmup" => Ok(ConfigPhase::Warmup),
+            "knife" => Ok(ConfigPhase::Knife),
+            "live" => Ok(ConfigPhase::Live),
+            "overtime" => Ok(ConfigPhase::Overtime),
+            _ => Err(AppError::NotFound(format!("Unknown config phase {}", s))),
+        }
+    }
+}
+
+/// Filled in from the server and its active match, custom variables can't take these names
+pub const BUILTIN_VARIABLES: [&str; 8] = [
+    "match_id",
+    "team1_name",
+    "team1_tag",
+    "team2_name",
+    "team2_tag",
+    "server_id",
+    "server_ip",
+    "server_port",
+];
+
+const MAX_TEMPLATE_LENGTH: usize = 65536;
+const MAX_VARIABLE_VALUE_LENGTH: usize = 255;
+/// Values are pasted into the server console, these would end a quoted value or the command
+const CONSOLE_SEPARATORS: [char; 4] = ['"', ';', '\n', '\r'];
+
+#[derive(Serialize)]
+pub struct ConfigTemplateDetails {
+    #[serde(flatten)]
+    pub template: ConfigTemplate,
+    pub versions: Vec<ConfigTemplateVersionSummary>,
+}
+
+#[derive(Serialize)]
+pub struct ConfigTemplateVersionSummary {
+    pub version: u32,
+    pub created_by: String,
+    pub created_at: FastDateTime,
+}
+
+/// Data of `backend_2_server_exec_config`, `content` has every variable substituted
+#[derive(Serialize)]
+pub struct ExecConfig {
+    pub phase: ConfigPhase,
+    pub version: u32,
+    pub content: String,
+}
+
+pub async fn get_templates() -> Result<Vec<ConfigTemplate>, AppError> {
+    config_template::select_templates(&global::RB)
+        .await
+        .map_err(AppError::DatabaseError)
+}
+
+pub async fn get_template(phase: ConfigPhase) -> Result<ConfigTemplateDetails, AppError> {
+    let template = find_template(phase).await?;
+    let versions = config_template::select_versions(&global::RB, phase.as_str())
+        .await
+        .map_err(AppError::DatabaseError)?
+        .into_iter()
+        .map(|version| ConfigTemplateVersionSummary {
+            version: version.version,
+            created_by: version.created_by,
+            created_at: version.created_at,
+        })
+        .collect();
+    Ok(ConfigTemplateDetails { template, versions })
+}
+
+pub async fn get_template_version(
+    phase: ConfigPhase,
+    version: u32,
+) -> Result<ConfigTemplateVersion, AppError> {
+    config_template::select_version(&global::RB, phase.as_str(), version)
+        .await
+        .map_err(AppError::DatabaseError)?
+        .ok_or_else(|| {
+            AppError::NotFound(format!(
+                "Version {} of the {} config not found",
+                version,
+                phase.as_str()
+            ))
+        })
+}
+
+/// Stores `content` as the next version of the phase and makes it the current template
+pub async fn save_template(
+    phase: ConfigPhase,
+    content: String,
+    steamid64: String,
+) -> Result<ConfigTemplateDetails, AppError> {
+    if content.trim().is_empty() {
+        return Err(AppError::invalid_field("content", "must not be empty"));
+    }
+    if content.len() > MAX_TEMPLATE_LENGTH {
+        return Err(AppError::invalid_field(
+            "content",
+            &format!("must be at most {} bytes", MAX_TEMPLATE_LENGTH),
+        ));
+    }
+    let custom_variables = config_template::select_variables(&global::RB)
+        .await
+        .map_err(AppError::DatabaseError)?;
+    let unknown: Vec<&str> = template_variables(&content)
+        .into_iter()
+        .filter(|name| {
+            !BUILTIN_VARIABLES.contains(name) && !custom_variables.iter().any(|v| v.name == *name)
+        })
+        .collect();
+    if !unknown.is_empty() {
+        return Err(AppError::invalid_field(
+            "content",
+            &format!("uses unknown variables {}", unknown.join(", ")),
+        ));
+    }
+
+    let now = FastDateTime::now();
+    let mut tx = begin_tx().await.map_err(AppError::DatabaseError)?;
+    let version = config_template::select_latest_version(&mut tx, phase.as_str())
+        .await
+        .map_err(AppError::DatabaseError)?
+        + 1;
+    ConfigTemplateVersion::insert(
+        &mut tx,
+        &ConfigTemplateVersion {
+            phase: phase.as_str().to_string(),
+            version,
+            content: content.clone(),
+            created_by: steamid64.clone(),
+            created_at: now.clone(),
+        },
+    )
+    .await
+    .map_err(AppError::DatabaseError)?;
+    config_template::upsert_template(&mut tx, phase.as_str(), version, &content, &steamid64, &now)
+        .await
+        .map_err(AppError::DatabaseError)?;
+    commit_tx(tx).await.map_err(AppError::DatabaseError)?;
+    tracing::info!(
+        "Version {} of the {} config saved by {}",
+        version,
+        phase.as_str(),
+        steamid64
+    );
+    get_template(phase).await
+}
+
+/// Servers stop receiving the phase, its versions stay for reference
+pub async fn delete_template(phase: ConfigPhase, steamid64: String) -> Result<(), AppError> {
+    find_template(phase).await?;
+    ConfigTemplate::delete_by_column(&mut global::RB.clone(), "phase", phase.as_str())
+        .await
+        .map_err(AppError::DatabaseError)?;
+    tracing::info!("The {} config was deleted by {}", phase.as_str(), steamid64);
+    Ok(())
+}
+
+pub async fn get_variables() -> Result<Vec<ConfigVariable>, AppError> {
+    config_template::select_variables(&global::RB)
+        .await
+        .map_err(AppError::DatabaseError)
+}
+
+pub async fn find_variable(name: &str) -> Result<Option<ConfigVariable>, AppError> {
+    Ok(
+        ConfigVariable::select_by_column(&mut global::RB.clone(), "name", name)
+            .await
+            .map_err(AppError::DatabaseError)?
+            .pop(),
+    )
+}
+
+pub async fn set_variable(
+    name: String,
+    value: String,
+    steamid64: String,
+) -> Result<ConfigVariable, AppError> {
+    if name.is_empty()
+        || name.len() > 32
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+    {
+        return Err(AppError::invalid_field(
+            "name",
+            "must be 1 to 32 lowercase letters, digits or underscores",
+        ));
+    }
+    if BUILTIN_VARIABLES.contains(&name.as_str()) {
+        return Err(AppError::invalid_field(
+            "name",
+            "is a built-in variable and can't be set",
+        ));
+    }
+    if value.len() > MAX_VARIABLE_VALUE_LENGTH {
+        return Err(AppError::invalid_field(
+            "value",
+            &format!("must be at most {} bytes", MAX_VARIABLE_VALUE_LENGTH),
+        ));
+    }
+    if value.contains(CONSOLE_SEPARATORS) {
+        return Err(AppError::invalid_field(
+            "value",
+            "must not contain quotes, semicolons or line breaks",
+        ));
+    }
+    let now = FastDateTime::now();
+    config_template::upsert_variable(&global::RB, &name, &value, &steamid64, &now)
+        .await
+        .map_err(AppError::DatabaseError)?;
+    tracing::info!("Config variable {} set by {}", name, steamid64);
+    Ok(ConfigVariable {
+        name,
+        value,
+        updated_by: steamid64,
+        updated_at: now,
+    })
+}
+
+/// Templates still using the variable render it empty until it is set again
+pub async fn delete_variable(name: String, steamid64: String) -> Result<(), AppError> {
+    let deleted = ConfigVariable::delete_by_column(&mut global::RB.clone(), "name", &name)
+        .await
+        .map_err(AppError::DatabaseError)?;
+    if deleted.rows_affected == 0 {
+        return Err(AppError::NotFound(format!(
+            "Config variable {} not found",
+            name
+        )));
+    }
+    tracing::info!("Config variable {} deleted by {}", name, steamid64);
+    Ok(())
+}
+
+/// Sends the template of the phase matching `status`, nothing is sent when the phase has none
+pub async fn push_for_status(
+    server_data: &server::Server,
+    status: ServerStatus,
+) -> Result<(), AppError> {
+    let phase = match ConfigPhase::for_status(status) {
+        Some(phase) => phase,
+        None => return Ok(()),
+    };
+    let template = match config_template::select_template(&global::RB, phase.as_str())
+        .await
+        .map_err(AppError::DatabaseError)?
+    {
+        Some(template) => template,
+        None => return Ok(()),
+    };
+    let server_id = server_data.id.unwrap();
+    let variables = server_variables(server_data).await?;
+    let content = render(&template.content, &variables, |name| {
+        tracing::warn!(
+            "The {} config of server {} uses the unset variable {}",
+            phase.as_str(),
+            server_id,
+            name
+        )
+    });
+    tracing::info!(
+        "Pushing version {} of the {} config to server {}",
+        template.version,
+        phase.as_str(),
+        server_id
+    );
+    send_message_to_server(
+        server_id,
+        BackendAction::Backend2ServerExecConfig,
+        ExecConfig {
+            phase,
+            version: template.version,
+            content,
+        },
+    )
+    .await;
+    Ok(())
+}
+
+/// Built-in values of the server and its active match, then every custom variable
+async fn server_variables(
+    server_data: &server::Server,
+) -> Result<HashMap<String, String>, AppError> {
+    let server_id = server_data.id.unwrap();
+    let mut variables: HashMap<String, String> = HashMap::new();
+    variables.insert("server_id".to_string(), server_id.to_string());
+    variables.insert("server_ip".to_string(), server_data.ip.to_string());
+    variables.insert("server_port".to_string(), server_data.port.to_string());
+
+    let active_match = game_match::select_active_by_server(&global::RB, server_id, server_id)
+        .await
+        .map_err(AppError::DatabaseError)?;
+    if let Some(active_match) = active_match {
+        if let Some(match_id) = active_match.id {
+            variables.insert("match_id".to_string(), match_id.to_string());
+        }
+        for (prefix, team_id) in [
+            ("team1", active_match.team1_id),
+            ("team2", active_match.team2_id),
+        ] {
+            if let Some(team) = team::select_by_id(&global::RB, team_id)
+                .await
+                .map_err(AppError::DatabaseError)?
+            {
+                //players name their teams, so the separators are dropped rather than refused
+                variables.insert(
+                    format!("{}_name", prefix),
+                    team.name.replace(CONSOLE_SEPARATORS, ""),
+                );
+                variables.insert(
+                    format!("{}_tag", prefix),
+                    team.tag.replace(CONSOLE_SEPARATORS, ""),
+                );
+            }
+        }
+    }
+
+    for variable in config_template::select_variables(&global::RB)
+        .await
+        .map_err(AppError::DatabaseError)?
+    {
+        variables.entry(variable.name).or_insert(variable.value);
+    }
+    Ok(variables)
+}
+
+/// Names of the `{{variable}}` placeholders of a template, in order of appearance
+fn template_variables(content: &str) -> Vec<&str> {
+    let mut names = Vec::new();
+    let mut rest = content;
+    while let Some(start) = rest.find("{{") {
+        let after = &rest[start + 2..];
+        match after.find("}}") {
+            Some(end) => {
+                let name = after[..end].trim();
+                if !names.contains(&name) {
+                    names.push(name);
+                }
+                rest = &after[end + 2..];
+            }
+            None => break,
+        }
+    }
+    names
+}
+
+/// Substitutes every placeholder, placeholders without a value become empty and are reported to `on_missing`
+fn render(
+    content: &str,
+    variables: &HashMap<String, String>,
+    mut on_missing: impl FnMut(&str),
+) -> String {
+    let mut rendered = String::with_capacity(content.len());
+    let mut rest = content;
+    while let Some(start) = rest.find("{{") {
+        let after = &rest[start + 2..];
+        let end = match after.find("}}") {
+            Some(end) => end,
+            None => break,
+        };
+        rendered.push_str(&rest[..start]);
+        let name = after[..end].trim();
+        match variables.get(name) {
+            Some(value) => rendered.push_str(value),
+            None => on_missing(name),
+        }
+        rest = &after[end + 2..];
+    }
+    rendered.push_str(rest);
+    rendered
+}
+
+async fn find_template(phase: ConfigPhase) -> Result<ConfigTemplate, AppError> {
+    config_template::select_template(&global::RB, phase.as_str())
+        .await
+        .map_err(AppError::DatabaseError)?
+        .ok_or_else(|| AppError::NotFound(format!("No {} config template", phase.as_str())))
+}
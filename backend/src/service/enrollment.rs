@@ -0,0 +1,427 @@
+//! Self-registration of game servers. A server connecting from an unknown address with a valid
+//! enrollment token is held as pending until an admin approves it, which registers it and issues
+//! the credential it presents from then on, or rejects it.
+
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+
+use rbatis::rbdc::datetime::FastDateTime;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{oneshot, Mutex};
+
+use crate::driver::db::{begin_tx, commit_tx, is_unique_violation};
+use crate::driver::inet::InetAddr;
+use crate::error::AppError;
+use crate::global::{self, PENDING_ENROLLMENTS};
+use crate::model::enrollment::{self, EnrollmentToken, PendingServer};
+use crate::model::server::{self, Server, ServerCredential};
+use crate::service::auth;
+use crate::ws::user::broadcast_to_admins;
+
+const DEFAULT_TOKEN_LIFETIME: Duration = Duration::from_secs(86400);
+const MAX_TOKEN_LIFETIME: Duration = Duration::from_secs(30 * 86400);
+const MAX_LABEL_LENGTH: usize = 64;
+
+/// Decisions waiting to reach the connections of pending servers, keyed by pending server id
+pub type PendingEnrollmentList = Mutex<HashMap<u32, oneshot::Sender<EnrollmentDecision>>>;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum PendingStatus {
+    Pending,
+    Approved,
+    Rejected,
+}
+
+impl PendingStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PendingStatus::Pending => "pending",
+            PendingStatus::Approved => "approved",
+            PendingStatus::Rejected => "rejected",
+        }
+    }
+}
+
+pub enum EnrollmentDecision {
+    Approved { server: Server, credential: String },
+    Rejected,
+}
+
+#[derive(Deserialize)]
+pub struct CreateEnrollmentTokenPayload {
+    pub label: Option<String>,
+    /// One day when missing, at most 30 days
+    pub expires_in_secs: Option<u64>,
+}
+
+/// Returned once, servers present `token` in the `Enrollment-Token` header
+#[derive(Serialize)]
+pub struct IssuedEnrollmentToken {
+    pub id: u32,
+    pub token: String,
+    pub label: Option<String>,
+    pub expires_at: FastDateTime,
+}
+
+#[derive(Serialize)]
+pub struct EnrollmentTokenSummary {
+    pub id: u32,
+    pub label: Option<String>,
+    pub created_by: String,
+    pub created_at: FastDateTime,
+    pub expires_at: FastDateTime,
+    pub revoked_at: Option<FastDateTime>,
+}
+
+impl From<EnrollmentToken> for EnrollmentTokenSummary {
+    fn from(token: EnrollmentToken) -> Self {
+        EnrollmentTokenSummary {
+            id: token.id.unwrap(),
+            label: token.label,
+            created_by: token.created_by,
+            created_at: token.created_at,
+            expires_at: token.expires_at,
+            revoked_at: token.revoked_at,
+        }
+    }
+}
+
+/// `PendingServer` as shown to admins
+#[derive(Serialize)]
+pub struct PendingServerEntry {
+    pub id: u32,
+    pub ip: IpAddr,
+    pub port: u16,
+    pub enrollment_token_id: u32,
+    pub status: String,
+    pub server_id: Option<u32>,
+    pub decided_by: Option<String>,
+    pub created_at: FastDateTime,
+    pub last_seen_at: FastDateTime,
+    pub decided_at: Option<FastDateTime>,
+}
+
+impl From<PendingServer> for PendingServerEntry {
+    fn from(pending: PendingServer) -> Self {
+        PendingServerEntry {
+            id: pending.id,
+            ip: pending.ip.ip(),
+            port: pending.port,
+            enrollment_token_id: pending.enrollment_token_id,
+            status: pending.status,
+            server_id: pending.server_id,
+            decided_by: pending.decided_by,
+            created_at: pending.created_at,
+            last_seen_at: pending.last_seen_at,
+            decided_at: pending.decided_at,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct ApprovedServer {
+    pub pending_server_id: u32,
+    pub server_id: u32,
+    /// Sent to the server as `backend_2_server_enrolled` when it's still connected, the admin
+    /// has to hand it over otherwise
+    pub credential: String,
+    pub delivered: bool,
+}
+
+/// Data of `backend_2_server_enrolled`, the server presents the credential as a bearer token
+#[derive(Serialize)]
+pub struct Enrolled<'a> {
+    pub server_id: u32,
+    pub credential: &'a str,
+}
+
+pub async fn create_enrollment_token(
+    payload: CreateEnrollmentTokenPayload,
+    steamid64: String,
+) -> Result<IssuedEnrollmentToken, AppError> {
+    let label = payload
+        .label
+        .map(|label| label.trim().to_string())
+        .filter(|label| !label.is_empty());
+    if label
+        .as_ref()
+        .is_some_and(|label| label.len() > MAX_LABEL_LENGTH)
+    {
+        return Err(AppError::invalid_field(
+            "label",
+            &format!("must be at most {} bytes", MAX_LABEL_LENGTH),
+        ));
+    }
+    let lifetime = payload
+        .expires_in_secs
+        .map_or(DEFAULT_TOKEN_LIFETIME, Duration::from_secs);
+    if lifetime.is_zero() || lifetime > MAX_TOKEN_LIFETIME {
+        return Err(AppError::invalid_field(
+            "expires_in_secs",
+            &format!("must be between 1 and {}", MAX_TOKEN_LIFETIME.as_secs()),
+        ));
+    }
+
+    let (secret, token_hash) = auth::generate_secret();
+    let now = FastDateTime::now();
+    let token = enrollment::insert_token_returning(
+        &global::RB,
+        &token_hash,
+        label.as_deref(),
+        &steamid64,
+        &now,
+        &(now.clone() + lifetime),
+    )
+    .await
+    .map_err(AppError::DatabaseError)?;
+    let id = token.id.unwrap();
+    tracing::info!("Enrollment token {} issued by {}", id, steamid64);
+    Ok(IssuedEnrollmentToken {
+        id,
+        //the id tells which hash to check
+        token: format!("{}.{}", id, secret),
+        label: token.label,
+        expires_at: token.expires_at,
+    })
+}
+
+pub async fn get_enrollment_tokens() -> Result<Vec<EnrollmentTokenSummary>, AppError> {
+    Ok(enrollment::select_tokens(&global::RB)
+        .await
+        .map_err(AppError::DatabaseError)?
+        .into_iter()
+        .map(EnrollmentTokenSummary::from)
+        .collect())
+}
+
+/// Servers that already enrolled with the token stay pending
+pub async fn revoke_enrollment_token(id: u32, steamid64: String) -> Result<(), AppError> {
+    enrollment::select_token(&global::RB, id)
+        .await
+        .map_err(AppError::DatabaseError)?
+        .ok_or_else(|| AppError::NotFound(format!("Enrollment token {} not found", id)))?;
+    let result = enrollment::update_token_revoked(&global::RB, &FastDateTime::now(), id)
+        .await
+        .map_err(AppError::DatabaseError)?;
+    if result.rows_affected == 0 {
+        return Err(AppError::Conflict(format!(
+            "Enrollment token {} is already revoked",
+            id
+        )));
+    }
+    tracing::info!("Enrollment token {} revoked by {}", id, steamid64);
+    Ok(())
+}
+
+/// Records the server as pending and shows it to the admins. Servers that were rejected are
+/// refused, they stay rejected.
+pub async fn enroll(ip: InetAddr, port: u16, token: &str) -> Result<PendingServer, AppError> {
+    let token_id = verify_enrollment_token(token).await?;
+    let now = FastDateTime::now();
+    let pending = enrollment::upsert_pending_returning(
+        &global::RB,
+        &ip,
+        port,
+        token_id,
+        PendingStatus::Pending.as_str(),
+        &now,
+        &now,
+    )
+    .await
+    .map_err(AppError::DatabaseError)?;
+    if pending.status != PendingStatus::Pending.as_str() {
+        tracing::info!(
+            "Server {}:{} enrolled again but was {}",
+            ip,
+            port,
+            pending.status
+        );
+        return Err(AppError::Forbidden);
+    }
+    tracing::info!(
+        "Server {}:{} enrolled with token {}, pending as {}",
+        ip,
+        port,
+        token_id,
+        pending.id
+    );
+    match serde_json::to_string(&PendingServerEntry::from(pending.clone())) {
+        Ok(message) => broadcast_to_admins(message, "pending_server").await,
+        Err(e) => tracing::error!("Couldn't serialize PendingServer json {}", e),
+    }
+    Ok(pending)
+}
+
+pub async fn get_pending_servers() -> Result<Vec<PendingServerEntry>, AppError> {
+    Ok(
+        enrollment::select_pending_by_status(&global::RB, PendingStatus::Pending.as_str())
+            .await
+            .map_err(AppError::DatabaseError)?
+            .into_iter()
+            .map(PendingServerEntry::from)
+            .collect(),
+    )
+}
+
+/// Registers the server with a new credential and hands it to the server if it's still waiting
+pub async fn approve(pending_id: u32, steamid64: String) -> Result<ApprovedServer, AppError> {
+    let pending = find_pending(pending_id).await?;
+    if server::select_by_address(&global::RB, &pending.ip, pending.port)
+        .await
+        .map_err(AppError::DatabaseError)?
+        .is_some()
+    {
+        return Err(AppError::Conflict(format!(
+            "A server is already registered at {}",
+            SocketAddr::new(pending.ip.ip(), pending.port)
+        )));
+    }
+
+    let (credential, credential_hash) = auth::generate_secret();
+    let now = FastDateTime::now();
+    let mut tx = begin_tx().await.map_err(AppError::DatabaseError)?;
+    //the same address approved twice at once
+    let server = server::insert_returning(&mut tx, &pending.ip, pending.port, &now)
+        .await
+        .map_err(|e| {
+            if is_unique_violation(&e) {
+                AppError::Conflict(format!(
+                    "A server is already registered at {}",
+                    SocketAddr::new(pending.ip.ip(), pending.port)
+                ))
+            } else {
+                AppError::DatabaseError(e)
+            }
+        })?;
+    let server_id = server.id.unwrap();
+    ServerCredential::insert(
+        &mut tx,
+        &ServerCredential {
+            server_id,
+            token_hash: credential_hash,
+            created_at: now.clone(),
+        },
+    )
+    .await
+    .map_err(AppError::DatabaseError)?;
+    decide(
+        &mut tx,
+        &pending,
+        PendingStatus::Approved,
+        Some(server_id),
+        &steamid64,
+    )
+    .await?;
+    commit_tx(tx).await.map_err(AppError::DatabaseError)?;
+    tracing::info!(
+        "Pending server {} approved as server {} by {}",
+        pending_id,
+        server_id,
+        steamid64
+    );
+
+    let delivered = match PENDING_ENROLLMENTS.lock().await.remove(&pending_id) {
+        Some(waiting) => waiting
+            .send(EnrollmentDecision::Approved {
+                server,
+                credential: credential.clone(),
+            })
+            .is_ok(),
+        None => false,
+    };
+    Ok(ApprovedServer {
+        pending_server_id: pending_id,
+        server_id,
+        credential,
+        delivered,
+    })
+}
+
+/// The server's connection is closed and later enrollments from its address are refused
+pub async fn reject(pending_id: u32, steamid64: String) -> Result<PendingServerEntry, AppError> {
+    let pending = find_pending(pending_id).await?;
+    decide(
+        &mut global::RB.clone(),
+        &pending,
+        PendingStatus::Rejected,
+        None,
+        &steamid64,
+    )
+    .await?;
+    tracing::info!("Pending server {} rejected by {}", pending_id, steamid64);
+    if let Some(waiting) = PENDING_ENROLLMENTS.lock().await.remove(&pending_id) {
+        waiting.send(EnrollmentDecision::Rejected).ok();
+    }
+    find_pending(pending_id).await.map(PendingServerEntry::from)
+}
+
+/// Servers issued a credential have to present it, the others are known by their address only
+pub async fn verify_credential(server_id: u32, presented: Option<&str>) -> Result<(), AppError> {
+    let credential = server::select_credential(&global::RB, server_id)
+        .await
+        .map_err(AppError::DatabaseError)?;
+    match (credential, presented) {
+        (None, _) => Ok(()),
+        (Some(credential), Some(presented))
+            if auth::verify_secret(&credential.token_hash, presented) =>
+        {
+            Ok(())
+        }
+        _ => {
+            tracing::info!("Server {} presented no valid credential", server_id);
+            Err(AppError::Unauthorized)
+        }
+    }
+}
+
+async fn find_pending(pending_id: u32) -> Result<PendingServer, AppError> {
+    enrollment::select_pending(&global::RB, pending_id)
+        .await
+        .map_err(AppError::DatabaseError)?
+        .ok_or_else(|| AppError::NotFound(format!("Pending server {} not found", pending_id)))
+}
+
+/// Only pending servers can be decided on, the first admin wins
+async fn decide(
+    rb: &mut dyn rbatis::executor::Executor,
+    pending: &PendingServer,
+    status: PendingStatus,
+    server_id: Option<u32>,
+    steamid64: &str,
+) -> Result<(), AppError> {
+    let result = enrollment::update_decision(
+        rb,
+        status.as_str(),
+        server_id,
+        steamid64,
+        &FastDateTime::now(),
+        pending.id,
+        PendingStatus::Pending.as_str(),
+    )
+    .await
+    .map_err(AppError::DatabaseError)?;
+    if result.rows_affected == 0 {
+        return Err(AppError::Conflict(format!(
+            "Server {} is already {}",
+            pending.id, pending.status
+        )));
+    }
+    Ok(())
+}
+
+/// Token ids are public, the secret after the dot is checked against the stored hash
+async fn verify_enrollment_token(token: &str) -> Result<u32, AppError> {
+    let (id, secret) = token.split_once('.').ok_or(AppError::Unauthorized)?;
+    let id: u32 = id.parse().map_err(|_| AppError::Unauthorized)?;
+    let stored = enrollment::select_token(&global::RB, id)
+        .await
+        .map_err(AppError::DatabaseError)?
+        .ok_or(AppError::Unauthorized)?;
+    let usable = stored.revoked_at.is_none() && FastDateTime::now().0 < stored.expires_at.0;
+    if !usable || !auth::verify_secret(&stored.token_hash, secret) {
+        tracing::info!("Enrollment refused with token {}", id);
+        return Err(AppError::Unauthorized);
+    }
+    Ok(id)
+}
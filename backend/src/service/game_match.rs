@@ -0,0 +1,117 @@
+use rbatis::rbdc::datetime::FastDateTime;
+use serde::Serialize;
+
+use crate::driver::db::{begin_tx, commit_tx};
+use crate::error::AppError;
+use crate::global;
+use crate::model::match_schedule::{self, MatchSchedule};
+use crate::model::{self, game_match, GameMatch};
+use crate::routes::game_match::CreateMatchPayload;
+use crate::service::scheduler::{self, ScheduleStatus};
+use crate::service::team::{self, TeamWithRoster};
+use crate::service::tournament;
+use crate::ws::spectator;
+
+#[derive(Serialize)]
+pub struct MatchDetails {
+    #[serde(flatten)]
+    pub game_match: GameMatch,
+    pub team1: TeamWithRoster,
+    pub team2: TeamWithRoster,
+    pub schedule: Option<MatchSchedule>,
+}
+
+pub async fn find_match(match_id: u32) -> Result<GameMatch, AppError> {
+    game_match::select_by_id(&global::RB, match_id)
+        .await
+        .map_err(AppError::DatabaseError)?
+        .ok_or_else(|| AppError::NotFound(format!("Match {} not found", match_id)))
+}
+
+pub async fn create_match(payload: CreateMatchPayload) -> Result<GameMatch, AppError> {
+    if payload.team1_id == payload.team2_id {
+        return Err(AppError::invalid_field(
+            "team2_id",
+            "A team can't play against itself",
+        ));
+    }
+    team::find_team(payload.team1_id).await?;
+    team::find_team(payload.team2_id).await?;
+    if let Some(server_id) = payload.server_id {
+        model::Server::select_by_column(&mut global::RB.clone(), "id", server_id)
+            .await
+            .map_err(AppError::DatabaseError)?
+            .pop()
+            .ok_or_else(|| AppError::NotFound(format!("Server {} not found", server_id)))?;
+    }
+
+    let created_match = game_match::insert_returning(
+        &mut global::RB.clone(),
+        payload.team1_id,
+        payload.team2_id,
+        payload.server_id,
+        &FastDateTime::now(),
+    )
+    .await
+    .map_err(AppError::DatabaseError)?;
+    if let Some(scheduled_at) = payload.scheduled_at {
+        scheduler::schedule_match(created_match.id.unwrap(), scheduled_at).await?;
+    }
+    Ok(created_match)
+}
+
+pub async fn get_match(match_id: u32) -> Result<MatchDetails, AppError> {
+    let game_match = find_match(match_id).await?;
+    let team1 = team::get_team(game_match.team1_id).await?;
+    let team2 = team::get_team(game_match.team2_id).await?;
+    let schedule = scheduler::get_schedule(match_id).await?;
+    Ok(MatchDetails {
+        game_match,
+        team1,
+        team2,
+        schedule,
+    })
+}
+
+/// Refuses reports about a match other than the one the server is hosting
+pub async fn ensure_hosted_by(server_id: u32, match_id: u32) -> Result<(), AppError> {
+    let active_match = game_match::select_active_by_server(&global::RB, server_id, server_id)
+        .await
+        .map_err(AppError::DatabaseError)?;
+    if active_match.and_then(|m| m.id) != Some(match_id) {
+        return Err(AppError::Conflict(format!(
+            "Server {} doesn't host match {}",
+            server_id, match_id
+        )));
+    }
+    Ok(())
+}
+
+/// Records the winner of a match and advances its tournament bracket, repeated reports are ignored.
+/// Both happen in one transaction along with finishing its schedule, a report failing to advance
+/// the bracket can be repeated.
+pub async fn finish_match(match_id: u32, winner_team_id: u32) -> Result<(), AppError> {
+    let game_match = find_match(match_id).await?;
+    if game_match.team1_id != winner_team_id && game_match.team2_id != winner_team_id {
+        return Err(AppError::BadRequest(format!(
+            "Team {} doesn't play in match {}",
+            winner_team_id, match_id
+        )));
+    }
+    let mut tx = begin_tx().await.map_err(AppError::DatabaseError)?;
+    let result = game_match::update_winner(&mut tx, winner_team_id, &FastDateTime::now(), match_id)
+        .await
+        .map_err(AppError::DatabaseError)?;
+    if result.rows_affected == 0 {
+        return Ok(());
+    }
+    match_schedule::update_finished(&mut tx, ScheduleStatus::Finished.as_str(), match_id)
+        .await
+        .map_err(AppError::DatabaseError)?;
+    tournament::on_match_finished(&mut tx, match_id, winner_team_id).await?;
+    commit_tx(tx).await.map_err(AppError::DatabaseError)?;
+
+    tracing::info!("Match {} won by team {}", match_id, winner_team_id);
+    spectator::end_match_feed(match_id, winner_team_id).await;
+    Ok(())
+}
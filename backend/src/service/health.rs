@@ -0,0 +1,36 @@
+use serde::Serialize;
+
+use crate::driver::db;
+use crate::service::shutdown;
+
+#[derive(Serialize)]
+pub struct Readiness {
+    pub ready: bool,
+    pub shutting_down: bool,
+    pub database: bool,
+    pub applied_migration: Option<i32>,
+    pub expected_migration: i32,
+}
+
+/// The backend is ready once the database answers and every embedded migration was applied,
+/// it stops being ready as soon as a shutdown starts so no new traffic is routed to it
+pub async fn check_readiness() -> Readiness {
+    let shutting_down = shutdown::is_shutting_down();
+    let expected_migration = db::expected_migration_version();
+    let (database, applied_migration) = match db::applied_migration_version().await {
+        Ok(version) => (true, version),
+        Err(e) => {
+            tracing::warn!("Readiness check couldn't reach the database {}", e);
+            (false, None)
+        }
+    };
+    Readiness {
+        shutting_down,
+        ready: !shutting_down
+            && database
+            && applied_migration.is_some_and(|version| version >= expected_migration),
+        database,
+        applied_migration,
+        expected_migration,
+    }
+}
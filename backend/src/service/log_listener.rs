@@ -0,0 +1,144 @@
+//! UDP receiver for servers logging with `logaddress_add ip:port`. Packets are attributed to the
+//! registered server they come from and their lines go through the same pipeline as the HTTP
+//! log stream, in batches.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use tokio::net::UdpSocket;
+
+use crate::config::Config;
+use crate::global;
+use crate::metrics::{self, LogPacketOutcome};
+use crate::model::server;
+use crate::service::server_log;
+
+/// Lines of each server are handed to the pipeline this often
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+/// Servers registered or removed while the listener runs are picked up after this
+const ADDRESS_CACHE_TTL: Duration = Duration::from_secs(60);
+/// Source addresses cached at most, packets of the others look their server up every time
+const MAX_CACHED_ADDRESSES: usize = 1024;
+/// Lines buffered per server between two flushes, more are dropped
+const MAX_BUFFERED_LINES: usize = 4096;
+/// srcds never sends more than this in one packet
+const MAX_PACKET_SIZE: usize = 2048;
+
+const PACKET_HEADER: &[u8] = b"\xFF\xFF\xFF\xFF";
+
+/// Takes the bound socket so `main` fails on a taken port instead of the task
+pub fn spawn_log_listener(
+    config: &'static Config,
+    socket: std::net::UdpSocket,
+) -> anyhow::Result<tokio::task::JoinHandle<()>> {
+    socket.set_nonblocking(true)?;
+    let socket = UdpSocket::from_std(socket)?;
+    tracing::info!(
+        "Listening for server logs on udp://{}",
+        socket.local_addr()?
+    );
+    Ok(tokio::task::spawn(async move {
+        let mut listener = LogListener::default();
+        let mut buf = vec![0; MAX_PACKET_SIZE];
+        let mut flush = tokio::time::interval(FLUSH_INTERVAL);
+        let mut shutdown = global::SHUTDOWN.subscribe();
+        loop {
+            if shutdown.borrow_and_update().is_some() {
+                break;
+            }
+            tokio::select! {
+                received = socket.recv_from(&mut buf) => match received {
+                    Ok((len, addr)) => listener.receive(config, &buf[..len], addr).await,
+                    Err(e) => tracing::warn!("Couldn't receive a log packet {}", e),
+                },
+                _ = flush.tick() => listener.flush().await,
+                _ = shutdown.changed() => break,
+            }
+        }
+        listener.flush().await;
+    }))
+}
+
+#[derive(Default)]
+struct LogListener {
+    /// Server id of every source address seen lately, `None` for unregistered ones
+    servers: HashMap<SocketAddr, (Option<u32>, Instant)>,
+    lines: HashMap<u32, Vec<String>>,
+}
+
+impl LogListener {
+    async fn receive(&mut self, config: &Config, packet: &[u8], addr: SocketAddr) {
+        let line = match parse_packet(packet, config.log_listener.secret.as_deref()) {
+            Some(line) if config.is_server_allowed(&addr.ip()) => line,
+            _ => return metrics::count_log_packet(LogPacketOutcome::Dropped),
+        };
+        let server_id = match self.server_id(addr).await {
+            Some(server_id) => server_id,
+            None => return metrics::count_log_packet(LogPacketOutcome::Unattributed),
+        };
+        let lines = self.lines.entry(server_id).or_default();
+        if lines.len() >= MAX_BUFFERED_LINES {
+            return metrics::count_log_packet(LogPacketOutcome::Dropped);
+        }
+        lines.push(line.to_string());
+        metrics::count_log_packet(LogPacketOutcome::Accepted);
+    }
+
+    /// srcds sends from its game port, which is the port the server was registered with
+    async fn server_id(&mut self, addr: SocketAddr) -> Option<u32> {
+        if let Some((server_id, cached_at)) = self.servers.get(&addr) {
+            if cached_at.elapsed() < ADDRESS_CACHE_TTL {
+                return *server_id;
+            }
+        }
+        let server_id =
+            match server::select_by_address(&global::RB, &addr.ip().into(), addr.port()).await {
+                Ok(server) => server.and_then(|server| server.id),
+                Err(e) => {
+                    //not cached, the next packet tries again
+                    tracing::error!(
+                        "Couldn't look up the server of log packets from {} {}",
+                        addr,
+                        e
+                    );
+                    return None;
+                }
+            };
+        if server_id.is_none() {
+            tracing::debug!("Log packets from unregistered address {}", addr);
+        }
+        if self.servers.len() < MAX_CACHED_ADDRESSES || self.servers.contains_key(&addr) {
+            self.servers.insert(addr, (server_id, Instant::now()));
+        }
+        server_id
+    }
+
+    async fn flush(&mut self) {
+        //expired addresses are looked up again anyway, spoofed sources mustn't pile up
+        self.servers
+            .retain(|_, (_, cached_at)| cached_at.elapsed() < ADDRESS_CACHE_TTL);
+        for (server_id, lines) in self.lines.drain() {
+            if let Err(e) = server_log::ingest(server_id, &lines.join("\n")).await {
+                tracing::error!(error = ?e, "Couldn't ingest the logs of server {}", server_id);
+            }
+        }
+    }
+}
+
+/// `FF FF FF FF 'R' line` or, with `sv_logsecret`, `FF FF FF FF 'S' secret line`. Lines end with
+/// a newline and a nul byte.
+fn parse_packet<'a>(packet: &'a [u8], secret: Option<&str>) -> Option<&'a str> {
+    let packet = std::str::from_utf8(packet.strip_prefix(PACKET_HEADER)?).ok()?;
+    let line = match (packet.strip_prefix('R'), packet.strip_prefix('S')) {
+        (Some(line), _) if secret.is_none() => line,
+        (_, Some(keyed)) => match secret {
+            Some(secret) => keyed.strip_prefix(secret)?,
+            //the secret is a number, the line starts at `L`
+            None => &keyed[keyed.find("L ")?..],
+        },
+        _ => return None,
+    };
+    let line = line.trim_end_matches(['\0', '\n', '\r']);
+    line.starts_with("L ").then_some(line)
+}
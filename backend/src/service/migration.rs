@@ -0,0 +1,78 @@
+use crate::driver::db;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum MigrationState {
+    Applied,
+    Pending,
+    /// Applied with another name or checksum than the embedded migration of the same version
+    Divergent,
+    /// Applied but not embedded in this binary
+    Missing,
+}
+
+impl MigrationState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MigrationState::Applied => "applied",
+            MigrationState::Pending => "pending",
+            MigrationState::Divergent => "divergent",
+            MigrationState::Missing => "missing",
+        }
+    }
+
+    pub fn is_drift(&self) -> bool {
+        matches!(self, MigrationState::Divergent | MigrationState::Missing)
+    }
+}
+
+pub struct MigrationStatus {
+    pub version: i32,
+    pub name: String,
+    pub state: MigrationState,
+    pub embedded_checksum: Option<String>,
+    pub applied_checksum: Option<String>,
+    pub applied_on: Option<String>,
+}
+
+/// Embedded migrations next to the history of the database, ordered by version
+pub async fn migration_status() -> Result<Vec<MigrationStatus>, rbatis::Error> {
+    let mut history = db::schema_history().await?;
+    let mut statuses: Vec<MigrationStatus> = db::embedded_migrations()
+        .into_iter()
+        .map(|migration| {
+            let version = migration.version() as i32;
+            let embedded_checksum = migration.checksum().to_string();
+            let applied = history
+                .iter()
+                .position(|row| row.version == version)
+                .map(|index| history.remove(index));
+            let state = match &applied {
+                None => MigrationState::Pending,
+                Some(row) if row.name == migration.name() && row.checksum == embedded_checksum => {
+                    MigrationState::Applied
+                }
+                Some(_) => MigrationState::Divergent,
+            };
+            MigrationStatus {
+                version,
+                name: migration.name().to_string(),
+                state,
+                embedded_checksum: Some(embedded_checksum),
+                applied_checksum: applied.as_ref().map(|row| row.checksum.clone()),
+                applied_on: applied.map(|row| row.applied_on),
+            }
+        })
+        .collect();
+
+    //whatever is left in the history has no embedded counterpart
+    statuses.extend(history.into_iter().map(|row| MigrationStatus {
+        version: row.version,
+        name: row.name,
+        state: MigrationState::Missing,
+        embedded_checksum: None,
+        applied_checksum: Some(row.checksum),
+        applied_on: Some(row.applied_on),
+    }));
+    statuses.sort_by_key(|status| status.version);
+    Ok(statuses)
+}
@@ -1,2 +1,26 @@
+pub mod a2s;
+pub mod admission;
+pub mod audit;
 pub mod auth;
+pub mod ban;
+pub mod bracket;
+pub mod command_queue;
+pub mod config_template;
+pub mod enrollment;
+pub mod game_match;
+pub mod health;
+pub mod log_listener;
+pub mod migration;
+pub mod pagination;
+pub mod round_backup;
+pub mod scheduler;
 pub mod server;
+pub mod server_command;
+pub mod server_log;
+pub mod server_query;
+pub mod shutdown;
+pub mod srcds_log;
+pub mod team;
+pub mod tournament;
+pub mod trust;
+pub mod user;
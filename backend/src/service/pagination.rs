@@ -0,0 +1,33 @@
+use crate::error::AppError;
+
+const DEFAULT_PAGE_SIZE: u64 = 50;
+const MAX_PAGE_SIZE: u64 = 100;
+
+/// Page of a list endpoint, read from its 1-based `page` and `page_size` query parameters
+pub struct Pagination {
+    pub page: u64,
+    pub page_size: u64,
+    pub offset: u64,
+}
+
+impl Pagination {
+    /// `page_size` is clamped to what a page may hold, pages past what the database can skip are refused
+    pub fn new(page: Option<u64>, page_size: Option<u64>) -> Result<Pagination, AppError> {
+        let page = page.unwrap_or(1);
+        if page == 0 {
+            return Err(AppError::invalid_field("page", "Pages start at 1"));
+        }
+        let page_size = page_size
+            .unwrap_or(DEFAULT_PAGE_SIZE)
+            .clamp(1, MAX_PAGE_SIZE);
+        let offset = (page - 1)
+            .checked_mul(page_size)
+            .filter(|offset| *offset <= i64::MAX as u64)
+            .ok_or_else(|| AppError::invalid_field("page", "The page is out of range"))?;
+        Ok(Pagination {
+            page,
+            page_size,
+            offset,
+        })
+    }
+}
@@ -0,0 +1,248 @@
+//! Round backups uploaded by the game servers after every round, and restoring a match from one
+//! on the server that hosted it or on another idle server.
+
+use std::net::SocketAddr;
+
+use rbatis::rbdc::datetime::FastDateTime;
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::driver::db::{begin_tx, commit_tx};
+use crate::error::AppError;
+use crate::global;
+use crate::model::round_backup::{self, RoundBackup, RoundBackupSummary};
+use crate::model::{game_match, match_schedule, GameMatch};
+use crate::service::command_queue;
+use crate::service::game_match::{ensure_hosted_by, find_match};
+use crate::service::scheduler::{self, match_config};
+use crate::ws::server::{get_online_servers, BackendAction, ServerStatus};
+
+const MAX_ROUND: u32 = 128;
+
+/// Data of `server_2_backend_round_backup`, the content of the `backup_round_XX.txt` written
+/// by the server at the end of a round
+#[derive(Deserialize)]
+pub struct RoundBackupUpload {
+    pub match_id: u32,
+    pub round: u32,
+    pub file_name: String,
+    pub content: String,
+}
+
+/// Data of `backend_2_server_restore_round`, the server writes the file and loads it
+#[derive(Serialize)]
+struct RestoreRound<'a> {
+    match_id: u32,
+    round: u32,
+    file_name: &'a str,
+    content: &'a str,
+}
+
+#[derive(Serialize)]
+struct RestoreNotification {
+    match_id: u32,
+    round: u32,
+    server: String,
+}
+
+/// Where a match was restored
+#[derive(Serialize)]
+pub struct RoundRestore {
+    pub match_id: u32,
+    pub round: u32,
+    pub server_id: u32,
+    /// The match moved away from the server it was hosted on
+    pub reassigned: bool,
+}
+
+/// Stores a backup of the match the server is hosting, uploads for other matches are refused
+pub async fn store_backup(server_id: u32, upload: RoundBackupUpload) -> Result<(), AppError> {
+    ensure_hosted_by(server_id, upload.match_id).await?;
+    if upload.round > MAX_ROUND {
+        return Err(AppError::invalid_field(
+            "round",
+            &format!("must be at most {}", MAX_ROUND),
+        ));
+    }
+    if !is_valid_file_name(&upload.file_name) {
+        return Err(AppError::invalid_field(
+            "file_name",
+            "must be a .txt file name of at most 64 letters, digits, dots, dashes or underscores",
+        ));
+    }
+    round_backup::upsert(
+        &global::RB,
+        upload.match_id,
+        server_id,
+        upload.round,
+        &upload.file_name,
+        &upload.content,
+        &FastDateTime::now(),
+    )
+    .await
+    .map_err(AppError::DatabaseError)?;
+    tracing::debug!(
+        "Stored the round {} backup of match {} from server {}",
+        upload.round,
+        upload.match_id,
+        server_id
+    );
+    Ok(())
+}
+
+pub async fn get_backups(match_id: u32) -> Result<Vec<RoundBackupSummary>, AppError> {
+    find_match(match_id).await?;
+    round_backup::select_by_match(&global::RB, match_id)
+        .await
+        .map_err(AppError::DatabaseError)
+}
+
+pub async fn get_backup(match_id: u32, round: u32) -> Result<RoundBackup, AppError> {
+    round_backup::select_round(&global::RB, match_id, round)
+        .await
+        .map_err(AppError::DatabaseError)?
+        .ok_or_else(|| {
+            AppError::NotFound(format!(
+                "No backup of round {} for match {}",
+                round, match_id
+            ))
+        })
+}
+
+/// Restores the match to the start of `round`. Without `server_id` the server hosting the match
+/// restores it; another server has to be idle, it gets the match config first and the previous
+/// server is told to drop the match.
+pub async fn restore_round(
+    config: &Config,
+    match_id: u32,
+    round: u32,
+    server_id: Option<u32>,
+    steamid64: String,
+) -> Result<RoundRestore, AppError> {
+    let game_match = find_match(match_id).await?;
+    if game_match.winner_team_id.is_some() {
+        return Err(AppError::Conflict(format!(
+            "Match {} is already finished",
+            match_id
+        )));
+    }
+    let backup = get_backup(match_id, round).await?;
+    let current_server_id = hosting_server(&game_match).await?;
+    let target_server_id = match server_id.or(current_server_id) {
+        Some(server_id) => server_id,
+        None => {
+            return Err(AppError::invalid_field(
+                "server_id",
+                "is required, the match isn't assigned to a server",
+            ))
+        }
+    };
+
+    let online_servers = get_online_servers().await;
+    let target = online_servers
+        .iter()
+        .find(|server| server.id == target_server_id)
+        .ok_or_else(|| {
+            AppError::Conflict(format!("Server {} is not connected", target_server_id))
+        })?;
+    let reassigned = current_server_id != Some(target_server_id);
+    if reassigned {
+        if target.status != ServerStatus::Idle {
+            return Err(AppError::Conflict(format!(
+                "Server {} is not idle",
+                target_server_id
+            )));
+        }
+        let hosted =
+            game_match::select_active_by_server(&global::RB, target_server_id, target_server_id)
+                .await
+                .map_err(AppError::DatabaseError)?;
+        if hosted.is_some_and(|hosted| hosted.id != Some(match_id)) {
+            return Err(AppError::Conflict(format!(
+                "Server {} hosts another match",
+                target_server_id
+            )));
+        }
+    }
+    let target_address = SocketAddr::new(target.ip, target.port).to_string();
+
+    if reassigned {
+        let load_match = match_config(config, &game_match).await?;
+        //the match and its schedule move together, the servers only hear about it once they did
+        let mut tx = begin_tx().await.map_err(AppError::DatabaseError)?;
+        game_match::update_server(&mut tx, target_server_id, match_id)
+            .await
+            .map_err(AppError::DatabaseError)?;
+        match_schedule::update_server(&mut tx, target_server_id, match_id)
+            .await
+            .map_err(AppError::DatabaseError)?;
+        commit_tx(tx).await.map_err(AppError::DatabaseError)?;
+        if let Some(previous_server_id) = current_server_id {
+            command_queue::enqueue(
+                config,
+                previous_server_id,
+                BackendAction::Backend2ServerCancelMatch,
+                match_id,
+            )
+            .await?;
+        }
+        command_queue::enqueue(
+            config,
+            target_server_id,
+            BackendAction::Backend2ServerLoadMatch,
+            load_match,
+        )
+        .await?;
+    }
+    command_queue::enqueue(
+        config,
+        target_server_id,
+        BackendAction::Backend2ServerRestoreRound,
+        RestoreRound {
+            match_id,
+            round,
+            file_name: &backup.file_name,
+            content: &backup.content,
+        },
+    )
+    .await?;
+    tracing::info!(
+        "Match {} restored to round {} on server {} by {}",
+        match_id,
+        round,
+        target_server_id,
+        steamid64
+    );
+
+    match serde_json::to_string(&RestoreNotification {
+        match_id,
+        round,
+        server: target_address,
+    }) {
+        Ok(message) => scheduler::notify_players(&game_match, message, "match_restored").await,
+        Err(e) => tracing::error!("Couldn't serialize RestoreNotification json {}", e),
+    }
+    Ok(RoundRestore {
+        match_id,
+        round,
+        server_id: target_server_id,
+        reassigned,
+    })
+}
+
+/// The server of the running schedule, or the one the match was created for
+async fn hosting_server(game_match: &GameMatch) -> Result<Option<u32>, AppError> {
+    let schedule = scheduler::get_schedule(game_match.id.unwrap()).await?;
+    Ok(schedule
+        .and_then(|schedule| schedule.server_id)
+        .or(game_match.server_id))
+}
+
+fn is_valid_file_name(file_name: &str) -> bool {
+    file_name.len() <= 64
+        && file_name.ends_with(".txt")
+        && !file_name.starts_with('.')
+        && file_name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+}
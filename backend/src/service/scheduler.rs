@@ -0,0 +1,552 @@
+//! Scheduled matches: reserves a server ahead of the start time, pushes the match config when the slot
+//! begins and forfeits teams that don't show up. Everything is read back from the database on each tick
+//! so pending matches survive backend restarts.
+
+use std::collections::HashSet;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::time::Duration;
+
+use rbatis::rbdc::datetime::FastDateTime;
+use serde::Serialize;
+
+use crate::config::Config;
+use crate::error::AppError;
+use crate::global;
+use crate::model::match_schedule::{self, MatchSchedule};
+use crate::model::{team, GameMatch, Team};
+use crate::service::command_queue;
+use crate::service::game_match::{find_match, finish_match};
+use crate::ws::server::{get_online_servers, BackendAction, ConnectedServer, ServerStatus};
+use crate::ws::spectator;
+use crate::ws::user::send_message_to_user;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ScheduleStatus {
+    Scheduled,
+    Reserved,
+    Started,
+    Live,
+    Finished,
+    Forfeited,
+    Cancelled,
+}
+
+impl ScheduleStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ScheduleStatus::Scheduled => "scheduled",
+            ScheduleStatus::Reserved => "reserved",
+            ScheduleStatus::Started => "started",
+            ScheduleStatus::Live => "live",
+            ScheduleStatus::Finished => "finished",
+            ScheduleStatus::Forfeited => "forfeited",
+            ScheduleStatus::Cancelled => "cancelled",
+        }
+    }
+}
+
+impl FromStr for ScheduleStatus {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "scheduled" => Ok(ScheduleStatus::Scheduled),
+            "reserved" => Ok(ScheduleStatus::Reserved),
+            "started" => Ok(ScheduleStatus::Started),
+            "live" => Ok(ScheduleStatus::Live),
+            "finished" => Ok(ScheduleStatus::Finished),
+            "forfeited" => Ok(ScheduleStatus::Forfeited),
+            "cancelled" => Ok(ScheduleStatus::Cancelled),
+            _ => Err(AppError::BadRequest(format!(
+                "Invalid schedule status {}",
+                s
+            ))),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct MatchTeamConfig {
+    id: u32,
+    name: String,
+    tag: String,
+    players: Vec<String>,
+}
+
+/// Sent to the reserved server with `backend_2_server_load_match`
+#[derive(Serialize)]
+pub struct MatchConfig {
+    match_id: u32,
+    team1: MatchTeamConfig,
+    team2: MatchTeamConfig,
+    forfeit_timeout_secs: u64,
+}
+
+#[derive(Serialize)]
+struct MatchNotification {
+    match_id: u32,
+    scheduled_at: FastDateTime,
+    server: Option<String>,
+    winner_team_id: Option<u32>,
+}
+
+pub async fn get_schedule(match_id: u32) -> Result<Option<MatchSchedule>, AppError> {
+    match_schedule::select_by_match(&global::RB, match_id)
+        .await
+        .map_err(AppError::DatabaseError)
+}
+
+/// Schedules a match or moves an existing schedule, as long as the match wasn't pushed to a server yet
+pub async fn schedule_match(
+    match_id: u32,
+    scheduled_at: FastDateTime,
+) -> Result<MatchSchedule, AppError> {
+    let game_match = find_match(match_id).await?;
+    if game_match.winner_team_id.is_some() {
+        return Err(AppError::Conflict(format!(
+            "Match {} is already finished",
+            match_id
+        )));
+    }
+    if scheduled_at.0 < FastDateTime::now().0 {
+        return Err(AppError::invalid_field(
+            "scheduled_at",
+            "The scheduled time is in the past",
+        ));
+    }
+
+    match get_schedule(match_id).await? {
+        Some(schedule) => {
+            let status = ScheduleStatus::from_str(&schedule.status)?;
+            if !matches!(
+                status,
+                ScheduleStatus::Scheduled | ScheduleStatus::Reserved | ScheduleStatus::Cancelled
+            ) {
+                return Err(AppError::Conflict(format!(
+                    "Match {} has already started",
+                    match_id
+                )));
+            }
+            match_schedule::update_scheduled_at(
+                &global::RB,
+                &scheduled_at,
+                ScheduleStatus::Scheduled.as_str(),
+                match_id,
+            )
+            .await
+            .map_err(AppError::DatabaseError)?;
+        }
+        None => {
+            MatchSchedule::insert(
+                &mut global::RB.clone(),
+                &MatchSchedule {
+                    game_match_id: match_id,
+                    scheduled_at,
+                    status: ScheduleStatus::Scheduled.as_str().to_string(),
+                    server_id: None,
+                    reserved_at: None,
+                    started_at: None,
+                    created_at: FastDateTime::now(),
+                },
+            )
+            .await
+            .map_err(AppError::DatabaseError)?;
+        }
+    }
+
+    get_schedule(match_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Match {} is not scheduled", match_id)))
+}
+
+pub async fn cancel_schedule(config: &Config, match_id: u32) -> Result<(), AppError> {
+    let schedule = get_schedule(match_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Match {} is not scheduled", match_id)))?;
+    let status = ScheduleStatus::from_str(&schedule.status)?;
+    if !matches!(
+        status,
+        ScheduleStatus::Scheduled | ScheduleStatus::Reserved | ScheduleStatus::Started
+    ) {
+        return Err(AppError::Conflict(format!(
+            "Match {} can't be cancelled anymore",
+            match_id
+        )));
+    }
+    if let (ScheduleStatus::Started, Some(server_id)) = (status, schedule.server_id) {
+        command_queue::enqueue(
+            config,
+            server_id,
+            BackendAction::Backend2ServerCancelMatch,
+            match_id,
+        )
+        .await?;
+    }
+    match_schedule::update_status(&global::RB, ScheduleStatus::Cancelled.as_str(), match_id)
+        .await
+        .map_err(AppError::DatabaseError)?;
+
+    let game_match = find_match(match_id).await?;
+    notify_participants(&game_match, &schedule, None, None, "match_cancelled").await;
+    tracing::info!("Scheduled match {} cancelled", match_id);
+    Ok(())
+}
+
+pub fn spawn_scheduler(config: &'static Config) -> tokio::task::JoinHandle<()> {
+    tokio::task::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(config.scheduler.tick_secs));
+        let mut shutdown = global::SHUTDOWN.subscribe();
+        //a tick that already started runs to completion so its writes aren't cut in half
+        loop {
+            if shutdown.borrow_and_update().is_some() {
+                break;
+            }
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = shutdown.changed() => break,
+            }
+            if let Err(e) = run_pending(config).await {
+                tracing::error!(error = ?e, "Error while running the match scheduler");
+            }
+        }
+    })
+}
+
+async fn run_pending(config: &Config) -> Result<(), AppError> {
+    let pending = match_schedule::select_pending(&global::RB)
+        .await
+        .map_err(AppError::DatabaseError)?;
+    if pending.is_empty() {
+        return Ok(());
+    }
+    let online_servers = get_online_servers().await;
+    let mut reserved: HashSet<u32> = pending
+        .iter()
+        .filter(|schedule| schedule.status != ScheduleStatus::Scheduled.as_str())
+        .filter_map(|schedule| schedule.server_id)
+        .collect();
+
+    for schedule in pending {
+        let match_id = schedule.game_match_id;
+        let result = match ScheduleStatus::from_str(&schedule.status)? {
+            ScheduleStatus::Scheduled => {
+                reserve_server(config, &schedule, &online_servers, &mut reserved).await
+            }
+            ScheduleStatus::Reserved => start_match(config, &schedule, &online_servers).await,
+            ScheduleStatus::Started => check_attendance(config, &schedule, &online_servers).await,
+            _ => Ok(()),
+        };
+        if let Err(e) = result {
+            tracing::error!(error = ?e, "Error while processing scheduled match {}", match_id);
+        }
+    }
+    Ok(())
+}
+
+/// Skips matches whose result was already entered by hand
+async fn find_unfinished_match(schedule: &MatchSchedule) -> Result<Option<GameMatch>, AppError> {
+    let game_match = find_match(schedule.game_match_id).await?;
+    if game_match.winner_team_id.is_some() {
+        match_schedule::update_status(
+            &global::RB,
+            ScheduleStatus::Finished.as_str(),
+            schedule.game_match_id,
+        )
+        .await
+        .map_err(AppError::DatabaseError)?;
+        return Ok(None);
+    }
+    Ok(Some(game_match))
+}
+
+async fn reserve_server(
+    config: &Config,
+    schedule: &MatchSchedule,
+    online_servers: &[ConnectedServer],
+    reserved: &mut HashSet<u32>,
+) -> Result<(), AppError> {
+    let now = FastDateTime::now();
+    let reserve_at =
+        schedule.scheduled_at.clone() - Duration::from_secs(config.scheduler.reserve_ahead_secs);
+    if now.0 < reserve_at.0 {
+        return Ok(());
+    }
+    let Some(game_match) = find_unfinished_match(schedule).await? else {
+        return Ok(());
+    };
+
+    //a server chosen when creating the match is the only candidate
+    let Some(server) = online_servers.iter().find(|server| {
+        server.status == ServerStatus::Idle
+            && !reserved.contains(&server.id)
+            && game_match.server_id.is_none_or(|id| id == server.id)
+    }) else {
+        if now.0 >= schedule.scheduled_at.0 {
+            tracing::warn!(
+                "No server available for scheduled match {}",
+                schedule.game_match_id
+            );
+        }
+        return Ok(());
+    };
+
+    let result = match_schedule::update_reserved(
+        &global::RB,
+        ScheduleStatus::Reserved.as_str(),
+        server.id,
+        &now,
+        schedule.game_match_id,
+        ScheduleStatus::Scheduled.as_str(),
+    )
+    .await
+    .map_err(AppError::DatabaseError)?;
+    if result.rows_affected == 0 {
+        return Ok(());
+    }
+    reserved.insert(server.id);
+
+    tracing::info!(
+        "Server {} reserved for scheduled match {}",
+        server.id,
+        schedule.game_match_id
+    );
+    notify_participants(
+        &game_match,
+        schedule,
+        Some(server),
+        None,
+        "match_server_reserved",
+    )
+    .await;
+    Ok(())
+}
+
+async fn start_match(
+    config: &Config,
+    schedule: &MatchSchedule,
+    online_servers: &[ConnectedServer],
+) -> Result<(), AppError> {
+    let now = FastDateTime::now();
+    if now.0 < schedule.scheduled_at.0 {
+        return Ok(());
+    }
+    let Some(game_match) = find_unfinished_match(schedule).await? else {
+        return Ok(());
+    };
+
+    let server = online_servers
+        .iter()
+        .find(|server| Some(server.id) == schedule.server_id);
+    let Some(server) = server else {
+        //the reserved server went offline, hand the match back so another one gets picked
+        tracing::warn!(
+            "Reserved server for scheduled match {} is offline",
+            schedule.game_match_id
+        );
+        match_schedule::update_scheduled_at(
+            &global::RB,
+            &schedule.scheduled_at,
+            ScheduleStatus::Scheduled.as_str(),
+            schedule.game_match_id,
+        )
+        .await
+        .map_err(AppError::DatabaseError)?;
+        return Ok(());
+    };
+
+    let load_match = match_config(config, &game_match).await?;
+    //the match is only pushed once this tick won it, a cancelled schedule must not reach the server
+    let result = match_schedule::update_started(
+        &global::RB,
+        ScheduleStatus::Started.as_str(),
+        &now,
+        schedule.game_match_id,
+        ScheduleStatus::Reserved.as_str(),
+    )
+    .await
+    .map_err(AppError::DatabaseError)?;
+    if result.rows_affected == 0 {
+        return Ok(());
+    }
+    command_queue::enqueue(
+        config,
+        server.id,
+        BackendAction::Backend2ServerLoadMatch,
+        load_match,
+    )
+    .await?;
+
+    tracing::info!(
+        "Scheduled match {} started on server {}",
+        schedule.game_match_id,
+        server.id
+    );
+    notify_participants(&game_match, schedule, Some(server), None, "match_starting").await;
+    Ok(())
+}
+
+/// Once the forfeit timeout is over, a team with none of its players on the server loses the match.
+/// The match is cancelled if neither team showed up.
+async fn check_attendance(
+    config: &Config,
+    schedule: &MatchSchedule,
+    online_servers: &[ConnectedServer],
+) -> Result<(), AppError> {
+    let match_id = schedule.game_match_id;
+    let Some(game_match) = find_unfinished_match(schedule).await? else {
+        return Ok(());
+    };
+    let live_match = spectator::get_live_match(match_id).await;
+    let server = online_servers
+        .iter()
+        .find(|server| Some(server.id) == schedule.server_id);
+
+    let is_live = |status: ServerStatus| {
+        !matches!(status, ServerStatus::Idle | ServerStatus::WaitingForPlayers)
+    };
+    if live_match.as_ref().is_some_and(|m| is_live(m.status))
+        || server.is_some_and(|s| is_live(s.status))
+    {
+        match_schedule::update_status(&global::RB, ScheduleStatus::Live.as_str(), match_id)
+            .await
+            .map_err(AppError::DatabaseError)?;
+        return Ok(());
+    }
+
+    let Some(started_at) = schedule.started_at.clone() else {
+        return Ok(());
+    };
+    let forfeit_at = started_at + Duration::from_secs(config.scheduler.forfeit_timeout_secs);
+    if FastDateTime::now().0 < forfeit_at.0 {
+        return Ok(());
+    }
+
+    let connected: HashSet<String> = live_match
+        .map(|m| m.players.into_iter().map(|p| p.steamid64).collect())
+        .unwrap_or_default();
+    let team1_present = team_present(game_match.team1_id, &connected).await?;
+    let team2_present = team_present(game_match.team2_id, &connected).await?;
+    let winner_team_id = match (team1_present, team2_present) {
+        (true, true) => return Ok(()),
+        (true, false) => Some(game_match.team1_id),
+        (false, true) => Some(game_match.team2_id),
+        (false, false) => None,
+    };
+
+    if let Some(server_id) = schedule.server_id {
+        command_queue::enqueue(
+            config,
+            server_id,
+            BackendAction::Backend2ServerCancelMatch,
+            match_id,
+        )
+        .await?;
+    }
+    match winner_team_id {
+        Some(winner_team_id) => {
+            match_schedule::update_status(
+                &global::RB,
+                ScheduleStatus::Forfeited.as_str(),
+                match_id,
+            )
+            .await
+            .map_err(AppError::DatabaseError)?;
+            tracing::info!("Match {} forfeited, team {} wins", match_id, winner_team_id);
+            finish_match(match_id, winner_team_id).await?;
+            notify_participants(
+                &game_match,
+                schedule,
+                server,
+                Some(winner_team_id),
+                "match_forfeited",
+            )
+            .await;
+        }
+        None => {
+            match_schedule::update_status(
+                &global::RB,
+                ScheduleStatus::Cancelled.as_str(),
+                match_id,
+            )
+            .await
+            .map_err(AppError::DatabaseError)?;
+            tracing::info!("Match {} cancelled, no team showed up", match_id);
+            notify_participants(&game_match, schedule, server, None, "match_cancelled").await;
+        }
+    }
+    Ok(())
+}
+
+async fn team_present(team_id: u32, connected: &HashSet<String>) -> Result<bool, AppError> {
+    let members = team::select_members(&global::RB, team_id)
+        .await
+        .map_err(AppError::DatabaseError)?;
+    Ok(members
+        .iter()
+        .any(|member| connected.contains(&member.steamid64)))
+}
+
+/// Rosters are read when the match is pushed, later roster changes don't reach the server
+pub async fn match_config(
+    config: &Config,
+    game_match: &GameMatch,
+) -> Result<MatchConfig, AppError> {
+    Ok(MatchConfig {
+        match_id: game_match.id.unwrap(),
+        team1: team_config(game_match.team1_id).await?,
+        team2: team_config(game_match.team2_id).await?,
+        forfeit_timeout_secs: config.scheduler.forfeit_timeout_secs,
+    })
+}
+
+async fn team_config(team_id: u32) -> Result<MatchTeamConfig, AppError> {
+    let team: Team = team::select_by_id(&global::RB, team_id)
+        .await
+        .map_err(AppError::DatabaseError)?
+        .ok_or_else(|| AppError::NotFound(format!("Team {} not found", team_id)))?;
+    let members = team::select_members(&global::RB, team_id)
+        .await
+        .map_err(AppError::DatabaseError)?;
+    Ok(MatchTeamConfig {
+        id: team_id,
+        name: team.name,
+        tag: team.tag,
+        players: members.into_iter().map(|member| member.steamid64).collect(),
+    })
+}
+
+async fn notify_participants(
+    game_match: &GameMatch,
+    schedule: &MatchSchedule,
+    server: Option<&ConnectedServer>,
+    winner_team_id: Option<u32>,
+    action: &str,
+) {
+    let notification = MatchNotification {
+        match_id: schedule.game_match_id,
+        scheduled_at: schedule.scheduled_at.clone(),
+        server: server.map(|server| SocketAddr::new(server.ip, server.port).to_string()),
+        winner_team_id,
+    };
+    match serde_json::to_string(&notification) {
+        Ok(message) => notify_players(game_match, message, action).await,
+        Err(e) => tracing::error!("Couldn't serialize MatchNotification json {}", e),
+    }
+}
+
+/// Sends `message` to every member of both teams who is online
+pub async fn notify_players(game_match: &GameMatch, message: String, action: &str) {
+    for team_id in [game_match.team1_id, game_match.team2_id] {
+        let members = match team::select_members(&global::RB, team_id).await {
+            Ok(members) => members,
+            Err(e) => {
+                tracing::error!(error = ?e, "Couldn't load the roster of team {}", team_id);
+                continue;
+            }
+        };
+        for member in members {
+            send_message_to_user(&member.steamid64, message.clone(), action).await;
+        }
+    }
+}
@@ -1,29 +1,89 @@
 use std::collections::HashMap;
+use std::net::IpAddr;
+use std::time::Duration;
 
 use rbatis::rbdc::datetime::FastDateTime;
 use rbatis::rbdc::db::ExecResult;
 use serde::Serialize;
 
-use crate::global;
+use crate::config::Config;
+use crate::driver::inet::InetAddr;
+use crate::global::{self, SERVER_QUERIES};
 use crate::model;
+use crate::service::server_query::ServerQuery;
 use crate::ws::server::get_online_servers;
 use crate::ws::server::ServerStatus;
 use crate::{error::AppError, routes::server::CreateServerPayload};
 
+/// Longest name DNS allows
+const MAX_HOSTNAME_LENGTH: usize = 253;
+
 #[derive(Serialize)]
 pub struct ServerWithStatus {
     id: u32,
-    ip: String,
-    port: String,
+    ip: IpAddr,
+    port: u16,
+    hostname: Option<String>,
     status: ServerStatus,
     online: bool,
+    /// Last A2S answer, also known for servers without the plugin
+    query: Option<ServerQuery>,
 }
 
 pub async fn create_server(payload: CreateServerPayload) -> Result<ExecResult, AppError> {
+    if payload.port == 0 {
+        return Err(AppError::invalid_field(
+            "port",
+            "must be between 1 and 65535",
+        ));
+    }
+    let hostname = payload
+        .hostname
+        .map(|hostname| hostname.trim().to_ascii_lowercase())
+        .filter(|hostname| !hostname.is_empty());
+    if hostname
+        .as_deref()
+        .is_some_and(|hostname| !is_valid_hostname(hostname))
+    {
+        return Err(AppError::invalid_field("hostname", "must be a DNS name"));
+    }
+    let ip = match payload
+        .ip
+        .as_deref()
+        .map(str::trim)
+        .filter(|ip| !ip.is_empty())
+    {
+        Some(ip) => Some(
+            ip.parse::<InetAddr>()
+                .map_err(|_| AppError::invalid_field("ip", "must be an IPv4 or IPv6 address"))?,
+        ),
+        None => None,
+    };
+    let (ip, resolved_at) = match (ip, &hostname) {
+        (Some(ip), _) => (ip, None),
+        (None, Some(hostname)) => match resolve_hostname(hostname, payload.port).await {
+            Some(ip) => (ip, Some(FastDateTime::now())),
+            None => {
+                return Err(AppError::invalid_field(
+                    "hostname",
+                    "doesn't resolve to an address",
+                ))
+            }
+        },
+        (None, None) => {
+            return Err(AppError::invalid_field(
+                "ip",
+                "is required for servers without a hostname",
+            ))
+        }
+    };
+
     let new_server = model::server::Server {
         id: None,
-        ip: payload.ip,
+        ip,
         port: payload.port,
+        hostname,
+        resolved_at,
         created_at: FastDateTime::now(),
     };
     model::server::Server::insert(&mut global::RB.clone(), &new_server)
@@ -43,10 +103,12 @@ pub async fn get_servers() -> Result<Vec<ServerWithStatus>, AppError> {
             i.id.unwrap(),
             ServerWithStatus {
                 id: i.id.unwrap(),
-                ip: i.ip,
+                ip: i.ip.ip(),
                 port: i.port,
+                hostname: i.hostname,
                 status: ServerStatus::Idle,
                 online: false,
+                query: None,
             },
         );
     }
@@ -56,5 +118,97 @@ pub async fn get_servers() -> Result<Vec<ServerWithStatus>, AppError> {
             server.online = true;
         }
     }
+    for (server_id, query) in SERVER_QUERIES.read().await.iter() {
+        if let Some(server) = server_map.get_mut(server_id) {
+            server.query = Some(query.clone());
+        }
+    }
     Ok(server_map.into_iter().map(|(_, v)| v).collect())
 }
+
+pub fn spawn_address_resolver(config: &'static Config) -> tokio::task::JoinHandle<()> {
+    tokio::task::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(
+            config.game_servers.resolve_interval_secs,
+        ));
+        let mut shutdown = global::SHUTDOWN.subscribe();
+        loop {
+            if shutdown.borrow_and_update().is_some() {
+                break;
+            }
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = shutdown.changed() => break,
+            }
+            if let Err(e) = resolve_addresses().await {
+                tracing::error!(error = ?e, "Error while resolving the server hostnames");
+            }
+        }
+    })
+}
+
+/// Points servers registered by hostname at the address it resolves to now. Servers whose name
+/// doesn't resolve keep their last address.
+pub async fn resolve_addresses() -> Result<(), AppError> {
+    let servers = model::server::select_with_hostname(&global::RB)
+        .await
+        .map_err(AppError::DatabaseError)?;
+    for server in servers {
+        let (Some(server_id), Some(hostname)) = (server.id, server.hostname.as_deref()) else {
+            continue;
+        };
+        let Some(ip) = resolve_hostname(hostname, server.port).await else {
+            tracing::warn!(
+                "Hostname {} of server {} didn't resolve, keeping {}",
+                hostname,
+                server_id,
+                server.ip
+            );
+            continue;
+        };
+        if ip != server.ip {
+            tracing::info!(
+                "Server {} moved from {} to {} ({})",
+                server_id,
+                server.ip,
+                ip,
+                hostname
+            );
+        }
+        //fails when another server is registered at the new address
+        if let Err(e) =
+            model::server::update_resolved_ip(&global::RB, &ip, &FastDateTime::now(), server_id)
+                .await
+        {
+            tracing::error!("Couldn't move server {} to {} {}", server_id, ip, e);
+        }
+    }
+    Ok(())
+}
+
+/// IPv4 is preferred, servers usually only listen on it
+async fn resolve_hostname(hostname: &str, port: u16) -> Option<InetAddr> {
+    let addrs: Vec<IpAddr> = match tokio::net::lookup_host((hostname, port)).await {
+        Ok(addrs) => addrs.map(|addr| addr.ip()).collect(),
+        Err(e) => {
+            tracing::debug!("Couldn't resolve {} {}", hostname, e);
+            return None;
+        }
+    };
+    addrs
+        .iter()
+        .find(|ip| ip.is_ipv4())
+        .or(addrs.first())
+        .map(|ip| InetAddr::new(*ip))
+}
+
+fn is_valid_hostname(hostname: &str) -> bool {
+    hostname.len() <= MAX_HOSTNAME_LENGTH
+        && hostname.split('.').all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
+}
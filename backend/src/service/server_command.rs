@@ -0,0 +1,349 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::{oneshot, Mutex};
+
+use crate::error::AppError;
+use crate::global::{self, PENDING_COMMANDS};
+use crate::model::game_match;
+use crate::ws::server::{get_online_servers, send_message_to_server, BackendAction};
+
+/// How long an admin waits for the server to acknowledge a command
+const COMMAND_ACK_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_MESSAGE_LENGTH: usize = 127;
+
+pub type PendingCommandList = Mutex<HashMap<String, PendingCommand>>;
+
+/// Match control commands relayed to the game server plugin, which answers each one with
+/// `server_2_backend_command_ack`. Bans issued here only apply to the server, network wide bans
+/// go through `/api/bans`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum ServerCommand {
+    Pause,
+    Unpause,
+    RestartRound,
+    ChangeMap {
+        map: String,
+    },
+    KickPlayer {
+        steamid64: String,
+        reason: Option<String>,
+    },
+    BanPlayer {
+        steamid64: String,
+        reason: Option<String>,
+        /// Permanent when missing
+        duration_minutes: Option<u32>,
+    },
+    ForceKnifeWinner {
+        team_id: u32,
+    },
+    Say {
+        message: String,
+    },
+    EndMatch {
+        winner_team_id: Option<u32>,
+    },
+}
+
+impl ServerCommand {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ServerCommand::Pause => "pause",
+            ServerCommand::Unpause => "unpause",
+            ServerCommand::RestartRound => "restart_round",
+            ServerCommand::ChangeMap { .. } => "change_map",
+            ServerCommand::KickPlayer { .. } => "kick_player",
+            ServerCommand::BanPlayer { .. } => "ban_player",
+            ServerCommand::ForceKnifeWinner { .. } => "force_knife_winner",
+            ServerCommand::Say { .. } => "say",
+            ServerCommand::EndMatch { .. } => "end_match",
+        }
+    }
+}
+
+/// Data of `backend_2_server_command`
+#[derive(Serialize)]
+struct CommandMessage<'a> {
+    command_id: &'a str,
+    #[serde(flatten)]
+    command: &'a ServerCommand,
+}
+
+/// Data of `server_2_backend_command_ack`
+#[derive(Deserialize)]
+pub struct CommandAck {
+    pub command_id: String,
+    pub success: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CommandStatus {
+    Acknowledged,
+    Failed,
+    TimedOut,
+}
+
+/// Outcome of a command as reported to the admin who issued it
+#[derive(Serialize)]
+pub struct CommandResult {
+    pub command_id: String,
+    pub server_id: u32,
+    pub command: &'static str,
+    pub status: CommandStatus,
+    pub error: Option<String>,
+}
+
+pub struct PendingCommand {
+    server_id: u32,
+    command: &'static str,
+    issued_by: String,
+    result: oneshot::Sender<CommandResult>,
+}
+
+/// A command sent to a server, wait for its result with `wait_for_result`
+pub struct IssuedCommand {
+    pub command_id: String,
+    pub server_id: u32,
+    command: &'static str,
+    result: oneshot::Receiver<CommandResult>,
+}
+
+/// Validates the command and sends it to the server under a fresh correlation ID
+pub async fn issue_command(
+    server_id: u32,
+    command: ServerCommand,
+    issued_by: String,
+) -> Result<IssuedCommand, AppError> {
+    validate(server_id, &command).await?;
+    if !get_online_servers()
+        .await
+        .iter()
+        .any(|server| server.id == server_id)
+    {
+        return Err(AppError::Conflict(format!(
+            "Server {} is not connected",
+            server_id
+        )));
+    }
+
+    let command_id = uuid::Uuid::new_v4().to_string();
+    let (sender, receiver) = oneshot::channel();
+    //registered before sending so an immediate ack finds it
+    PENDING_COMMANDS.lock().await.insert(
+        command_id.clone(),
+        PendingCommand {
+            server_id,
+            command: command.as_str(),
+            issued_by: issued_by.clone(),
+            result: sender,
+        },
+    );
+    let sent = send_message_to_server(
+        server_id,
+        BackendAction::Backend2ServerCommand,
+        CommandMessage {
+            command_id: &command_id,
+            command: &command,
+        },
+    )
+    .await;
+    if !sent {
+        PENDING_COMMANDS.lock().await.remove(&command_id);
+        return Err(AppError::Conflict(format!(
+            "Server {} is not connected",
+            server_id
+        )));
+    }
+    tracing::info!(
+        "Command {} ({}) sent to server {} by {}",
+        command_id,
+        command.as_str(),
+        server_id,
+        issued_by
+    );
+    Ok(IssuedCommand {
+        command_id,
+        server_id,
+        command: command.as_str(),
+        result: receiver,
+    })
+}
+
+/// Resolves once the server answered, the server went away or the ack didn't come in time
+pub async fn wait_for_result(issued: IssuedCommand) -> CommandResult {
+    match tokio::time::timeout(COMMAND_ACK_TIMEOUT, issued.result).await {
+        Ok(Ok(result)) => result,
+        Ok(Err(_)) => CommandResult {
+            command_id: issued.command_id,
+            server_id: issued.server_id,
+            command: issued.command,
+            status: CommandStatus::Failed,
+            error: Some("The command was dropped".to_string()),
+        },
+        Err(_) => {
+            PENDING_COMMANDS.lock().await.remove(&issued.command_id);
+            tracing::warn!(
+                "Server {} didn't acknowledge command {} in time",
+                issued.server_id,
+                issued.command_id
+            );
+            CommandResult {
+                command_id: issued.command_id,
+                server_id: issued.server_id,
+                command: issued.command,
+                status: CommandStatus::TimedOut,
+                error: None,
+            }
+        }
+    }
+}
+
+/// Handles `server_2_backend_command_ack`, acks for commands of other servers are ignored
+pub async fn acknowledge(server_id: u32, ack: CommandAck) {
+    let mut pending_commands = PENDING_COMMANDS.lock().await;
+    let pending = match pending_commands.get(&ack.command_id) {
+        Some(pending) if pending.server_id == server_id => {
+            pending_commands.remove(&ack.command_id).unwrap()
+        }
+        _ => {
+            tracing::warn!(
+                "Server {} acknowledged unknown command {}",
+                server_id,
+                ack.command_id
+            );
+            return;
+        }
+    };
+    drop(pending_commands);
+    tracing::info!(
+        "Server {} {} command {} of {}",
+        server_id,
+        if ack.success { "executed" } else { "refused" },
+        ack.command_id,
+        pending.issued_by
+    );
+    let status = if ack.success {
+        CommandStatus::Acknowledged
+    } else {
+        CommandStatus::Failed
+    };
+    //the admin may have given up waiting already
+    pending
+        .result
+        .send(CommandResult {
+            command_id: ack.command_id,
+            server_id,
+            command: pending.command,
+            status,
+            error: ack.error,
+        })
+        .ok();
+}
+
+/// Fails the commands still waiting on a server that disconnected
+pub async fn fail_pending_commands(server_id: u32) {
+    let mut pending_commands = PENDING_COMMANDS.lock().await;
+    let command_ids: Vec<String> = pending_commands
+        .iter()
+        .filter(|(_, pending)| pending.server_id == server_id)
+        .map(|(command_id, _)| command_id.clone())
+        .collect();
+    for command_id in command_ids {
+        let pending = pending_commands.remove(&command_id).unwrap();
+        pending
+            .result
+            .send(CommandResult {
+                command_id,
+                server_id,
+                command: pending.command,
+                status: CommandStatus::Failed,
+                error: Some("The server disconnected".to_string()),
+            })
+            .ok();
+    }
+}
+
+/// Everything ends up in the server console, so free text can't break out of its quotes
+async fn validate(server_id: u32, command: &ServerCommand) -> Result<(), AppError> {
+    match command {
+        ServerCommand::Pause | ServerCommand::Unpause | ServerCommand::RestartRound => {}
+        ServerCommand::ChangeMap { map } => {
+            if map.is_empty()
+                || map.len() > 64
+                || !map
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '/'))
+            {
+                return Err(AppError::invalid_field(
+                    "map",
+                    "must be 1 to 64 letters, digits, dashes, underscores or slashes",
+                ));
+            }
+        }
+        ServerCommand::KickPlayer { steamid64, reason }
+        | ServerCommand::BanPlayer {
+            steamid64, reason, ..
+        } => {
+            if steamid64.len() != 17 || !steamid64.chars().all(|c| c.is_ascii_digit()) {
+                return Err(AppError::invalid_field(
+                    "steamid64",
+                    "must be a 17 digit SteamID64",
+                ));
+            }
+            if let Some(reason) = reason {
+                validate_text("reason", reason)?;
+            }
+        }
+        ServerCommand::Say { message } => {
+            if message.trim().is_empty() {
+                return Err(AppError::invalid_field("message", "must not be empty"));
+            }
+            validate_text("message", message)?;
+        }
+        ServerCommand::ForceKnifeWinner { team_id } => {
+            validate_match_team(server_id, "team_id", *team_id).await?;
+        }
+        ServerCommand::EndMatch { winner_team_id } => {
+            if let Some(winner_team_id) = winner_team_id {
+                validate_match_team(server_id, "winner_team_id", *winner_team_id).await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn validate_text(field: &str, text: &str) -> Result<(), AppError> {
+    if text.len() > MAX_MESSAGE_LENGTH {
+        return Err(AppError::invalid_field(
+            field,
+            &format!("must be at most {} bytes", MAX_MESSAGE_LENGTH),
+        ));
+    }
+    if text.contains(['"', ';', '\n', '\r']) {
+        return Err(AppError::invalid_field(
+            field,
+            "must not contain quotes, semicolons or line breaks",
+        ));
+    }
+    Ok(())
+}
+
+/// The team has to play the match the server is hosting
+async fn validate_match_team(server_id: u32, field: &str, team_id: u32) -> Result<(), AppError> {
+    let active_match = game_match::select_active_by_server(&global::RB, server_id, server_id)
+        .await
+        .map_err(AppError::DatabaseError)?
+        .ok_or_else(|| AppError::Conflict(format!("Server {} hosts no match", server_id)))?;
+    if active_match.team1_id != team_id && active_match.team2_id != team_id {
+        return Err(AppError::invalid_field(
+            field,
+            "must be a team of the match hosted by the server",
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,286 @@
+//! Log stream of the game servers, posted by `logaddress_add_http`. The parsed events add up to
+//! per player stats of the match the server hosts, and keep the spectator feed going on servers
+//! that don't run the plugin.
+
+use std::collections::HashMap;
+
+use rbatis::rbdc::datetime::FastDateTime;
+use serde::Serialize;
+
+use crate::config::Config;
+use crate::error::AppError;
+use crate::global;
+use crate::model::player_match_stats::{self, PlayerMatchStats};
+use crate::model::{game_match, server, server_log_token, team, GameMatch};
+use crate::service::auth;
+use crate::service::game_match::find_match;
+use crate::service::srcds_log::{self, LogEvent, LogPlayer, LogTeam};
+use crate::ws::server::{get_online_servers, ServerStatus};
+use crate::ws::spectator::{self, LiveMatch, LivePlayer};
+
+/// Returned once, only its hash is stored
+#[derive(Serialize)]
+pub struct LogToken {
+    pub server_id: u32,
+    pub token: String,
+    /// Ready for `logaddress_add_http`
+    pub log_url: String,
+}
+
+#[derive(Serialize)]
+pub struct IngestSummary {
+    pub lines: usize,
+    pub events: usize,
+    /// Match the events were counted for, `None` when the server hosts none
+    pub match_id: Option<u32>,
+}
+
+/// Counters of one player over a batch of lines
+#[derive(Default)]
+struct PlayerTally {
+    name: String,
+    side: Option<LogTeam>,
+    kills: u32,
+    deaths: u32,
+    assists: u32,
+    headshots: u32,
+    bomb_plants: u32,
+    bomb_defuses: u32,
+}
+
+/// Issues a new log token for the server, revoking the previous one
+pub async fn create_log_token(
+    config: &Config,
+    server_id: u32,
+    steamid64: String,
+) -> Result<LogToken, AppError> {
+    server::select_by_id(&global::RB, server_id)
+        .await
+        .map_err(AppError::DatabaseError)?
+        .ok_or_else(|| AppError::NotFound(format!("Server {} not found", server_id)))?;
+    let (token, token_hash) = auth::generate_secret();
+    server_log_token::upsert(
+        &global::RB,
+        server_id,
+        &token_hash,
+        &steamid64,
+        &FastDateTime::now(),
+    )
+    .await
+    .map_err(AppError::DatabaseError)?;
+    tracing::info!("Log token of server {} issued by {}", server_id, steamid64);
+    Ok(LogToken {
+        server_id,
+        log_url: format!(
+            "{}/api/logs/{}?token={}",
+            config.server.api_url.trim_end_matches('/'),
+            server_id,
+            token
+        ),
+        token,
+    })
+}
+
+pub async fn authorize(server_id: u32, token: &str) -> Result<(), AppError> {
+    let stored = server_log_token::select_by_server(&global::RB, server_id)
+        .await
+        .map_err(AppError::DatabaseError)?
+        .ok_or(AppError::Unauthorized)?;
+    if !auth::verify_secret(&stored.token_hash, token) {
+        tracing::info!("Invalid log token for server {}", server_id);
+        return Err(AppError::Unauthorized);
+    }
+    Ok(())
+}
+
+/// Parses a batch of log lines and counts them for the match the server hosts
+pub async fn ingest(server_id: u32, body: &str) -> Result<IngestSummary, AppError> {
+    let lines = body.lines().filter(|line| !line.trim().is_empty()).count();
+    let events: Vec<LogEvent> = body.lines().filter_map(srcds_log::parse_line).collect();
+    let active_match = game_match::select_active_by_server(&global::RB, server_id, server_id)
+        .await
+        .map_err(AppError::DatabaseError)?;
+    let game_match = match active_match {
+        Some(game_match) if !events.is_empty() => game_match,
+        active_match => {
+            return Ok(IngestSummary {
+                lines,
+                events: events.len(),
+                match_id: active_match.and_then(|m| m.id),
+            })
+        }
+    };
+    let match_id = game_match.id.unwrap();
+    apply_events(&game_match, server_id, &events).await?;
+    tracing::debug!(
+        "{} events of server {} counted for match {}",
+        events.len(),
+        server_id,
+        match_id
+    );
+    Ok(IngestSummary {
+        lines,
+        events: events.len(),
+        match_id: Some(match_id),
+    })
+}
+
+pub async fn get_match_stats(match_id: u32) -> Result<Vec<PlayerMatchStats>, AppError> {
+    find_match(match_id).await?;
+    player_match_stats::select_by_match(&global::RB, match_id)
+        .await
+        .map_err(AppError::DatabaseError)
+}
+
+async fn apply_events(
+    game_match: &GameMatch,
+    server_id: u32,
+    events: &[LogEvent],
+) -> Result<(), AppError> {
+    let match_id = game_match.id.unwrap();
+    let mut tallies: HashMap<String, PlayerTally> = HashMap::new();
+    //CT and T scores after the last round of the batch
+    let mut score = None;
+    for event in events {
+        match event {
+            LogEvent::Kill {
+                killer,
+                victim,
+                headshot,
+                ..
+            } => {
+                if let Some(tally) = tally(&mut tallies, killer) {
+                    tally.kills += 1;
+                    tally.headshots += u32::from(*headshot);
+                }
+                if let Some(tally) = tally(&mut tallies, victim) {
+                    tally.deaths += 1;
+                }
+            }
+            LogEvent::Assist { assister, .. } => {
+                if let Some(tally) = tally(&mut tallies, assister) {
+                    tally.assists += 1;
+                }
+            }
+            LogEvent::BombPlanted { player, .. } => {
+                if let Some(tally) = tally(&mut tallies, player) {
+                    tally.bomb_plants += 1;
+                }
+            }
+            LogEvent::BombDefused { player } => {
+                if let Some(tally) = tally(&mut tallies, player) {
+                    tally.bomb_defuses += 1;
+                }
+            }
+            LogEvent::TeamSwitch { player, to, .. } => {
+                if let Some(tally) = tally(&mut tallies, player) {
+                    tally.side = Some(*to);
+                }
+            }
+            LogEvent::RoundWon {
+                ct_score, t_score, ..
+            } => score = Some((*ct_score, *t_score)),
+            LogEvent::Say { player, .. } => {
+                tally(&mut tallies, player);
+            }
+            LogEvent::RoundStart | LogEvent::RoundEnd => {}
+        }
+    }
+
+    let now = FastDateTime::now();
+    for (steamid64, tally) in &tallies {
+        player_match_stats::upsert_increment(
+            &global::RB,
+            match_id,
+            steamid64,
+            &tally.name,
+            tally.side.map(|side| side.as_str()),
+            tally.kills,
+            tally.deaths,
+            tally.assists,
+            tally.headshots,
+            tally.bomb_plants,
+            tally.bomb_defuses,
+            &now,
+        )
+        .await
+        .map_err(AppError::DatabaseError)?;
+    }
+
+    //the plugin reports the live state itself
+    if get_online_servers()
+        .await
+        .iter()
+        .any(|server| server.id == server_id)
+    {
+        return Ok(());
+    }
+    let stats = player_match_stats::select_by_match(&global::RB, match_id)
+        .await
+        .map_err(AppError::DatabaseError)?;
+    let mut live_match = spectator::get_live_match(match_id)
+        .await
+        .unwrap_or(LiveMatch {
+            match_id,
+            status: ServerStatus::Live,
+            round: 0,
+            team1_score: 0,
+            team2_score: 0,
+            players: Vec::new(),
+            winner_team_id: None,
+        });
+    if let Some((ct_score, t_score)) = score {
+        live_match.round = ct_score + t_score;
+        if let Some(team1_side) = team_side(game_match.team1_id, &stats).await? {
+            let (team1_score, team2_score) = if team1_side == LogTeam::CounterTerrorist.as_str() {
+                (ct_score, t_score)
+            } else {
+                (t_score, ct_score)
+            };
+            live_match.team1_score = team1_score;
+            live_match.team2_score = team2_score;
+        }
+    }
+    live_match.players = stats
+        .into_iter()
+        .map(|stats| LivePlayer {
+            steamid64: stats.steamid64,
+            name: stats.name,
+            kills: stats.kills,
+            deaths: stats.deaths,
+        })
+        .collect();
+    spectator::publish_match_update(live_match).await;
+    Ok(())
+}
+
+/// Bots and the console aren't counted
+fn tally<'a>(
+    tallies: &'a mut HashMap<String, PlayerTally>,
+    player: &LogPlayer,
+) -> Option<&'a mut PlayerTally> {
+    let tally = tallies.entry(player.steamid64.clone()?).or_default();
+    tally.name = player.name.clone();
+    if let Some(team) = player
+        .team
+        .filter(|team| matches!(team, LogTeam::Terrorist | LogTeam::CounterTerrorist))
+    {
+        tally.side = Some(team);
+    }
+    Some(tally)
+}
+
+/// Side a member of the team was last seen on
+async fn team_side(team_id: u32, stats: &[PlayerMatchStats]) -> Result<Option<String>, AppError> {
+    let members = team::select_members(&global::RB, team_id)
+        .await
+        .map_err(AppError::DatabaseError)?;
+    Ok(stats
+        .iter()
+        .filter(|stats| {
+            members
+                .iter()
+                .any(|member| member.steamid64 == stats.steamid64)
+        })
+        .find_map(|stats| stats.side.clone()))
+}
@@ -0,0 +1,108 @@
+//! Periodic A2S probes of every registered server, so admins see the map and players of servers
+//! that aren't connected over the websocket.
+
+use std::collections::{BTreeMap, HashMap};
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use rbatis::rbdc::datetime::FastDateTime;
+use serde::Serialize;
+use tokio::sync::RwLock;
+
+use crate::config::Config;
+use crate::error::AppError;
+use crate::global::{self, SERVER_QUERIES};
+use crate::model::Server;
+use crate::service::a2s::{self, A2sPlayer};
+
+pub type ServerQueryList = RwLock<HashMap<u32, ServerQuery>>;
+
+/// Last answer of a server to the A2S queries
+#[derive(Serialize, Clone)]
+pub struct ServerQuery {
+    pub name: String,
+    pub map: String,
+    pub players: u8,
+    pub max_players: u8,
+    pub bots: u8,
+    pub vac: bool,
+    pub ping_ms: u32,
+    /// `None` when the server hides its players
+    pub player_list: Option<Vec<A2sPlayer>>,
+    /// `None` when the server hides its rules
+    pub rules: Option<BTreeMap<String, String>>,
+    pub queried_at: FastDateTime,
+}
+
+pub fn spawn_server_query(config: &'static Config) -> tokio::task::JoinHandle<()> {
+    tokio::task::spawn(async move {
+        let mut interval =
+            tokio::time::interval(Duration::from_secs(config.server_query.interval_secs));
+        let mut shutdown = global::SHUTDOWN.subscribe();
+        loop {
+            if shutdown.borrow_and_update().is_some() {
+                break;
+            }
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = shutdown.changed() => break,
+            }
+            if let Err(e) = probe_servers(config).await {
+                tracing::error!(error = ?e, "Error while probing the servers");
+            }
+        }
+    })
+}
+
+/// Queries every registered server at once, servers that don't answer lose their last result
+pub async fn probe_servers(config: &Config) -> Result<(), AppError> {
+    let servers = Server::select_all(&mut global::RB.clone())
+        .await
+        .map_err(AppError::DatabaseError)?;
+    let timeout = Duration::from_millis(config.server_query.timeout_ms);
+    let probes: Vec<_> = servers
+        .into_iter()
+        .filter_map(|server| {
+            Some((
+                server.id?,
+                tokio::task::spawn(probe(server.socket_addr(), timeout)),
+            ))
+        })
+        .collect();
+    let mut queries = HashMap::new();
+    for (server_id, probe) in probes {
+        match probe.await {
+            Ok(Ok(query)) => {
+                queries.insert(server_id, query);
+            }
+            Ok(Err(e)) => tracing::debug!("Server {} didn't answer the A2S query {}", server_id, e),
+            Err(e) => tracing::error!("The A2S query of server {} failed {}", server_id, e),
+        }
+    }
+    *SERVER_QUERIES.write().await = queries;
+    Ok(())
+}
+
+pub async fn get_server_query(server_id: u32) -> Option<ServerQuery> {
+    SERVER_QUERIES.read().await.get(&server_id).cloned()
+}
+
+async fn probe(addr: SocketAddr, timeout: Duration) -> anyhow::Result<ServerQuery> {
+    let info = a2s::query_info(addr, timeout).await?;
+    let (player_list, rules) = tokio::join!(
+        a2s::query_players(addr, timeout),
+        a2s::query_rules(addr, timeout)
+    );
+    Ok(ServerQuery {
+        name: info.name,
+        map: info.map,
+        players: info.players,
+        max_players: info.max_players,
+        bots: info.bots,
+        vac: info.vac,
+        ping_ms: info.ping_ms,
+        player_list: player_list.ok(),
+        rules: rules.ok(),
+        queried_at: FastDateTime::now(),
+    })
+}
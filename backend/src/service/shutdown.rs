@@ -0,0 +1,172 @@
+use std::time::{Duration, Instant};
+
+use rbatis::rbdc::datetime::FastDateTime;
+use serde::Serialize;
+use tokio::task::JoinHandle;
+
+use crate::config::Config;
+use crate::driver::db::{begin_tx, commit_tx};
+use crate::error::AppError;
+use crate::global::{self, ONLINE_SERVERS, ONLINE_USERS};
+use crate::model::live_match_snapshot::LiveMatchSnapshot;
+use crate::ws::server::{broadcast_to_servers, close_server_connections, BackendAction};
+use crate::ws::spectator::{self, LiveMatch};
+use crate::ws::user::{close_user_connections, get_online_users, send_message_to_user};
+
+/// Sent to servers and users with `backend_shutting_down` right before their connection is closed
+#[derive(Serialize)]
+struct ShuttingDown {
+    reconnect_after_secs: u64,
+}
+
+pub fn is_shutting_down() -> bool {
+    global::SHUTDOWN.borrow().is_some()
+}
+
+/// When the shutdown has to be over, `None` while the backend is running
+fn deadline(config: &Config) -> Option<tokio::time::Instant> {
+    global::SHUTDOWN.borrow().map(|started_at| {
+        tokio::time::Instant::from_std(started_at)
+            + Duration::from_secs(config.server.shutdown_grace_secs)
+    })
+}
+
+/// Resolves once the grace period of a started shutdown has elapsed
+pub async fn grace_period_elapsed(config: &Config) {
+    let mut shutdown = global::SHUTDOWN.subscribe();
+    loop {
+        if let Some(deadline) = deadline(config) {
+            tokio::time::sleep_until(deadline).await;
+            return;
+        }
+        if shutdown.changed().await.is_err() {
+            std::future::pending::<()>().await;
+        }
+    }
+}
+
+async fn wait_for_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("Failed to listen for SIGINT");
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to listen for SIGTERM")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+}
+
+/// Waits for SIGTERM or SIGINT, then tells every websocket client to reconnect later and closes the connections.
+/// Passed to `with_graceful_shutdown` so new connections are refused from then on.
+pub async fn on_signal(config: &Config) {
+    wait_for_signal().await;
+    tracing::info!(
+        "Shutting down, {}s left to finish in-flight work",
+        config.server.shutdown_grace_secs
+    );
+    global::SHUTDOWN.send_replace(Some(Instant::now()));
+
+    let notice = ShuttingDown {
+        reconnect_after_secs: config.server.reconnect_delay_secs,
+    };
+    broadcast_to_servers(BackendAction::BackendShuttingDown, &notice).await;
+    match serde_json::to_string(&notice) {
+        Ok(notice) => {
+            for user in get_online_users().await {
+                send_message_to_user(&user.steamid64, notice.clone(), "backend_shutting_down")
+                    .await;
+            }
+        }
+        Err(e) => tracing::error!("Couldn't serialize the shutdown notice {}", e),
+    }
+    close_server_connections().await;
+    close_user_connections().await;
+}
+
+/// Lets the background tasks and the websocket handlers finish what they are doing, then persists the live
+/// matches. Gives up once the grace period is over.
+pub async fn finish(config: &Config, background_tasks: Vec<JoinHandle<()>>) {
+    let deadline = match deadline(config) {
+        Some(deadline) => deadline,
+        None => return,
+    };
+    let drain = async {
+        for task in background_tasks {
+            task.await.ok();
+        }
+        //handlers remove their connection from the online lists once their last message was handled
+        while !ONLINE_SERVERS.read().await.is_empty() || !ONLINE_USERS.read().await.is_empty() {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    };
+    if tokio::time::timeout_at(deadline, drain).await.is_err() {
+        tracing::warn!("Grace period elapsed before every websocket handler finished");
+    }
+    match tokio::time::timeout_at(deadline, flush_live_matches()).await {
+        Ok(Ok(count)) => tracing::info!("Saved the state of {} live matches", count),
+        Ok(Err(e)) => tracing::error!(error = ?e, "Couldn't save the live matches"),
+        Err(_) => tracing::warn!("Grace period elapsed before the live matches were saved"),
+    }
+}
+
+async fn flush_live_matches() -> Result<usize, AppError> {
+    let live_matches = spectator::get_live_matches().await;
+    let now = FastDateTime::now();
+    let mut snapshots = Vec::new();
+    for live_match in &live_matches {
+        snapshots.push(LiveMatchSnapshot {
+            game_match_id: live_match.match_id,
+            state: serde_json::to_string(live_match).map_err(AppError::JsonParseError)?,
+            created_at: now.clone(),
+        });
+    }
+
+    let mut tx = begin_tx().await.map_err(AppError::DatabaseError)?;
+    tx.exec("delete from live_match_snapshot", vec![])
+        .await
+        .map_err(AppError::DatabaseError)?;
+    if !snapshots.is_empty() {
+        LiveMatchSnapshot::insert_batch(&mut tx, &snapshots, snapshots.len() as u64)
+            .await
+            .map_err(AppError::DatabaseError)?;
+    }
+    commit_tx(tx).await.map_err(AppError::DatabaseError)?;
+    Ok(snapshots.len())
+}
+
+/// Loads the live matches saved by the previous shutdown so spectators get a snapshot before the servers
+/// reconnect and report again. Snapshots are only used once.
+pub async fn restore_live_matches() -> Result<(), AppError> {
+    let snapshots = LiveMatchSnapshot::select_all(&mut global::RB.clone())
+        .await
+        .map_err(AppError::DatabaseError)?;
+    for snapshot in &snapshots {
+        match serde_json::from_str::<LiveMatch>(&snapshot.state) {
+            Ok(live_match) => spectator::publish_match_update(live_match).await,
+            Err(e) => tracing::warn!(
+                "Ignoring unreadable snapshot of match {}: {}",
+                snapshot.game_match_id,
+                e
+            ),
+        }
+    }
+    global::RB
+        .exec("delete from live_match_snapshot", vec![])
+        .await
+        .map_err(AppError::DatabaseError)?;
+    if !snapshots.is_empty() {
+        tracing::info!("Restored {} live matches", snapshots.len());
+    }
+    Ok(())
+}
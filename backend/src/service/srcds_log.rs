@@ -0,0 +1,513 @@
+//! Parser for the srcds log lines of CS:GO, as sent by `logaddress_add_http` or over UDP.
+//! Lines look like `L 10/19/2026 - 20:01:02: "Name<2><STEAM_1:0:123><CT>" say "gl hf"`, the HTTP
+//! stream writes the time as `20:01:02.345 - ` instead. Lines of other kinds are skipped.
+
+use serde::Serialize;
+
+/// Steam ids of individual accounts start here, `STEAM_X:Y:Z` is `BASE + 2Z + Y`
+const STEAMID64_BASE: u64 = 76561197960265728;
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum LogTeam {
+    Terrorist,
+    CounterTerrorist,
+    Spectator,
+    Unassigned,
+}
+
+impl LogTeam {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LogTeam::Terrorist => "terrorist",
+            LogTeam::CounterTerrorist => "counter_terrorist",
+            LogTeam::Spectator => "spectator",
+            LogTeam::Unassigned => "unassigned",
+        }
+    }
+
+    /// Names used by the logs, `TERRORIST` inside player tags and `T` in score notices
+    fn parse(s: &str) -> Option<LogTeam> {
+        match s {
+            "TERRORIST" | "T" => Some(LogTeam::Terrorist),
+            "CT" => Some(LogTeam::CounterTerrorist),
+            "Spectator" => Some(LogTeam::Spectator),
+            "Unassigned" | "" => Some(LogTeam::Unassigned),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct LogPlayer {
+    pub name: String,
+    pub user_id: u32,
+    /// `None` for bots and the console
+    pub steamid64: Option<String>,
+    pub team: Option<LogTeam>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum LogEvent {
+    Kill {
+        killer: LogPlayer,
+        victim: LogPlayer,
+        weapon: String,
+        headshot: bool,
+    },
+    Assist {
+        assister: LogPlayer,
+        victim: LogPlayer,
+        /// Assisted with a flashbang rather than with damage
+        flash: bool,
+    },
+    BombPlanted {
+        player: LogPlayer,
+        site: Option<String>,
+    },
+    BombDefused {
+        player: LogPlayer,
+    },
+    RoundStart,
+    /// Follows `RoundWon` once the round is over
+    RoundEnd,
+    RoundWon {
+        winner: LogTeam,
+        /// `SFUI_Notice_...` code, e.g. `SFUI_Notice_Target_Bombed`
+        reason: String,
+        ct_score: u32,
+        t_score: u32,
+    },
+    TeamSwitch {
+        player: LogPlayer,
+        from: LogTeam,
+        to: LogTeam,
+    },
+    Say {
+        player: LogPlayer,
+        message: String,
+        team_only: bool,
+    },
+}
+
+/// `None` for lines that aren't one of the events above
+pub fn parse_line(line: &str) -> Option<LogEvent> {
+    let message = strip_timestamp(line.trim_end_matches(['\r', '\n']))?;
+    if let Some(rest) = message.strip_prefix("World triggered ") {
+        return match unquote(rest)?.0 {
+            "Round_Start" => Some(LogEvent::RoundStart),
+            "Round_End" => Some(LogEvent::RoundEnd),
+            _ => None,
+        };
+    }
+    if let Some(rest) = message.strip_prefix("Team ") {
+        return parse_team_trigger(rest);
+    }
+
+    let (player, rest) = take_player(message)?;
+    let rest = skip_position(rest);
+    if let Some(rest) = rest.strip_prefix("killed ") {
+        let (victim, rest) = take_player(rest)?;
+        let rest = skip_position(rest).strip_prefix("with ")?;
+        let (weapon, rest) = unquote(rest)?;
+        return Some(LogEvent::Kill {
+            killer: player,
+            victim,
+            weapon: weapon.to_string(),
+            headshot: rest.trim_start().starts_with("(headshot"),
+        });
+    }
+    for (prefix, flash) in [
+        ("assisted killing ", false),
+        ("flash-assisted killing ", true),
+    ] {
+        if let Some(rest) = rest.strip_prefix(prefix) {
+            let (victim, _) = take_player(rest)?;
+            return Some(LogEvent::Assist {
+                assister: player,
+                victim,
+                flash,
+            });
+        }
+    }
+    if let Some(rest) = rest.strip_prefix("triggered ") {
+        let (trigger, rest) = unquote(rest)?;
+        return match trigger {
+            "Planted_The_Bomb" => Some(LogEvent::BombPlanted {
+                player,
+                site: rest
+                    .trim()
+                    .strip_prefix("at bombsite ")
+                    .map(|site| site.to_string()),
+            }),
+            "Defused_The_Bomb" => Some(LogEvent::BombDefused { player }),
+            _ => None,
+        };
+    }
+    if let Some(rest) = rest.strip_prefix("switched from team <") {
+        let (from, rest) = rest.split_once("> to <")?;
+        let to = rest.strip_suffix('>')?;
+        return Some(LogEvent::TeamSwitch {
+            player,
+            from: LogTeam::parse(from)?,
+            to: LogTeam::parse(to)?,
+        });
+    }
+    for (prefix, team_only) in [("say_team ", true), ("say ", false)] {
+        if let Some(rest) = rest.strip_prefix(prefix) {
+            return Some(LogEvent::Say {
+                player,
+                message: unquote(rest)?.0.to_string(),
+                team_only,
+            });
+        }
+    }
+    None
+}
+
+/// Converts `STEAM_X:Y:Z` and `[U:1:Z]` ids, bots have neither
+pub fn to_steamid64(steamid: &str) -> Option<String> {
+    let account = if let Some(rest) = steamid.strip_prefix("STEAM_") {
+        let mut parts = rest.split(':').skip(1);
+        let low: u64 = parts.next()?.parse().ok()?;
+        let high: u64 = parts.next()?.parse().ok()?;
+        high.checked_mul(2)?.checked_add(low)?
+    } else {
+        steamid
+            .strip_prefix("[U:1:")?
+            .strip_suffix(']')?
+            .parse()
+            .ok()?
+    };
+    Some(STEAMID64_BASE.checked_add(account)?.to_string())
+}
+
+/// Drops `L MM/DD/YYYY - HH:MM:SS: ` or the `MM/DD/YYYY - HH:MM:SS.mmm - ` of the HTTP stream
+fn strip_timestamp(line: &str) -> Option<&str> {
+    let line = line.strip_prefix("L ").unwrap_or(line);
+    //the date and time take 21 characters in both formats
+    let (timestamp, rest) = (line.get(..21)?, line.get(21..)?);
+    let bytes = timestamp.as_bytes();
+    if bytes[2] != b'/'
+        || bytes[5] != b'/'
+        || timestamp.get(10..13) != Some(" - ")
+        || bytes[15] != b':'
+    {
+        return None;
+    }
+    let rest = match rest.strip_prefix('.') {
+        Some(rest) => rest.trim_start_matches(|c: char| c.is_ascii_digit()),
+        None => rest,
+    };
+    rest.strip_prefix(": ").or_else(|| rest.strip_prefix(" - "))
+}
+
+/// `"CT" triggered "SFUI_Notice_CTs_Win" (CT "3") (T "2")`
+fn parse_team_trigger(rest: &str) -> Option<LogEvent> {
+    let (team, rest) = unquote(rest)?;
+    let (reason, rest) = unquote(rest.trim_start().strip_prefix("triggered ")?)?;
+    let (ct_score, rest) = unquote(rest.trim_start().strip_prefix("(CT ")?)?;
+    let (t_score, _) = unquote(
+        rest.trim_start()
+            .strip_prefix(")")?
+            .trim_start()
+            .strip_prefix("(T ")?,
+    )?;
+    Some(LogEvent::RoundWon {
+        winner: LogTeam::parse(team)?,
+        reason: reason.to_string(),
+        ct_score: ct_score.parse().ok()?,
+        t_score: t_score.parse().ok()?,
+    })
+}
+
+/// Splits `"Name<uid><steamid><team>" rest`, switch lines leave the team out and the console has
+/// `<Console>` as its team. Names may contain anything, so the tags are read from the end of the
+/// quoted part.
+fn take_player(s: &str) -> Option<(LogPlayer, &str)> {
+    let s = s.strip_prefix('"')?;
+    let end = s.find(">\"")?;
+    let (mut tagged, rest) = (&s[..end + 1], s[end + 2..].trim_start());
+    let mut next_tag = || {
+        let start = tagged.strip_suffix('>')?.rfind('<')?;
+        let tag = &tagged[start + 1..tagged.len() - 1];
+        tagged = &tagged[..start];
+        Some(tag)
+    };
+
+    //switch lines end with the steam id, which is never a number
+    let last = next_tag()?;
+    let before_last = next_tag()?;
+    let (team, steamid, user_id) = match before_last.parse() {
+        Ok(user_id) => (None, last, user_id),
+        Err(_) => (LogTeam::parse(last), before_last, next_tag()?.parse().ok()?),
+    };
+    Some((
+        LogPlayer {
+            name: tagged.to_string(),
+            user_id,
+            steamid64: to_steamid64(steamid),
+            team,
+        },
+        rest,
+    ))
+}
+
+/// Kill lines carry `[x y z]` after each player
+fn skip_position(s: &str) -> &str {
+    match s.strip_prefix('[').and_then(|s| s.split_once(']')) {
+        Some((_, rest)) => rest.trim_start(),
+        None => s,
+    }
+}
+
+/// Splits `"value" rest`
+fn unquote(s: &str) -> Option<(&str, &str)> {
+    let s = s.strip_prefix('"')?;
+    let end = s.find('"')?;
+    Some((&s[..end], &s[end + 1..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(message: &str) -> LogEvent {
+        let line = format!("L 10/19/2026 - 20:01:02: {}", message);
+        parse_line(&line).unwrap_or_else(|| panic!("{} wasn't parsed", line))
+    }
+
+    fn assert_player(player: &LogPlayer, name: &str, user_id: u32, steamid64: Option<&str>) {
+        assert_eq!(player.name, name);
+        assert_eq!(player.user_id, user_id);
+        assert_eq!(player.steamid64.as_deref(), steamid64);
+    }
+
+    #[test]
+    fn both_timestamp_formats() {
+        let message = r#"World triggered "Round_Start""#;
+        for line in [
+            format!("L 10/19/2026 - 20:01:02: {}", message),
+            format!("10/19/2026 - 20:01:02.345 - {}\r\n", message),
+        ] {
+            assert!(
+                matches!(parse_line(&line), Some(LogEvent::RoundStart)),
+                "{}",
+                line
+            );
+        }
+        assert!(parse_line(&format!("10/19/2026 20:01:02: {}", message)).is_none());
+        assert!(parse_line("L 10/19").is_none());
+    }
+
+    #[test]
+    fn kills() {
+        let event = parse(
+            r#""Alice<2><STEAM_1:0:123><CT>" [-1 20 -5] killed "Bob<3><[U:1:7]><TERRORIST>" [1 2 3] with "ak47" (headshot)"#,
+        );
+        match event {
+            LogEvent::Kill {
+                killer,
+                victim,
+                weapon,
+                headshot,
+            } => {
+                assert_player(&killer, "Alice", 2, Some("76561197960265974"));
+                assert_eq!(killer.team, Some(LogTeam::CounterTerrorist));
+                assert_player(&victim, "Bob", 3, Some("76561197960265735"));
+                assert_eq!(victim.team, Some(LogTeam::Terrorist));
+                assert_eq!(weapon, "ak47");
+                assert!(headshot);
+            }
+            event => panic!("{:?}", event),
+        }
+
+        let event = parse(
+            r#""Alice<2><STEAM_1:0:123><CT>" killed "Bob<3><STEAM_1:1:4><TERRORIST>" with "knife""#,
+        );
+        assert!(matches!(
+            event,
+            LogEvent::Kill {
+                headshot: false,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn assists() {
+        for (verb, expected_flash) in [("assisted", false), ("flash-assisted", true)] {
+            let event = parse(&format!(
+                r#""Alice<2><STEAM_1:0:123><CT>" {} killing "Bob<3><STEAM_1:1:4><TERRORIST>""#,
+                verb
+            ));
+            match event {
+                LogEvent::Assist {
+                    assister,
+                    victim,
+                    flash,
+                } => {
+                    assert_eq!(assister.name, "Alice");
+                    assert_eq!(victim.name, "Bob");
+                    assert_eq!(flash, expected_flash);
+                }
+                event => panic!("{:?}", event),
+            }
+        }
+    }
+
+    #[test]
+    fn bomb_events() {
+        let event =
+            parse(r#""Bob<3><STEAM_1:1:4><TERRORIST>" triggered "Planted_The_Bomb" at bombsite B"#);
+        match event {
+            LogEvent::BombPlanted { player, site } => {
+                assert_eq!(player.name, "Bob");
+                assert_eq!(site.as_deref(), Some("B"));
+            }
+            event => panic!("{:?}", event),
+        }
+        let event = parse(r#""Alice<2><STEAM_1:0:123><CT>" triggered "Defused_The_Bomb""#);
+        assert!(matches!(event, LogEvent::BombDefused { player } if player.name == "Alice"));
+        assert!(parse_line(
+            r#"L 10/19/2026 - 20:01:02: "Bob<3><STEAM_1:1:4><TERRORIST>" triggered "Got_The_Bomb""#
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn rounds() {
+        assert!(matches!(
+            parse(r#"World triggered "Round_Start""#),
+            LogEvent::RoundStart
+        ));
+        assert!(matches!(
+            parse(r#"World triggered "Round_End""#),
+            LogEvent::RoundEnd
+        ));
+        match parse(r#"Team "T" triggered "SFUI_Notice_Target_Bombed" (CT "3") (T "12")"#) {
+            LogEvent::RoundWon {
+                winner,
+                reason,
+                ct_score,
+                t_score,
+            } => {
+                assert_eq!(winner, LogTeam::Terrorist);
+                assert_eq!(reason, "SFUI_Notice_Target_Bombed");
+                assert_eq!((ct_score, t_score), (3, 12));
+            }
+            event => panic!("{:?}", event),
+        }
+    }
+
+    #[test]
+    fn team_switches_have_no_team_tag() {
+        match parse(r#""Alice<2><STEAM_1:0:123>" switched from team <Unassigned> to <CT>"#) {
+            LogEvent::TeamSwitch { player, from, to } => {
+                assert_player(&player, "Alice", 2, Some("76561197960265974"));
+                assert_eq!(player.team, None);
+                assert_eq!(from, LogTeam::Unassigned);
+                assert_eq!(to, LogTeam::CounterTerrorist);
+            }
+            event => panic!("{:?}", event),
+        }
+    }
+
+    #[test]
+    fn chat() {
+        match parse(r#""Alice<2><STEAM_1:0:123><CT>" say_team "rotate b""#) {
+            LogEvent::Say {
+                player,
+                message,
+                team_only,
+            } => {
+                assert_eq!(player.name, "Alice");
+                assert_eq!(message, "rotate b");
+                assert!(team_only);
+            }
+            event => panic!("{:?}", event),
+        }
+        assert!(matches!(
+            parse(r#""Alice<2><STEAM_1:0:123><CT>" say "gl hf""#),
+            LogEvent::Say {
+                team_only: false,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn bots_and_the_console_have_no_steam_id() {
+        match parse(
+            r#""BOT Eli<5><BOT><TERRORIST>" killed "Alice<2><STEAM_1:0:123><CT>" with "glock""#,
+        ) {
+            LogEvent::Kill { killer, .. } => {
+                assert_player(&killer, "BOT Eli", 5, None);
+                assert_eq!(killer.team, Some(LogTeam::Terrorist));
+            }
+            event => panic!("{:?}", event),
+        }
+        match parse(r#""Console<0><Console><Console>" say "restarting""#) {
+            LogEvent::Say {
+                player, message, ..
+            } => {
+                assert_player(&player, "Console", 0, None);
+                assert_eq!(player.team, None);
+                assert_eq!(message, "restarting");
+            }
+            event => panic!("{:?}", event),
+        }
+    }
+
+    #[test]
+    fn names_may_contain_tags() {
+        match parse(r#""<3 a<b>c<7><2><STEAM_1:0:123><CT>" say "hi""#) {
+            LogEvent::Say { player, .. } => {
+                assert_player(&player, "<3 a<b>c<7>", 2, Some("76561197960265974"));
+                assert_eq!(player.team, Some(LogTeam::CounterTerrorist));
+            }
+            event => panic!("{:?}", event),
+        }
+        match parse(r#""x<9><2><STEAM_1:0:123>" switched from team <CT> to <TERRORIST>"#) {
+            LogEvent::TeamSwitch { player, .. } => {
+                assert_player(&player, "x<9>", 2, Some("76561197960265974"))
+            }
+            event => panic!("{:?}", event),
+        }
+    }
+
+    #[test]
+    fn steam_ids() {
+        assert_eq!(
+            to_steamid64("STEAM_1:1:4").as_deref(),
+            Some("76561197960265737")
+        );
+        assert_eq!(
+            to_steamid64("STEAM_0:0:123").as_deref(),
+            Some("76561197960265974")
+        );
+        assert_eq!(
+            to_steamid64("[U:1:7]").as_deref(),
+            Some("76561197960265735")
+        );
+        assert_eq!(to_steamid64("BOT"), None);
+        assert_eq!(to_steamid64("STEAM_1:0:9223372036854775807"), None);
+        assert_eq!(to_steamid64("STEAM_1:1:9223372036854775807"), None);
+        assert_eq!(to_steamid64("[U:1:18446744073709551615]"), None);
+    }
+
+    #[test]
+    fn other_lines_are_skipped() {
+        assert!(
+            parse_line(r#"L 10/19/2026 - 20:01:02: Log file started (file "logs/L000.log")"#)
+                .is_none()
+        );
+        assert!(parse_line(
+            r#"L 10/19/2026 - 20:01:02: "Alice<2><STEAM_1:0:123><CT>" purchased "ak47""#
+        )
+        .is_none());
+        assert!(parse_line("").is_none());
+        assert!(parse_line("L 10/19/202é - 20:01:02: hi").is_none());
+    }
+}
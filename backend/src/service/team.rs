@@ -0,0 +1,392 @@
+use std::str::FromStr;
+
+use rbatis::rbdc::datetime::FastDateTime;
+use serde::{Deserialize, Serialize};
+
+use crate::driver::db::{begin_tx, commit_tx, is_unique_violation};
+use crate::error::AppError;
+use crate::global;
+use crate::model::team::{self, Team, TeamInvite, TeamMember, TeamRosterEvent};
+use crate::model::user;
+use crate::routes::team::{CreateTeamPayload, InviteMemberPayload};
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TeamRole {
+    Player,
+    Sub,
+    Coach,
+}
+
+impl TeamRole {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TeamRole::Player => "player",
+            TeamRole::Sub => "sub",
+            TeamRole::Coach => "coach",
+        }
+    }
+}
+
+impl FromStr for TeamRole {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "player" => Ok(TeamRole::Player),
+            "sub" => Ok(TeamRole::Sub),
+            "coach" => Ok(TeamRole::Coach),
+            _ => Err(AppError::BadRequest(format!("Invalid team role {}", s))),
+        }
+    }
+}
+
+/// Kinds of entries stored in the roster history
+pub enum RosterEvent {
+    Joined,
+    Left,
+    Kicked,
+    Captain,
+}
+
+impl RosterEvent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RosterEvent::Joined => "joined",
+            RosterEvent::Left => "left",
+            RosterEvent::Kicked => "kicked",
+            RosterEvent::Captain => "captain",
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct TeamWithRoster {
+    #[serde(flatten)]
+    pub team: Team,
+    pub members: Vec<TeamMember>,
+}
+
+fn roster_event(
+    team_id: u32,
+    steamid64: &str,
+    event: RosterEvent,
+    role: Option<TeamRole>,
+    actor_steamid64: &str,
+) -> TeamRosterEvent {
+    TeamRosterEvent {
+        id: None,
+        team_id,
+        steamid64: steamid64.to_string(),
+        event: event.as_str().to_string(),
+        role: role.map(|r| r.as_str().to_string()),
+        actor_steamid64: actor_steamid64.to_string(),
+        created_at: FastDateTime::now(),
+    }
+}
+
+pub async fn find_team(team_id: u32) -> Result<Team, AppError> {
+    team::select_by_id(&global::RB, team_id)
+        .await
+        .map_err(AppError::DatabaseError)?
+        .ok_or_else(|| AppError::NotFound(format!("Team {} not found", team_id)))
+}
+
+/// Fails with `Forbidden` unless the user is the captain of the team
+async fn find_captained_team(team_id: u32, steamid64: &str) -> Result<Team, AppError> {
+    let team = find_team(team_id).await?;
+    if team.captain_steamid64 != steamid64 {
+        return Err(AppError::Forbidden);
+    }
+    Ok(team)
+}
+
+pub async fn create_team(payload: CreateTeamPayload, steamid64: String) -> Result<Team, AppError> {
+    let name = payload.name.trim();
+    let tag = payload.tag.trim();
+    if name.is_empty() || name.len() > 64 {
+        return Err(AppError::invalid_field(
+            "name",
+            "Team name must have between 1 and 64 characters",
+        ));
+    }
+    if tag.is_empty() || tag.len() > 12 {
+        return Err(AppError::invalid_field(
+            "tag",
+            "Team tag must have between 1 and 12 characters",
+        ));
+    }
+    if team::select_by_name(&global::RB, name)
+        .await
+        .map_err(AppError::DatabaseError)?
+        .is_some()
+    {
+        return Err(AppError::Conflict(format!("Team name {} is taken", name)));
+    }
+
+    let now = FastDateTime::now();
+    let mut tx = begin_tx().await.map_err(AppError::DatabaseError)?;
+    let created_team = team::insert_returning(&mut tx, name, tag, &payload.logo, &steamid64, &now)
+        .await
+        .map_err(|e| {
+            if is_unique_violation(&e) {
+                AppError::Conflict(format!("Team name {} is taken", name))
+            } else {
+                AppError::DatabaseError(e)
+            }
+        })?;
+    let team_id = created_team.id.unwrap();
+    TeamMember::insert(
+        &mut tx,
+        &TeamMember {
+            team_id,
+            steamid64: steamid64.clone(),
+            role: TeamRole::Player.as_str().to_string(),
+            joined_at: now,
+        },
+    )
+    .await
+    .map_err(AppError::DatabaseError)?;
+    TeamRosterEvent::insert(
+        &mut tx,
+        &roster_event(
+            team_id,
+            &steamid64,
+            RosterEvent::Captain,
+            Some(TeamRole::Player),
+            &steamid64,
+        ),
+    )
+    .await
+    .map_err(AppError::DatabaseError)?;
+    commit_tx(tx).await.map_err(AppError::DatabaseError)?;
+
+    tracing::info!("Team {} created by {}", team_id, steamid64);
+    Ok(created_team)
+}
+
+pub async fn get_teams() -> Result<Vec<Team>, AppError> {
+    Team::select_all(&mut global::RB.clone())
+        .await
+        .map_err(AppError::DatabaseError)
+}
+
+pub async fn get_team(team_id: u32) -> Result<TeamWithRoster, AppError> {
+    let team = find_team(team_id).await?;
+    let members = team::select_members(&global::RB, team_id)
+        .await
+        .map_err(AppError::DatabaseError)?;
+    Ok(TeamWithRoster { team, members })
+}
+
+pub async fn invite_member(
+    team_id: u32,
+    payload: InviteMemberPayload,
+    steamid64: String,
+) -> Result<TeamInvite, AppError> {
+    find_captained_team(team_id, &steamid64).await?;
+    let role = TeamRole::from_str(&payload.role)?;
+
+    user::select_by_steamid(&global::RB, payload.steamid64.clone())
+        .await
+        .map_err(AppError::DatabaseError)?
+        .ok_or_else(|| AppError::NotFound(format!("User {} not found", payload.steamid64)))?;
+    if team::select_member(&global::RB, team_id, &payload.steamid64)
+        .await
+        .map_err(AppError::DatabaseError)?
+        .is_some()
+    {
+        return Err(AppError::Conflict(
+            "User is already a member of the team".to_string(),
+        ));
+    }
+    if team::select_invite_for(&global::RB, team_id, &payload.steamid64)
+        .await
+        .map_err(AppError::DatabaseError)?
+        .is_some()
+    {
+        return Err(AppError::Conflict(
+            "User has already been invited".to_string(),
+        ));
+    }
+
+    let invite = TeamInvite {
+        id: None,
+        team_id,
+        steamid64: payload.steamid64,
+        role: role.as_str().to_string(),
+        invited_by: steamid64,
+        created_at: FastDateTime::now(),
+    };
+    TeamInvite::insert(&mut global::RB.clone(), &invite)
+        .await
+        .map_err(AppError::DatabaseError)?;
+    team::select_invite_for(&global::RB, team_id, &invite.steamid64)
+        .await
+        .map_err(AppError::DatabaseError)?
+        .ok_or_else(|| AppError::NotFound("Invite not found".to_string()))
+}
+
+pub async fn get_invites(steamid64: String) -> Result<Vec<TeamInvite>, AppError> {
+    team::select_invites_by_steamid(&global::RB, &steamid64)
+        .await
+        .map_err(AppError::DatabaseError)
+}
+
+async fn find_invite(invite_id: u32) -> Result<TeamInvite, AppError> {
+    team::select_invite(&global::RB, invite_id)
+        .await
+        .map_err(AppError::DatabaseError)?
+        .ok_or_else(|| AppError::NotFound(format!("Invite {} not found", invite_id)))
+}
+
+pub async fn accept_invite(invite_id: u32, steamid64: String) -> Result<TeamMember, AppError> {
+    let invite = find_invite(invite_id).await?;
+    if invite.steamid64 != steamid64 {
+        return Err(AppError::Forbidden);
+    }
+    let role = TeamRole::from_str(&invite.role)?;
+    if team::select_member(&global::RB, invite.team_id, &steamid64)
+        .await
+        .map_err(AppError::DatabaseError)?
+        .is_some()
+    {
+        return Err(AppError::Conflict(
+            "User is already a member of the team".to_string(),
+        ));
+    }
+    let member = TeamMember {
+        team_id: invite.team_id,
+        steamid64: steamid64.clone(),
+        role: invite.role.clone(),
+        joined_at: FastDateTime::now(),
+    };
+
+    let mut tx = begin_tx().await.map_err(AppError::DatabaseError)?;
+    //the same invite accepted twice at once
+    TeamMember::insert(&mut tx, &member).await.map_err(|e| {
+        if is_unique_violation(&e) {
+            AppError::Conflict("User is already a member of the team".to_string())
+        } else {
+            AppError::DatabaseError(e)
+        }
+    })?;
+    TeamInvite::delete_by_column(&mut tx, "id", invite_id)
+        .await
+        .map_err(AppError::DatabaseError)?;
+    TeamRosterEvent::insert(
+        &mut tx,
+        &roster_event(
+            invite.team_id,
+            &steamid64,
+            RosterEvent::Joined,
+            Some(role),
+            &invite.invited_by,
+        ),
+    )
+    .await
+    .map_err(AppError::DatabaseError)?;
+    commit_tx(tx).await.map_err(AppError::DatabaseError)?;
+    Ok(member)
+}
+
+/// Invites can be declined by the invited user or withdrawn by the team captain
+pub async fn delete_invite(invite_id: u32, steamid64: String) -> Result<(), AppError> {
+    let invite = find_invite(invite_id).await?;
+    if invite.steamid64 != steamid64 {
+        find_captained_team(invite.team_id, &steamid64).await?;
+    }
+    TeamInvite::delete_by_column(&mut global::RB.clone(), "id", invite_id)
+        .await
+        .map_err(AppError::DatabaseError)?;
+    Ok(())
+}
+
+async fn remove_member(
+    team: &Team,
+    steamid64: &str,
+    event: RosterEvent,
+    actor_steamid64: &str,
+) -> Result<(), AppError> {
+    let team_id = team.id.unwrap();
+    if team.captain_steamid64 == steamid64 {
+        return Err(AppError::Conflict(
+            "The captain has to hand over the captaincy before leaving the team".to_string(),
+        ));
+    }
+    let member = team::select_member(&global::RB, team_id, steamid64)
+        .await
+        .map_err(AppError::DatabaseError)?
+        .ok_or_else(|| AppError::NotFound("User is not a member of the team".to_string()))?;
+
+    let mut tx = begin_tx().await.map_err(AppError::DatabaseError)?;
+    team::delete_member(&mut tx, team_id, steamid64)
+        .await
+        .map_err(AppError::DatabaseError)?;
+    TeamRosterEvent::insert(
+        &mut tx,
+        &roster_event(
+            team_id,
+            steamid64,
+            event,
+            TeamRole::from_str(&member.role).ok(),
+            actor_steamid64,
+        ),
+    )
+    .await
+    .map_err(AppError::DatabaseError)?;
+    commit_tx(tx).await.map_err(AppError::DatabaseError)?;
+    Ok(())
+}
+
+pub async fn leave_team(team_id: u32, steamid64: String) -> Result<(), AppError> {
+    let team = find_team(team_id).await?;
+    remove_member(&team, &steamid64, RosterEvent::Left, &steamid64).await
+}
+
+pub async fn kick_member(
+    team_id: u32,
+    target_steamid64: String,
+    steamid64: String,
+) -> Result<(), AppError> {
+    let team = find_captained_team(team_id, &steamid64).await?;
+    remove_member(&team, &target_steamid64, RosterEvent::Kicked, &steamid64).await
+}
+
+pub async fn transfer_captaincy(
+    team_id: u32,
+    new_captain_steamid64: String,
+    steamid64: String,
+) -> Result<(), AppError> {
+    find_captained_team(team_id, &steamid64).await?;
+    let member = team::select_member(&global::RB, team_id, &new_captain_steamid64)
+        .await
+        .map_err(AppError::DatabaseError)?
+        .ok_or_else(|| AppError::NotFound("User is not a member of the team".to_string()))?;
+
+    let mut tx = begin_tx().await.map_err(AppError::DatabaseError)?;
+    team::update_captain(&mut tx, &new_captain_steamid64, team_id)
+        .await
+        .map_err(AppError::DatabaseError)?;
+    TeamRosterEvent::insert(
+        &mut tx,
+        &roster_event(
+            team_id,
+            &new_captain_steamid64,
+            RosterEvent::Captain,
+            TeamRole::from_str(&member.role).ok(),
+            &steamid64,
+        ),
+    )
+    .await
+    .map_err(AppError::DatabaseError)?;
+    commit_tx(tx).await.map_err(AppError::DatabaseError)?;
+    Ok(())
+}
+
+pub async fn get_roster_history(team_id: u32) -> Result<Vec<TeamRosterEvent>, AppError> {
+    find_team(team_id).await?;
+    team::select_roster_history(&global::RB, team_id)
+        .await
+        .map_err(AppError::DatabaseError)
+}
@@ -0,0 +1,589 @@
+use std::str::FromStr;
+
+use rbatis::executor::Executor;
+use rbatis::rbdc::datetime::FastDateTime;
+use serde::{Deserialize, Serialize};
+
+use crate::driver::db::{begin_tx, commit_tx};
+use crate::error::AppError;
+use crate::global;
+use crate::model::game_match;
+use crate::model::tournament::{self, Tournament, TournamentMatch, TournamentTeam};
+use crate::routes::tournament::{CreateTournamentPayload, RegisterTeamPayload};
+use crate::service::bracket::{self, BracketKind, BracketNode, SwissResult};
+use crate::service::team;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TournamentFormat {
+    SingleElimination,
+    DoubleElimination,
+    Swiss,
+}
+
+impl TournamentFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TournamentFormat::SingleElimination => "single_elimination",
+            TournamentFormat::DoubleElimination => "double_elimination",
+            TournamentFormat::Swiss => "swiss",
+        }
+    }
+}
+
+impl FromStr for TournamentFormat {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "single_elimination" => Ok(TournamentFormat::SingleElimination),
+            "double_elimination" => Ok(TournamentFormat::DoubleElimination),
+            "swiss" => Ok(TournamentFormat::Swiss),
+            _ => Err(AppError::BadRequest(format!(
+                "Invalid tournament format {}",
+                s
+            ))),
+        }
+    }
+}
+
+pub enum TournamentStatus {
+    Registration,
+    Running,
+    Finished,
+}
+
+impl TournamentStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TournamentStatus::Registration => "registration",
+            TournamentStatus::Running => "running",
+            TournamentStatus::Finished => "finished",
+        }
+    }
+}
+
+/// Lifecycle of a bracket slot: `pending` until both teams are known, `ready` once its game match exists,
+/// `finished` after a winner was reported and `bye` when it got resolved without being played
+pub enum MatchStatus {
+    Pending,
+    Ready,
+    Finished,
+    Bye,
+}
+
+impl MatchStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MatchStatus::Pending => "pending",
+            MatchStatus::Ready => "ready",
+            MatchStatus::Finished => "finished",
+            MatchStatus::Bye => "bye",
+        }
+    }
+}
+
+fn is_resolved(tournament_match: &TournamentMatch) -> bool {
+    tournament_match.status == MatchStatus::Finished.as_str()
+        || tournament_match.status == MatchStatus::Bye.as_str()
+}
+
+#[derive(Serialize)]
+pub struct TournamentDetails {
+    #[serde(flatten)]
+    pub tournament: Tournament,
+    pub teams: Vec<TournamentTeam>,
+    pub matches: Vec<TournamentMatch>,
+}
+
+#[derive(Serialize)]
+pub struct StandingResponse {
+    pub team_id: u32,
+    pub seed: u32,
+    pub wins: u32,
+    pub losses: u32,
+    pub buchholz: u32,
+}
+
+pub async fn find_tournament(tournament_id: u32) -> Result<Tournament, AppError> {
+    tournament::select_by_id(&mut global::RB.clone(), tournament_id)
+        .await
+        .map_err(AppError::DatabaseError)?
+        .ok_or_else(|| AppError::NotFound(format!("Tournament {} not found", tournament_id)))
+}
+
+async fn find_tournament_in_registration(tournament_id: u32) -> Result<Tournament, AppError> {
+    let tournament = find_tournament(tournament_id).await?;
+    if tournament.status != TournamentStatus::Registration.as_str() {
+        return Err(AppError::Conflict(
+            "The tournament has already started".to_string(),
+        ));
+    }
+    Ok(tournament)
+}
+
+pub async fn create_tournament(payload: CreateTournamentPayload) -> Result<Tournament, AppError> {
+    let format = TournamentFormat::from_str(&payload.format)?;
+    if payload.name.trim().is_empty() || payload.name.len() > 64 {
+        return Err(AppError::invalid_field(
+            "name",
+            "Tournament name must have between 1 and 64 characters",
+        ));
+    }
+    let swiss_rounds = match format {
+        TournamentFormat::Swiss => payload.swiss_rounds,
+        _ => None,
+    };
+    tournament::insert_returning(
+        &global::RB,
+        payload.name.trim(),
+        format.as_str(),
+        TournamentStatus::Registration.as_str(),
+        swiss_rounds,
+        &FastDateTime::now(),
+    )
+    .await
+    .map_err(AppError::DatabaseError)
+}
+
+pub async fn get_tournaments() -> Result<Vec<Tournament>, AppError> {
+    Tournament::select_all(&mut global::RB.clone())
+        .await
+        .map_err(AppError::DatabaseError)
+}
+
+pub async fn get_tournament(tournament_id: u32) -> Result<TournamentDetails, AppError> {
+    let tournament = find_tournament(tournament_id).await?;
+    let teams = tournament::select_teams(&global::RB, tournament_id)
+        .await
+        .map_err(AppError::DatabaseError)?;
+    let matches = tournament::select_matches(&mut global::RB.clone(), tournament_id)
+        .await
+        .map_err(AppError::DatabaseError)?;
+    Ok(TournamentDetails {
+        tournament,
+        teams,
+        matches,
+    })
+}
+
+pub async fn register_team(
+    tournament_id: u32,
+    payload: RegisterTeamPayload,
+) -> Result<TournamentTeam, AppError> {
+    find_tournament_in_registration(tournament_id).await?;
+    team::find_team(payload.team_id).await?;
+    let teams = tournament::select_teams(&global::RB, tournament_id)
+        .await
+        .map_err(AppError::DatabaseError)?;
+    if teams.iter().any(|t| t.team_id == payload.team_id) {
+        return Err(AppError::Conflict("Team is already registered".to_string()));
+    }
+    let registered_team = TournamentTeam {
+        tournament_id,
+        team_id: payload.team_id,
+        seed: payload
+            .seed
+            .unwrap_or_else(|| teams.iter().map(|t| t.seed).max().unwrap_or(0) + 1),
+    };
+    TournamentTeam::insert(&mut global::RB.clone(), &registered_team)
+        .await
+        .map_err(AppError::DatabaseError)?;
+    Ok(registered_team)
+}
+
+pub async fn unregister_team(tournament_id: u32, team_id: u32) -> Result<(), AppError> {
+    find_tournament_in_registration(tournament_id).await?;
+    tournament::delete_team(&global::RB, tournament_id, team_id)
+        .await
+        .map_err(AppError::DatabaseError)?;
+    Ok(())
+}
+
+/// Reseeds the tournament, `team_ids` must list every registered team from the first to the last seed
+pub async fn seed_teams(tournament_id: u32, team_ids: Vec<u32>) -> Result<(), AppError> {
+    find_tournament_in_registration(tournament_id).await?;
+    let teams = tournament::select_teams(&global::RB, tournament_id)
+        .await
+        .map_err(AppError::DatabaseError)?;
+    let mut registered: Vec<u32> = teams.iter().map(|t| t.team_id).collect();
+    let mut requested = team_ids.clone();
+    registered.sort_unstable();
+    requested.sort_unstable();
+    if registered != requested {
+        return Err(AppError::BadRequest(
+            "Seeding must contain every registered team exactly once".to_string(),
+        ));
+    }
+
+    let mut tx = begin_tx().await.map_err(AppError::DatabaseError)?;
+    for (index, team_id) in team_ids.into_iter().enumerate() {
+        tournament::update_seed(&mut tx, index as u32 + 1, tournament_id, team_id)
+            .await
+            .map_err(AppError::DatabaseError)?;
+    }
+    commit_tx(tx).await.map_err(AppError::DatabaseError)?;
+    Ok(())
+}
+
+pub async fn start_tournament(tournament_id: u32) -> Result<TournamentDetails, AppError> {
+    let _lock = global::TOURNAMENT_LOCK.lock().await;
+    let tournament = find_tournament_in_registration(tournament_id).await?;
+    let format = TournamentFormat::from_str(&tournament.format)?;
+    let teams: Vec<u32> = tournament::select_teams(&global::RB, tournament_id)
+        .await
+        .map_err(AppError::DatabaseError)?
+        .into_iter()
+        .map(|t| t.team_id)
+        .collect();
+    let min_teams = match format {
+        TournamentFormat::DoubleElimination => 3,
+        _ => 2,
+    };
+    if teams.len() < min_teams {
+        return Err(AppError::Conflict(format!(
+            "At least {} teams are needed to start the tournament",
+            min_teams
+        )));
+    }
+
+    let nodes = match format {
+        TournamentFormat::SingleElimination => bracket::single_elimination(&teams),
+        TournamentFormat::DoubleElimination => bracket::double_elimination(&teams),
+        TournamentFormat::Swiss => swiss_round_nodes(
+            1,
+            &bracket::swiss_standings(&seeds_of(tournament_id).await?, &[]),
+        ),
+    };
+
+    let mut tx = begin_tx().await.map_err(AppError::DatabaseError)?;
+    insert_nodes(&mut tx, tournament_id, &nodes).await?;
+    let swiss_rounds = match format {
+        TournamentFormat::Swiss => Some(
+            tournament
+                .swiss_rounds
+                .unwrap_or_else(|| bracket::default_swiss_rounds(teams.len())),
+        ),
+        _ => None,
+    };
+    tournament::update_started(
+        &mut tx,
+        TournamentStatus::Running.as_str(),
+        swiss_rounds,
+        tournament_id,
+    )
+    .await
+    .map_err(AppError::DatabaseError)?;
+    advance(&mut tx, tournament_id).await?;
+    commit_tx(tx).await.map_err(AppError::DatabaseError)?;
+
+    tracing::info!("Tournament {} started", tournament_id);
+    get_tournament(tournament_id).await
+}
+
+async fn seeds_of(tournament_id: u32) -> Result<Vec<(u32, u32)>, AppError> {
+    Ok(tournament::select_teams(&global::RB, tournament_id)
+        .await
+        .map_err(AppError::DatabaseError)?
+        .into_iter()
+        .map(|t| (t.team_id, t.seed))
+        .collect())
+}
+
+fn swiss_round_nodes(round: u32, standings: &[bracket::SwissStanding]) -> Vec<BracketNode> {
+    let (pairs, bye) = bracket::swiss_pairings(standings);
+    pairs
+        .into_iter()
+        .map(|(team1_id, team2_id)| (Some(team1_id), Some(team2_id)))
+        .chain(bye.map(|team_id| (Some(team_id), None)))
+        .enumerate()
+        .map(|(position, (team1_id, team2_id))| BracketNode {
+            bracket: BracketKind::Swiss,
+            round,
+            position: position as u32,
+            team1_id,
+            team2_id,
+            next: None,
+            loser_next: None,
+        })
+        .collect()
+}
+
+async fn insert_nodes(
+    tx: &mut dyn Executor,
+    tournament_id: u32,
+    nodes: &[BracketNode],
+) -> Result<(), AppError> {
+    let mut ids = Vec::with_capacity(nodes.len());
+    for node in nodes {
+        let id = tournament::insert_match_returning_id(
+            tx,
+            tournament_id,
+            node.bracket.as_str(),
+            node.round,
+            node.position,
+            MatchStatus::Pending.as_str(),
+            node.team1_id,
+            node.team2_id,
+        )
+        .await
+        .map_err(AppError::DatabaseError)?;
+        ids.push(id);
+    }
+    for (node, &id) in nodes.iter().zip(ids.iter()) {
+        if node.next.is_none() && node.loser_next.is_none() {
+            continue;
+        }
+        tournament::update_match_links(
+            tx,
+            node.next.map(|(index, _)| ids[index]),
+            node.next.map(|(_, slot)| slot),
+            node.loser_next.map(|(index, _)| ids[index]),
+            node.loser_next.map(|(_, slot)| slot),
+            id,
+        )
+        .await
+        .map_err(AppError::DatabaseError)?;
+    }
+    Ok(())
+}
+
+fn place_team(matches: &mut [TournamentMatch], target: Option<(u32, u8)>, team_id: Option<u32>) {
+    let (Some((match_id, slot)), Some(team_id)) = (target, team_id) else {
+        return;
+    };
+    if let Some(target) = matches.iter_mut().find(|m| m.id == Some(match_id)) {
+        if slot == 1 {
+            target.team1_id = Some(team_id);
+        } else {
+            target.team2_id = Some(team_id);
+        }
+    }
+}
+
+/// Moves the winner and loser of a resolved match into the slots they were waiting for
+fn propagate(matches: &mut [TournamentMatch], index: usize) {
+    let resolved = matches[index].clone();
+    place_team(
+        matches,
+        resolved.next_match_id.zip(resolved.next_match_slot),
+        resolved.winner_team_id,
+    );
+    place_team(
+        matches,
+        resolved
+            .loser_next_match_id
+            .zip(resolved.loser_next_match_slot),
+        resolved.loser_team_id,
+    );
+}
+
+/// Settles every pending slot whose feeders are resolved, either by scheduling its game match or
+/// by advancing the only team present as a bye. Runs until the bracket stops changing, the caller
+/// commits the transaction.
+async fn advance(tx: &mut dyn Executor, tournament_id: u32) -> Result<(), AppError> {
+    let mut matches = tournament::select_matches(tx, tournament_id)
+        .await
+        .map_err(AppError::DatabaseError)?;
+
+    loop {
+        let mut progressed = false;
+        for index in 0..matches.len() {
+            if matches[index].status != MatchStatus::Pending.as_str() {
+                continue;
+            }
+            let id = matches[index].id;
+            let feeders_resolved = matches
+                .iter()
+                .filter(|m| m.next_match_id == id || m.loser_next_match_id == id)
+                .all(is_resolved);
+            if !feeders_resolved {
+                continue;
+            }
+
+            let current = &mut matches[index];
+            match (current.team1_id, current.team2_id) {
+                (Some(team1_id), Some(team2_id)) => {
+                    let created = game_match::insert_returning(
+                        tx,
+                        team1_id,
+                        team2_id,
+                        None,
+                        &FastDateTime::now(),
+                    )
+                    .await
+                    .map_err(AppError::DatabaseError)?;
+                    current.game_match_id = created.id;
+                    current.status = MatchStatus::Ready.as_str().to_string();
+                }
+                (team1_id, team2_id) => {
+                    current.winner_team_id = team1_id.or(team2_id);
+                    current.status = MatchStatus::Bye.as_str().to_string();
+                    propagate(&mut matches, index);
+                }
+            }
+            progressed = true;
+        }
+        if !progressed {
+            break;
+        }
+    }
+
+    //slots that received a propagated team changed as well, so the whole bracket is written back
+    for tournament_match in matches.iter() {
+        TournamentMatch::update_by_column(tx, tournament_match, "id")
+            .await
+            .map_err(AppError::DatabaseError)?;
+    }
+
+    check_completion(tx, tournament_id, &matches).await
+}
+
+/// Finishes elimination tournaments once the final is resolved and pairs the next swiss round when the current one is over
+async fn check_completion(
+    tx: &mut dyn Executor,
+    tournament_id: u32,
+    matches: &[TournamentMatch],
+) -> Result<(), AppError> {
+    let tournament = tournament::select_by_id(tx, tournament_id)
+        .await
+        .map_err(AppError::DatabaseError)?
+        .ok_or_else(|| AppError::NotFound(format!("Tournament {} not found", tournament_id)))?;
+    if tournament.status != TournamentStatus::Running.as_str() {
+        return Ok(());
+    }
+
+    match TournamentFormat::from_str(&tournament.format)? {
+        TournamentFormat::SingleElimination | TournamentFormat::DoubleElimination => {
+            let Some(last) = matches
+                .iter()
+                .find(|m| m.next_match_id.is_none() && m.loser_next_match_id.is_none())
+            else {
+                return Ok(());
+            };
+            if is_resolved(last) {
+                finish_tournament(tx, tournament_id, last.winner_team_id).await?;
+            }
+        }
+        TournamentFormat::Swiss => {
+            if !matches.iter().all(is_resolved) {
+                return Ok(());
+            }
+            let round = matches.iter().map(|m| m.round).max().unwrap_or(0);
+            let standings =
+                bracket::swiss_standings(&seeds_of(tournament_id).await?, &swiss_results(matches));
+            if round >= tournament.swiss_rounds.unwrap_or(0) {
+                finish_tournament(tx, tournament_id, standings.first().map(|s| s.team_id)).await?;
+                return Ok(());
+            }
+
+            let nodes = swiss_round_nodes(round + 1, &standings);
+            insert_nodes(tx, tournament_id, &nodes).await?;
+            Box::pin(advance(tx, tournament_id)).await?;
+        }
+    }
+    Ok(())
+}
+
+async fn finish_tournament(
+    tx: &mut dyn Executor,
+    tournament_id: u32,
+    winner_team_id: Option<u32>,
+) -> Result<(), AppError> {
+    tournament::update_status(
+        tx,
+        TournamentStatus::Finished.as_str(),
+        winner_team_id,
+        tournament_id,
+    )
+    .await
+    .map_err(AppError::DatabaseError)?;
+    tracing::info!("Tournament {} finished", tournament_id);
+    Ok(())
+}
+
+fn swiss_results(matches: &[TournamentMatch]) -> Vec<SwissResult> {
+    matches
+        .iter()
+        .filter(|m| is_resolved(m))
+        .filter_map(|m| {
+            m.winner_team_id.map(|winner_team_id| SwissResult {
+                winner_team_id,
+                loser_team_id: m.loser_team_id,
+            })
+        })
+        .collect()
+}
+
+pub async fn get_standings(tournament_id: u32) -> Result<Vec<StandingResponse>, AppError> {
+    find_tournament(tournament_id).await?;
+    let matches = tournament::select_matches(&mut global::RB.clone(), tournament_id)
+        .await
+        .map_err(AppError::DatabaseError)?;
+    let standings =
+        bracket::swiss_standings(&seeds_of(tournament_id).await?, &swiss_results(&matches));
+    Ok(standings
+        .into_iter()
+        .map(|s| StandingResponse {
+            team_id: s.team_id,
+            seed: s.seed,
+            wins: s.wins,
+            losses: s.losses,
+            buchholz: s.buchholz,
+        })
+        .collect())
+}
+
+/// Called once a game match has a winner, advances the bracket it belongs to if there is one.
+/// Runs in the transaction recording the winner so a failure leaves the match unfinished.
+pub async fn on_match_finished(
+    tx: &mut dyn Executor,
+    game_match_id: u32,
+    winner_team_id: u32,
+) -> Result<(), AppError> {
+    let _lock = global::TOURNAMENT_LOCK.lock().await;
+    let Some(mut tournament_match) = tournament::select_match_by_game_match(tx, game_match_id)
+        .await
+        .map_err(AppError::DatabaseError)?
+    else {
+        return Ok(());
+    };
+    if tournament_match.status != MatchStatus::Ready.as_str() {
+        return Ok(());
+    }
+
+    if tournament_match.team1_id != Some(winner_team_id)
+        && tournament_match.team2_id != Some(winner_team_id)
+    {
+        return Err(AppError::BadRequest(
+            "The winner doesn't play in this match".to_string(),
+        ));
+    }
+
+    tournament_match.loser_team_id = if tournament_match.team1_id == Some(winner_team_id) {
+        tournament_match.team2_id
+    } else {
+        tournament_match.team1_id
+    };
+    tournament_match.winner_team_id = Some(winner_team_id);
+    tournament_match.status = MatchStatus::Finished.as_str().to_string();
+
+    let tournament_id = tournament_match.tournament_id;
+    let mut matches = tournament::select_matches(tx, tournament_id)
+        .await
+        .map_err(AppError::DatabaseError)?;
+    let index = matches
+        .iter()
+        .position(|m| m.id == tournament_match.id)
+        .ok_or_else(|| AppError::NotFound("Tournament match not found".to_string()))?;
+    matches[index] = tournament_match;
+    propagate(&mut matches, index);
+
+    for tournament_match in matches.iter() {
+        TournamentMatch::update_by_column(tx, tournament_match, "id")
+            .await
+            .map_err(AppError::DatabaseError)?;
+    }
+
+    advance(tx, tournament_id).await
+}
@@ -0,0 +1,319 @@
+use std::str::FromStr;
+use std::time::Duration;
+
+use rbatis::rbdc::datetime::FastDateTime;
+use serde::Serialize;
+
+use crate::config::Config;
+use crate::driver::db::{begin_tx, commit_tx};
+use crate::error::AppError;
+use crate::global;
+use crate::model::trust::{self, SteamTrustCheck, TrustPolicy};
+use crate::routes::trust::UpdateTrustPolicyPayload;
+use crate::service::auth::{query_player_bans, query_steam_user, SteamUser};
+
+/// Outcome of a trust check, ordered from the least to the most severe
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum TrustVerdict {
+    Allow,
+    Flag,
+    Block,
+}
+
+impl TrustVerdict {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TrustVerdict::Allow => "allow",
+            TrustVerdict::Flag => "flag",
+            TrustVerdict::Block => "block",
+        }
+    }
+}
+
+impl FromStr for TrustVerdict {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "allow" => Ok(TrustVerdict::Allow),
+            "flag" => Ok(TrustVerdict::Flag),
+            "block" => Ok(TrustVerdict::Block),
+            _ => Err(AppError::BadRequest(format!("Invalid trust action {}", s))),
+        }
+    }
+}
+
+/// Steam's `communityvisibilitystate` for public profiles
+const PUBLIC_PROFILE: i32 = 3;
+
+pub async fn get_policy() -> Result<TrustPolicy, AppError> {
+    trust::select_policy(&global::RB)
+        .await
+        .map_err(AppError::DatabaseError)?
+        .ok_or_else(|| AppError::NotFound("Trust policy not found".to_string()))
+}
+
+pub async fn update_policy(
+    payload: UpdateTrustPolicyPayload,
+    steamid64: String,
+) -> Result<TrustPolicy, AppError> {
+    for action in [
+        &payload.vac_ban_action,
+        &payload.game_ban_action,
+        &payload.community_ban_action,
+        &payload.economy_ban_action,
+        &payload.private_profile_action,
+        &payload.new_account_action,
+    ] {
+        TrustVerdict::from_str(action)?;
+    }
+    trust::update_policy(
+        &global::RB,
+        &payload.vac_ban_action,
+        &payload.game_ban_action,
+        &payload.community_ban_action,
+        &payload.economy_ban_action,
+        &payload.private_profile_action,
+        &payload.new_account_action,
+        payload.min_account_age_days,
+        payload.ignore_bans_older_than_days,
+        &steamid64,
+        &FastDateTime::now(),
+    )
+    .await
+    .map_err(AppError::DatabaseError)?;
+    tracing::info!("Trust policy updated by {}", steamid64);
+    get_policy().await
+}
+
+/// Applies the policy to a check, the verdict is the most severe action of the matching rules
+fn evaluate(
+    check: &SteamTrustCheck,
+    policy: &TrustPolicy,
+) -> Result<(TrustVerdict, Vec<&'static str>), AppError> {
+    let recent_ban = policy
+        .ignore_bans_older_than_days
+        .is_none_or(|days| check.days_since_last_ban <= days);
+    let account_age_days = check
+        .time_created
+        .map(|time_created| (FastDateTime::now().unix_timestamp() - time_created) / 86400);
+
+    let rules = [
+        (
+            "vac_ban",
+            &policy.vac_ban_action,
+            check.vac_banned && recent_ban,
+        ),
+        (
+            "game_ban",
+            &policy.game_ban_action,
+            check.number_of_game_bans > 0 && recent_ban,
+        ),
+        (
+            "community_ban",
+            &policy.community_ban_action,
+            check.community_banned,
+        ),
+        (
+            "economy_ban",
+            &policy.economy_ban_action,
+            check.economy_ban != "none",
+        ),
+        (
+            "private_profile",
+            &policy.private_profile_action,
+            !check.public_profile,
+        ),
+        (
+            "new_account",
+            &policy.new_account_action,
+            account_age_days.is_some_and(|days| days < policy.min_account_age_days as i64),
+        ),
+    ];
+
+    let mut verdict = TrustVerdict::Allow;
+    let mut reasons = Vec::new();
+    for (reason, action, matched) in rules {
+        let action = TrustVerdict::from_str(action)?;
+        if matched && action != TrustVerdict::Allow {
+            verdict = verdict.max(action);
+            reasons.push(reason);
+        }
+    }
+    Ok((verdict, reasons))
+}
+
+/// Looks up the Steam bans of a user, reusing the cached result while it's fresh, and stores the verdict
+pub async fn check_steam_user(
+    config: &Config,
+    steam_user: &SteamUser,
+    force: bool,
+) -> Result<SteamTrustCheck, AppError> {
+    let now = FastDateTime::now();
+    let cache_duration = Duration::from_secs(config.steam.trust_cache_secs);
+    let cached = trust::select_check(&global::RB, &steam_user.steamid)
+        .await
+        .map_err(AppError::DatabaseError)?
+        .filter(|check| !force && (check.checked_at.clone() + cache_duration).0 > now.0);
+
+    let mut check = match cached {
+        Some(check) => check,
+        None => {
+            let bans = query_player_bans(config, &steam_user.steamid).await?;
+            SteamTrustCheck {
+                steamid64: steam_user.steamid.clone(),
+                vac_banned: bans.vac_banned,
+                number_of_vac_bans: bans.number_of_vac_bans,
+                number_of_game_bans: bans.number_of_game_bans,
+                days_since_last_ban: bans.days_since_last_ban,
+                community_banned: bans.community_banned,
+                economy_ban: bans.economy_ban,
+                time_created: None,
+                public_profile: false,
+                verdict: TrustVerdict::Allow.as_str().to_string(),
+                reasons: String::new(),
+                checked_at: now,
+            }
+        }
+    };
+    check.time_created = steam_user.timecreated;
+    check.public_profile = steam_user.communityvisibilitystate == PUBLIC_PROFILE;
+
+    //the policy may have changed since the last check, so the verdict is always recomputed
+    let policy = get_policy().await?;
+    let (verdict, reasons) = evaluate(&check, &policy)?;
+    check.verdict = verdict.as_str().to_string();
+    check.reasons = reasons.join(",");
+
+    let mut tx = begin_tx().await.map_err(AppError::DatabaseError)?;
+    SteamTrustCheck::delete_by_column(&mut tx, "steamid64", &check.steamid64)
+        .await
+        .map_err(AppError::DatabaseError)?;
+    SteamTrustCheck::insert(&mut tx, &check)
+        .await
+        .map_err(AppError::DatabaseError)?;
+    commit_tx(tx).await.map_err(AppError::DatabaseError)?;
+    Ok(check)
+}
+
+/// Fetches the Steam profile again and bypasses the cache
+pub async fn refresh_check(
+    config: &Config,
+    steamid64: String,
+) -> Result<SteamTrustCheck, AppError> {
+    let steamid = steamid64
+        .parse::<u64>()
+        .map_err(|_| AppError::BadRequest(format!("Invalid steamid64 {}", steamid64)))?;
+    let steam_user = query_steam_user(config, steamid).await?;
+    check_steam_user(config, &steam_user, true).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clean_check() -> SteamTrustCheck {
+        SteamTrustCheck {
+            steamid64: "76561190000000001".to_string(),
+            vac_banned: false,
+            number_of_vac_bans: 0,
+            number_of_game_bans: 0,
+            days_since_last_ban: 0,
+            community_banned: false,
+            economy_ban: "none".to_string(),
+            time_created: Some(FastDateTime::now().unix_timestamp() - 365 * 86400),
+            public_profile: true,
+            verdict: TrustVerdict::Allow.as_str().to_string(),
+            reasons: String::new(),
+            checked_at: FastDateTime::now(),
+        }
+    }
+
+    fn policy() -> TrustPolicy {
+        TrustPolicy {
+            id: 1,
+            vac_ban_action: "block".to_string(),
+            game_ban_action: "flag".to_string(),
+            community_ban_action: "allow".to_string(),
+            economy_ban_action: "flag".to_string(),
+            private_profile_action: "flag".to_string(),
+            new_account_action: "block".to_string(),
+            min_account_age_days: 30,
+            ignore_bans_older_than_days: Some(365),
+            updated_by: None,
+            updated_at: FastDateTime::now(),
+        }
+    }
+
+    #[test]
+    fn clean_accounts_are_allowed() {
+        let (verdict, reasons) = evaluate(&clean_check(), &policy()).unwrap();
+        assert!(verdict == TrustVerdict::Allow);
+        assert!(reasons.is_empty());
+    }
+
+    #[test]
+    fn the_most_severe_matching_rule_wins() {
+        let mut check = clean_check();
+        check.number_of_game_bans = 1;
+        check.public_profile = false;
+        check.vac_banned = true;
+        let (verdict, reasons) = evaluate(&check, &policy()).unwrap();
+        assert!(verdict == TrustVerdict::Block);
+        assert_eq!(reasons, ["vac_ban", "game_ban", "private_profile"]);
+    }
+
+    #[test]
+    fn allowed_rules_are_not_reported() {
+        let mut check = clean_check();
+        check.community_banned = true;
+        let (verdict, reasons) = evaluate(&check, &policy()).unwrap();
+        assert!(verdict == TrustVerdict::Allow);
+        assert!(reasons.is_empty());
+    }
+
+    #[test]
+    fn bans_past_the_grace_period_are_ignored() {
+        let mut check = clean_check();
+        check.vac_banned = true;
+        check.days_since_last_ban = 365;
+        let (verdict, _) = evaluate(&check, &policy()).unwrap();
+        assert!(verdict == TrustVerdict::Block);
+
+        check.days_since_last_ban = 366;
+        let (verdict, _) = evaluate(&check, &policy()).unwrap();
+        assert!(verdict == TrustVerdict::Allow);
+
+        let mut policy = policy();
+        policy.ignore_bans_older_than_days = None;
+        let (verdict, _) = evaluate(&check, &policy).unwrap();
+        assert!(verdict == TrustVerdict::Block);
+    }
+
+    #[test]
+    fn accounts_younger_than_the_minimum_age_are_new() {
+        let now = FastDateTime::now().unix_timestamp();
+        let mut check = clean_check();
+        check.time_created = Some(now - 29 * 86400);
+        let (verdict, reasons) = evaluate(&check, &policy()).unwrap();
+        assert!(verdict == TrustVerdict::Block);
+        assert_eq!(reasons, ["new_account"]);
+
+        check.time_created = Some(now - 30 * 86400);
+        let (verdict, _) = evaluate(&check, &policy()).unwrap();
+        assert!(verdict == TrustVerdict::Allow);
+
+        //private profiles hide their creation date
+        check.time_created = None;
+        let (verdict, _) = evaluate(&check, &policy()).unwrap();
+        assert!(verdict == TrustVerdict::Allow);
+    }
+
+    #[test]
+    fn unknown_actions_are_rejected() {
+        let mut policy = policy();
+        policy.economy_ban_action = "kick".to_string();
+        assert!(evaluate(&clean_check(), &policy).is_err());
+    }
+}
@@ -0,0 +1,61 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::error::AppError;
+use crate::global;
+use crate::model::trust::SteamTrustCheck;
+use crate::model::user::{self, User};
+use crate::routes::user::ListUsersQuery;
+
+/// A user as listed to the admins, with the verdict of the last Steam trust check
+#[derive(Serialize)]
+pub struct UserWithTrust {
+    #[serde(flatten)]
+    pub user: User,
+    pub trust: Option<SteamTrustCheck>,
+}
+
+pub async fn find_user(steamid64: &str) -> Result<User, AppError> {
+    user::select_by_steamid(&global::RB, steamid64.to_string())
+        .await
+        .map_err(AppError::DatabaseError)?
+        .ok_or_else(|| AppError::NotFound(format!("User {} not found", steamid64)))
+}
+
+/// Casters are admitted on every match server as spectators
+pub async fn set_caster(steamid64: String, is_caster: bool) -> Result<User, AppError> {
+    find_user(&steamid64).await?;
+    user::update_caster(&global::RB, is_caster, &steamid64)
+        .await
+        .map_err(AppError::DatabaseError)?;
+    find_user(&steamid64).await
+}
+
+pub async fn get_users(query: ListUsersQuery) -> Result<Vec<UserWithTrust>, AppError> {
+    let users = User::select_all(&mut global::RB.clone())
+        .await
+        .map_err(AppError::DatabaseError)?;
+    let mut checks: HashMap<String, SteamTrustCheck> =
+        SteamTrustCheck::select_all(&mut global::RB.clone())
+            .await
+            .map_err(AppError::DatabaseError)?
+            .into_iter()
+            .map(|check| (check.steamid64.clone(), check))
+            .collect();
+
+    Ok(users
+        .into_iter()
+        .map(|user| {
+            let trust = checks.remove(&user.steamid64);
+            UserWithTrust { user, trust }
+        })
+        .filter(|user| {
+            query.verdict.as_ref().is_none_or(|verdict| {
+                user.trust
+                    .as_ref()
+                    .is_some_and(|trust| trust.verdict == *verdict)
+            })
+        })
+        .collect())
+}
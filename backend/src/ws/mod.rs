@@ -1,2 +1,13 @@
+use axum::extract::WebSocketUpgrade;
+
+use crate::config::Config;
+
 pub mod server;
+pub mod spectator;
 pub mod user;
+
+/// Applies the configured message and frame size limits to an incoming websocket
+pub fn with_limits(ws: WebSocketUpgrade, config: &Config) -> WebSocketUpgrade {
+    ws.max_message_size(config.websocket.max_message_size)
+        .max_frame_size(config.websocket.max_frame_size)
+}
@@ -1,4 +1,5 @@
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
 
 use axum::{
     body::Body,
@@ -8,32 +9,46 @@ use axum::{
     },
     http::Request,
     response::IntoResponse,
+    Extension,
 };
 use futures_util::{FutureExt, StreamExt};
 
-use crate::{error::AppError, model::server};
+use crate::config::Config;
+use crate::driver::inet::InetAddr;
+use crate::metrics::{self, Direction, Peer};
+use crate::service::admission::{self, PlayerConnecting};
+use crate::service::ban;
+use crate::service::command_queue::{self, SequenceAck};
+use crate::service::config_template;
+use crate::service::enrollment::{self, Enrolled, EnrollmentDecision};
+use crate::service::game_match;
+use crate::service::round_backup::{self, RoundBackupUpload};
+use crate::service::server_command::{self, CommandAck};
+use crate::ws::spectator::{self, LiveMatch};
+use crate::{error::AppError, model::enrollment::PendingServer, model::server};
 use serde::{Deserialize, Serialize};
-use tokio::sync::{mpsc, RwLock};
+use tokio::sync::{mpsc, oneshot, RwLock};
 use tokio_stream::wrappers::UnboundedReceiverStream;
 
-use crate::global::ONLINE_SERVERS;
+use crate::global::{self, ONLINE_SERVERS, PENDING_ENROLLMENTS};
 pub type ServerList = RwLock<Vec<ConnectedServer>>;
 
-#[derive(Serialize, Deserialize, Clone, Copy)]
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
 pub enum ServerStatus {
     Idle,
     WaitingForPlayers,
     Starting,
     KnifeRound,
     Live,
+    Overtime,
     Ending,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct ConnectedServer {
     pub id: u32,
-    pub ip: String,
-    pub port: String,
+    pub ip: IpAddr,
+    pub port: u16,
     pub status: ServerStatus,
     #[serde(skip, default = "default_conn")]
     pub conn: mpsc::UnboundedSender<Result<Message, axum::Error>>,
@@ -43,6 +58,51 @@ pub struct ConnectedServer {
 enum ServerMessageData {
     #[serde(rename = "status")]
     Status(ServerStatus),
+    #[serde(rename = "match_update")]
+    MatchUpdate(LiveMatch),
+    #[serde(rename = "player_connecting")]
+    PlayerConnecting(PlayerConnecting),
+    #[serde(rename = "command_ack")]
+    CommandAck(CommandAck),
+    #[serde(rename = "ack")]
+    Ack(SequenceAck),
+    #[serde(rename = "round_backup")]
+    RoundBackup(RoundBackupUpload),
+}
+
+#[derive(Serialize, Deserialize)]
+pub enum BackendAction {
+    #[serde(rename = "backend_2_server_load_match")]
+    Backend2ServerLoadMatch,
+    #[serde(rename = "backend_2_server_cancel_match")]
+    Backend2ServerCancelMatch,
+    #[serde(rename = "backend_2_server_player_admission")]
+    Backend2ServerPlayerAdmission,
+    #[serde(rename = "backend_2_server_ban")]
+    Backend2ServerBan,
+    #[serde(rename = "backend_2_server_unban")]
+    Backend2ServerUnban,
+    #[serde(rename = "backend_2_server_sync_bans")]
+    Backend2ServerSyncBans,
+    #[serde(rename = "backend_2_server_exec_config")]
+    Backend2ServerExecConfig,
+    #[serde(rename = "backend_2_server_command")]
+    Backend2ServerCommand,
+    #[serde(rename = "backend_2_server_restore_round")]
+    Backend2ServerRestoreRound,
+    #[serde(rename = "backend_2_server_enrolled")]
+    Backend2ServerEnrolled,
+    #[serde(rename = "backend_shutting_down")]
+    BackendShuttingDown,
+}
+
+#[derive(Serialize)]
+struct BackendMessage<T: Serialize> {
+    action: BackendAction,
+    data: T,
+    /// Set on messages of the command queue, the server acks it with `server_2_backend_ack`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seq: Option<u32>,
 }
 
 fn default_conn() -> mpsc::UnboundedSender<Result<Message, axum::Error>> {
@@ -54,36 +114,231 @@ pub async fn get_online_servers() -> Vec<ConnectedServer> {
     ONLINE_SERVERS.read().await.to_vec()
 }
 
+/// Returns false if the server isn't connected or the message couldn't be queued
+pub async fn send_message_to_server<T: Serialize>(
+    server_id: u32,
+    action: BackendAction,
+    data: T,
+) -> bool {
+    send_to_server(
+        server_id,
+        BackendMessage {
+            action,
+            data,
+            seq: None,
+        },
+    )
+    .await
+}
+
+/// Like `send_message_to_server` for a message of the command queue
+pub async fn send_sequenced_to_server<T: Serialize>(
+    server_id: u32,
+    action: BackendAction,
+    seq: u32,
+    data: T,
+) -> bool {
+    send_to_server(
+        server_id,
+        BackendMessage {
+            action,
+            data,
+            seq: Some(seq),
+        },
+    )
+    .await
+}
+
+async fn send_to_server<T: Serialize>(server_id: u32, message: BackendMessage<T>) -> bool {
+    metrics::count_ws_message(Peer::Server, Direction::Out, &message.action);
+    let message = match serde_json::to_string(&message) {
+        Ok(message) => message,
+        Err(e) => {
+            tracing::error!("Couldn't serialize BackendMessage json {}", e);
+            return false;
+        }
+    };
+    let online_servers = ONLINE_SERVERS.read().await;
+    match online_servers.iter().find(|server| server.id == server_id) {
+        Some(server) => server
+            .conn
+            .send(Ok(Message::Text(message)))
+            .map_err(|e| tracing::error!(error = ?e, "Error while sending message to server"))
+            .is_ok(),
+        None => false,
+    }
+}
+
+/// Sends a close frame to every server, each connection is dropped once its handler returns
+pub async fn close_server_connections() {
+    for server in ONLINE_SERVERS.read().await.iter() {
+        server.conn.send(Ok(Message::Close(None))).ok();
+    }
+}
+
+pub async fn broadcast_to_servers<T: Serialize>(action: BackendAction, data: T) {
+    metrics::count_ws_message(Peer::Server, Direction::Out, &action);
+    let message = match serde_json::to_string(&BackendMessage {
+        action,
+        data,
+        seq: None,
+    }) {
+        Ok(message) => message,
+        Err(e) => {
+            tracing::error!("Couldn't serialize BackendMessage json {}", e);
+            return;
+        }
+    };
+    for server in ONLINE_SERVERS.read().await.iter() {
+        server
+            .conn
+            .send(Ok(Message::Text(message.clone())))
+            .map_err(|e| tracing::error!(error = ?e, "Error while sending message to server"))
+            .ok();
+    }
+}
+
+/// Registered servers connect as themselves, unknown ones presenting an `Enrollment-Token` wait
+/// for an admin on the same connection
+pub enum ServerAdmission {
+    Registered(server::Server),
+    Pending(PendingServer),
+}
+
 pub async fn on_server_connection(
     ConnectInfo(addr): ConnectInfo<SocketAddr>,
     ws: WebSocketUpgrade,
+    Extension(config): Extension<&'static Config>,
     req: Request<Body>,
 ) -> Result<impl IntoResponse, AppError> {
-    let data = authorize_server_connection(req, addr).await?;
-    Ok(ws.on_upgrade(move |ws| handle_server_connection(ws, data)))
+    if !config.is_server_allowed(&addr.ip()) {
+        tracing::info!(
+            "Server outside of the allowlist tried to connect: {}",
+            addr.ip()
+        );
+        return Err(AppError::Unauthorized);
+    }
+    let admission = authorize_server_connection(req, addr).await?;
+    Ok(
+        super::with_limits(ws, config).on_upgrade(move |ws| async move {
+            match admission {
+                ServerAdmission::Registered(server) => {
+                    handle_server_connection(ws, config, server).await
+                }
+                ServerAdmission::Pending(pending) => {
+                    handle_pending_connection(ws, config, pending).await
+                }
+            }
+        }),
+    )
 }
 
 pub async fn authorize_server_connection(
     req: Request<Body>,
     addr: SocketAddr,
-) -> Result<server::Server, AppError> {
-    let port: String = req
-        .headers()
-        .get("PORT")
-        .map(|p| p.to_str().unwrap().to_string())
+) -> Result<ServerAdmission, AppError> {
+    let header = |name: &str| {
+        req.headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+    };
+    let port: u16 = header("PORT")
+        .and_then(|port| port.trim().parse().ok())
         .ok_or(AppError::Unauthorized)?;
-    let found_server =
-        server::select_by_full_ip(&mut crate::global::RB.clone(), addr.ip().to_string(), port)
-            .await
-            .map_err(|e| AppError::DatabaseError(e))?
-            .ok_or({
-                tracing::info!("Unauthorized server tried to connect: {}", addr.ip());
-                AppError::Unauthorized
-            })?;
-    Ok(found_server)
+    //IPv4 clients of a dual-stack listener show up as IPv4-mapped IPv6
+    let ip = InetAddr::new(addr.ip());
+    let found_server = server::select_by_address(&global::RB, &ip, port)
+        .await
+        .map_err(AppError::DatabaseError)?;
+    if let Some(found_server) = found_server {
+        let credential = header("Authorization").and_then(|value| value.strip_prefix("Bearer "));
+        enrollment::verify_credential(found_server.id.unwrap(), credential).await?;
+        return Ok(ServerAdmission::Registered(found_server));
+    }
+    match header("Enrollment-Token") {
+        Some(token) => Ok(ServerAdmission::Pending(
+            enrollment::enroll(ip, port, token).await?,
+        )),
+        None => {
+            tracing::info!("Unauthorized server tried to connect: {}", ip);
+            Err(AppError::Unauthorized)
+        }
+    }
 }
 
-pub async fn handle_server_connection(ws: WebSocket, connected_server: server::Server) {
+/// Holds the connection until an admin decides. Approved servers get their credential and carry
+/// on as registered servers, rejected ones and the ones idle for
+/// `websocket.pending_idle_timeout_secs` are closed.
+async fn handle_pending_connection(
+    mut ws: WebSocket,
+    config: &'static Config,
+    pending: PendingServer,
+) {
+    let idle_timeout = Duration::from_secs(config.websocket.pending_idle_timeout_secs);
+    let (tx, mut rx) = oneshot::channel();
+    //a server enrolling again replaces its previous connection, which is closed
+    PENDING_ENROLLMENTS.lock().await.insert(pending.id, tx);
+    let mut shutdown = global::SHUTDOWN.subscribe();
+    let decision = loop {
+        if shutdown.borrow_and_update().is_some() {
+            break None;
+        }
+        tokio::select! {
+            decision = &mut rx => break decision.ok(),
+            msg = tokio::time::timeout(idle_timeout, ws.next()) => match msg {
+                Ok(Some(Ok(Message::Close(_)))) | Ok(Some(Err(_))) | Ok(None) => break None,
+                //nothing is handled before the approval
+                Ok(Some(Ok(_))) => {}
+                Err(_) => {
+                    tracing::info!("Pending server {} was idle for too long", pending.id);
+                    break None;
+                }
+            },
+            _ = shutdown.changed() => break None,
+        }
+    };
+    drop(rx);
+    PENDING_ENROLLMENTS
+        .lock()
+        .await
+        .retain(|_, waiting| !waiting.is_closed());
+
+    match decision {
+        Some(EnrollmentDecision::Approved { server, credential }) => {
+            let action = BackendAction::Backend2ServerEnrolled;
+            metrics::count_ws_message(Peer::Server, Direction::Out, &action);
+            let message = BackendMessage {
+                action,
+                data: Enrolled {
+                    server_id: server.id.unwrap(),
+                    credential: &credential,
+                },
+                seq: None,
+            };
+            let message = match serde_json::to_string(&message) {
+                Ok(message) => message,
+                Err(e) => {
+                    tracing::error!("Couldn't serialize BackendMessage json {}", e);
+                    return;
+                }
+            };
+            if let Err(e) = ws.send(Message::Text(message)).await {
+                tracing::error!(error = ?e, "Couldn't send the credential of server {}", server.ip);
+                return;
+            }
+            handle_server_connection(ws, config, server).await;
+        }
+        Some(EnrollmentDecision::Rejected) | None => {
+            ws.send(Message::Close(None)).await.ok();
+        }
+    }
+}
+
+pub async fn handle_server_connection(
+    ws: WebSocket,
+    config: &'static Config,
+    connected_server: server::Server,
+) {
     //adds the server to the list
     let (server_ws_tx, mut server_ws_rx) = ws.split();
 
@@ -97,13 +352,20 @@ pub async fn handle_server_connection(ws: WebSocket, connected_server: server::S
     tokio::task::spawn(fut);
     let _connected_server = connected_server.clone();
     let fut = async move {
-        ONLINE_SERVERS.write().await.push(ConnectedServer {
+        let conn = tx.clone();
+        register_server(ConnectedServer {
             id: _connected_server.id.unwrap(),
-            ip: _connected_server.ip.clone(),
-            port: _connected_server.port.clone(),
+            ip: _connected_server.ip.ip(),
+            port: _connected_server.port,
             status: ServerStatus::Idle,
             conn: tx,
-        });
+        })
+        .await;
+        sync_bans(_connected_server.id.unwrap()).await;
+        command_queue::replay(config, _connected_server.id.unwrap())
+            .await
+            .map_err(|e| tracing::error!(error = ?e, "Couldn't replay the queued commands"))
+            .ok();
 
         while let Some(result) = server_ws_rx.next().await {
             let msg = match result {
@@ -125,15 +387,26 @@ pub async fn handle_server_connection(ws: WebSocket, connected_server: server::S
                 .ok();
         }
 
-        on_server_disconnected(&_connected_server).await;
+        on_server_disconnected(&_connected_server, &conn).await;
     };
     tokio::task::spawn(fut);
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize)]
+#[allow(clippy::enum_variant_names)]
 enum ServerAction {
     #[serde(rename = "server_2_backend_update_status")]
     Server2BackendUpdateStatus,
+    #[serde(rename = "server_2_backend_match_update")]
+    Server2BackendMatchUpdate,
+    #[serde(rename = "server_2_backend_player_connecting")]
+    Server2BackendPlayerConnecting,
+    #[serde(rename = "server_2_backend_command_ack")]
+    Server2BackendCommandAck,
+    #[serde(rename = "server_2_backend_ack")]
+    Server2BackendAck,
+    #[serde(rename = "server_2_backend_round_backup")]
+    Server2BackendRoundBackup,
 }
 #[derive(Deserialize)]
 struct ServerMessage {
@@ -143,29 +416,160 @@ struct ServerMessage {
 
 async fn on_server_message(server_data: &server::Server, msg: Message) -> anyhow::Result<()> {
     let msg = msg.to_text()?;
-    let parsed_msg: ServerMessage = serde_json::from_str(msg)?;
+    let parsed_msg: ServerMessage = serde_json::from_str(msg).inspect_err(|_| {
+        metrics::count_ws_message(Peer::Server, Direction::In, &"invalid");
+    })?;
+    metrics::count_ws_message(Peer::Server, Direction::In, &parsed_msg.action);
 
     match parsed_msg.action {
         ServerAction::Server2BackendUpdateStatus => {
             let status = match parsed_msg.data {
                 ServerMessageData::Status(status) => status,
+                _ => return Err(anyhow::anyhow!("Invalid data for status update")),
             };
-            for server in ONLINE_SERVERS.write().await.iter_mut() {
-                if server.ip == server_data.ip && server.port == server_data.port {
-                    server.status = status;
+            set_server_status(server_data, status).await;
+        }
+        ServerAction::Server2BackendMatchUpdate => {
+            let live_match = match parsed_msg.data {
+                ServerMessageData::MatchUpdate(live_match) => live_match,
+                _ => return Err(anyhow::anyhow!("Invalid data for match update")),
+            };
+            game_match::ensure_hosted_by(server_data.id.unwrap(), live_match.match_id)
+                .await
+                .map_err(|e| anyhow::anyhow!("Refused the match update: {:?}", e))?;
+            set_server_status(server_data, live_match.status).await;
+            let finished = match (live_match.status, live_match.winner_team_id) {
+                (ServerStatus::Ending, Some(winner_team_id)) => {
+                    Some((live_match.match_id, winner_team_id))
                 }
+                _ => None,
+            };
+            spectator::publish_match_update(live_match).await;
+            if let Some((match_id, winner_team_id)) = finished {
+                game_match::finish_match(match_id, winner_team_id)
+                    .await
+                    .map_err(|_| {
+                        anyhow::anyhow!("Couldn't record the result of match {}", match_id)
+                    })?;
             }
         }
+        ServerAction::Server2BackendPlayerConnecting => {
+            let player = match parsed_msg.data {
+                ServerMessageData::PlayerConnecting(player) => player,
+                _ => return Err(anyhow::anyhow!("Invalid data for player connecting")),
+            };
+            let server_id = server_data.id.unwrap();
+            let admission = admission::admit_player(server_id, player)
+                .await
+                .map_err(|e| anyhow::anyhow!("Couldn't decide the player admission: {:?}", e))?;
+            send_message_to_server(
+                server_id,
+                BackendAction::Backend2ServerPlayerAdmission,
+                admission,
+            )
+            .await;
+        }
+        ServerAction::Server2BackendCommandAck => {
+            let ack = match parsed_msg.data {
+                ServerMessageData::CommandAck(ack) => ack,
+                _ => return Err(anyhow::anyhow!("Invalid data for command ack")),
+            };
+            server_command::acknowledge(server_data.id.unwrap(), ack).await;
+        }
+        ServerAction::Server2BackendAck => {
+            let ack = match parsed_msg.data {
+                ServerMessageData::Ack(ack) => ack,
+                _ => return Err(anyhow::anyhow!("Invalid data for ack")),
+            };
+            command_queue::acknowledge(server_data.id.unwrap(), ack.seq)
+                .await
+                .map_err(|e| {
+                    anyhow::anyhow!("Couldn't acknowledge command {}: {:?}", ack.seq, e)
+                })?;
+        }
+        ServerAction::Server2BackendRoundBackup => {
+            let upload = match parsed_msg.data {
+                ServerMessageData::RoundBackup(upload) => upload,
+                _ => return Err(anyhow::anyhow!("Invalid data for round backup")),
+            };
+            let (match_id, round) = (upload.match_id, upload.round);
+            round_backup::store_backup(server_data.id.unwrap(), upload)
+                .await
+                .map_err(|e| {
+                    anyhow::anyhow!(
+                        "Couldn't store the round {} backup of match {}: {:?}",
+                        round,
+                        match_id,
+                        e
+                    )
+                })?;
+        }
     }
     Ok(())
 }
 
-async fn on_server_disconnected(server_data: &server::Server) {
-    tracing::info!("Server {} disconnected", server_data.ip);
-    ONLINE_SERVERS.write().await.retain(|server| {
-        if server.ip == server_data.ip && server.port == server_data.port {
-            return false;
+async fn sync_bans(server_id: u32) {
+    match ban::get_active_bans_for_server(server_id).await {
+        Ok(bans) => {
+            send_message_to_server(server_id, BackendAction::Backend2ServerSyncBans, bans).await;
         }
-        true
-    });
+        Err(e) => {
+            tracing::error!(error = ?e, "Couldn't load the active bans of server {}", server_id)
+        }
+    }
+}
+
+/// Pushes the config template of the new phase when the status changed
+async fn set_server_status(server_data: &server::Server, status: ServerStatus) {
+    let mut changed = false;
+    for server in ONLINE_SERVERS.write().await.iter_mut() {
+        if server.id == server_data.id.unwrap() {
+            changed |= server.status != status;
+            server.status = status;
+        }
+    }
+    if changed {
+        config_template::push_for_status(server_data, status)
+            .await
+            .map_err(|e| {
+                tracing::error!(error = ?e, "Couldn't push the config of server {}", server_data.ip)
+            })
+            .ok();
+    }
+}
+
+/// A server reconnecting before its previous connection was noticed as closed replaces it
+async fn register_server(connected_server: ConnectedServer) {
+    let mut online_servers = ONLINE_SERVERS.write().await;
+    if let Some(index) = online_servers
+        .iter()
+        .position(|server| server.id == connected_server.id)
+    {
+        tracing::info!(
+            "Server {} reconnected, closing its previous connection",
+            connected_server.id
+        );
+        let replaced = online_servers.remove(index);
+        replaced.conn.send(Ok(Message::Close(None))).ok();
+        //the commands sent on the previous connection won't be acked anymore
+        server_command::fail_pending_commands(connected_server.id).await;
+    }
+    online_servers.push(connected_server);
+}
+
+/// Only removes the entry of this connection, a newer connection of the same server stays online
+async fn on_server_disconnected(
+    server_data: &server::Server,
+    conn: &mpsc::UnboundedSender<Result<Message, axum::Error>>,
+) {
+    tracing::info!("Server {} disconnected", server_data.ip);
+    let removed = {
+        let mut online_servers = ONLINE_SERVERS.write().await;
+        let count = online_servers.len();
+        online_servers.retain(|server| !server.conn.same_channel(conn));
+        online_servers.len() < count
+    };
+    if removed {
+        server_command::fail_pending_commands(server_data.id.unwrap()).await;
+    }
 }
@@ -0,0 +1,211 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use axum::{
+    extract::{
+        ws::{Message, WebSocket},
+        Path, WebSocketUpgrade,
+    },
+    response::IntoResponse,
+    Extension,
+};
+use futures_util::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{watch, RwLock};
+
+use crate::config::Config;
+use crate::error::AppError;
+use crate::global::LIVE_MATCHES;
+use crate::ws::server::ServerStatus;
+
+pub type MatchFeedList = RwLock<HashMap<u32, watch::Sender<Option<LiveMatch>>>>;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct LivePlayer {
+    pub steamid64: String,
+    pub name: String,
+    pub kills: u32,
+    pub deaths: u32,
+}
+
+/// State of a running match as reported by the game server
+#[derive(Serialize, Deserialize, Clone)]
+pub struct LiveMatch {
+    pub match_id: u32,
+    pub status: ServerStatus,
+    pub round: u32,
+    pub team1_score: u32,
+    pub team2_score: u32,
+    pub players: Vec<LivePlayer>,
+    /// Reported together with `ServerStatus::Ending` once the match is decided
+    #[serde(default)]
+    pub winner_team_id: Option<u32>,
+}
+
+#[derive(Serialize)]
+struct SpectatorMessage<'a> {
+    action: &'a str,
+    data: &'a LiveMatch,
+}
+
+pub async fn on_spectator_connection(
+    ws: WebSocketUpgrade,
+    Path(match_id): Path<u32>,
+    Extension(config): Extension<&'static Config>,
+) -> Result<impl IntoResponse, AppError> {
+    let max_spectators = config.websocket.max_spectators_per_match;
+    let spectators = LIVE_MATCHES
+        .read()
+        .await
+        .get(&match_id)
+        .map_or(0, |feed| feed.receiver_count());
+    if max_spectators > 0 && spectators >= max_spectators {
+        return Err(AppError::TooManyRequests(format!(
+            "Match {} has too many spectators",
+            match_id
+        )));
+    }
+    let throttle = Duration::from_millis(config.websocket.spectator_throttle_ms);
+    Ok(super::with_limits(ws, config)
+        .on_upgrade(move |ws| handle_spectator_connection(ws, match_id, throttle)))
+}
+
+/// Returns a receiver for the match feed, creating an empty feed if the match hasn't reported anything yet
+async fn subscribe(match_id: u32) -> watch::Receiver<Option<LiveMatch>> {
+    LIVE_MATCHES
+        .write()
+        .await
+        .entry(match_id)
+        .or_insert_with(|| watch::channel(None).0)
+        .subscribe()
+}
+
+/// Called whenever a game server reports a match update, wakes up every spectator of that match.
+/// The reporting server was checked to host the match.
+pub async fn publish_match_update(live_match: LiveMatch) {
+    let match_id = live_match.match_id;
+    let mut matches = LIVE_MATCHES.write().await;
+    match matches.get(&match_id) {
+        Some(feed) => {
+            feed.send_replace(Some(live_match));
+            if is_over(feed) {
+                matches.remove(&match_id);
+            }
+        }
+        //nobody is left to see the end of the match
+        None if live_match.status == ServerStatus::Ending => {}
+        None => {
+            matches.insert(match_id, watch::channel(Some(live_match)).0);
+        }
+    }
+}
+
+/// Called once the result of a match is recorded, spectators get the final state and the feed
+/// goes away with the last of them
+pub async fn end_match_feed(match_id: u32, winner_team_id: u32) {
+    let mut matches = LIVE_MATCHES.write().await;
+    if let Some(feed) = matches.get(&match_id) {
+        feed.send_if_modified(|live_match| match live_match {
+            Some(live_match) => {
+                live_match.status = ServerStatus::Ending;
+                live_match.winner_team_id = Some(winner_team_id);
+                true
+            }
+            None => false,
+        });
+        if is_over(feed) {
+            matches.remove(&match_id);
+        }
+    }
+}
+
+/// A feed nobody watches that has nothing more to show
+fn is_over(feed: &watch::Sender<Option<LiveMatch>>) -> bool {
+    feed.receiver_count() == 0
+        && feed
+            .borrow()
+            .as_ref()
+            .is_none_or(|live_match| live_match.status == ServerStatus::Ending)
+}
+
+/// Every match that reported its state at least once
+pub async fn get_live_matches() -> Vec<LiveMatch> {
+    LIVE_MATCHES
+        .read()
+        .await
+        .values()
+        .filter_map(|feed| feed.borrow().clone())
+        .collect()
+}
+
+pub async fn get_live_match(match_id: u32) -> Option<LiveMatch> {
+    LIVE_MATCHES
+        .read()
+        .await
+        .get(&match_id)
+        .and_then(|feed| feed.borrow().clone())
+}
+
+/// Drops the feed once its last spectator left when the match ended or never reported anything,
+/// so finished matches and unknown match ids don't pile up
+async fn release_match_feed(match_id: u32) {
+    let mut matches = LIVE_MATCHES.write().await;
+    if matches.get(&match_id).is_some_and(is_over) {
+        matches.remove(&match_id);
+    }
+}
+
+fn serialize_update(action: &str, live_match: &LiveMatch) -> Option<String> {
+    serde_json::to_string(&SpectatorMessage {
+        action,
+        data: live_match,
+    })
+    .map_err(|e| tracing::error!("Couldn't serialize spectator message {}", e))
+    .ok()
+}
+
+pub async fn handle_spectator_connection(ws: WebSocket, match_id: u32, throttle: Duration) {
+    let (mut spectator_ws_tx, mut spectator_ws_rx) = ws.split();
+    let mut feed = subscribe(match_id).await;
+
+    let send_fut = async move {
+        //the snapshot is sent right away, subsequent updates are coalesced to at most one per throttle interval
+        let snapshot = feed.borrow_and_update().clone();
+        if let Some(snapshot) = snapshot {
+            if let Some(msg) = serialize_update("match_snapshot", &snapshot) {
+                if spectator_ws_tx.send(Message::Text(msg)).await.is_err() {
+                    return;
+                }
+            }
+        }
+        while feed.changed().await.is_ok() {
+            let update = feed.borrow_and_update().clone();
+            if let Some(update) = update {
+                if let Some(msg) = serialize_update("match_update", &update) {
+                    if spectator_ws_tx.send(Message::Text(msg)).await.is_err() {
+                        return;
+                    }
+                }
+            }
+            tokio::time::sleep(throttle).await;
+        }
+    };
+
+    //spectators are read-only, incoming messages are only drained to notice when the socket closes
+    let recv_fut = async move {
+        while let Some(Ok(msg)) = spectator_ws_rx.next().await {
+            if let Message::Close(_) = msg {
+                break;
+            }
+        }
+    };
+
+    tokio::task::spawn(async move {
+        tokio::select! {
+            _ = send_fut => {},
+            _ = recv_fut => {},
+        }
+        release_match_feed(match_id).await;
+        tracing::debug!("Spectator of match {} disconnected", match_id);
+    });
+}
@@ -3,15 +3,21 @@ use axum::{
     body::Body,
     extract::{
         ws::{Message, WebSocket},
-        WebSocketUpgrade,
+        ConnectInfo, WebSocketUpgrade,
     },
     http::Request,
     response::IntoResponse,
     Extension,
 };
 use futures_util::{FutureExt, StreamExt};
+use std::net::SocketAddr;
 
+use crate::config::Config;
+use crate::metrics::{self, Direction, Peer};
 use crate::model::user;
+use crate::service::audit::{self, AuditSource};
+use crate::service::ban::{self, BanKind};
+use crate::service::server_command::{self, ServerCommand};
 use crate::{error::AppError, service::auth::TokenData};
 
 use serde::{Deserialize, Serialize};
@@ -24,6 +30,8 @@ pub type UserList = RwLock<Vec<ConnectedUser>>;
 #[derive(Serialize, Deserialize, Clone)]
 pub struct ConnectedUser {
     pub steamid64: String,
+    #[serde(skip)]
+    pub is_admin: bool,
     #[serde(skip, default = "default_conn")]
     pub conn: mpsc::UnboundedSender<Result<Message, axum::Error>>,
 }
@@ -40,12 +48,14 @@ pub async fn get_online_users() -> Vec<ConnectedUser> {
 pub async fn on_user_connection(
     ws: WebSocketUpgrade,
     Extension(token_data): Extension<TokenData>,
+    Extension(config): Extension<&'static Config>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
 ) -> Result<impl IntoResponse, AppError> {
     let user = user::select_by_steamid(&mut crate::global::RB.clone(), token_data.steamid64)
         .await
         .map_err(|e| AppError::DatabaseError(e))?
         .ok_or(AppError::Unauthorized)?;
-    Ok(ws.on_upgrade(move |ws| handle_user_connection(ws, user)))
+    Ok(super::with_limits(ws, config).on_upgrade(move |ws| handle_user_connection(ws, user, addr)))
 }
 
 pub async fn authorize_user_connection(
@@ -59,7 +69,7 @@ pub async fn authorize_user_connection(
     Ok(found_user)
 }
 
-pub async fn handle_user_connection(ws: WebSocket, connected_user: user::User) {
+pub async fn handle_user_connection(ws: WebSocket, connected_user: user::User, addr: SocketAddr) {
     let (user_ws_tx, mut user_ws_rx) = ws.split();
 
     let (tx, rx) = mpsc::unbounded_channel();
@@ -74,6 +84,7 @@ pub async fn handle_user_connection(ws: WebSocket, connected_user: user::User) {
     let fut = async move {
         ONLINE_USERS.write().await.push(ConnectedUser {
             steamid64: _connected_user.steamid64.clone(),
+            is_admin: _connected_user.is_admin,
             conn: tx,
         });
 
@@ -88,7 +99,7 @@ pub async fn handle_user_connection(ws: WebSocket, connected_user: user::User) {
                     break;
                 }
             };
-            on_user_message(&_connected_user, msg)
+            on_user_message(&_connected_user, &addr, msg)
                 .await
                 .map_err(|e| {
                     close_stream = true;
@@ -102,8 +113,27 @@ pub async fn handle_user_connection(ws: WebSocket, connected_user: user::User) {
     tokio::task::spawn(fut);
 }
 
-async fn on_user_message(user_data: &user::User, msg: Message) -> anyhow::Result<()> {
+/// Actions the user socket handles, the others are counted as `unknown`
+const USER_ACTIONS: [&str; 4] = [
+    "user_ping",
+    "admin_get_servers",
+    "admin_get_pending_servers",
+    "admin_server_command",
+];
+
+async fn on_user_message(
+    user_data: &user::User,
+    addr: &SocketAddr,
+    msg: Message,
+) -> anyhow::Result<()> {
     let msg = msg.to_text()?;
+    //admin commands carry their JSON payload after the action name
+    let action = msg.split_once(' ').map_or(msg, |(action, _)| action);
+    let counted = USER_ACTIONS
+        .into_iter()
+        .find(|known| *known == action)
+        .unwrap_or("unknown");
+    metrics::count_ws_message(Peer::User, Direction::In, &counted);
     if msg.starts_with("user") {
         handle_user_message(user_data, msg)
             .await
@@ -111,16 +141,29 @@ async fn on_user_message(user_data: &user::User, msg: Message) -> anyhow::Result
     }
     if msg.starts_with("admin") {
         if !user_data.is_admin {
+            //players share the socket for their notifications, the message is only refused
             tracing::warn!(
                 "Unauthorized user {} is trying to send privileged messages",
                 user_data.steamid64
             );
-            ONLINE_USERS
-                .write()
-                .await
-                .retain(|u| u.steamid64 != user_data.steamid64);
             return Ok(());
         }
+        let payload = msg
+            .split_once(' ')
+            .and_then(|(_, payload)| serde_json::from_str(payload).ok())
+            .map(|mut payload| {
+                audit::redact(&mut payload);
+                payload.to_string()
+            });
+        audit::record(audit::audit_entry(
+            &user_data.steamid64,
+            AuditSource::Ws,
+            action.to_string(),
+            None,
+            payload,
+            Some(addr.ip().to_string()),
+        ))
+        .await;
         handle_admin_message(user_data, msg)
             .await
             .map_err(|_| anyhow!("Invalid admin message"))?
@@ -134,19 +177,30 @@ async fn on_user_message(user_data: &user::User, msg: Message) -> anyhow::Result
 #[derive(Deserialize, Serialize)]
 struct UserResponse<T: Serialize> {
     action: String,
-    data: T
+    data: T,
 }
 
 impl<T: Serialize> UserResponse<T> {
     pub fn new(action: String, data: T) -> Self {
-        Self {
-            action,
-            data
-        }
+        Self { action, data }
     }
 }
 
 async fn handle_user_message(user_data: &user::User, msg: &str) -> Result<(), AppError> {
+    //queue actions are refused while the user has a game ban or a queue cooldown
+    if msg.starts_with("user_queue") {
+        let ban = ban::find_active_ban(
+            &user_data.steamid64,
+            &[BanKind::Game, BanKind::QueueCooldown],
+            None,
+        )
+        .await?;
+        if let Some(ban) = ban {
+            let ban = serde_json::to_string(&ban).map_err(AppError::JsonParseError)?;
+            send_message_to_user(&user_data.steamid64, ban, "response_queue_refused").await;
+            return Ok(());
+        }
+    }
     match msg {
         "user_ping" => send_message_to_user(&user_data.steamid64, "pong".to_string(), "ping").await,
         _ => {
@@ -156,14 +210,41 @@ async fn handle_user_message(user_data: &user::User, msg: &str) -> Result<(), Ap
     Ok(())
 }
 
+/// Payload of `admin_server_command`, e.g. `admin_server_command {"server_id":1,"command":"pause"}`
+#[derive(Deserialize)]
+struct ServerCommandRequest {
+    server_id: u32,
+    #[serde(flatten)]
+    command: ServerCommand,
+}
+
+/// Data of `response_server_command`, the result follows as `response_server_command_result`
+#[derive(Serialize)]
+struct ServerCommandIssued {
+    command_id: String,
+    server_id: u32,
+}
+
 async fn handle_admin_message(user_data: &user::User, msg: &str) -> Result<(), AppError> {
-    match msg {
+    let (action, payload) = msg.split_once(' ').unwrap_or((msg, ""));
+    match action {
         "admin_get_servers" => {
             let servers = crate::service::server::get_servers().await?;
             let servers =
                 serde_json::to_string(&servers).map_err(|e| AppError::JsonParseError(e))?;
             send_message_to_user(&user_data.steamid64, servers, "response_get_servers").await;
         }
+        "admin_get_pending_servers" => {
+            let pending = crate::service::enrollment::get_pending_servers().await?;
+            let pending = serde_json::to_string(&pending).map_err(AppError::JsonParseError)?;
+            send_message_to_user(
+                &user_data.steamid64,
+                pending,
+                "response_get_pending_servers",
+            )
+            .await;
+        }
+        "admin_server_command" => send_server_command(user_data, payload).await?,
         _ => {
             tracing::warn!("Unknown message {} from user {}", msg, user_data.steamid64);
         }
@@ -172,23 +253,109 @@ async fn handle_admin_message(user_data: &user::User, msg: &str) -> Result<(), A
     Ok(())
 }
 
-async fn send_message_to_user(steamid64: &String, message: String, action: &str) {
+/// Refusals are answered with `response_server_command_refused` instead of closing the socket
+async fn send_server_command(user_data: &user::User, payload: &str) -> Result<(), AppError> {
+    let steamid64 = user_data.steamid64.clone();
+    let issued = match serde_json::from_str::<ServerCommandRequest>(payload) {
+        Ok(request) => {
+            server_command::issue_command(request.server_id, request.command, steamid64.clone())
+                .await
+        }
+        Err(e) => Err(AppError::BadRequest(format!(
+            "Invalid server command: {}",
+            e
+        ))),
+    };
+    let issued = match issued {
+        Ok(issued) => issued,
+        Err(e) => {
+            let reason = match e {
+                AppError::NotFound(reason)
+                | AppError::BadRequest(reason)
+                | AppError::Conflict(reason) => reason,
+                AppError::Validation(details) => details
+                    .iter()
+                    .map(|detail| format!("{} {}", detail.field, detail.message))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                e => {
+                    tracing::error!(error = ?e, "Couldn't issue the server command of {}", steamid64);
+                    "Internal server error".to_string()
+                }
+            };
+            send_message_to_user(&steamid64, reason, "response_server_command_refused").await;
+            return Ok(());
+        }
+    };
+
+    let response = serde_json::to_string(&ServerCommandIssued {
+        command_id: issued.command_id.clone(),
+        server_id: issued.server_id,
+    })
+    .map_err(AppError::JsonParseError)?;
+    send_message_to_user(&steamid64, response, "response_server_command").await;
+    tokio::spawn(async move {
+        let result = server_command::wait_for_result(issued).await;
+        match serde_json::to_string(&result) {
+            Ok(result) => {
+                send_message_to_user(&steamid64, result, "response_server_command_result").await
+            }
+            Err(e) => tracing::error!("Couldn't serialize CommandResult json {}", e),
+        }
+    });
+    Ok(())
+}
+
+/// Sends a close frame to every user, each connection is dropped once its handler returns
+pub async fn close_user_connections() {
+    for user in ONLINE_USERS.read().await.iter() {
+        user.conn.send(Ok(Message::Close(None))).ok();
+    }
+}
+
+pub async fn send_message_to_user(steamid64: &String, message: String, action: &str) {
     let online_users = ONLINE_USERS.read().await;
     let user = online_users
         .iter()
         .find(|user| user.steamid64 == *steamid64);
 
     if let Some(user) = user {
-        let response_string = match serde_json::to_string(&UserResponse::new(action.to_string(), message)) {
+        let response_string =
+            match serde_json::to_string(&UserResponse::new(action.to_string(), message)) {
+                Ok(data) => data,
+                Err(e) => {
+                    tracing::error!("Couldn't serialize UserResponse json {}", e.to_string());
+                    return;
+                }
+            };
+
+        metrics::count_ws_message(Peer::User, Direction::Out, &action);
+        user.conn
+            .send(Ok(Message::Text(response_string)))
+            .map_err(|e| tracing::error!(error = ?e, "Error while sending message to user"))
+            .ok();
+    }
+}
+
+/// Sends the message to every connected admin
+pub async fn broadcast_to_admins(message: String, action: &str) {
+    let response_string =
+        match serde_json::to_string(&UserResponse::new(action.to_string(), message)) {
             Ok(data) => data,
             Err(e) => {
-                tracing::error!("Couldn't serialize UserResponse json {}", e.to_string());
+                tracing::error!("Couldn't serialize UserResponse json {}", e);
                 return;
             }
         };
-
+    for user in ONLINE_USERS
+        .read()
+        .await
+        .iter()
+        .filter(|user| user.is_admin)
+    {
+        metrics::count_ws_message(Peer::User, Direction::Out, &action);
         user.conn
-        .send(Ok(Message::Text(response_string)))
+            .send(Ok(Message::Text(response_string.clone())))
             .map_err(|e| tracing::error!(error = ?e, "Error while sending message to user"))
             .ok();
     }
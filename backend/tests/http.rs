@@ -0,0 +1,612 @@
+mod support;
+
+use noname::global;
+use noname::model::team::TeamMember;
+use rbatis::rbdc::datetime::FastDateTime;
+use serde_json::{json, Value};
+use support::{app, TestApp, STEAM_MALFORMED_BANS, STEAM_VAC_BANNED};
+
+#[tokio::test]
+async fn readyz_reports_migrated_database() {
+    let app = app();
+    let (status, body) = app.get("/readyz", None).await;
+    assert_eq!(status, 200);
+    assert_eq!(body["data"]["ready"], true);
+    assert_eq!(body["data"]["database"], true);
+}
+
+#[tokio::test]
+async fn admin_routes_reject_missing_and_non_admin_tokens() {
+    let app = app();
+    let player = app.create_user("76561190000000201", false).await;
+
+    let (status, body) = app.get("/api/users", None).await;
+    assert_eq!(status, 401);
+    assert_eq!(body["error"]["code"], "unauthorized");
+    assert!(body["error"]["request_id"].is_string());
+
+    let (status, _) = app.get("/api/users", Some("not-a-token")).await;
+    assert_eq!(status, 401);
+    let (status, _) = app.get("/api/users", Some(&player)).await;
+    assert_eq!(status, 401);
+}
+
+#[tokio::test]
+async fn admins_can_register_servers() {
+    let app = app();
+    let admin = app.create_user("76561190000000202", true).await;
+
+    let (status, _) = app
+        .post(
+            "/api/servers",
+            Some(&admin),
+            json!({ "ip": "10.0.0.1", "port": 27015 }),
+        )
+        .await;
+    assert_eq!(status, 201);
+    let (status, _) = app
+        .post(
+            "/api/servers",
+            Some(&admin),
+            json!({ "ip": "2001:db8::1", "port": 27015 }),
+        )
+        .await;
+    assert_eq!(status, 201);
+    let (status, body) = app
+        .post(
+            "/api/servers",
+            Some(&admin),
+            json!({ "ip": "10.0.0.300", "hostname": "-bad", "port": 27015 }),
+        )
+        .await;
+    assert_eq!(status, 422);
+    assert_eq!(body["error"]["details"][0]["field"], "hostname");
+    let (status, _) = app
+        .post("/api/servers", Some(&admin), json!({ "port": 27015 }))
+        .await;
+    assert_eq!(status, 422);
+
+    let (status, body) = app.get("/api/users", Some(&admin)).await;
+    assert_eq!(status, 200);
+    let users = body["data"].as_array().unwrap();
+    assert!(users.iter().any(|u| u["steamid64"] == "76561190000000202"));
+}
+
+#[tokio::test]
+async fn validation_errors_list_the_fields() {
+    let app = app();
+    let captain = app.create_user("76561190000000203", false).await;
+
+    let (status, body) = app
+        .post(
+            "/api/teams",
+            Some(&captain),
+            json!({ "name": "", "tag": "T" }),
+        )
+        .await;
+    assert_eq!(status, 422);
+    assert_eq!(body["error"]["code"], "validation_failed");
+    assert_eq!(body["error"]["details"][0]["field"], "name");
+}
+
+#[tokio::test]
+async fn config_templates_are_versioned_and_reject_unknown_variables() {
+    let app = app();
+    let admin = app.create_user("76561190000000204", true).await;
+
+    let (status, body) = app
+        .put(
+            "/api/config_templates/overtime",
+            Some(&admin),
+            json!({ "content": "mp_overtime_enable 1 // {{nope}}" }),
+        )
+        .await;
+    assert_eq!(status, 422);
+    assert_eq!(body["error"]["details"][0]["field"], "content");
+
+    for content in ["mp_overtime_enable 1", "mp_overtime_maxrounds 6"] {
+        let (status, _) = app
+            .put(
+                "/api/config_templates/overtime",
+                Some(&admin),
+                json!({ "content": content }),
+            )
+            .await;
+        assert_eq!(status, 200);
+    }
+    let (status, body) = app
+        .get("/api/config_templates/overtime", Some(&admin))
+        .await;
+    assert_eq!(status, 200);
+    assert_eq!(body["data"]["content"], "mp_overtime_maxrounds 6");
+    assert_eq!(body["data"]["versions"].as_array().unwrap().len(), 2);
+
+    let version = body["data"]["versions"][1]["version"].as_u64().unwrap();
+    let (status, body) = app
+        .get(
+            &format!("/api/config_templates/overtime/versions/{}", version),
+            Some(&admin),
+        )
+        .await;
+    assert_eq!(status, 200);
+    assert_eq!(body["data"]["content"], "mp_overtime_enable 1");
+
+    let (status, _) = app.get("/api/config_templates/pistol", Some(&admin)).await;
+    assert_eq!(status, 404);
+}
+
+#[tokio::test]
+async fn server_logs_add_up_to_match_stats() {
+    let app = app();
+    let admin = app.create_user("76561190000000207", true).await;
+    let server_id = app.create_server("27204").await;
+    let match_id = create_match(
+        app,
+        &admin,
+        [
+            ("76561190000000208", "Logs A"),
+            ("76561190000000209", "Logs B"),
+        ],
+        server_id,
+    )
+    .await;
+
+    let (status, body) = app
+        .post(
+            &format!("/api/servers/{}/log_token", server_id),
+            Some(&admin),
+            json!({}),
+        )
+        .await;
+    assert_eq!(status, 201, "{}", body);
+    let token = body["data"]["token"].as_str().unwrap().to_string();
+    assert!(body["data"]["log_url"]
+        .as_str()
+        .unwrap()
+        .ends_with(&format!("/api/logs/{}?token={}", server_id, token)));
+
+    let logs = concat!(
+        "10/19/2026 - 20:01:00.120 - World triggered \"Round_Start\"\n",
+        "10/19/2026 - 20:01:20.500 - \"Alpha<2><STEAM_1:0:1><CT>\" [-1 2 3] killed \"Bravo <1><3><STEAM_1:1:2><TERRORIST>\" [4 5 6] with \"ak47\" (headshot)\n",
+        "10/19/2026 - 20:01:21.000 - \"Charlie<4><STEAM_1:0:3><CT>\" assisted killing \"Bravo <1><3><STEAM_1:1:2><TERRORIST>\"\n",
+        "10/19/2026 - 20:01:30.000 - \"Bravo <1><3><STEAM_1:1:2><TERRORIST>\" say \"nt\"\n",
+        "10/19/2026 - 20:01:40.000 - \"Bot<5><BOT><TERRORIST>\" triggered \"Planted_The_Bomb\" at bombsite A\n",
+        "10/19/2026 - 20:01:50.000 - \"Alpha<2><STEAM_1:0:1><CT>\" triggered \"Defused_The_Bomb\"\n",
+        "10/19/2026 - 20:01:50.100 - Team \"CT\" triggered \"SFUI_Notice_Bomb_Defused\" (CT \"1\") (T \"0\")\n",
+        "10/19/2026 - 20:01:50.200 - World triggered \"Round_End\"\n",
+        "10/19/2026 - 20:01:51.000 - server cvars start\n",
+    );
+    let logs_path = format!("/api/logs/{}", server_id);
+    let (status, _) = app
+        .post_text(&format!("{}?token=wrong", logs_path), logs)
+        .await;
+    assert_eq!(status, 401);
+
+    let (status, body) = app
+        .post_text(&format!("{}?token={}", logs_path, token), logs)
+        .await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["data"]["lines"], 9);
+    assert_eq!(body["data"]["events"], 8);
+    assert_eq!(body["data"]["match_id"], match_id);
+
+    let (status, body) = app
+        .get(&format!("/api/matches/{}/stats", match_id), None)
+        .await;
+    assert_eq!(status, 200, "{}", body);
+    let stats = body["data"].as_array().unwrap();
+    assert_eq!(stats.len(), 3, "bots aren't counted: {}", body);
+    let alpha = &stats[0];
+    assert_eq!(alpha["steamid64"], "76561197960265730");
+    assert_eq!(alpha["side"], "counter_terrorist");
+    assert_eq!(alpha["kills"], 1);
+    assert_eq!(alpha["headshots"], 1);
+    assert_eq!(alpha["bomb_defuses"], 1);
+    let bravo = stats.iter().find(|s| s["name"] == "Bravo <1>").unwrap();
+    assert_eq!(bravo["steamid64"], "76561197960265733");
+    assert_eq!(bravo["deaths"], 1);
+    let charlie = stats.iter().find(|s| s["name"] == "Charlie").unwrap();
+    assert_eq!(charlie["assists"], 1);
+}
+
+#[tokio::test]
+async fn udp_log_packets_are_attributed_to_their_server() {
+    let app = app();
+    let admin = app.create_user("76561190000000210", true).await;
+    let listener = app.spawn_log_listener().await;
+    let game_server = tokio::net::UdpSocket::bind((support::SERVER_IP, 0))
+        .await
+        .unwrap();
+    let server_id = app
+        .create_server(&game_server.local_addr().unwrap().port().to_string())
+        .await;
+    let match_id = create_match(
+        app,
+        &admin,
+        [
+            ("76561190000000211", "Udp A"),
+            ("76561190000000212", "Udp B"),
+        ],
+        server_id,
+    )
+    .await;
+
+    let kill = "L 10/19/2026 - 20:01:20: \"Alpha<2><STEAM_1:0:1><CT>\" [-1 2 3] killed \"Bravo<3><STEAM_1:1:2><TERRORIST>\" [4 5 6] with \"awp\"\n";
+    for packet in [
+        [b"\xFF\xFF\xFF\xFFR".as_slice(), kill.as_bytes(), b"\0"].concat(),
+        //the secret variant, accepted while no log secret is configured
+        [b"\xFF\xFF\xFF\xFFS1234".as_slice(), kill.as_bytes(), b"\0"].concat(),
+        b"not a log packet".to_vec(),
+    ] {
+        game_server.send_to(&packet, listener).await.unwrap();
+    }
+    let stranger = tokio::net::UdpSocket::bind((support::SERVER_IP, 0))
+        .await
+        .unwrap();
+    let packet = [b"\xFF\xFF\xFF\xFFR".as_slice(), kill.as_bytes()].concat();
+    stranger.send_to(&packet, listener).await.unwrap();
+
+    let stats_path = format!("/api/matches/{}/stats", match_id);
+    let mut kills = serde_json::Value::Null;
+    for _ in 0..30 {
+        let (status, body) = app.get(&stats_path, None).await;
+        assert_eq!(status, 200);
+        kills = body["data"][0]["kills"].clone();
+        if kills == 2 {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    assert_eq!(kills, 2, "both log packets of the server are counted");
+
+    let (_, metrics) = app.get("/metrics", None).await;
+    let metrics = metrics.as_str().unwrap();
+    for outcome in ["accepted", "dropped", "unattributed"] {
+        assert!(
+            metrics.contains(&format!(
+                "srcds_log_packets_total{{outcome=\"{}\"}}",
+                outcome
+            )),
+            "{}",
+            metrics
+        );
+    }
+}
+
+#[tokio::test]
+async fn match_results_advance_the_tournament_bracket() {
+    let app = app();
+    let admin = app.create_user("76561190000000213", true).await;
+    let (status, body) = app
+        .post(
+            "/api/tournaments",
+            Some(&admin),
+            json!({ "name": "Bracket Cup", "format": "single_elimination" }),
+        )
+        .await;
+    assert_eq!(status, 201, "{}", body);
+    let tournament_path = format!("/api/tournaments/{}", body["data"]["id"]);
+    let mut team_ids = Vec::new();
+    for (seed, (steamid64, name)) in [
+        ("76561190000000214", "Bracket A"),
+        ("76561190000000215", "Bracket B"),
+        ("76561190000000216", "Bracket C"),
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        let (_, team_id) = app.create_team(steamid64, name).await;
+        let (status, body) = app
+            .post(
+                &format!("{}/teams", tournament_path),
+                Some(&admin),
+                json!({ "team_id": team_id, "seed": seed + 1 }),
+            )
+            .await;
+        assert!(status < 300, "{}", body);
+        team_ids.push(team_id);
+    }
+    let (status, body) = app
+        .post(
+            &format!("{}/start", tournament_path),
+            Some(&admin),
+            json!({}),
+        )
+        .await;
+    assert_eq!(status, 200, "{}", body);
+
+    //the top seed has a bye, the two others play the semifinal
+    let ready = |body: &Value| -> Vec<Value> {
+        body["data"]["matches"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter(|m| m["status"] == "ready")
+            .cloned()
+            .collect()
+    };
+    let semifinal = ready(&body);
+    assert_eq!(semifinal.len(), 1, "{}", body);
+    let (status, body) = app
+        .post(
+            &format!("/api/matches/{}/result", semifinal[0]["game_match_id"]),
+            Some(&admin),
+            json!({ "winner_team_id": team_ids[2] }),
+        )
+        .await;
+    assert_eq!(status, 200, "{}", body);
+
+    let (_, body) = app.get(&tournament_path, None).await;
+    let final_match = ready(&body);
+    assert_eq!(final_match.len(), 1, "{}", body);
+    assert_eq!(final_match[0]["team1_id"], team_ids[0]);
+    assert_eq!(final_match[0]["team2_id"], team_ids[2]);
+    let (status, _) = app
+        .post(
+            &format!("/api/matches/{}/result", final_match[0]["game_match_id"]),
+            Some(&admin),
+            json!({ "winner_team_id": team_ids[0] }),
+        )
+        .await;
+    assert_eq!(status, 200);
+    let (_, body) = app.get(&tournament_path, None).await;
+    assert_eq!(body["data"]["status"], "finished");
+    assert_eq!(body["data"]["winner_team_id"], team_ids[0]);
+}
+
+#[tokio::test]
+async fn accepting_an_invite_as_a_member_conflicts() {
+    let app = app();
+    let (captain, team_id) = app.create_team("76561190000000217", "Invite A").await;
+    let player = app.create_user("76561190000000218", false).await;
+    let (status, body) = app
+        .post(
+            &format!("/api/teams/{}/invites", team_id),
+            Some(&captain),
+            json!({ "steamid64": "76561190000000218", "role": "player" }),
+        )
+        .await;
+    assert_eq!(status, 201, "{}", body);
+    let invite_path = format!("/api/teams/invites/{}/accept", body["data"]["id"]);
+
+    //joined through the same invite in the meantime
+    app.run(async move {
+        let member = TeamMember {
+            team_id,
+            steamid64: "76561190000000218".to_string(),
+            role: "player".to_string(),
+            joined_at: FastDateTime::now(),
+        };
+        TeamMember::insert(&mut global::RB.clone(), &member)
+            .await
+            .unwrap();
+    })
+    .await;
+    let (status, body) = app.post(&invite_path, Some(&player), json!({})).await;
+    assert_eq!(status, 409, "{}", body);
+    assert_eq!(body["error"]["code"], "conflict");
+}
+
+#[tokio::test]
+async fn bans_are_filtered_and_paginated() {
+    let app = app();
+    let admin = app.create_user("76561190000000219", true).await;
+    let steamid64 = "76561190000000220";
+    app.create_user(steamid64, false).await;
+    let mut ban_ids = Vec::new();
+    for (kind, duration_secs) in [("mute", Some(3600)), ("game", None)] {
+        let (status, body) = app
+            .post(
+                "/api/bans",
+                Some(&admin),
+                json!({
+                    "steamid64": steamid64,
+                    "kind": kind,
+                    "reason": "Testing the ban list",
+                    "duration_secs": duration_secs
+                }),
+            )
+            .await;
+        assert!(status < 300, "{}", body);
+        ban_ids.push(body["data"]["id"].clone());
+    }
+    for duration_secs in [0, u64::MAX] {
+        let (status, body) = app
+            .post(
+                "/api/bans",
+                Some(&admin),
+                json!({
+                    "steamid64": steamid64,
+                    "kind": "mute",
+                    "reason": "Testing the ban duration",
+                    "duration_secs": duration_secs
+                }),
+            )
+            .await;
+        assert_eq!(status, 422, "{}", body);
+        assert_eq!(
+            body["error"]["details"][0]["field"], "duration_secs",
+            "{}",
+            body
+        );
+    }
+    let (status, body) = app
+        .post(
+            &format!("/api/bans/{}/lift", ban_ids[1]),
+            Some(&admin),
+            json!({ "reason": "Appealed" }),
+        )
+        .await;
+    assert_eq!(status, 200, "{}", body);
+
+    let list = |filters: &str| format!("/api/bans?steamid64={}{}", steamid64, filters);
+    let (status, body) = app.get(&list(""), Some(&admin)).await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["data"]["total"], 2);
+    let (_, body) = app.get(&list("&active=true"), Some(&admin)).await;
+    assert_eq!(body["data"]["total"], 1);
+    assert_eq!(body["data"]["bans"][0]["id"], ban_ids[0]);
+    let (_, body) = app.get(&list("&active=false"), Some(&admin)).await;
+    assert_eq!(body["data"]["bans"][0]["id"], ban_ids[1]);
+    let (_, body) = app.get(&list("&kind=game"), Some(&admin)).await;
+    assert_eq!(body["data"]["total"], 1);
+    assert_eq!(body["data"]["bans"][0]["kind"], "game");
+    let (_, body) = app.get(&list("&page=2&page_size=1"), Some(&admin)).await;
+    assert_eq!(body["data"]["total"], 2);
+    assert_eq!(body["data"]["bans"].as_array().unwrap().len(), 1);
+    assert_eq!(body["data"]["bans"][0]["id"], ban_ids[0]);
+    for page in ["0", &u64::MAX.to_string()] {
+        let (status, body) = app
+            .get(
+                &list(&format!("&page={}&page_size=100", page)),
+                Some(&admin),
+            )
+            .await;
+        assert_eq!(status, 422, "{}", body);
+        assert_eq!(body["error"]["details"][0]["field"], "page", "{}", body);
+    }
+}
+
+#[tokio::test]
+async fn audit_log_stores_redacted_changes() {
+    let app = app();
+    let admin_steamid64 = "76561190000000221";
+    let admin = app.create_user(admin_steamid64, true).await;
+    let path = "/api/config_templates/variables/audit_password";
+    for value in ["hunter2", "hunter3"] {
+        let (status, body) = app.put(path, Some(&admin), json!({ "value": value })).await;
+        assert_eq!(status, 200, "{}", body);
+    }
+    let server_id = app.create_server("27221").await;
+    let (status, body) = app
+        .post(
+            &format!("/api/servers/{}/log_token", server_id),
+            Some(&admin),
+            json!({}),
+        )
+        .await;
+    assert_eq!(status, 201, "{}", body);
+    let log_token = body["data"]["token"].as_str().unwrap().to_string();
+
+    let (status, body) = app
+        .get(
+            &format!("/api/audit?actor={}", admin_steamid64),
+            Some(&admin),
+        )
+        .await;
+    assert_eq!(status, 200, "{}", body);
+    let payloads: Vec<Value> = body["data"]["entries"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|entry| entry["target"] == path)
+        .map(|entry| serde_json::from_str(entry["payload"].as_str().unwrap()).unwrap())
+        .collect();
+    assert_eq!(payloads.len(), 2, "{}", body);
+    assert!(!body.to_string().contains("hunter"), "{}", body);
+    //the log url embeds the token, neither may be read back from the audit log
+    assert!(!body.to_string().contains(&log_token), "{}", body);
+    let update = payloads
+        .iter()
+        .find(|payload| payload.get("before").is_some())
+        .unwrap();
+    //the unchanged fields are left out, the changed secret only shows that it changed
+    assert_eq!(update["before"]["value"], "[redacted]");
+    assert_eq!(update["after"]["value"], "[redacted]");
+    assert!(update["after"].get("name").is_none());
+    let (status, body) = app.get("/api/audit?page=0", Some(&admin)).await;
+    assert_eq!(status, 422, "{}", body);
+
+    let content = "x".repeat(300 << 10);
+    let (status, body) = app
+        .put(
+            "/api/config_templates/warmup",
+            Some(&admin),
+            json!({ "content": content }),
+        )
+        .await;
+    assert_eq!(status, 413);
+    assert_eq!(body["error"]["code"], "payload_too_large");
+}
+
+#[tokio::test]
+async fn reported_results_finish_the_schedule() {
+    let app = app();
+    let admin = app.create_user("76561190000000222", true).await;
+    let server_id = app.create_server("27222").await;
+    let match_id = create_match(
+        app,
+        &admin,
+        [
+            ("76561190000000223", "Schedule A"),
+            ("76561190000000224", "Schedule B"),
+        ],
+        server_id,
+    )
+    .await;
+    let match_path = format!("/api/matches/{}", match_id);
+    let (status, body) = app
+        .put(
+            &format!("{}/schedule", match_path),
+            Some(&admin),
+            json!({ "scheduled_at": "2099-01-01T12:00:00Z" }),
+        )
+        .await;
+    assert_eq!(status, 200, "{}", body);
+
+    let (_, body) = app.get(&match_path, None).await;
+    let winner_team_id = body["data"]["team1_id"].clone();
+    let (status, body) = app
+        .post(
+            &format!("{}/result", match_path),
+            Some(&admin),
+            json!({ "winner_team_id": winner_team_id }),
+        )
+        .await;
+    assert_eq!(status, 200, "{}", body);
+    let (_, body) = app.get(&match_path, None).await;
+    assert_eq!(body["data"]["schedule"]["status"], "finished", "{}", body);
+}
+
+#[tokio::test]
+async fn trust_checks_apply_the_policy_to_steam_bans() {
+    let app = app();
+    let admin = app.create_user("76561190000000225", true).await;
+    for (steamid64, verdict, reasons) in [
+        ("76561190000000226", "allow", ""),
+        (STEAM_VAC_BANNED, "block", "vac_ban"),
+    ] {
+        let (status, body) = app
+            .post(
+                &format!("/api/users/{}/trust", steamid64),
+                Some(&admin),
+                json!({}),
+            )
+            .await;
+        assert_eq!(status, 200, "{}", body);
+        assert_eq!(body["data"]["verdict"], verdict, "{}", body);
+        assert_eq!(body["data"]["reasons"], reasons, "{}", body);
+    }
+
+    let (status, body) = app
+        .post(
+            &format!("/api/users/{}/trust", STEAM_MALFORMED_BANS),
+            Some(&admin),
+            json!({}),
+        )
+        .await;
+    assert_eq!(status, 502, "{}", body);
+    assert_eq!(body["error"]["code"], "upstream_error", "{}", body);
+}
+
+/// Creates a team for each captain and a match between them on the server
+async fn create_match(
+    app: &TestApp,
+    admin: &str,
+    captains: [(&str, &str); 2],
+    server_id: u32,
+) -> u64 {
+    let (_, team1_id) = app.create_team(captains[0].0, captains[0].1).await;
+    let (_, team2_id) = app.create_team(captains[1].0, captains[1].1).await;
+    app.create_match(admin, team1_id, team2_id, server_id).await
+}
@@ -0,0 +1,521 @@
+//! Boots the backend once per test binary and drives it through HTTP and both websocket channels
+
+#![allow(dead_code)]
+
+use std::future::Future;
+use std::net::{SocketAddr, TcpListener};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use axum::extract::Query;
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use futures_util::{SinkExt, StreamExt};
+use noname::config::Config;
+use noname::driver::inet::InetAddr;
+use noname::model::{server, user::User};
+use noname::service::auth::create_access_token;
+use noname::service::log_listener;
+use noname::ws::server::ServerStatus;
+use noname::{app, driver::db, global};
+use rbatis::rbdc::datetime::FastDateTime;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tokio::net::TcpStream;
+use tokio::runtime::Handle;
+use tokio_tungstenite::tungstenite::{self, client::IntoClientRequest, http::HeaderValue, Message};
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+pub const JWT_KEY: &str = "integration-tests";
+/// Game servers of the tests connect from here, the backend identifies them by ip and `PORT` header
+pub const SERVER_IP: &str = "127.0.0.1";
+const RECV_TIMEOUT: Duration = Duration::from_secs(5);
+/// The fake Steam Web API reports a recent VAC ban for this steamid, every other one is clean
+pub const STEAM_VAC_BANNED: &str = "76561190000000291";
+/// The fake Steam Web API answers the ban lookup of this steamid with a body that isn't JSON
+pub const STEAM_MALFORMED_BANS: &str = "76561190000000292";
+
+type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+pub struct TestApp {
+    pub addr: SocketAddr,
+    /// Config the app was started with, for the services the tests call directly
+    pub config: &'static Config,
+    /// Runtime serving the app, the database pool belongs to it so database work of the tests runs there too
+    runtime: Handle,
+    http: reqwest::Client,
+}
+
+static APP: OnceLock<TestApp> = OnceLock::new();
+
+/// Every test of a binary shares one backend and database, tests stay apart by using their own
+/// steamids and server ports
+pub fn app() -> &'static TestApp {
+    APP.get_or_init(TestApp::spawn)
+}
+
+impl TestApp {
+    fn spawn() -> TestApp {
+        //a fresh SQLite file per test binary, left in target/tmp for inspection after a failure
+        let db_path = format!(
+            "{}/{}.db",
+            env!("CARGO_TARGET_TMPDIR"),
+            env!("CARGO_CRATE_NAME")
+        );
+        for suffix in ["", "-wal", "-shm"] {
+            std::fs::remove_file(format!("{}{}", db_path, suffix)).ok();
+        }
+        let steam_listener =
+            TcpListener::bind("127.0.0.1:0").expect("Couldn't bind the fake Steam API");
+        let mut config = Config::default();
+        config.database.url = format!("sqlite://{}?mode=rwc", db_path);
+        config.steam.api_key = "integration-tests".to_string();
+        config.steam.api_url = format!("http://{}", steam_listener.local_addr().unwrap());
+        config.auth.jwt_key = JWT_KEY.to_string();
+        //short enough for a test to watch an idle pending server get closed
+        config.websocket.pending_idle_timeout_secs = 2;
+        let config: &'static Config = Box::leak(Box::new(config));
+
+        let listener = TcpListener::bind("127.0.0.1:0").expect("Couldn't bind the test server");
+        let addr = listener.local_addr().unwrap();
+        let (ready_tx, ready_rx) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let runtime = tokio::runtime::Runtime::new().expect("Couldn't build the app runtime");
+            runtime.block_on(async move {
+                db::init_and_migrate(&config.database.url)
+                    .await
+                    .expect("Couldn't set up the test database");
+                tokio::spawn(
+                    axum::Server::from_tcp(steam_listener)
+                        .expect("Couldn't serve the fake Steam API")
+                        .serve(fake_steam_api().into_make_service()),
+                );
+                ready_tx.send(Handle::current()).unwrap();
+                axum::Server::from_tcp(listener)
+                    .expect("Couldn't serve on the test listener")
+                    .serve(
+                        app::build_router(config)
+                            .into_make_service_with_connect_info::<SocketAddr>(),
+                    )
+                    .await
+                    .expect("The test server stopped");
+            });
+        });
+        let runtime = ready_rx.recv().expect("The test server didn't start");
+        TestApp {
+            addr,
+            config,
+            runtime,
+            http: reqwest::Client::new(),
+        }
+    }
+
+    pub fn url(&self, path: &str) -> String {
+        format!("http://{}{}", self.addr, path)
+    }
+
+    pub fn ws_url(&self, path: &str) -> String {
+        format!("ws://{}{}", self.addr, path)
+    }
+
+    /// Runs `future` on the app runtime and waits for it from the test's own runtime
+    pub async fn run<F, T>(&self, future: F) -> T
+    where
+        F: Future<Output = T> + Send + 'static,
+        T: Send + 'static,
+    {
+        self.runtime
+            .spawn(future)
+            .await
+            .expect("A task on the app runtime panicked")
+    }
+
+    /// Starts the UDP log listener on a free port of `SERVER_IP`
+    pub async fn spawn_log_listener(&self) -> SocketAddr {
+        let socket =
+            std::net::UdpSocket::bind((SERVER_IP, 0)).expect("Couldn't bind the log listener");
+        let addr = socket.local_addr().unwrap();
+        let config = self.config;
+        self.run(async move {
+            log_listener::spawn_log_listener(config, socket)
+                .expect("Couldn't start the log listener");
+        })
+        .await;
+        addr
+    }
+
+    /// Stores a user and returns an access token for it
+    pub async fn create_user(&self, steamid64: &str, is_admin: bool) -> String {
+        let user = User {
+            steamid64: steamid64.to_string(),
+            is_admin,
+            is_caster: false,
+            created_at: FastDateTime::now(),
+        };
+        self.run(async move {
+            User::insert(&mut global::RB.clone(), &user)
+                .await
+                .expect("Couldn't store the test user");
+        })
+        .await;
+        create_access_token(self.config, steamid64.to_string()).expect("Couldn't mint a token")
+    }
+
+    /// Registers a game server reachable at `SERVER_IP` and `port`, returns its id
+    pub async fn create_server(&self, port: &str) -> u32 {
+        let port: u16 = port.parse().expect("Test servers need a numeric port");
+        self.run(async move {
+            let ip: InetAddr = SERVER_IP.parse().unwrap();
+            let new_server = server::Server {
+                id: None,
+                ip,
+                port,
+                hostname: None,
+                resolved_at: None,
+                created_at: FastDateTime::now(),
+            };
+            server::Server::insert(&mut global::RB.clone(), &new_server)
+                .await
+                .expect("Couldn't store the test server");
+            server::select_by_address(&global::RB, &ip, port)
+                .await
+                .expect("Couldn't load the test server")
+                .and_then(|server| server.id)
+                .expect("The test server wasn't stored")
+        })
+        .await
+    }
+
+    /// Creates a team captained by a new user, returns the captain's token and the team id
+    pub async fn create_team(&self, captain_steamid64: &str, name: &str) -> (String, u32) {
+        let captain = self.create_user(captain_steamid64, false).await;
+        let tag = &name[name.len().saturating_sub(4)..];
+        let (status, body) = self
+            .post(
+                "/api/teams",
+                Some(&captain),
+                json!({ "name": name, "tag": tag }),
+            )
+            .await;
+        assert_eq!(status, 201, "{}", body);
+        let team_id = body["data"]["id"].as_u64().expect("The team has no id") as u32;
+        (captain, team_id)
+    }
+
+    /// Creates a match between two teams hosted by `server_id`, returns its id
+    pub async fn create_match(
+        &self,
+        admin: &str,
+        team1_id: u32,
+        team2_id: u32,
+        server_id: u32,
+    ) -> u64 {
+        let (status, body) = self
+            .post(
+                "/api/matches",
+                Some(admin),
+                json!({ "team1_id": team1_id, "team2_id": team2_id, "server_id": server_id }),
+            )
+            .await;
+        assert_eq!(status, 201, "{}", body);
+        body["data"]["id"].as_u64().expect("The match has no id")
+    }
+
+    /// Sends a request to the API, returns the status and the JSON body (`Null` when empty)
+    pub async fn request(
+        &self,
+        method: reqwest::Method,
+        path: &str,
+        token: Option<&str>,
+        body: Option<Value>,
+    ) -> (u16, Value) {
+        let mut request = self.http.request(method, self.url(path));
+        if let Some(token) = token {
+            request = request.header("authorization", format!("Bearer {}", token));
+        }
+        if let Some(body) = body {
+            request = request
+                .header("content-type", "application/json")
+                .body(body.to_string());
+        }
+        let response = request.send().await.expect("The request failed");
+        let status = response.status().as_u16();
+        let text = response.text().await.expect("Couldn't read the body");
+        let body = if text.is_empty() {
+            Value::Null
+        } else {
+            serde_json::from_str(&text).unwrap_or(Value::String(text))
+        };
+        (status, body)
+    }
+
+    pub async fn get(&self, path: &str, token: Option<&str>) -> (u16, Value) {
+        self.request(reqwest::Method::GET, path, token, None).await
+    }
+
+    pub async fn post(&self, path: &str, token: Option<&str>, body: Value) -> (u16, Value) {
+        self.request(reqwest::Method::POST, path, token, Some(body))
+            .await
+    }
+
+    /// Posts a plain text body, the way srcds sends its logs
+    pub async fn post_text(&self, path: &str, body: &str) -> (u16, Value) {
+        let response = self
+            .http
+            .post(self.url(path))
+            .header("content-type", "text/plain")
+            .body(body.to_string())
+            .send()
+            .await
+            .expect("The request failed");
+        let status = response.status().as_u16();
+        let text = response.text().await.expect("Couldn't read the body");
+        let body = serde_json::from_str(&text).unwrap_or(Value::Null);
+        (status, body)
+    }
+
+    pub async fn put(&self, path: &str, token: Option<&str>, body: Value) -> (u16, Value) {
+        self.request(reqwest::Method::PUT, path, token, Some(body))
+            .await
+    }
+}
+
+/// Status of a refused websocket upgrade, `None` when the connection failed otherwise
+#[derive(Deserialize)]
+struct SteamQuery {
+    steamids: String,
+}
+
+/// Stands in for the two Steam Web API endpoints the backend calls
+fn fake_steam_api() -> Router {
+    Router::new()
+        .route(
+            "/ISteamUser/GetPlayerSummaries/v0002/",
+            get(steam_player_summaries),
+        )
+        .route("/ISteamUser/GetPlayerBans/v1/", get(steam_player_bans))
+}
+
+async fn steam_player_summaries(Query(query): Query<SteamQuery>) -> Json<Value> {
+    Json(json!({ "response": { "players": [{
+        "steamid": query.steamids,
+        "communityvisibilitystate": 3,
+        "personaname": "Player",
+        "profileurl": "",
+        "avatar": "",
+        "avatarmedium": "",
+        "avatarfull": "",
+        "avatarhash": "",
+        "personastate": 0,
+        "timecreated": 946684800
+    }] } }))
+}
+
+async fn steam_player_bans(Query(query): Query<SteamQuery>) -> Response {
+    if query.steamids == STEAM_MALFORMED_BANS {
+        return "<html>Service Unavailable</html>".into_response();
+    }
+    let vac_banned = query.steamids == STEAM_VAC_BANNED;
+    Json(json!({ "players": [{
+        "SteamId": query.steamids,
+        "CommunityBanned": false,
+        "VACBanned": vac_banned,
+        "NumberOfVACBans": u32::from(vac_banned),
+        "DaysSinceLastBan": if vac_banned { 10 } else { 0 },
+        "NumberOfGameBans": 0,
+        "EconomyBan": "none"
+    }] }))
+    .into_response()
+}
+
+pub fn refused_status(error: &tungstenite::Error) -> Option<u16> {
+    match error {
+        tungstenite::Error::Http(response) => Some(response.status().as_u16()),
+        _ => None,
+    }
+}
+
+#[derive(Deserialize)]
+struct Envelope {
+    action: String,
+    data: Value,
+    seq: Option<u32>,
+}
+
+/// Waits for the next text message with `action`, anything else arriving first is skipped
+async fn next_with_action(socket: &mut Socket, action: &str) -> Envelope {
+    loop {
+        let message = match tokio::time::timeout(RECV_TIMEOUT, socket.next()).await {
+            Ok(Some(message)) => message.expect("The socket failed"),
+            Ok(None) => panic!("The socket closed while waiting for {}", action),
+            Err(_) => panic!("No {} message in time", action),
+        };
+        if let Message::Text(text) = message {
+            let envelope: Envelope =
+                serde_json::from_str(&text).expect("The backend sent a message without action");
+            if envelope.action == action {
+                return envelope;
+            }
+        }
+    }
+}
+
+/// Impersonates a game server on `/ws/server`
+pub struct ServerClient {
+    socket: Socket,
+}
+
+impl ServerClient {
+    /// Announces `port`, the backend only accepts ports registered for `SERVER_IP`
+    pub async fn connect(app: &TestApp, port: &str) -> Result<ServerClient, tungstenite::Error> {
+        ServerClient::connect_with(app, port, &[]).await
+    }
+
+    /// Like `connect` with extra headers, e.g. `Enrollment-Token` or `Authorization`
+    pub async fn connect_with(
+        app: &TestApp,
+        port: &str,
+        headers: &[(&'static str, &str)],
+    ) -> Result<ServerClient, tungstenite::Error> {
+        let mut request = app.ws_url("/ws/server").into_client_request()?;
+        request
+            .headers_mut()
+            .insert("PORT", HeaderValue::from_str(port).unwrap());
+        for (name, value) in headers {
+            request
+                .headers_mut()
+                .insert(*name, HeaderValue::from_str(value).unwrap());
+        }
+        let (socket, _) = tokio_tungstenite::connect_async(request).await?;
+        Ok(ServerClient { socket })
+    }
+
+    /// Connects and waits for the ban list every server receives once it is registered as online
+    pub async fn connect_online(app: &TestApp, port: &str) -> ServerClient {
+        let mut client = ServerClient::connect(app, port)
+            .await
+            .expect("The server connection was refused");
+        client.recv("backend_2_server_sync_bans").await;
+        client
+    }
+
+    pub async fn send(&mut self, action: &str, data: Value) {
+        let message = json!({ "action": action, "data": data }).to_string();
+        self.socket
+            .send(Message::Text(message))
+            .await
+            .expect("Couldn't send to the backend");
+    }
+
+    pub async fn update_status(&mut self, status: ServerStatus) {
+        self.send(
+            "server_2_backend_update_status",
+            json!({ "status": status }),
+        )
+        .await;
+    }
+
+    /// Data of the next `action` sent by the backend
+    pub async fn recv(&mut self, action: &str) -> Value {
+        next_with_action(&mut self.socket, action).await.data
+    }
+
+    /// Like `recv` for a message of the command queue, returns its `seq` with the data
+    pub async fn recv_queued(&mut self, action: &str) -> (u32, Value) {
+        let envelope = next_with_action(&mut self.socket, action).await;
+        let seq = envelope
+            .seq
+            .unwrap_or_else(|| panic!("{} wasn't sent through the queue", action));
+        (seq, envelope.data)
+    }
+
+    /// Waits for the backend to close the connection, skipping the messages before
+    pub async fn closed(&mut self) {
+        loop {
+            match tokio::time::timeout(RECV_TIMEOUT, self.socket.next()).await {
+                Ok(Some(Ok(Message::Close(_)))) | Ok(Some(Err(_))) | Ok(None) => return,
+                Ok(Some(Ok(_))) => {}
+                Err(_) => panic!("The backend didn't close the connection in time"),
+            }
+        }
+    }
+
+    pub async fn ack(&mut self, seq: u32) {
+        self.send("server_2_backend_ack", json!({ "ack": { "seq": seq } }))
+            .await;
+    }
+}
+
+/// Impersonates an admin dashboard on `/ws/user`
+pub struct UserClient {
+    socket: Socket,
+}
+
+impl UserClient {
+    pub async fn connect(app: &TestApp, token: &str) -> Result<UserClient, tungstenite::Error> {
+        let url = app.ws_url(&format!("/ws/user?token={}", token));
+        let (socket, _) = tokio_tungstenite::connect_async(url).await?;
+        Ok(UserClient { socket })
+    }
+
+    /// User messages are plain action names such as `admin_get_servers`
+    pub async fn send(&mut self, message: &str) {
+        self.socket
+            .send(Message::Text(message.to_string()))
+            .await
+            .expect("Couldn't send to the backend");
+    }
+
+    /// Data of the next `action`, which the backend sends as a string
+    pub async fn recv(&mut self, action: &str) -> String {
+        match next_with_action(&mut self.socket, action).await.data {
+            Value::String(data) => data,
+            data => panic!("{} carried non-string data {}", action, data),
+        }
+    }
+
+    /// Like `recv` for responses whose string data holds JSON
+    pub async fn recv_json<T: DeserializeOwned>(&mut self, action: &str) -> T {
+        let data = self.recv(action).await;
+        serde_json::from_str(&data)
+            .unwrap_or_else(|e| panic!("{} data didn't parse: {}", action, e))
+    }
+}
+
+/// Watches a match on `/ws/spectator`
+pub struct SpectatorClient {
+    socket: Socket,
+}
+
+impl SpectatorClient {
+    pub async fn connect(app: &TestApp, match_id: u64) -> SpectatorClient {
+        let url = app.ws_url(&format!("/ws/spectate/{}", match_id));
+        let (socket, _) = tokio_tungstenite::connect_async(url)
+            .await
+            .expect("The spectator connection was refused");
+        SpectatorClient { socket }
+    }
+
+    /// The next `match_snapshot` or `match_update`
+    pub async fn recv(&mut self, action: &str) -> Value {
+        next_with_action(&mut self.socket, action).await.data
+    }
+
+    pub async fn close(mut self) {
+        self.socket.close(None).await.ok();
+    }
+}
+
+/// Entry of `response_get_servers`
+#[derive(Deserialize)]
+pub struct ServerEntry {
+    pub id: u32,
+    pub ip: String,
+    pub port: u16,
+    pub hostname: Option<String>,
+    pub status: ServerStatus,
+    pub online: bool,
+    pub query: Option<Value>,
+}
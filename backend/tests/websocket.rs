@@ -0,0 +1,834 @@
+mod support;
+
+use noname::service::auth::create_access_token;
+use noname::service::{command_queue, server, server_query};
+use noname::ws::server::{self as ws_server, BackendAction, ServerStatus};
+use noname::ws::spectator;
+use serde_json::{json, Value};
+use support::{app, refused_status, ServerClient, ServerEntry, SpectatorClient, UserClient};
+
+#[tokio::test]
+async fn admin_get_servers_lists_connected_servers() {
+    let app = app();
+    let admin = app.create_user("76561190000000101", true).await;
+    let online_id = app.create_server("27101").await;
+    let offline_id = app.create_server("27102").await;
+    let _server = ServerClient::connect_online(app, "27101").await;
+
+    let mut user = UserClient::connect(app, &admin).await.unwrap();
+    user.send("admin_get_servers").await;
+    let servers: Vec<ServerEntry> = user.recv_json("response_get_servers").await;
+
+    let online = servers.iter().find(|s| s.id == online_id).unwrap();
+    assert!(online.online);
+    assert!(online.status == ServerStatus::Idle);
+    assert_eq!(online.port, 27101);
+    let offline = servers.iter().find(|s| s.id == offline_id).unwrap();
+    assert!(!offline.online);
+}
+
+#[tokio::test]
+async fn status_updates_are_reported_to_admins() {
+    let app = app();
+    let admin = app.create_user("76561190000000102", true).await;
+    let server_id = app.create_server("27103").await;
+    let mut server = ServerClient::connect_online(app, "27103").await;
+    let mut user = UserClient::connect(app, &admin).await.unwrap();
+
+    server.update_status(ServerStatus::Live).await;
+
+    //the status is applied asynchronously, poll until it shows up
+    for _ in 0..20 {
+        user.send("admin_get_servers").await;
+        let servers: Vec<ServerEntry> = user.recv_json("response_get_servers").await;
+        let entry = servers.iter().find(|s| s.id == server_id).unwrap();
+        if entry.status == ServerStatus::Live {
+            return;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+    panic!("The Live status never reached the admin");
+}
+
+#[tokio::test]
+async fn unregistered_servers_are_refused() {
+    let app = app();
+    let error = ServerClient::connect(app, "27999").await.err().unwrap();
+    assert_eq!(refused_status(&error), Some(401));
+}
+
+#[tokio::test]
+async fn user_socket_requires_a_known_user() {
+    let app = app();
+    let player = app.create_user("76561190000000103", false).await;
+
+    let mut socket = UserClient::connect(app, &player).await.unwrap();
+    socket.send("user_ping").await;
+    assert_eq!(socket.recv("ping").await, "pong");
+    let error = UserClient::connect(app, "not-a-token").await.err().unwrap();
+    assert_eq!(refused_status(&error), Some(401));
+    let unknown = create_access_token(app.config, "76561190000000199".to_string()).unwrap();
+    let error = UserClient::connect(app, &unknown).await.err().unwrap();
+    assert_eq!(refused_status(&error), Some(401));
+}
+
+#[tokio::test]
+async fn queue_actions_are_refused_during_a_cooldown() {
+    let app = app();
+    let admin = app.create_user("76561190000000130", true).await;
+    let steamid64 = "76561190000000131";
+    let player = app.create_user(steamid64, false).await;
+    let (status, body) = app
+        .post(
+            "/api/bans",
+            Some(&admin),
+            json!({
+                "steamid64": steamid64,
+                "kind": "queue_cooldown",
+                "reason": "Left a match early",
+                "duration_secs": 600
+            }),
+        )
+        .await;
+    assert_eq!(status, 201, "{}", body);
+
+    let mut socket = UserClient::connect(app, &player).await.unwrap();
+    socket.send("user_queue_join").await;
+    let refusal: Value = socket.recv_json("response_queue_refused").await;
+    assert_eq!(refusal["id"], body["data"]["id"]);
+    assert_eq!(refusal["kind"], "queue_cooldown");
+}
+
+#[tokio::test]
+async fn connecting_players_are_checked_against_the_roster_and_bans() {
+    let app = app();
+    let admin = app.create_user("76561190000000132", true).await;
+    let (_, team1_id) = app.create_team("76561190000000133", "Admission A").await;
+    let banned = "76561190000000134";
+    let (_, team2_id) = app.create_team(banned, "Admission B").await;
+    app.create_user("76561190000000135", false).await;
+    let server_id = app.create_server("27121").await;
+    let match_id = app
+        .create_match(&admin, team1_id, team2_id, server_id)
+        .await;
+    let (status, body) = app
+        .post(
+            "/api/bans",
+            Some(&admin),
+            json!({
+                "steamid64": banned,
+                "kind": "game",
+                "reason": "Cheating"
+            }),
+        )
+        .await;
+    assert_eq!(status, 201, "{}", body);
+
+    let mut server = ServerClient::connect_online(app, "27121").await;
+    for (steamid64, allowed, team_id, reason) in [
+        ("76561190000000133", true, json!(team1_id), "team1"),
+        ("76561190000000135", false, Value::Null, "not_on_roster"),
+        (banned, false, Value::Null, "banned"),
+    ] {
+        server
+            .send(
+                "server_2_backend_player_connecting",
+                json!({ "player_connecting": { "steamid64": steamid64, "name": "Player" } }),
+            )
+            .await;
+        let admission = server.recv("backend_2_server_player_admission").await;
+        assert_eq!(admission["steamid64"], steamid64, "{}", admission);
+        assert_eq!(admission["allowed"], allowed, "{}", admission);
+        assert_eq!(admission["match_id"], match_id, "{}", admission);
+        assert_eq!(admission["team_id"], team_id, "{}", admission);
+        assert_eq!(admission["reason"], reason, "{}", admission);
+    }
+}
+
+#[tokio::test]
+async fn made_up_user_actions_share_one_metric_series() {
+    let app = app();
+    let player = app.create_user("76561190000000129", false).await;
+
+    let mut socket = UserClient::connect(app, &player).await.unwrap();
+    for action in ["user_made_up_1", "user_made_up_2"] {
+        socket.send(action).await;
+    }
+    //messages are handled in order, the pong comes once both were counted
+    socket.send("user_ping").await;
+    assert_eq!(socket.recv("ping").await, "pong");
+
+    let (_, metrics) = app.get("/metrics", None).await;
+    let metrics = metrics.as_str().unwrap();
+    assert!(!metrics.contains("user_made_up"), "{}", metrics);
+    assert!(metrics.contains("action=\"unknown\""), "{}", metrics);
+    assert!(metrics.contains("action=\"user_ping\""), "{}", metrics);
+}
+
+#[tokio::test]
+async fn players_are_notified_of_cancelled_matches() {
+    let app = app();
+    let admin = app.create_user("76561190000000121", true).await;
+    let (captain, team1_id) = app.create_team("76561190000000122", "Notify A").await;
+    let (_, team2_id) = app.create_team("76561190000000123", "Notify B").await;
+    let server_id = app.create_server("27117").await;
+    let match_id = app
+        .create_match(&admin, team1_id, team2_id, server_id)
+        .await;
+    let schedule_path = format!("/api/matches/{}/schedule", match_id);
+    let (status, body) = app
+        .put(
+            &schedule_path,
+            Some(&admin),
+            json!({ "scheduled_at": "2099-01-01T12:00:00Z" }),
+        )
+        .await;
+    assert_eq!(status, 200, "{}", body);
+
+    let mut player = UserClient::connect(app, &captain).await.unwrap();
+    //the socket is registered once it answers
+    player.send("user_ping").await;
+    player.recv("ping").await;
+    let (status, body) = app
+        .request(reqwest::Method::DELETE, &schedule_path, Some(&admin), None)
+        .await;
+    assert!(status < 300, "{}", body);
+    let notification: serde_json::Value = player.recv_json("match_cancelled").await;
+    assert_eq!(notification["match_id"], match_id);
+}
+
+#[tokio::test]
+async fn status_transitions_push_the_phase_config() {
+    let app = app();
+    let admin = app.create_user("76561190000000104", true).await;
+    app.create_server("27104").await;
+
+    let (status, body) = app
+        .put(
+            "/api/config_templates/variables/knife_password",
+            Some(&admin),
+            json!({ "value": "secret" }),
+        )
+        .await;
+    assert_eq!(status, 200, "{}", body);
+    let (status, body) = app
+        .put(
+            "/api/config_templates/knife",
+            Some(&admin),
+            json!({ "content": "sv_password \"{{ knife_password }}\"\nhostname {{server_port}}" }),
+        )
+        .await;
+    assert_eq!(status, 200, "{}", body);
+    let version = body["data"]["version"].as_u64().unwrap();
+
+    let mut server = ServerClient::connect_online(app, "27104").await;
+    server.update_status(ServerStatus::KnifeRound).await;
+    let config = server.recv("backend_2_server_exec_config").await;
+    assert_eq!(config["phase"], "knife");
+    assert_eq!(config["version"], version);
+    assert_eq!(config["content"], "sv_password \"secret\"\nhostname 27104");
+}
+
+#[tokio::test]
+async fn team_names_cant_inject_console_commands() {
+    let app = app();
+    let admin = app.create_user("76561190000000126", true).await;
+    let server_id = app.create_server("27120").await;
+    let (_, team1_id) = app
+        .create_team("76561190000000127", "x\"; rcon_password pwned\nquit")
+        .await;
+    let (_, team2_id) = app.create_team("76561190000000128", "Nice Team").await;
+    app.create_match(&admin, team1_id, team2_id, server_id)
+        .await;
+
+    let (status, body) = app
+        .put(
+            "/api/config_templates/warmup",
+            Some(&admin),
+            json!({ "content": "mp_teamname_1 \"{{team1_name}}\"\nmp_teamflag_1 {{team1_tag}}" }),
+        )
+        .await;
+    assert_eq!(status, 200, "{}", body);
+    let (status, body) = app
+        .put(
+            "/api/config_templates/variables/team_password",
+            Some(&admin),
+            json!({ "value": "a; quit" }),
+        )
+        .await;
+    assert_eq!(status, 422, "{}", body);
+
+    let mut server = ServerClient::connect_online(app, "27120").await;
+    server.update_status(ServerStatus::WaitingForPlayers).await;
+    let config = server.recv("backend_2_server_exec_config").await;
+    assert_eq!(
+        config["content"],
+        "mp_teamname_1 \"x rcon_password pwnedquit\"\nmp_teamflag_1 quit"
+    );
+}
+
+#[tokio::test]
+async fn rest_commands_wait_for_the_server_ack() {
+    let app = app();
+    let admin = app.create_user("76561190000000105", true).await;
+    let server_id = app.create_server("27105").await;
+    let mut server = ServerClient::connect_online(app, "27105").await;
+
+    let path = format!("/api/servers/{}/commands", server_id);
+    let request = app.post(
+        &path,
+        Some(&admin),
+        json!({ "command": "change_map", "map": "de_inferno" }),
+    );
+    let game_server = async {
+        let command = server.recv("backend_2_server_command").await;
+        assert_eq!(command["command"], "change_map");
+        assert_eq!(command["map"], "de_inferno");
+        server
+            .send(
+                "server_2_backend_command_ack",
+                json!({ "command_ack": { "command_id": command["command_id"], "success": true } }),
+            )
+            .await;
+        command["command_id"].clone()
+    };
+    let ((status, body), command_id) = tokio::join!(request, game_server);
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["data"]["status"], "acknowledged");
+    assert_eq!(body["data"]["command_id"], command_id);
+
+    let (status, body) = app
+        .post(
+            &path,
+            Some(&admin),
+            json!({ "command": "say", "message": "gl\"; quit" }),
+        )
+        .await;
+    assert_eq!(status, 422);
+    assert_eq!(body["error"]["details"][0]["field"], "message");
+}
+
+#[tokio::test]
+async fn websocket_commands_relay_the_server_error() {
+    let app = app();
+    let admin = app.create_user("76561190000000106", true).await;
+    let server_id = app.create_server("27106").await;
+    let mut server = ServerClient::connect_online(app, "27106").await;
+    let mut user = UserClient::connect(app, &admin).await.unwrap();
+
+    user.send(&format!(
+        "admin_server_command {}",
+        json!({ "server_id": server_id, "command": "pause" })
+    ))
+    .await;
+    let issued: serde_json::Value = user.recv_json("response_server_command").await;
+    let command = server.recv("backend_2_server_command").await;
+    assert_eq!(command["command"], "pause");
+    assert_eq!(command["command_id"], issued["command_id"]);
+
+    server
+        .send(
+            "server_2_backend_command_ack",
+            json!({ "command_ack": {
+                "command_id": command["command_id"],
+                "success": false,
+                "error": "Already paused"
+            } }),
+        )
+        .await;
+    let result: serde_json::Value = user.recv_json("response_server_command_result").await;
+    assert_eq!(result["command_id"], issued["command_id"]);
+    assert_eq!(result["status"], "failed");
+    assert_eq!(result["error"], "Already paused");
+
+    user.send(&format!(
+        "admin_server_command {}",
+        json!({ "server_id": 999999, "command": "pause" })
+    ))
+    .await;
+    let reason = user.recv("response_server_command_refused").await;
+    assert_eq!(reason, "Server 999999 is not connected");
+}
+
+#[tokio::test]
+async fn queued_commands_are_replayed_until_acked() {
+    let app = app();
+    let admin = app.create_user("76561190000000107", true).await;
+    let server_id = app.create_server("27107").await;
+
+    //queued while the server is offline
+    let queued = app
+        .run(command_queue::enqueue(
+            app.config,
+            server_id,
+            BackendAction::Backend2ServerLoadMatch,
+            json!({ "match_id": 4242 }),
+        ))
+        .await
+        .unwrap();
+
+    let mut server = ServerClient::connect_online(app, "27107").await;
+    let (seq, data) = server.recv_queued("backend_2_server_load_match").await;
+    assert_eq!(seq, queued.seq);
+    assert_eq!(data["match_id"], 4242);
+    drop(server);
+
+    //the ack was never sent, the next connection gets the command again
+    let mut server = ServerClient::connect_online(app, "27107").await;
+    let (replayed, _) = server.recv_queued("backend_2_server_load_match").await;
+    assert_eq!(replayed, seq);
+    server.ack(seq).await;
+
+    let path = format!("/api/servers/{}/queue", server_id);
+    for _ in 0..20 {
+        let (status, body) = app.get(&path, Some(&admin)).await;
+        assert_eq!(status, 200);
+        if body["data"].as_array().unwrap().is_empty() {
+            return;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+    panic!("The acked command stayed in the queue");
+}
+
+#[tokio::test]
+async fn round_backups_restore_on_another_server() {
+    let app = app();
+    let admin = app.create_user("76561190000000108", true).await;
+    let (captain, team1_id) = app.create_team("76561190000000109", "Backup A").await;
+    let (_, team2_id) = app.create_team("76561190000000110", "Backup B").await;
+    let crashed_id = app.create_server("27108").await;
+    let spare_id = app.create_server("27109").await;
+    let match_id = app
+        .create_match(&admin, team1_id, team2_id, crashed_id)
+        .await;
+
+    let mut crashed = ServerClient::connect_online(app, "27108").await;
+    crashed
+        .send(
+            "server_2_backend_round_backup",
+            json!({ "round_backup": {
+                "match_id": match_id,
+                "round": 7,
+                "file_name": "backup_round07.txt",
+                "content": "\"SaveFile\" { \"round\" \"7\" }"
+            } }),
+        )
+        .await;
+    let backups_path = format!("/api/matches/{}/backups", match_id);
+    let mut stored = false;
+    for _ in 0..20 {
+        let (status, body) = app.get(&backups_path, Some(&admin)).await;
+        assert_eq!(status, 200);
+        if body["data"].as_array().unwrap().len() == 1 {
+            assert_eq!(body["data"][0]["round"], 7);
+            stored = true;
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+    assert!(stored, "The backup was never stored");
+
+    let mut spare = ServerClient::connect_online(app, "27109").await;
+    let mut player = UserClient::connect(app, &captain).await.unwrap();
+    player.send("user_ping").await;
+    player.recv("ping").await;
+    let (status, body) = app
+        .post(
+            &format!("{}/7/restore", backups_path),
+            Some(&admin),
+            json!({ "server_id": spare_id }),
+        )
+        .await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["data"]["reassigned"], true);
+
+    let (_, config) = spare.recv_queued("backend_2_server_load_match").await;
+    assert_eq!(config["match_id"], match_id);
+    let (_, restore) = spare.recv_queued("backend_2_server_restore_round").await;
+    assert_eq!(restore["round"], 7);
+    assert_eq!(restore["file_name"], "backup_round07.txt");
+    let (_, cancelled) = crashed.recv_queued("backend_2_server_cancel_match").await;
+    assert_eq!(cancelled, match_id);
+
+    let notification: serde_json::Value = player.recv_json("match_restored").await;
+    assert_eq!(notification["match_id"], match_id);
+    assert_eq!(notification["round"], 7);
+    assert_eq!(notification["server"], "127.0.0.1:27109");
+
+    let (status, body) = app.get(&format!("/api/matches/{}", match_id), None).await;
+    assert_eq!(status, 200);
+    assert_eq!(body["data"]["server_id"], spare_id);
+}
+
+#[tokio::test]
+async fn a2s_probes_reach_servers_without_the_plugin() {
+    let app = app();
+    let admin = app.create_user("76561190000000111", true).await;
+    let responder = tokio::net::UdpSocket::bind((support::SERVER_IP, 0))
+        .await
+        .unwrap();
+    let server_id = app
+        .create_server(&responder.local_addr().unwrap().port().to_string())
+        .await;
+    tokio::spawn(fake_a2s_server(responder));
+
+    app.run(server_query::probe_servers(app.config))
+        .await
+        .unwrap();
+    let mut user = UserClient::connect(app, &admin).await.unwrap();
+    user.send("admin_get_servers").await;
+    let servers: Vec<ServerEntry> = user.recv_json("response_get_servers").await;
+    let server = servers.iter().find(|s| s.id == server_id).unwrap();
+    assert!(!server.online);
+    let query = server.query.as_ref().expect("The server wasn't probed");
+    assert_eq!(query["map"], "de_mirage");
+    assert_eq!(query["players"], 2);
+    assert_eq!(query["max_players"], 10);
+    assert_eq!(query["vac"], true);
+    assert!(query["ping_ms"].is_u64());
+    //sent as two split packets, the second one first
+    assert_eq!(query["player_list"][0]["name"], "alice");
+    assert_eq!(query["player_list"][1]["score"], 7);
+    assert_eq!(query["rules"]["mp_maxrounds"], "30");
+}
+
+#[tokio::test]
+async fn enrolled_servers_wait_for_approval() {
+    let app = app();
+    let admin = app.create_user("76561190000000112", true).await;
+    let mut user = UserClient::connect(app, &admin).await.unwrap();
+    let (status, body) = app
+        .post(
+            "/api/servers/enrollment_tokens",
+            Some(&admin),
+            json!({ "label": "lan-1" }),
+        )
+        .await;
+    assert_eq!(status, 201);
+    let token = body["data"]["token"].as_str().unwrap().to_string();
+
+    let mut server = ServerClient::connect_with(app, "27110", &[("Enrollment-Token", &token)])
+        .await
+        .expect("The enrollment was refused");
+    let pending: serde_json::Value = user.recv_json("pending_server").await;
+    assert_eq!(pending["port"], 27110);
+    assert_eq!(pending["status"], "pending");
+    let (_, body) = app.get("/api/servers/pending", Some(&admin)).await;
+    assert!(body["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|p| p["id"] == pending["id"]));
+
+    let approve_path = format!("/api/servers/pending/{}/approve", pending["id"]);
+    let (status, body) = app.post(&approve_path, Some(&admin), json!({})).await;
+    assert_eq!(status, 200);
+    assert_eq!(body["data"]["delivered"], true);
+    let server_id = body["data"]["server_id"].as_u64().unwrap();
+    let enrolled = server.recv("backend_2_server_enrolled").await;
+    assert_eq!(enrolled["server_id"], server_id);
+    let credential = enrolled["credential"].as_str().unwrap().to_string();
+    //the same connection carries on as the registered server
+    server.recv("backend_2_server_sync_bans").await;
+    user.send("admin_get_servers").await;
+    let servers: Vec<ServerEntry> = user.recv_json("response_get_servers").await;
+    assert!(servers.iter().any(|s| s.id as u64 == server_id && s.online));
+
+    //from now on the server has to present its credential
+    drop(server);
+    let error = ServerClient::connect(app, "27110").await.err().unwrap();
+    assert_eq!(refused_status(&error), Some(401));
+    let bearer = format!("Bearer {}", credential);
+    ServerClient::connect_with(app, "27110", &[("Authorization", &bearer)])
+        .await
+        .expect("The credential was refused");
+
+    let (status, _) = app.post(&approve_path, Some(&admin), json!({})).await;
+    assert_eq!(status, 409);
+}
+
+#[tokio::test]
+async fn rejected_and_invalid_enrollments_are_refused() {
+    let app = app();
+    let admin = app.create_user("76561190000000113", true).await;
+    let error = ServerClient::connect_with(app, "27111", &[("Enrollment-Token", "1.guessed")])
+        .await
+        .err()
+        .unwrap();
+    assert_eq!(refused_status(&error), Some(401));
+
+    let (_, body) = app
+        .post("/api/servers/enrollment_tokens", Some(&admin), json!({}))
+        .await;
+    let token_id = body["data"]["id"].as_u64().unwrap();
+    let token = body["data"]["token"].as_str().unwrap().to_string();
+    let mut server = ServerClient::connect_with(app, "27111", &[("Enrollment-Token", &token)])
+        .await
+        .expect("The enrollment was refused");
+    let (_, body) = app.get("/api/servers/pending", Some(&admin)).await;
+    let pending_id = body["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|p| p["port"] == 27111)
+        .map(|p| p["id"].clone())
+        .unwrap();
+
+    let reject_path = format!("/api/servers/pending/{}/reject", pending_id);
+    let (status, body) = app.post(&reject_path, Some(&admin), json!({})).await;
+    assert_eq!(status, 200);
+    assert_eq!(body["data"]["status"], "rejected");
+    server.closed().await;
+    let error = ServerClient::connect_with(app, "27111", &[("Enrollment-Token", &token)])
+        .await
+        .err()
+        .unwrap();
+    assert_eq!(refused_status(&error), Some(403));
+
+    let revoke_path = format!("/api/servers/enrollment_tokens/{}", token_id);
+    let (status, _) = app
+        .request(reqwest::Method::DELETE, &revoke_path, Some(&admin), None)
+        .await;
+    assert_eq!(status, 200);
+    let error = ServerClient::connect_with(app, "27112", &[("Enrollment-Token", &token)])
+        .await
+        .err()
+        .unwrap();
+    assert_eq!(refused_status(&error), Some(401));
+}
+
+#[tokio::test]
+async fn idle_pending_servers_are_closed() {
+    let app = app();
+    let admin = app.create_user("76561190000000125", true).await;
+    let (_, body) = app
+        .post("/api/servers/enrollment_tokens", Some(&admin), json!({}))
+        .await;
+    let token = body["data"]["token"].as_str().unwrap().to_string();
+
+    let mut server = ServerClient::connect_with(app, "27119", &[("Enrollment-Token", &token)])
+        .await
+        .expect("The enrollment was refused");
+    //nothing is sent while waiting for an admin
+    server.closed().await;
+}
+
+#[tokio::test]
+async fn servers_registered_by_hostname_follow_it() {
+    let app = app();
+    let admin = app.create_user("76561190000000114", true).await;
+    let (status, _) = app
+        .post(
+            "/api/servers",
+            Some(&admin),
+            json!({ "ip": "10.0.0.9", "hostname": "localhost", "port": 27113 }),
+        )
+        .await;
+    assert_eq!(status, 201);
+    let error = ServerClient::connect(app, "27113").await.err().unwrap();
+    assert_eq!(refused_status(&error), Some(401));
+
+    app.run(async { server::resolve_addresses().await.unwrap() })
+        .await;
+    let _server = ServerClient::connect_online(app, "27113").await;
+    let mut user = UserClient::connect(app, &admin).await.unwrap();
+    user.send("admin_get_servers").await;
+    let servers: Vec<ServerEntry> = user.recv_json("response_get_servers").await;
+    let entry = servers.iter().find(|s| s.port == 27113).unwrap();
+    assert_eq!(entry.ip, support::SERVER_IP);
+    assert_eq!(entry.hostname.as_deref(), Some("localhost"));
+    assert!(entry.online);
+}
+
+/// Answers A2S queries like srcds, asking for a challenge first
+async fn fake_a2s_server(socket: tokio::net::UdpSocket) {
+    const CHALLENGE: [u8; 4] = [1, 2, 3, 4];
+    let header = [0xFF; 4];
+    let mut buf = [0; 1400];
+    loop {
+        let (len, addr) = socket.recv_from(&mut buf).await.unwrap();
+        let request = &buf[..len];
+        let challenged = request.ends_with(&CHALLENGE);
+        let mut response = header.to_vec();
+        if !challenged {
+            response.push(0x41);
+            response.extend_from_slice(&CHALLENGE);
+            socket.send_to(&response, addr).await.unwrap();
+            continue;
+        }
+        match request[4] {
+            0x54 => {
+                response.extend_from_slice(
+                    b"\x49\x11Fake\0de_mirage\0csgo\0Counter-Strike: Global Offensive\0",
+                );
+                response.extend_from_slice(&730u16.to_le_bytes());
+                response.extend_from_slice(&[2, 10, 0, b'd', b'l', 0, 1]);
+                response.extend_from_slice(b"1.38.7.9\0");
+                socket.send_to(&response, addr).await.unwrap();
+            }
+            0x55 => {
+                response.extend_from_slice(&[0x44, 2]);
+                for (index, name, score) in [(0u8, "alice", 12), (1, "bob", 7)] {
+                    response.push(index);
+                    response.extend_from_slice(name.as_bytes());
+                    response.push(0);
+                    response.extend_from_slice(&i32::to_le_bytes(score));
+                    response.extend_from_slice(&f32::to_le_bytes(60.5));
+                }
+                let (first, second) = response.split_at(response.len() / 2);
+                for (number, part) in [(1u8, second), (0, first)] {
+                    let mut packet = vec![0xFE, 0xFF, 0xFF, 0xFF];
+                    packet.extend_from_slice(&42i32.to_le_bytes());
+                    packet.extend_from_slice(&[2, number]);
+                    packet.extend_from_slice(&1248u16.to_le_bytes());
+                    packet.extend_from_slice(part);
+                    socket.send_to(&packet, addr).await.unwrap();
+                }
+            }
+            _ => {
+                response.push(0x45);
+                response.extend_from_slice(&1u16.to_le_bytes());
+                response.extend_from_slice(b"mp_maxrounds\x0030\0");
+                socket.send_to(&response, addr).await.unwrap();
+            }
+        }
+    }
+}
+
+#[tokio::test]
+async fn match_results_only_count_from_the_hosting_server() {
+    let app = app();
+    let admin = app.create_user("76561190000000115", true).await;
+    let (_, team1_id) = app.create_team("76561190000000116", "Result A").await;
+    let (_, team2_id) = app.create_team("76561190000000117", "Result B").await;
+    let host_id = app.create_server("27114").await;
+    let other_id = app.create_server("27115").await;
+    let match_id = app.create_match(&admin, team1_id, team2_id, host_id).await;
+    let ending = json!({ "match_update": {
+        "match_id": match_id,
+        "status": "Ending",
+        "round": 24,
+        "team1_score": 16,
+        "team2_score": 8,
+        "players": [],
+        "winner_team_id": team1_id
+    } });
+
+    let mut other = ServerClient::connect_online(app, "27115").await;
+    other
+        .send("server_2_backend_match_update", ending.clone())
+        .await;
+    //messages of a socket are handled in order, once the status shows up the report was handled too
+    other.update_status(ServerStatus::Live).await;
+    let mut user = UserClient::connect(app, &admin).await.unwrap();
+    let mut handled = false;
+    for _ in 0..20 {
+        user.send("admin_get_servers").await;
+        let servers: Vec<ServerEntry> = user.recv_json("response_get_servers").await;
+        if servers
+            .iter()
+            .any(|s| s.id == other_id && s.status == ServerStatus::Live)
+        {
+            handled = true;
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+    assert!(handled, "The other server's messages were never handled");
+    let (_, body) = app.get(&format!("/api/matches/{}", match_id), None).await;
+    assert!(body["data"]["winner_team_id"].is_null(), "{}", body);
+
+    let mut host = ServerClient::connect_online(app, "27114").await;
+    host.send("server_2_backend_match_update", ending).await;
+    for _ in 0..20 {
+        let (_, body) = app.get(&format!("/api/matches/{}", match_id), None).await;
+        if body["data"]["winner_team_id"] == team1_id {
+            return;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+    panic!("The result of the hosting server was never recorded");
+}
+
+#[tokio::test]
+async fn finished_match_feeds_leave_with_their_last_spectator() {
+    let app = app();
+    let admin = app.create_user("76561190000000118", true).await;
+    let (_, team1_id) = app.create_team("76561190000000119", "Feed A").await;
+    let (_, team2_id) = app.create_team("76561190000000120", "Feed B").await;
+    let server_id = app.create_server("27116").await;
+    let match_id = app
+        .create_match(&admin, team1_id, team2_id, server_id)
+        .await;
+    let update = |status: &str, winner_team_id: Option<u32>| {
+        json!({ "match_update": {
+            "match_id": match_id,
+            "status": status,
+            "round": 24,
+            "team1_score": 16,
+            "team2_score": 8,
+            "players": [],
+            "winner_team_id": winner_team_id
+        } })
+    };
+
+    let mut spectator = SpectatorClient::connect(app, match_id).await;
+    let mut server = ServerClient::connect_online(app, "27116").await;
+    server
+        .send("server_2_backend_match_update", update("Live", None))
+        .await;
+    assert_eq!(spectator.recv("match_update").await["status"], "Live");
+    server
+        .send(
+            "server_2_backend_match_update",
+            update("Ending", Some(team1_id)),
+        )
+        .await;
+    let last = spectator.recv("match_update").await;
+    assert_eq!(last["status"], "Ending");
+    assert_eq!(last["winner_team_id"], team1_id);
+
+    spectator.close().await;
+    let match_id = match_id as u32;
+    for _ in 0..20 {
+        if app.run(spectator::get_live_match(match_id)).await.is_none() {
+            return;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+    panic!("The feed of the finished match was kept");
+}
+
+#[tokio::test]
+async fn reconnecting_servers_replace_their_previous_connection() {
+    let app = app();
+    let admin = app.create_user("76561190000000124", true).await;
+    let server_id = app.create_server("27118").await;
+    let mut stale = ServerClient::connect_online(app, "27118").await;
+
+    //the new connection registers before the backend noticed the old one is gone
+    let mut server = ServerClient::connect_online(app, "27118").await;
+    stale.closed().await;
+    drop(stale);
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+    let online = app.run(ws_server::get_online_servers()).await;
+    assert_eq!(online.iter().filter(|s| s.id == server_id).count(), 1);
+
+    //closing the old connection left the new one reachable
+    let path = format!("/api/servers/{}/commands", server_id);
+    let request = app.post(&path, Some(&admin), json!({ "command": "pause" }));
+    let game_server = async {
+        let command = server.recv("backend_2_server_command").await;
+        server
+            .send(
+                "server_2_backend_command_ack",
+                json!({ "command_ack": { "command_id": command["command_id"], "success": true } }),
+            )
+            .await;
+    };
+    let ((status, body), _) = tokio::join!(request, game_server);
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["data"]["status"], "acknowledged");
+}
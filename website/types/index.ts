@@ -10,12 +10,28 @@ export type ServerStatus =
   | "Starting"
   | "KnifeRound"
   | "Live"
+  | "Overtime"
   | "Ending";
 
 export type ServerWithStatus = {
   id: number;
   ip: string;
-  port: string;
+  port: number;
+  hostname: string | null;
   status: ServerStatus;
   online: boolean;
+  query: ServerQuery | null;
+};
+
+export type ServerQuery = {
+  name: string;
+  map: string;
+  players: number;
+  max_players: number;
+  bots: number;
+  vac: boolean;
+  ping_ms: number;
+  player_list: { name: string; score: number; duration_secs: number }[] | null;
+  rules: Record<string, string> | null;
+  queried_at: string;
 };